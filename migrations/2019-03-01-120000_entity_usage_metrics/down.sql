@@ -0,0 +1,5 @@
+DROP INDEX "entity_usage_entity_id_used_at_idx";
+
+ALTER TABLE "entity_usage" DROP COLUMN "row_count";
+ALTER TABLE "entity_usage" DROP COLUMN "latency_ms";
+ALTER TABLE "entity_usage" DROP COLUMN "action";
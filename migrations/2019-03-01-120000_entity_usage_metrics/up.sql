@@ -0,0 +1,5 @@
+ALTER TABLE "entity_usage" ADD COLUMN "action" VARCHAR NOT NULL DEFAULT '';
+ALTER TABLE "entity_usage" ADD COLUMN "latency_ms" BIGINT;
+ALTER TABLE "entity_usage" ADD COLUMN "row_count" BIGINT;
+
+CREATE INDEX "entity_usage_entity_id_used_at_idx" ON "entity_usage" ("entity_id", "used_at");
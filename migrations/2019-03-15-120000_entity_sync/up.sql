@@ -0,0 +1,6 @@
+-- The revision of each entity as of the last sync with a directory, anything newer was changed outside of it
+CREATE TABLE "entity_sync" (
+    "entity_id"               BIGINT PRIMARY KEY REFERENCES "entity" ON DELETE CASCADE,
+    "revision_id"             BIGINT NOT NULL,
+    "synced_at"               TIMESTAMP NOT NULL DEFAULT NOW()
+);
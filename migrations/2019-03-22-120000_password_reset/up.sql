@@ -0,0 +1,9 @@
+-- Single use tokens for resetting a forgotten password, only the hash of the token is stored
+CREATE TABLE "password_reset" (
+    "password_reset_id"       BIGSERIAL PRIMARY KEY,
+    "user_id"                 BIGINT NOT NULL REFERENCES "user" ON DELETE CASCADE,
+    "token_hash"              VARCHAR NOT NULL UNIQUE,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW(),
+    "expires_at"              TIMESTAMP NOT NULL,
+    "used_at"                 TIMESTAMP
+);
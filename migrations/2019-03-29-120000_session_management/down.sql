@@ -0,0 +1,3 @@
+ALTER TABLE "session" DROP COLUMN "last_used_at";
+ALTER TABLE "session" DROP COLUMN "ip_address";
+ALTER TABLE "session" DROP COLUMN "device";
@@ -0,0 +1,4 @@
+-- Where each session was started and when it was last used, so that users can tell their sessions apart
+ALTER TABLE "session" ADD COLUMN "device" VARCHAR;
+ALTER TABLE "session" ADD COLUMN "ip_address" VARCHAR;
+ALTER TABLE "session" ADD COLUMN "last_used_at" TIMESTAMP NOT NULL DEFAULT NOW();
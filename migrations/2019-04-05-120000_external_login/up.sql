@@ -0,0 +1,18 @@
+-- Logins that were sent to the identity provider and haven't come back yet
+CREATE TABLE "login_request" (
+    "state"                   VARCHAR PRIMARY KEY,
+    "code_verifier"           VARCHAR NOT NULL,
+    "nonce"                   VARCHAR NOT NULL,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW(),
+    "expires_at"              TIMESTAMP NOT NULL
+);
+
+-- Users that log in through an identity provider, the subject is the provider's id for the user
+CREATE TABLE "user_identity" (
+    "user_identity_id"        BIGSERIAL PRIMARY KEY,
+    "user_id"                 BIGINT NOT NULL REFERENCES "user" ON DELETE CASCADE,
+    "issuer"                  VARCHAR NOT NULL,
+    "subject"                 VARCHAR NOT NULL,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE("issuer", "subject")
+);
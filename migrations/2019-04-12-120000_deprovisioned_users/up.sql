@@ -0,0 +1,2 @@
+-- Users that were removed from the directory keep their history, roles and references, but can not log in
+ALTER TABLE "user" ADD COLUMN "deprovisioned" BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+DROP TABLE "api_key";
+ALTER TABLE "user" DROP COLUMN "is_service_account";
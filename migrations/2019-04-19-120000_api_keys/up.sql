@@ -0,0 +1,16 @@
+-- Service accounts are users for automation, they can only authenticate with api keys
+ALTER TABLE "user" ADD COLUMN "is_service_account" BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Long lived keys, only the hash is kept, `scopes` limits the key to some of the permissions of the user
+CREATE TABLE "api_key" (
+    "api_key_id"              BIGSERIAL PRIMARY KEY,
+    "user_id"                 BIGINT NOT NULL REFERENCES "user" ON DELETE CASCADE,
+    "name"                    VARCHAR NOT NULL,
+    "key_prefix"              VARCHAR NOT NULL,
+    "key_hash"                VARCHAR NOT NULL UNIQUE,
+    "scopes"                  JSON,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW(),
+    "expires_at"              TIMESTAMP,
+    "last_used_at"            TIMESTAMP,
+    "use_count"               BIGINT NOT NULL DEFAULT 0
+);
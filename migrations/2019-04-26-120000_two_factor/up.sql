@@ -0,0 +1,26 @@
+-- TOTP secret of the user, it is only in use once `confirmed_at` is set
+-- `last_used_step` keeps a code from being used twice
+CREATE TABLE "user_mfa" (
+    "user_id"                 BIGINT PRIMARY KEY REFERENCES "user" ON DELETE CASCADE,
+    "secret"                  VARCHAR NOT NULL,
+    "confirmed_at"            TIMESTAMP,
+    "last_used_step"          BIGINT,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Single use codes for when the authenticator is lost, only the hash is kept
+CREATE TABLE "mfa_recovery_code" (
+    "mfa_recovery_code_id"    BIGSERIAL PRIMARY KEY,
+    "user_id"                 BIGINT NOT NULL REFERENCES "user" ON DELETE CASCADE,
+    "code_hash"               VARCHAR NOT NULL,
+    "used_at"                 TIMESTAMP
+);
+
+-- Handed out after the password was checked, exchanged for a session together with a code
+CREATE TABLE "mfa_challenge" (
+    "challenge_hash"          VARCHAR PRIMARY KEY,
+    "user_id"                 BIGINT NOT NULL REFERENCES "user" ON DELETE CASCADE,
+    "failed_attempts"         INTEGER NOT NULL DEFAULT 0,
+    "created_at"              TIMESTAMP NOT NULL DEFAULT NOW(),
+    "expires_at"              TIMESTAMP NOT NULL
+);
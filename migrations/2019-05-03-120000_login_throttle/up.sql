@@ -0,0 +1,19 @@
+-- Failed logins in a row, keyed by `user:<user id or identifier>` and `ip:<address>`
+CREATE TABLE "login_throttle" (
+    "throttle_key"            VARCHAR PRIMARY KEY,
+    "failed_attempts"         INTEGER NOT NULL DEFAULT 0,
+    "last_failed_at"          TIMESTAMP NOT NULL DEFAULT NOW(),
+    "blocked_until"           TIMESTAMP,
+    "is_locked"               BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+-- Audit trail, one row for every failed or rejected login
+CREATE TABLE "login_failure" (
+    "login_failure_id"        BIGSERIAL PRIMARY KEY,
+    "user_identifier"         VARCHAR NOT NULL,
+    "user_id"                 BIGINT REFERENCES "user" ON DELETE SET NULL,
+    "ip_address"              VARCHAR,
+    "device"                  VARCHAR,
+    "reason"                  VARCHAR NOT NULL,
+    "failed_at"               TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+-- Disabled users keep their history, roles and references, but can not log in
+ALTER TABLE "user" ADD COLUMN "disabled" BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+
+use ldap3::LdapConn;
+use ldap3::Scope;
+use ldap3::SearchEntry;
+
+use auth::oidc::RoleMapping;
+use data::auth::ExternalUser;
+
+/// The result code of a bind with the wrong password
+const INVALID_CREDENTIALS: u32 = 49;
+
+#[derive(Debug, Fail, Clone, PartialEq, Eq)]
+pub enum LdapError {
+    #[fail(display = "LDAP is not configured")]
+    NotConfigured,
+    #[fail(display = "Invalid credentials")]
+    InvalidCredentials,
+    #[fail(display = "Could not reach the directory: {}", 0)]
+    ConnectionError(String),
+    #[fail(display = "Directory search failed: {}", 0)]
+    SearchError(String),
+    #[fail(display = "An unknown error occurred")]
+    Unknown,
+}
+
+/// A user as the directory knows it, `groups` are the names of the groups that the user is a member of
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirectoryUser {
+    pub dn: String,
+    pub username: String,
+    pub email: String,
+    pub display_name: Option<String>,
+    pub groups: Vec<String>,
+}
+
+impl DirectoryUser {
+    /// The dn identifies the user, so renaming them in the directory doesn't create a new user
+    pub fn to_external_user(&self, issuer: &str) -> ExternalUser {
+        ExternalUser {
+            issuer: issuer.to_string(),
+            subject: self.dn.to_owned(),
+            username: self.username.to_owned(),
+            email: self.email.to_owned(),
+            display_name: self.display_name.to_owned(),
+            claims: json!({ "groups": self.groups }),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LdapSettings {
+    /// i.e. `ldap://localhost:389` or `ldaps://ldap.example.com`
+    pub url: String,
+    /// The service account that searches for users and groups
+    pub bind_dn: String,
+    pub bind_password: String,
+    pub user_base_dn: String,
+    pub user_filter: String,
+    pub username_attribute: String,
+    pub email_attribute: String,
+    pub display_name_attribute: String,
+    /// Group membership is not synced if this is not set
+    pub group_base_dn: Option<String>,
+    pub group_filter: String,
+    pub group_name_attribute: String,
+    /// Holds the dns of the members
+    pub group_member_attribute: String,
+    pub role_mappings: Vec<RoleMapping>,
+}
+
+impl LdapSettings {
+    pub fn new(url: &str, bind_dn: &str, bind_password: &str, user_base_dn: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            bind_dn: bind_dn.to_string(),
+            bind_password: bind_password.to_string(),
+            user_base_dn: user_base_dn.to_string(),
+            user_filter: "(objectClass=inetOrgPerson)".to_string(),
+            username_attribute: "uid".to_string(),
+            email_attribute: "mail".to_string(),
+            display_name_attribute: "cn".to_string(),
+            group_base_dn: None,
+            group_filter: "(objectClass=groupOfNames)".to_string(),
+            group_name_attribute: "cn".to_string(),
+            group_member_attribute: "member".to_string(),
+            role_mappings: vec![],
+        }
+    }
+
+    /// Only the users matching the filter can log in and are synced
+    pub fn user_filter(mut self, user_filter: &str) -> Self {
+        self.user_filter = user_filter.to_string();
+        self
+    }
+
+    /// i.e. `sAMAccountName`, `mail` and `displayName` for Active Directory
+    pub fn user_attributes(mut self, username_attribute: &str, email_attribute: &str, display_name_attribute: &str) -> Self {
+        self.username_attribute = username_attribute.to_string();
+        self.email_attribute = email_attribute.to_string();
+        self.display_name_attribute = display_name_attribute.to_string();
+        self
+    }
+
+    pub fn groups(mut self, group_base_dn: &str, group_filter: &str, group_member_attribute: &str) -> Self {
+        self.group_base_dn = Some(group_base_dn.to_string());
+        self.group_filter = group_filter.to_string();
+        self.group_member_attribute = group_member_attribute.to_string();
+        self
+    }
+
+    /// Members of the directory group get the role, and lose it once they are no longer in the group
+    pub fn map_group(mut self, group: &str, role: &str) -> Self {
+        self.role_mappings.push(RoleMapping {
+            claim: "groups".to_string(),
+            value: group.to_string(),
+            role: role.to_string(),
+        });
+        self
+    }
+
+    fn get_user_attributes(&self) -> Vec<&str> {
+        vec![
+            self.username_attribute.as_str(),
+            self.email_attribute.as_str(),
+            self.display_name_attribute.as_str(),
+        ]
+    }
+}
+
+pub trait DirectoryOps {
+    /// Who the users of the directory are recorded as coming from
+    fn get_issuer(&self) -> Result<String, LdapError>;
+
+    /// Checks the password by binding as the user, `None` if the directory has no such user
+    fn authenticate(&self, username: &str, password: &str) -> Result<Option<DirectoryUser>, LdapError>;
+
+    fn get_users(&self) -> Result<Vec<DirectoryUser>, LdapError>;
+
+    fn get_role_mappings(&self) -> Vec<RoleMapping>;
+}
+
+pub struct LdapClient<'a> {
+    pub settings: &'a Option<LdapSettings>,
+}
+
+/// Escapes a value for use inside a search filter (RFC 4515)
+pub fn escape_filter_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '*' => escaped.push_str("\\2a"),
+            '(' => escaped.push_str("\\28"),
+            ')' => escaped.push_str("\\29"),
+            '\\' => escaped.push_str("\\5c"),
+            '\0' => escaped.push_str("\\00"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn get_attribute(entry: &SearchEntry, attribute: &str) -> Option<String> {
+    entry.attrs
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(attribute))
+        .and_then(|(_, values)| values.first().cloned())
+}
+
+impl<'a> LdapClient<'a> {
+    fn get_settings(&self) -> Result<&'a LdapSettings, LdapError> {
+        self.settings.as_ref().ok_or(LdapError::NotConfigured)
+    }
+
+    /// Connects as the service account
+    fn connect(&self, settings: &LdapSettings) -> Result<LdapConn, LdapError> {
+        let ldap = LdapConn::new(&settings.url)
+            .map_err(|err| LdapError::ConnectionError(err.to_string()))?;
+        ldap.simple_bind(&settings.bind_dn, &settings.bind_password)
+            .and_then(|result| result.success())
+            .map_err(|err| LdapError::ConnectionError(err.to_string()))?;
+
+        Ok(ldap)
+    }
+
+    fn search(&self, ldap: &LdapConn, base_dn: &str, filter: &str, attributes: Vec<&str>) -> Result<Vec<SearchEntry>, LdapError> {
+        let (entries, _) = ldap.search(base_dn, Scope::Subtree, filter, attributes)
+            .and_then(|result| result.success())
+            .map_err(|err| LdapError::SearchError(err.to_string()))?;
+
+        Ok(entries.into_iter().map(SearchEntry::construct).collect())
+    }
+
+    fn to_directory_user(&self, settings: &LdapSettings, entry: &SearchEntry) -> Option<DirectoryUser> {
+        let username = get_attribute(entry, &settings.username_attribute);
+        let email = get_attribute(entry, &settings.email_attribute);
+        match (username, email) {
+            (Some(username), Some(email)) => Some(DirectoryUser {
+                dn: entry.dn.to_owned(),
+                username,
+                email,
+                display_name: get_attribute(entry, &settings.display_name_attribute),
+                groups: vec![],
+            }),
+            _ => {
+                warn!("Skipping {:?}, it does not have a {:?} and a {:?}", &entry.dn, &settings.username_attribute, &settings.email_attribute);
+                None
+            },
+        }
+    }
+
+    /// The groups of every member, keyed by the lowercased dn since dns are not case sensitive
+    fn get_memberships(&self, ldap: &LdapConn, settings: &LdapSettings, member_filter: &str) -> Result<HashMap<String, Vec<String>>, LdapError> {
+        let mut memberships: HashMap<String, Vec<String>> = HashMap::new();
+        let group_base_dn = match &settings.group_base_dn {
+            Some(group_base_dn) => group_base_dn,
+            None => return Ok(memberships),
+        };
+
+        let filter = format!("(&{}{})", &settings.group_filter, member_filter);
+        let attributes = vec![settings.group_name_attribute.as_str(), settings.group_member_attribute.as_str()];
+        for group in self.search(ldap, group_base_dn, &filter, attributes)? {
+            let group_name = match get_attribute(&group, &settings.group_name_attribute) {
+                Some(group_name) => group_name,
+                None => continue,
+            };
+            let members = group.attrs
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&settings.group_member_attribute))
+                .map(|(_, members)| members.to_owned())
+                .unwrap_or_default();
+            for member in members {
+                memberships.entry(member.to_lowercase())
+                    .or_insert_with(Vec::new)
+                    .push(group_name.to_owned());
+            }
+        }
+
+        Ok(memberships)
+    }
+}
+
+impl<'a> DirectoryOps for LdapClient<'a> {
+    fn get_issuer(&self) -> Result<String, LdapError> {
+        Ok(self.get_settings()?.url.to_owned())
+    }
+
+    fn authenticate(&self, username: &str, password: &str) -> Result<Option<DirectoryUser>, LdapError> {
+        let settings = self.get_settings()?;
+        // an empty password is an anonymous bind, which most servers accept
+        if password.is_empty() {
+            return Err(LdapError::InvalidCredentials);
+        }
+
+        let ldap = self.connect(settings)?;
+        let filter = format!("(&{}({}={}))", &settings.user_filter, &settings.username_attribute, escape_filter_value(username));
+        let entries = self.search(&ldap, &settings.user_base_dn, &filter, settings.get_user_attributes())?;
+        // binding as the first of several matches could log the user in as somebody else
+        if entries.len() > 1 {
+            warn!("The search for user {:?} returned {} entries, refusing the login", username, entries.len());
+            let _ = ldap.unbind();
+            return Err(LdapError::InvalidCredentials);
+        }
+        let mut user = match entries.first().and_then(|entry| self.to_directory_user(settings, entry)) {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+
+        let member_filter = format!("({}={})", &settings.group_member_attribute, escape_filter_value(&user.dn));
+        user.groups = self.get_memberships(&ldap, settings, &member_filter)?
+            .remove(&user.dn.to_lowercase())
+            .unwrap_or_default();
+        let _ = ldap.unbind();
+
+        let user_ldap = LdapConn::new(&settings.url)
+            .map_err(|err| LdapError::ConnectionError(err.to_string()))?;
+        let bind_result = user_ldap.simple_bind(&user.dn, password)
+            .map_err(|err| LdapError::ConnectionError(err.to_string()))?;
+        let _ = user_ldap.unbind();
+
+        match bind_result.rc {
+            0 => Ok(Some(user)),
+            INVALID_CREDENTIALS => Err(LdapError::InvalidCredentials),
+            _ => Err(LdapError::ConnectionError(format!("{:?}", bind_result))),
+        }
+    }
+
+    fn get_users(&self) -> Result<Vec<DirectoryUser>, LdapError> {
+        let settings = self.get_settings()?;
+        let ldap = self.connect(settings)?;
+
+        let entries = self.search(&ldap, &settings.user_base_dn, &settings.user_filter, settings.get_user_attributes())?;
+        let mut memberships = self.get_memberships(&ldap, settings, "")?;
+        let _ = ldap.unbind();
+
+        let users = entries
+            .iter()
+            .filter_map(|entry| self.to_directory_user(settings, entry))
+            .map(|user| DirectoryUser {
+                groups: memberships.remove(&user.dn.to_lowercase()).unwrap_or_default(),
+                ..user
+            })
+            .collect();
+
+        Ok(users)
+    }
+
+    fn get_role_mappings(&self) -> Vec<RoleMapping> {
+        self.settings
+            .as_ref()
+            .map(|settings| settings.role_mappings.to_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// An in-process directory, for tests and for trying things out without an LDAP server
+#[derive(Clone, Debug)]
+pub struct StaticDirectory {
+    pub issuer: String,
+    pub users: Vec<(DirectoryUser, String)>,
+    pub role_mappings: Vec<RoleMapping>,
+}
+
+impl StaticDirectory {
+    pub fn new(issuer: &str) -> Self {
+        Self {
+            issuer: issuer.to_string(),
+            users: vec![],
+            role_mappings: vec![],
+        }
+    }
+
+    pub fn user(mut self, user: DirectoryUser, password: &str) -> Self {
+        self.users.push((user, password.to_string()));
+        self
+    }
+
+    pub fn map_group(mut self, group: &str, role: &str) -> Self {
+        self.role_mappings.push(RoleMapping {
+            claim: "groups".to_string(),
+            value: group.to_string(),
+            role: role.to_string(),
+        });
+        self
+    }
+}
+
+impl DirectoryOps for StaticDirectory {
+    fn get_issuer(&self) -> Result<String, LdapError> {
+        Ok(self.issuer.to_owned())
+    }
+
+    fn authenticate(&self, username: &str, password: &str) -> Result<Option<DirectoryUser>, LdapError> {
+        match self.users.iter().find(|(user, _)| user.username == username) {
+            Some((user, user_password)) if !password.is_empty() && user_password == password => Ok(Some(user.to_owned())),
+            Some(_) => Err(LdapError::InvalidCredentials),
+            None => Ok(None),
+        }
+    }
+
+    fn get_users(&self) -> Result<Vec<DirectoryUser>, LdapError> {
+        Ok(self.users.iter().map(|(user, _)| user.to_owned()).collect())
+    }
+
+    fn get_role_mappings(&self) -> Vec<RoleMapping> {
+        self.role_mappings.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_escape_filter_value() {
+        assert_eq!(escape_filter_value("jdoe"), "jdoe");
+        assert_eq!(escape_filter_value("*)(uid=*"), "\\2a\\29\\28uid=\\2a");
+        assert_eq!(escape_filter_value("cn=a\\,b"), "cn=a\\5c,b");
+    }
+
+    #[test]
+    fn test_not_configured() {
+        let client = LdapClient { settings: &None };
+        assert_eq!(client.authenticate("jdoe", "password").unwrap_err(), LdapError::NotConfigured);
+        assert_eq!(client.get_users().unwrap_err(), LdapError::NotConfigured);
+        assert!(client.get_role_mappings().is_empty());
+    }
+
+    #[test]
+    fn test_static_directory() {
+        let user = DirectoryUser {
+            dn: "uid=jdoe,ou=people,dc=example,dc=com".to_string(),
+            username: "jdoe".to_string(),
+            email: "jdoe@example.com".to_string(),
+            display_name: None,
+            groups: vec!["engineering".to_string()],
+        };
+        let directory = StaticDirectory::new("ldap://localhost")
+            .user(user.clone(), "secret")
+            .map_group("engineering", "developer");
+
+        assert_eq!(directory.authenticate("jdoe", "secret").unwrap(), Some(user.clone()));
+        assert_eq!(directory.authenticate("jdoe", "wrong").unwrap_err(), LdapError::InvalidCredentials);
+        assert_eq!(directory.authenticate("jdoe", "").unwrap_err(), LdapError::InvalidCredentials);
+        assert_eq!(directory.authenticate("nobody", "secret").unwrap(), None);
+
+        let external_user = user.to_external_user("ldap://localhost");
+        assert_eq!(external_user.subject, user.dn);
+        assert!(directory.get_role_mappings()[0].matches(&external_user.claims));
+    }
+}
@@ -0,0 +1,66 @@
+/// How failed logins slow down the next attempts, counts are kept per user and per address
+#[derive(Clone, Debug)]
+pub struct LoginThrottleSettings {
+    /// failed logins in a row for a user before they have to wait
+    pub user_free_attempts: i32,
+    /// failed logins from an address, across users, before it has to wait
+    pub ip_free_attempts: i32,
+    /// the first wait, it is doubled for every failure after that
+    pub base_delay_secs: i64,
+    pub max_delay_secs: i64,
+    /// the user is locked after this many failed logins in a row, until an admin unlocks them or the lockout ends
+    pub lockout_attempts: i32,
+    pub lockout_secs: i64,
+    /// failures are forgotten when there was none for this long
+    pub reset_after_secs: i64,
+}
+
+impl Default for LoginThrottleSettings {
+    fn default() -> Self {
+        Self {
+            user_free_attempts: 3,
+            ip_free_attempts: 20,
+            base_delay_secs: 1,
+            max_delay_secs: 300,
+            lockout_attempts: 10,
+            lockout_secs: 3600,
+            reset_after_secs: 3600,
+        }
+    }
+}
+
+impl LoginThrottleSettings {
+    /// Seconds to wait after the latest of `failed_attempts` failures in a row
+    pub fn get_delay(&self, failed_attempts: i32, free_attempts: i32) -> Option<i64> {
+        if failed_attempts <= free_attempts {
+            return None;
+        }
+
+        let doublings = (failed_attempts - free_attempts - 1).min(32) as u32;
+        let delay = self.base_delay_secs.saturating_mul(2i64.saturating_pow(doublings));
+
+        Some(delay.min(self.max_delay_secs))
+    }
+
+    pub fn is_lockout(&self, failed_attempts: i32) -> bool {
+        failed_attempts >= self.lockout_attempts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_delay() {
+        let settings = LoginThrottleSettings::default();
+        assert_eq!(settings.get_delay(3, 3), None);
+        assert_eq!(settings.get_delay(4, 3), Some(1));
+        assert_eq!(settings.get_delay(5, 3), Some(2));
+        assert_eq!(settings.get_delay(8, 3), Some(16));
+        assert_eq!(settings.get_delay(1000, 3), Some(300));
+
+        assert!(!settings.is_lockout(9));
+        assert!(settings.is_lockout(10));
+    }
+}
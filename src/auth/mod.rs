@@ -1,4 +1,8 @@
-
+pub mod ldap;
+pub mod login_throttle;
+pub mod oidc;
+pub mod password_policy;
 pub mod send_mail;
+pub mod sessions;
 pub mod tokens;
-
+pub mod totp;
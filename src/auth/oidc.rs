@@ -0,0 +1,503 @@
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
+
+use base64;
+use jsonwebtoken;
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::Validation;
+use openssl::bn::BigNum;
+use openssl::rsa::Rsa;
+use openssl::sha::sha256;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use reqwest;
+use serde::de::DeserializeOwned;
+use serde_json;
+use url::Url;
+
+use data::auth::ExternalUser;
+use data::auth::LoginRequest;
+
+/// How long the provider's configuration and keys are used before they are fetched again
+const PROVIDER_CACHE_SECS: u64 = 60 * 60;
+
+#[derive(Debug, Fail, Clone, PartialEq, Eq)]
+pub enum OidcError {
+    #[fail(display = "Single sign-on is not configured")]
+    NotConfigured,
+    #[fail(display = "Could not reach the identity provider: {}", 0)]
+    ProviderError(String),
+    #[fail(display = "The identity provider rejected the code: {}", 0)]
+    TokenExchangeError(String),
+    #[fail(display = "Invalid id token: {}", 0)]
+    InvalidToken(String),
+    #[fail(display = "The id token is missing the {} claim", 0)]
+    MissingClaim(String),
+    #[fail(display = "An unknown error occurred")]
+    Unknown,
+}
+
+/// Grants `role` to users whose `claim` is `value`, or contains `value` if the claim is a list (i.e. groups)
+#[derive(Clone, Debug)]
+pub struct RoleMapping {
+    pub claim: String,
+    pub value: String,
+    pub role: String,
+}
+
+impl RoleMapping {
+    pub fn matches(&self, claims: &serde_json::Value) -> bool {
+        match claims.get(&self.claim) {
+            Some(serde_json::Value::String(value)) => value == &self.value,
+            Some(serde_json::Value::Array(values)) => values.iter().any(|value| value.as_str() == Some(self.value.as_str())),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct OidcSettings {
+    /// The provider's configuration is discovered from `{issuer}/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    /// Public clients only use PKCE
+    pub client_secret: Option<String>,
+    /// The page of the frontend that the provider redirects back to, it calls `oidcCallback` with the code and state
+    pub redirect_url: String,
+    pub scopes: Vec<String>,
+    /// The claim that becomes the username of users that log in for the first time
+    pub username_claim: String,
+    pub groups_claim: String,
+    pub role_mappings: Vec<RoleMapping>,
+    /// Shared by the clones of the settings, so every executor uses the same one
+    cache: ProviderCache,
+}
+
+impl OidcSettings {
+    pub fn new(issuer: &str, client_id: &str, redirect_url: &str) -> Self {
+        Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: client_id.to_string(),
+            client_secret: None,
+            redirect_url: redirect_url.to_string(),
+            scopes: vec!["openid".to_string(), "email".to_string(), "profile".to_string()],
+            username_claim: "preferred_username".to_string(),
+            groups_claim: "groups".to_string(),
+            role_mappings: vec![],
+            cache: ProviderCache::default(),
+        }
+    }
+
+    pub fn client_secret(mut self, client_secret: &str) -> Self {
+        self.client_secret = Some(client_secret.to_string());
+        self
+    }
+
+    /// Extra scopes on top of `openid`, i.e. `groups` for providers that only send the groups when asked
+    pub fn scope(mut self, scope: &str) -> Self {
+        self.scopes.push(scope.to_string());
+        self
+    }
+
+    pub fn username_claim(mut self, username_claim: &str) -> Self {
+        self.username_claim = username_claim.to_string();
+        self
+    }
+
+    pub fn groups_claim(mut self, groups_claim: &str) -> Self {
+        self.groups_claim = groups_claim.to_string();
+        self
+    }
+
+    /// Members of the provider's group get the role, and lose it once they are no longer in the group
+    pub fn map_group(self, group: &str, role: &str) -> Self {
+        let groups_claim = self.groups_claim.to_owned();
+        self.map_claim(&groups_claim, group, role)
+    }
+
+    pub fn map_claim(mut self, claim: &str, value: &str, role: &str) -> Self {
+        self.role_mappings.push(RoleMapping {
+            claim: claim.to_string(),
+            value: value.to_string(),
+            role: role.to_string(),
+        });
+        self
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct JsonWebKey {
+    kty: String,
+    kid: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct JsonWebKeySet {
+    keys: Vec<JsonWebKey>,
+}
+
+/// The provider's configuration and signing keys, so that not every login has to fetch them
+#[derive(Clone, Debug, Default)]
+struct ProviderCache {
+    metadata: Arc<RwLock<Option<(ProviderMetadata, Instant)>>>,
+    key_set: Arc<RwLock<Option<(JsonWebKeySet, Instant)>>>,
+}
+
+fn get_cached<T: Clone>(cached: &RwLock<Option<(T, Instant)>>) -> Option<T> {
+    let ttl = Duration::from_secs(PROVIDER_CACHE_SECS);
+    cached
+        .read()
+        .ok()
+        .and_then(|cached| match &*cached {
+            Some((value, fetched_at)) if fetched_at.elapsed() < ttl => Some(value.to_owned()),
+            _ => None,
+        })
+}
+
+fn set_cached<T>(cached: &RwLock<Option<(T, Instant)>>, value: T) {
+    if let Ok(mut cached) = cached.write() {
+        *cached = Some((value, Instant::now()));
+    }
+}
+
+pub struct OidcClient<'a> {
+    pub settings: &'a Option<OidcSettings>,
+}
+
+pub trait OidcOps {
+    /// The url that the user is sent to, and what has to be kept around until the provider redirects back
+    fn authorization_request(&self) -> Result<(String, LoginRequest), OidcError>;
+
+    /// Trades the code from the redirect for the id token, and returns who logged in
+    fn exchange_code(&self, code: &str, login_request: &LoginRequest) -> Result<ExternalUser, OidcError>;
+
+    fn get_role_mappings(&self) -> Vec<RoleMapping>;
+}
+
+/// Url safe, 32 random bytes are 43 characters which is the shortest PKCE verifier allowed
+fn random_string() -> Result<String, OidcError> {
+    let mut rng = OsRng::new().map_err(|_| OidcError::Unknown)?;
+    let mut bytes = [0u8; 32];
+    rng.fill_bytes(&mut bytes);
+    Ok(base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD))
+}
+
+fn code_challenge(code_verifier: &str) -> String {
+    base64::encode_config(&sha256(code_verifier.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+fn get_json<T: DeserializeOwned>(url: &str) -> Result<T, OidcError> {
+    reqwest::get(url)
+        .and_then(|response| response.error_for_status())
+        .and_then(|mut response| response.json())
+        .map_err(|err| OidcError::ProviderError(err.to_string()))
+}
+
+impl<'a> OidcClient<'a> {
+    fn get_settings(&self) -> Result<&'a OidcSettings, OidcError> {
+        self.settings.as_ref().ok_or(OidcError::NotConfigured)
+    }
+
+    fn get_provider_metadata(&self, settings: &OidcSettings) -> Result<ProviderMetadata, OidcError> {
+        if let Some(metadata) = get_cached(&settings.cache.metadata) {
+            return Ok(metadata);
+        }
+
+        let metadata: ProviderMetadata = get_json(&format!("{}/.well-known/openid-configuration", &settings.issuer))?;
+        set_cached(&settings.cache.metadata, metadata.to_owned());
+
+        Ok(metadata)
+    }
+
+    /// The keys are fetched again when the token is signed with one that isn't known, the provider might have rotated them
+    fn get_signing_key(&self, settings: &OidcSettings, metadata: &ProviderMetadata, kid: &Option<String>) -> Result<JsonWebKey, OidcError> {
+        let find_key = |key_set: &JsonWebKeySet| key_set.keys
+            .iter()
+            .find(|key| key.kty == "RSA" && (kid.is_none() || &key.kid == kid))
+            .cloned();
+
+        if let Some(key) = get_cached(&settings.cache.key_set).and_then(|key_set| find_key(&key_set)) {
+            return Ok(key);
+        }
+
+        let key_set: JsonWebKeySet = get_json(&metadata.jwks_uri)?;
+        let key = find_key(&key_set);
+        set_cached(&settings.cache.key_set, key_set);
+
+        key.ok_or_else(|| OidcError::InvalidToken("no matching signing key".to_string()))
+    }
+
+    fn get_id_token(&self, settings: &OidcSettings, metadata: &ProviderMetadata, code: &str, code_verifier: &str) -> Result<String, OidcError> {
+        let mut params = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", settings.redirect_url.as_str()),
+            ("client_id", settings.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &settings.client_secret {
+            params.push(("client_secret", client_secret.as_str()));
+        }
+
+        let mut response = reqwest::Client::new()
+            .post(&metadata.token_endpoint)
+            .form(&params)
+            .send()
+            .map_err(|err| OidcError::ProviderError(err.to_string()))?;
+
+        if !response.status().is_success() {
+            let body = response.text().unwrap_or_default();
+            return Err(OidcError::TokenExchangeError(body));
+        }
+
+        response
+            .json::<TokenResponse>()
+            .map(|token_response| token_response.id_token)
+            .map_err(|err| OidcError::TokenExchangeError(err.to_string()))
+    }
+
+    /// The id token has to be signed by one of the provider's keys, for this client, and not be expired
+    fn verify_id_token(&self, settings: &OidcSettings, metadata: &ProviderMetadata, id_token: &str) -> Result<serde_json::Value, OidcError> {
+        let header = jsonwebtoken::decode_header(id_token)
+            .map_err(|err| OidcError::InvalidToken(err.to_string()))?;
+        if header.alg != Algorithm::RS256 {
+            return Err(OidcError::InvalidToken(format!("unsupported algorithm {:?}", header.alg)));
+        }
+
+        let key = self.get_signing_key(settings, metadata, &header.kid)?;
+
+        let component = |value: &Option<String>| -> Result<BigNum, OidcError> {
+            let bytes = value
+                .as_ref()
+                .and_then(|value| base64::decode_config(value, base64::URL_SAFE_NO_PAD).ok())
+                .ok_or_else(|| OidcError::InvalidToken("malformed signing key".to_string()))?;
+            BigNum::from_slice(&bytes).map_err(|err| OidcError::InvalidToken(err.to_string()))
+        };
+        let public_key = Rsa::from_public_components(component(&key.n)?, component(&key.e)?)
+            .and_then(|rsa| rsa.public_key_to_der_pkcs1())
+            .map_err(|err| OidcError::InvalidToken(err.to_string()))?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.iss = Some(metadata.issuer.to_owned());
+        validation.set_audience(&settings.client_id);
+
+        jsonwebtoken::decode::<serde_json::Value>(id_token, &public_key, &validation)
+            .map(|token_data| token_data.claims)
+            .map_err(|err| OidcError::InvalidToken(err.to_string()))
+    }
+}
+
+impl<'a> OidcOps for OidcClient<'a> {
+    fn authorization_request(&self) -> Result<(String, LoginRequest), OidcError> {
+        let settings = self.get_settings()?;
+        let metadata = self.get_provider_metadata(settings)?;
+
+        let login_request = LoginRequest {
+            state: random_string()?,
+            code_verifier: random_string()?,
+            nonce: random_string()?,
+        };
+
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .map_err(|err| OidcError::ProviderError(err.to_string()))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &settings.client_id)
+            .append_pair("redirect_uri", &settings.redirect_url)
+            .append_pair("scope", &settings.scopes.join(" "))
+            .append_pair("state", &login_request.state)
+            .append_pair("nonce", &login_request.nonce)
+            .append_pair("code_challenge", &code_challenge(&login_request.code_verifier))
+            .append_pair("code_challenge_method", "S256");
+
+        Ok((url.to_string(), login_request))
+    }
+
+    fn exchange_code(&self, code: &str, login_request: &LoginRequest) -> Result<ExternalUser, OidcError> {
+        let settings = self.get_settings()?;
+        let metadata = self.get_provider_metadata(settings)?;
+
+        let id_token = self.get_id_token(settings, &metadata, code, &login_request.code_verifier)?;
+        let claims = self.verify_id_token(settings, &metadata, &id_token)?;
+
+        if claims.get("nonce").and_then(|nonce| nonce.as_str()) != Some(login_request.nonce.as_str()) {
+            return Err(OidcError::InvalidToken("the nonce does not match".to_string()));
+        }
+
+        let claim = |name: &str| claims.get(name).and_then(|value| value.as_str()).map(|value| value.to_string());
+        let subject = claim("sub").ok_or_else(|| OidcError::MissingClaim("sub".to_string()))?;
+        let email = claim("email").ok_or_else(|| OidcError::MissingClaim("email".to_string()))?;
+        let username = claim(&settings.username_claim).unwrap_or_else(|| email.to_owned());
+        let display_name = claim("name");
+
+        info!("{:?} logged in through {:?}", &username, &metadata.issuer);
+        Ok(ExternalUser {
+            issuer: metadata.issuer,
+            subject,
+            username,
+            email,
+            display_name,
+            claims,
+        })
+    }
+
+    fn get_role_mappings(&self) -> Vec<RoleMapping> {
+        self.settings
+            .as_ref()
+            .map(|settings| settings.role_mappings.to_owned())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::thread;
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    use chrono::Utc;
+    use jsonwebtoken::Header;
+
+    /// Answers the discovery, the key set, and a single token request, then returns the body of the token request
+    fn mock_provider(nonce: &'static str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let issuer = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let rsa = Rsa::generate(2048).unwrap();
+
+        let provider_issuer = issuer.to_owned();
+        let handle = thread::spawn(move || {
+            let mut token_request = String::new();
+            // discovery for the authorization request, then token and keys for the code exchange, the discovery is cached
+            for stream in listener.incoming().take(3) {
+                let stream = stream.unwrap();
+                let mut writer = stream.try_clone().unwrap();
+                let mut reader = BufReader::new(stream);
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    let line = line.to_lowercase();
+                    if line.starts_with("content-length:") {
+                        content_length = line["content-length:".len()..].trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let response = if request_line.contains("/.well-known/openid-configuration") {
+                    json!({
+                        "issuer": provider_issuer,
+                        "authorization_endpoint": format!("{}/authorize", provider_issuer),
+                        "token_endpoint": format!("{}/token", provider_issuer),
+                        "jwks_uri": format!("{}/keys", provider_issuer),
+                    })
+                } else if request_line.contains("/keys") {
+                    json!({
+                        "keys": [{
+                            "kty": "RSA",
+                            "kid": "test-key",
+                            "n": base64::encode_config(&rsa.n().to_vec(), base64::URL_SAFE_NO_PAD),
+                            "e": base64::encode_config(&rsa.e().to_vec(), base64::URL_SAFE_NO_PAD),
+                        }],
+                    })
+                } else {
+                    token_request = String::from_utf8(body).unwrap();
+                    let now = Utc::now().timestamp();
+                    let claims = json!({
+                        "iss": provider_issuer,
+                        "aud": "kakapo",
+                        "sub": "external-id-1",
+                        "iat": now,
+                        "exp": now + 60,
+                        "nonce": nonce,
+                        "email": "sso@example.com",
+                        "preferred_username": "sso_user",
+                        "name": "SSO User",
+                        "groups": ["engineering", "everyone"],
+                    });
+                    let mut header = Header::new(Algorithm::RS256);
+                    header.kid = Some("test-key".to_string());
+                    let id_token = jsonwebtoken::encode(&header, &claims, &rsa.private_key_to_der().unwrap()).unwrap();
+                    json!({ "access_token": "unused", "token_type": "Bearer", "id_token": id_token })
+                };
+
+                let response = response.to_string();
+                write!(writer, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                       response.len(), response).unwrap();
+            }
+
+            token_request
+        });
+
+        (issuer, handle)
+    }
+
+    #[test]
+    fn test_authorization_code_flow() {
+        let nonce = "a-nonce-from-the-login-request";
+        let (issuer, provider) = mock_provider(nonce);
+        let settings = Some(OidcSettings::new(&issuer, "kakapo", "http://kakapo.test/sso")
+            .map_group("engineering", "developer")
+            .map_group("finance", "accountant"));
+        let client = OidcClient { settings: &settings };
+
+        let (url, login_request) = client.authorization_request().unwrap();
+        assert!(url.starts_with(&format!("{}/authorize?", issuer)));
+        assert!(url.contains(&format!("code_challenge={}", code_challenge(&login_request.code_verifier))));
+        assert!(url.contains("code_challenge_method=S256"));
+
+        let login_request = LoginRequest {
+            nonce: nonce.to_string(),
+            ..login_request
+        };
+        let user = client.exchange_code("the-code", &login_request).unwrap();
+        assert_eq!(user.subject, "external-id-1");
+        assert_eq!(user.username, "sso_user");
+        assert_eq!(user.email, "sso@example.com");
+
+        let roles: Vec<_> = client.get_role_mappings()
+            .into_iter()
+            .filter(|mapping| mapping.matches(&user.claims))
+            .map(|mapping| mapping.role)
+            .collect();
+        assert_eq!(roles, vec!["developer"]);
+
+        let token_request = provider.join().unwrap();
+        assert!(token_request.contains("code=the-code"));
+        assert!(token_request.contains(&format!("code_verifier={}", login_request.code_verifier)));
+    }
+
+    #[test]
+    fn test_not_configured() {
+        let client = OidcClient { settings: &None };
+        assert_eq!(client.authorization_request().unwrap_err(), OidcError::NotConfigured);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::Duration;
+use chrono::NaiveDateTime;
+
+/// Rejected by `reject_common_passwords`, a longer list can be added with `breached_passwords_file`
+const COMMON_PASSWORDS: &'static [&'static str] = &[
+    "123456", "123456789", "12345678", "12345", "1234567", "1234567890", "111111", "000000",
+    "123123", "654321", "666666", "121212", "password", "password1", "password123", "passw0rd",
+    "qwerty", "qwerty123", "qwertyuiop", "1q2w3e4r", "1qaz2wsx", "abc123", "iloveyou", "admin",
+    "admin123", "welcome", "welcome1", "letmein", "monkey", "dragon", "football", "baseball",
+    "sunshine", "princess", "master", "shadow", "superman", "trustno1", "hello123", "freedom",
+    "whatever", "starwars", "zaq12wsx", "changeme", "secret", "hunter2", "p@ssw0rd", "login",
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+}
+
+impl CharacterClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharacterClass::Lowercase => c.is_lowercase(),
+            CharacterClass::Uppercase => c.is_uppercase(),
+            CharacterClass::Digit => c.is_numeric(),
+            CharacterClass::Symbol => !c.is_alphanumeric() && !c.is_whitespace(),
+        }
+    }
+}
+
+/// A rule that the password broke, sent back so that clients can show what has to change
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "rule")]
+pub enum PasswordViolation {
+    #[serde(rename_all = "camelCase")]
+    TooShort { min_length: usize },
+    #[serde(rename_all = "camelCase")]
+    MissingCharacterClass { character_class: CharacterClass },
+    Common,
+    #[serde(rename_all = "camelCase")]
+    Reused { history_size: usize },
+}
+
+/// Checked whenever a password is set, nothing is enforced by default
+#[derive(Clone, Debug, Default)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub required_character_classes: Vec<CharacterClass>,
+    /// lowercase, compared against the lowercase password
+    pub rejected_passwords: HashSet<String>,
+    /// how many of the latest passwords can't be used again, their hashes are kept in `user_info`
+    pub history_size: usize,
+    /// the password has to be reset after this many days
+    pub max_age_days: Option<i64>,
+}
+
+impl PasswordPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    pub fn require(mut self, character_class: CharacterClass) -> Self {
+        if !self.required_character_classes.contains(&character_class) {
+            self.required_character_classes.push(character_class);
+        }
+        self
+    }
+
+    pub fn reject_common_passwords(mut self) -> Self {
+        self.rejected_passwords.extend(COMMON_PASSWORDS.iter().map(|password| password.to_string()));
+        self
+    }
+
+    /// One password per line, i.e. a list from a breach corpus
+    pub fn breached_passwords_file(mut self, path: &Path) -> io::Result<Self> {
+        let passwords = fs::read_to_string(path)?;
+        self.rejected_passwords.extend(passwords
+            .lines()
+            .map(|password| password.trim().to_lowercase())
+            .filter(|password| !password.is_empty()));
+        Ok(self)
+    }
+
+    pub fn history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self
+    }
+
+    pub fn max_age_days(mut self, max_age_days: i64) -> Self {
+        self.max_age_days = Some(max_age_days);
+        self
+    }
+
+    /// Everything except reuse, which needs the hashes of the previous passwords
+    pub fn validate(&self, password: &str) -> Vec<PasswordViolation> {
+        let mut violations = vec![];
+
+        if password.chars().count() < self.min_length {
+            violations.push(PasswordViolation::TooShort { min_length: self.min_length });
+        }
+
+        for character_class in self.required_character_classes.iter() {
+            if !password.chars().any(|c| character_class.matches(c)) {
+                violations.push(PasswordViolation::MissingCharacterClass { character_class: character_class.to_owned() });
+            }
+        }
+
+        if self.rejected_passwords.contains(&password.to_lowercase()) {
+            violations.push(PasswordViolation::Common);
+        }
+
+        violations
+    }
+
+    /// Passwords without a change date never expire, i.e. the ones set before the policy
+    pub fn is_expired(&self, changed_at: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+        match (self.max_age_days, changed_at) {
+            (Some(max_age_days), Some(changed_at)) => changed_at + Duration::days(max_age_days) < now,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_validate() {
+        let policy = PasswordPolicy::new()
+            .min_length(10)
+            .require(CharacterClass::Uppercase)
+            .require(CharacterClass::Symbol)
+            .reject_common_passwords();
+
+        assert_eq!(policy.validate("correct Horse battery staple!"), vec![]);
+        assert_eq!(policy.validate("Password"), vec![
+            PasswordViolation::TooShort { min_length: 10 },
+            PasswordViolation::MissingCharacterClass { character_class: CharacterClass::Symbol },
+            PasswordViolation::Common,
+        ]);
+        assert_eq!(PasswordPolicy::default().validate("hunter2"), vec![]);
+
+        let violation = serde_json::to_value(PasswordViolation::TooShort { min_length: 10 }).unwrap();
+        assert_eq!(violation, json!({ "rule": "tooShort", "minLength": 10 }));
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let policy = PasswordPolicy::new().max_age_days(90);
+        let changed_at = NaiveDateTime::from_timestamp(0, 0);
+
+        assert!(policy.is_expired(Some(changed_at), changed_at + Duration::days(91)));
+        assert!(!policy.is_expired(Some(changed_at), changed_at + Duration::days(89)));
+        assert!(!policy.is_expired(None, changed_at + Duration::days(91)));
+    }
+}
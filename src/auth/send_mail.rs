@@ -1,22 +1,218 @@
+use std::path::PathBuf;
+
+use lettre::Transport;
+use lettre::SmtpClient;
+use lettre::FileTransport;
+use lettre::ClientSecurity;
+use lettre::ClientTlsParameters;
+use lettre::smtp::authentication::Credentials;
+use lettre_email::Email;
+use lettre_email::EmailBuilder;
+use native_tls::TlsConnector;
+
 use data::auth::InvitationToken;
 use data::auth::Invitation;
+use data::auth::PasswordResetToken;
 
 #[derive(Debug, Fail, Clone, PartialEq, Eq)]
 pub enum EmailError {
+    #[fail(display = "Mail is not configured")]
+    NotConfigured,
+    #[fail(display = "Invalid email: {}", 0)]
+    InvalidEmail(String),
+    #[fail(display = "Could not send email: {}", 0)]
+    SendError(String),
     #[fail(display = "An unknown error occurred")]
     Unknown,
 }
 
-pub struct EmailSender {}
+#[derive(Clone, Debug)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<(String, String)>,
+    pub starttls: bool,
+}
+
+impl SmtpSettings {
+    /// STARTTLS is required unless it is turned off with `without_tls`
+    pub fn new(host: &str, port: u16) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            credentials: None,
+            starttls: true,
+        }
+    }
+
+    pub fn credentials(mut self, username: &str, password: &str) -> Self {
+        self.credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Only for local relays and test sinks
+    pub fn without_tls(mut self) -> Self {
+        self.starttls = false;
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum MailTransport {
+    /// Nothing is sent, sending fails with `EmailError::NotConfigured`
+    Disabled,
+    Smtp(SmtpSettings),
+    /// Every email is written as a file into the directory, meant for development
+    Spool(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+pub struct EmailTemplate {
+    pub subject: String,
+    pub body: String,
+}
+
+impl EmailTemplate {
+    pub fn new(subject: &str, body: &str) -> Self {
+        Self {
+            subject: subject.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    /// Replaces every `{{name}}` in the subject and the body with the value of the variable
+    pub fn render(&self, variables: &[(&str, &str)]) -> (String, String) {
+        let mut subject = self.subject.to_owned();
+        let mut body = self.body.to_owned();
+        for (name, value) in variables {
+            let placeholder = format!("{{{{{}}}}}", name);
+            subject = subject.replace(&placeholder, value);
+            body = body.replace(&placeholder, value);
+        }
+
+        (subject, body)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MailSettings {
+    pub transport: MailTransport,
+    pub from: String,
+    /// The page that accepts invitations, the token is added as the `token` query parameter
+    pub invitation_url: String,
+    pub invitation_template: EmailTemplate,
+    /// The page where the new password is chosen, the token is added as the `token` query parameter
+    pub password_reset_url: String,
+    pub password_reset_template: EmailTemplate,
+    pub notification_template: EmailTemplate,
+}
+
+impl Default for MailSettings {
+    fn default() -> Self {
+        Self {
+            transport: MailTransport::Disabled,
+            from: "kakapo@localhost".to_string(),
+            invitation_url: "http://localhost:1845/invitation".to_string(),
+            invitation_template: EmailTemplate::new(
+                "You have been invited to Kakapo",
+                "You have been invited to join Kakapo as {{email}}.\n\nAccept the invitation here: {{link}}\n\nThe invitation expires at {{expiresAt}}.\n"),
+            password_reset_url: "http://localhost:1845/resetPassword".to_string(),
+            password_reset_template: EmailTemplate::new(
+                "Reset your Kakapo password",
+                "A password reset was requested for {{email}}.\n\nChoose a new password here: {{link}}\n\nThe link can only be used once and expires at {{expiresAt}}. If you didn't request this, you can ignore this email.\n"),
+            notification_template: EmailTemplate::new(
+                "{{subject}}",
+                "{{message}}\n"),
+        }
+    }
+}
+
+impl MailSettings {
+    pub fn get_accept_link(&self, token: &str) -> String {
+        with_token(&self.invitation_url, token)
+    }
+
+    pub fn get_password_reset_link(&self, token: &str) -> String {
+        with_token(&self.password_reset_url, token)
+    }
+}
+
+fn with_token(url: &str, token: &str) -> String {
+    let separator = if url.contains('?') { "&" } else { "?" };
+    format!("{}{}token={}", url, separator, token)
+}
+
+pub struct EmailSender<'a> {
+    pub settings: &'a MailSettings,
+}
 
 pub trait EmailOps {
     fn send_email(&self, invitation_token: InvitationToken) -> Result<Invitation, EmailError>;
+
+    fn send_password_reset(&self, password_reset_token: PasswordResetToken) -> Result<(), EmailError>;
+
+    fn send_notification(&self, email: &str, subject: &str, message: &str) -> Result<(), EmailError>;
 }
 
+impl<'a> EmailSender<'a> {
+    fn build_email(&self, to: &str, subject: String, body: String) -> Result<Email, EmailError> {
+        EmailBuilder::new()
+            .to(to)
+            .from(self.settings.from.as_str())
+            .subject(subject)
+            .text(body)
+            .build()
+            .map_err(|err| EmailError::InvalidEmail(err.to_string()))
+    }
+
+    fn deliver(&self, email: Email) -> Result<(), EmailError> {
+        match &self.settings.transport {
+            MailTransport::Disabled => Err(EmailError::NotConfigured),
+            MailTransport::Smtp(smtp) => {
+                let security = if smtp.starttls {
+                    let connector = TlsConnector::new()
+                        .map_err(|err| EmailError::SendError(err.to_string()))?;
+                    ClientSecurity::Required(ClientTlsParameters::new(smtp.host.to_owned(), connector))
+                } else {
+                    ClientSecurity::None
+                };
 
-impl EmailOps for EmailSender {
+                let mut client = SmtpClient::new((smtp.host.as_str(), smtp.port), security)
+                    .map_err(|err| EmailError::SendError(err.to_string()))?;
+                if let Some((username, password)) = &smtp.credentials {
+                    client = client.credentials(Credentials::new(username.to_owned(), password.to_owned()));
+                }
+
+                client
+                    .transport()
+                    .send(email.into())
+                    .map(|_| ())
+                    .map_err(|err| EmailError::SendError(err.to_string()))
+            },
+            MailTransport::Spool(directory) => {
+                FileTransport::new(directory)
+                    .send(email.into())
+                    .map_err(|err| EmailError::SendError(err.to_string()))
+            },
+        }
+    }
+}
+
+impl<'a> EmailOps for EmailSender<'a> {
     fn send_email(&self, invitation_token: InvitationToken) -> Result<Invitation, EmailError> {
-        //TODO: send email
+        let link = self.settings.get_accept_link(&invitation_token.token);
+        let expires_at = invitation_token.expires_at.to_string();
+        let (subject, body) = self.settings.invitation_template.render(&[
+            ("email", invitation_token.email.as_str()),
+            ("token", invitation_token.token.as_str()),
+            ("link", link.as_str()),
+            ("expiresAt", expires_at.as_str()),
+        ]);
+
+        let email = self.build_email(&invitation_token.email, subject, body)?;
+        self.deliver(email)?;
+        info!("sent invitation to {:?}", &invitation_token.email);
+
         let inviatation = Invitation {
             email: invitation_token.email,
             expires_at: invitation_token.expires_at,
@@ -24,4 +220,146 @@ impl EmailOps for EmailSender {
 
         Ok(inviatation)
     }
-}
\ No newline at end of file
+
+    fn send_password_reset(&self, password_reset_token: PasswordResetToken) -> Result<(), EmailError> {
+        let link = self.settings.get_password_reset_link(&password_reset_token.token);
+        let expires_at = password_reset_token.expires_at.to_string();
+        let (subject, body) = self.settings.password_reset_template.render(&[
+            ("email", password_reset_token.email.as_str()),
+            ("link", link.as_str()),
+            ("expiresAt", expires_at.as_str()),
+        ]);
+
+        let email = self.build_email(&password_reset_token.email, subject, body)?;
+        self.deliver(email)?;
+        info!("sent password reset to {:?}", &password_reset_token.email);
+
+        Ok(())
+    }
+
+    fn send_notification(&self, email: &str, subject: &str, message: &str) -> Result<(), EmailError> {
+        let (subject, body) = self.settings.notification_template.render(&[
+            ("email", email),
+            ("subject", subject),
+            ("message", message),
+        ]);
+
+        let email = self.build_email(email, subject, body)?;
+        self.deliver(email)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::thread;
+    use std::io::BufRead;
+    use std::io::BufReader;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    use chrono::NaiveDate;
+    use tempfile;
+
+    fn invitation_token() -> InvitationToken {
+        InvitationToken {
+            email: "invited@example.com".to_string(),
+            token: "abc123".to_string(),
+            expires_at: NaiveDate::from_ymd(2019, 3, 1).and_hms(12, 0, 0),
+        }
+    }
+
+    /// Accepts a single email and returns everything that was sent as the message
+    fn smtp_sink() -> (u16, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut message = String::new();
+            let mut in_data = false;
+
+            writer.write_all(b"220 localhost ESMTP sink\r\n").unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+
+                if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        writer.write_all(b"250 OK\r\n").unwrap();
+                    } else {
+                        message.push_str(&line);
+                    }
+                    continue;
+                }
+
+                let command = line.to_uppercase();
+                if command.starts_with("EHLO") || command.starts_with("HELO") {
+                    writer.write_all(b"250 localhost\r\n").unwrap();
+                } else if command.starts_with("DATA") {
+                    in_data = true;
+                    writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").unwrap();
+                } else if command.starts_with("QUIT") {
+                    writer.write_all(b"221 Bye\r\n").unwrap();
+                    break;
+                } else {
+                    writer.write_all(b"250 OK\r\n").unwrap();
+                }
+            }
+
+            message
+        });
+
+        (port, handle)
+    }
+
+    #[test]
+    fn test_send_invitation_over_smtp() {
+        let (port, sink) = smtp_sink();
+        let settings = MailSettings {
+            transport: MailTransport::Smtp(SmtpSettings::new("127.0.0.1", port).without_tls()),
+            invitation_url: "http://kakapo.test/invitation".to_string(),
+            ..MailSettings::default()
+        };
+        let sender = EmailSender { settings: &settings };
+
+        let invitation = sender.send_email(invitation_token()).unwrap();
+        assert_eq!(invitation.email, "invited@example.com");
+
+        let message = sink.join().unwrap();
+        assert!(message.contains("invited@example.com"));
+        assert!(message.contains("http://kakapo.test/invitation?token=abc123"));
+    }
+
+    #[test]
+    fn test_disabled_transport() {
+        let settings = MailSettings::default();
+        let sender = EmailSender { settings: &settings };
+
+        assert_eq!(sender.send_email(invitation_token()).unwrap_err(), EmailError::NotConfigured);
+    }
+
+    #[test]
+    fn test_spool_notification() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = MailSettings {
+            transport: MailTransport::Spool(dir.path().to_owned()),
+            ..MailSettings::default()
+        };
+        let sender = EmailSender { settings: &settings };
+
+        sender.send_notification("someone@example.com", "Hello", "Something happened").unwrap();
+
+        let files: Vec<_> = fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        let contents = fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+        assert!(contents.contains("someone@example.com"));
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Sessions that were recently found to be active, so that not every request has to hit the metastore.
+/// The cache is shared between the executors, revoking a session evicts it right away,
+/// `ttl` only bounds how long a session revoked by another server stays usable.
+/// Evicted sessions are remembered for `ttl` as well, a request that checked the session before the revoking
+/// transaction committed could otherwise put it right back
+#[derive(Clone, Debug)]
+pub struct SessionCache {
+    checked: Arc<RwLock<HashMap<i64, Instant>>>,
+    revoked: Arc<RwLock<HashMap<i64, Instant>>>,
+    ttl: Duration,
+}
+
+impl Default for SessionCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(30))
+    }
+}
+
+impl SessionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            checked: Arc::new(RwLock::new(HashMap::new())),
+            revoked: Arc::new(RwLock::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    pub fn is_active(&self, session_id: i64) -> bool {
+        self.checked
+            .read()
+            .ok()
+            .and_then(|checked| checked.get(&session_id).map(|checked_at| checked_at.elapsed() < self.ttl))
+            .unwrap_or(false)
+    }
+
+    fn is_revoked(&self, session_id: i64) -> bool {
+        self.revoked
+            .read()
+            .ok()
+            .and_then(|revoked| revoked.get(&session_id).map(|revoked_at| revoked_at.elapsed() < self.ttl))
+            .unwrap_or(false)
+    }
+
+    pub fn set_active(&self, session_id: i64) {
+        if self.is_revoked(session_id) {
+            return;
+        }
+
+        if let Ok(mut checked) = self.checked.write() {
+            // dropping the stale entries here keeps the cache from growing with every session ever seen
+            let ttl = self.ttl;
+            checked.retain(|_, checked_at| checked_at.elapsed() < ttl);
+            checked.insert(session_id, Instant::now());
+        }
+    }
+
+    pub fn evict(&self, session_id: i64) {
+        if let Ok(mut revoked) = self.revoked.write() {
+            let ttl = self.ttl;
+            revoked.retain(|_, revoked_at| revoked_at.elapsed() < ttl);
+            revoked.insert(session_id, Instant::now());
+        }
+
+        if let Ok(mut checked) = self.checked.write() {
+            checked.remove(&session_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_session_cache() {
+        let cache = SessionCache::default();
+        assert!(!cache.is_active(1));
+
+        cache.set_active(1);
+        let shared = cache.clone();
+        assert!(shared.is_active(1));
+
+        shared.evict(1);
+        assert!(!cache.is_active(1));
+
+        // a check that raced the revocation doesn't bring it back
+        cache.set_active(1);
+        assert!(!cache.is_active(1));
+
+        let expired = SessionCache::new(Duration::from_secs(0));
+        expired.set_active(1);
+        assert!(!expired.is_active(1));
+    }
+}
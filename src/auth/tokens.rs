@@ -1,5 +1,6 @@
 
 use base64;
+use openssl::sha::sha256;
 use rand::rngs::OsRng;
 use rand::RngCore;
 use rand::Error;
@@ -28,4 +29,12 @@ impl Token {
     pub fn as_string(&self) -> String {
         base64::encode_config(&self.bytes, base64::URL_SAFE)
     }
+
+    /// Tokens that are stored in the metastore are only stored as their hash
+    pub fn digest(token: &str) -> String {
+        sha256(token.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
 }
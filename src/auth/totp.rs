@@ -0,0 +1,183 @@
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use url::form_urlencoded::byte_serialize;
+
+use data::permissions::Permission;
+
+const BASE32_ALPHABET: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+/// 160 bits, as recommended for HMAC-SHA1
+const SECRET_LENGTH: usize = 20;
+const DIGITS: u32 = 6;
+const PERIOD_SECS: i64 = 30;
+/// Codes from one step before and after are accepted, for clocks that are a bit off
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct MfaSettings {
+    /// Shown next to the account in the authenticator app
+    pub issuer: String,
+    /// Users that hold any of these permissions have to use two-factor authentication, so does the admin
+    pub required_for: Vec<Permission>,
+}
+
+impl Default for MfaSettings {
+    fn default() -> Self {
+        Self {
+            issuer: "Kakapo".to_string(),
+            required_for: vec![],
+        }
+    }
+}
+
+fn random_bytes(length: usize) -> Result<Vec<u8>, String> {
+    let mut rng = OsRng::new().map_err(|err| err.to_string())?;
+    let mut bytes = vec![0u8; length];
+    rng.fill_bytes(&mut bytes);
+    Ok(bytes)
+}
+
+/// RFC 4648 without padding, which is what authenticator apps expect
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1F) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1F) as usize] as char);
+    }
+    encoded
+}
+
+pub fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in encoded.trim_end_matches('=').chars() {
+        let value = BASE32_ALPHABET.iter().position(|x| *x as char == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push(((buffer >> bits) & 0xFF) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// A new secret, base32 encoded
+pub fn generate_secret() -> Result<String, String> {
+    Ok(base32_encode(&random_bytes(SECRET_LENGTH)?))
+}
+
+/// Single use codes for when the authenticator is lost, i.e. `k3v9-x2mq`
+pub fn generate_recovery_codes() -> Result<Vec<String>, String> {
+    let alphabet = b"abcdefghjkmnpqrstuvwxyz23456789";
+    // bytes past the last whole multiple of the alphabet are skipped, otherwise the first letters would come up more often
+    let limit = 256 - 256 % alphabet.len();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut code = String::new();
+            while code.len() < 8 {
+                for byte in random_bytes(8)? {
+                    if (byte as usize) < limit && code.len() < 8 {
+                        code.push(alphabet[byte as usize % alphabet.len()] as char);
+                    }
+                }
+            }
+            Ok(format!("{}-{}", &code[..4], &code[4..]))
+        })
+        .collect()
+}
+
+/// RFC 4226
+pub fn hotp(secret: &[u8], counter: u64) -> Result<u32, String> {
+    let key = PKey::hmac(secret).map_err(|err| err.to_string())?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &key).map_err(|err| err.to_string())?;
+    signer.update(&counter.to_be_bytes()).map_err(|err| err.to_string())?;
+    let hmac = signer.sign_to_vec().map_err(|err| err.to_string())?;
+
+    let offset = (hmac[hmac.len() - 1] & 0x0F) as usize;
+    let truncated = (u32::from(hmac[offset]) & 0x7F) << 24
+        | u32::from(hmac[offset + 1]) << 16
+        | u32::from(hmac[offset + 2]) << 8
+        | u32::from(hmac[offset + 3]);
+
+    Ok(truncated % 10u32.pow(DIGITS))
+}
+
+pub fn time_step(unix_time: i64) -> i64 {
+    unix_time / PERIOD_SECS
+}
+
+/// The step that the code belongs to, steps up to `last_used_step` are rejected so a code can't be used twice
+pub fn verify_code(secret: &str, code: &str, unix_time: i64, last_used_step: Option<i64>) -> Result<Option<i64>, String> {
+    let secret = base32_decode(secret).ok_or_else(|| "invalid secret".to_string())?;
+    let code = code.trim().replace(" ", "");
+    if code.len() != DIGITS as usize {
+        return Ok(None);
+    }
+
+    let current_step = time_step(unix_time);
+    for step in (current_step - ALLOWED_DRIFT_STEPS)..(current_step + ALLOWED_DRIFT_STEPS + 1) {
+        if step < 0 || last_used_step.map(|last_used_step| step <= last_used_step).unwrap_or(false) {
+            continue;
+        }
+
+        let expected = format!("{:0width$}", hotp(&secret, step as u64)?, width = DIGITS as usize);
+        if expected == code {
+            return Ok(Some(step));
+        }
+    }
+
+    Ok(None)
+}
+
+/// What authenticator apps scan from the QR code
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let issuer: String = byte_serialize(issuer.as_bytes()).collect();
+    let account: String = byte_serialize(account.as_bytes()).collect();
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        &issuer, &account, secret, &issuer, DIGITS, PERIOD_SECS)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base32() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("MZXW6YTBOI").unwrap(), b"foobar".to_vec());
+        assert_eq!(base32_decode("mzxw6ytboi======").unwrap(), b"foobar".to_vec());
+        assert_eq!(base32_decode("not base32!"), None);
+    }
+
+    #[test]
+    fn test_rfc_6238_vectors() {
+        let secret = base32_encode(b"12345678901234567890");
+        assert_eq!(verify_code(&secret, "287082", 59, None).unwrap(), Some(1));
+        assert_eq!(verify_code(&secret, "081804", 1111111109, None).unwrap(), Some(37037036));
+        assert_eq!(verify_code(&secret, "000000", 59, None).unwrap(), None);
+        // the same code can't be used twice
+        assert_eq!(verify_code(&secret, "287082", 59, Some(1)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_recovery_codes() {
+        let codes = generate_recovery_codes().unwrap();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|code| code.len() == 9 && code.chars().nth(4) == Some('-')));
+    }
+}
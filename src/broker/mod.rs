@@ -37,7 +37,9 @@ use view::bearer_token::to_bearer_token;
 use model::actions::Action;
 
 use data::claims::AuthClaims;
+use data::auth::ClientInfo;
 use data::channels::Channels;
+use scripting::ScriptOutput;
 
 use broker::input::WsInputData;
 use broker::routes::CallAction;
@@ -78,6 +80,7 @@ impl<S> Actor for WsClientSession<S>
                 data, params, ctx,
                 on_received: &Self::do_nothing_for_unsubscribe,
                 on_received_error: &Self::do_nothing_for_unsubscribe_err,
+                spawn: false,
             };
 
             //TODO: refactor this, why is a string getting passed explicitly?
@@ -164,6 +167,7 @@ impl<S> WsClientSession<S>
                 data, params, ctx,
                 on_received: &Self::process_message_when_callback_is_ok,
                 on_received_error: &Self::process_message_when_callback_is_not_ok,
+                spawn: false,
             };
 
             //TODO: refactor this, why is a string getting passed explicitly?
@@ -239,6 +243,7 @@ pub struct WsClientSession<S>
     last_beat: Instant,
     last_message: chrono::NaiveDateTime,
     auth_header: Option<Vec<u8>>,
+    client_info: ClientInfo,
 
     phantom_data: PhantomData<(S)>,
 }
@@ -255,10 +260,17 @@ impl<S> WsClientSession<S>
             last_beat: Instant::now(),
             last_message: chrono::Utc::now().naive_utc(),
             auth_header: None,
+            client_info: ClientInfo::default(),
             phantom_data: PhantomData,
         }
     }
 
+    /// Logins over the websocket record the device and address of the connection
+    pub fn with_client_info(mut self, client_info: ClientInfo) -> Self {
+        self.client_info = client_info;
+        self
+    }
+
     fn callback_when_action_is_ok(ctx: &mut ws::WebsocketContext<Self, S>, res: serde_json::Value) {
         //TODO: need the action name
         let message = serde_json::to_string(&res).unwrap_or_default();
@@ -285,6 +297,7 @@ impl<S> WsClientSession<S>
                     data, params, ctx,
                     on_received: &Self::callback_when_action_is_ok,
                     on_received_error: &Self::callback_when_action_is_not_ok,
+                    spawn: true,
                 };
 
                 let result = routes::call_procedure(&procedure, self, &mut call_params);
@@ -310,7 +323,9 @@ impl<S> CallAction<S> for WsClientSession<S>
         let action = procedure_builder
             .build(call_params.data.to_owned(), call_params.params.to_owned());
 
-        let mut action_wrapper = ActionWrapper::new(action);
+        let mut action_wrapper = ActionWrapper::new(action)
+            .with_script_output(call_params.ctx.address().recipient())
+            .with_client_info(self.client_info.to_owned());
 
         if let Some(ref auth) = self.auth_header {
             action_wrapper = action_wrapper.with_auth(&auth);
@@ -319,7 +334,7 @@ impl<S> CallAction<S> for WsClientSession<S>
         let on_received = call_params.on_received;
         let on_received_error = call_params.on_received_error;
 
-        call_params
+        let action_future = call_params
             .ctx
             .state()
             .connect()
@@ -345,8 +360,13 @@ impl<S> CallAction<S> for WsClientSession<S>
                 }
 
                 fut::ok(())
-            })
-            .wait(&mut call_params.ctx); //TODO: is spawn better here?
+            });
+
+        if call_params.spawn {
+            action_future.spawn(&mut call_params.ctx);
+        } else {
+            action_future.wait(&mut call_params.ctx);
+        }
     }
 
     fn error<'a, F, EF>(&mut self, call_params: &'a mut CallParams<'a, S, F, EF>)
@@ -362,6 +382,21 @@ impl<S> CallAction<S> for WsClientSession<S>
 }
 
 
+impl<S> Handler<ScriptOutput> for WsClientSession<S>
+    where S: AppStateLike + 'static
+{
+    type Result = ();
+
+    fn handle(&mut self, msg: ScriptOutput, ctx: &mut Self::Context) {
+        let message = json!({
+            "action": "scriptOutput",
+            "data": msg,
+        });
+        let message = serde_json::to_string(&message).unwrap_or_default();
+        ctx.text(message);
+    }
+}
+
 impl<S> WsClientSession<S>
     where S: AppStateLike
 {
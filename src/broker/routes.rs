@@ -19,6 +19,8 @@ pub struct CallParams<'a, S, F, EF>
     pub ctx: &'a mut ws::WebsocketContext<WsClientSession<S>, S>,
     pub on_received: &'static F,
     pub on_received_error: &'static EF,
+    /// don't block the session while the action runs, i.e. so that script output can be streamed in the meantime
+    pub spawn: bool,
 }
 
 
@@ -48,39 +50,85 @@ pub fn call_procedure<'a, CB, S, F, EF>(procedure: &str, cb: &mut CB, call_param
     //TODO: put this in a macro, we are using this in the routes as well
     match procedure {
         "getAllDomains" => cb.call(manage::get_all_domains, call_params),
+        "exportDomain" => cb.call(manage::export_domain, call_params),
+        "importDomain" => cb.call(manage::import_domain, call_params),
+        "planSync" => cb.call(manage::plan_sync, call_params),
+        "applySync" => cb.call(manage::apply_sync, call_params),
 
         "getAllTables" => cb.call(manage::get_all_tables, call_params),
         "getAllQueries" => cb.call(manage::get_all_queries, call_params),
         "getAllScripts" => cb.call(manage::get_all_scripts, call_params),
+        "getAllViews" => cb.call(manage::get_all_views, call_params),
 
         "getTable" => cb.call(manage::get_table, call_params),
         "getQuery" => cb.call(manage::get_query, call_params),
         "getScript" => cb.call(manage::get_script, call_params),
+        "getView" => cb.call(manage::get_view, call_params),
 
         "createTable" => cb.call(manage::create_table, call_params),
         "createQuery" => cb.call(manage::create_query, call_params),
         "createScript" => cb.call(manage::create_script, call_params),
+        "createView" => cb.call(manage::create_view, call_params),
 
         "updateTable" => cb.call(manage::update_table, call_params),
         "updateQuery" => cb.call(manage::update_query, call_params),
         "updateScript" => cb.call(manage::update_script, call_params),
+        "updateView" => cb.call(manage::update_view, call_params),
+        "renameTable" => cb.call(manage::rename_table, call_params),
+        "renameQuery" => cb.call(manage::rename_query, call_params),
+        "renameScript" => cb.call(manage::rename_script, call_params),
+        "renameView" => cb.call(manage::rename_view, call_params),
+        "patchScript" => cb.call(manage::patch_script, call_params),
+        "uploadScriptArchive" => cb.call(manage::upload_script_archive, call_params),
 
         "deleteTable" => cb.call(manage::delete_table, call_params),
         "deleteQuery" => cb.call(manage::delete_query, call_params),
         "deleteScript" => cb.call(manage::delete_script, call_params),
+        "deleteView" => cb.call(manage::delete_view, call_params),
+
+        "getTableHistory" => cb.call(manage::get_table_history, call_params),
+        "getQueryHistory" => cb.call(manage::get_query_history, call_params),
+        "getScriptHistory" => cb.call(manage::get_script_history, call_params),
+
+        "getTableRevision" => cb.call(manage::get_table_revision, call_params),
+        "getQueryRevision" => cb.call(manage::get_query_revision, call_params),
+        "getScriptRevision" => cb.call(manage::get_script_revision, call_params),
+
+        "diffTableRevisions" => cb.call(manage::diff_table_revisions, call_params),
+        "diffQueryRevisions" => cb.call(manage::diff_query_revisions, call_params),
+        "diffScriptRevisions" => cb.call(manage::diff_script_revisions, call_params),
+
+        "revertTable" => cb.call(manage::revert_table, call_params),
+        "revertQuery" => cb.call(manage::revert_query, call_params),
+        "revertScript" => cb.call(manage::revert_script, call_params),
+
+        "getAllTags" => cb.call(manage::get_all_tags, call_params),
+        "createTag" => cb.call(manage::create_tag, call_params),
+        "deleteTag" => cb.call(manage::delete_tag, call_params),
+        "getDependents" => cb.call(manage::get_dependents, call_params),
+        "getEntityTags" => cb.call(manage::get_entity_tags, call_params),
+        "attachTag" => cb.call(manage::attach_tag, call_params),
+        "detachTag" => cb.call(manage::detach_tag, call_params),
+        "searchEntities" => cb.call(manage::search_entities, call_params),
+
+        "getEntityUsage" => cb.call(manage::get_entity_usage, call_params),
 
         "queryTableData" => cb.call(manage::query_table_data, call_params),
         "insertTableData" => cb.call(manage::insert_table_data, call_params),
         "modifyTableData" => cb.call(manage::modify_table_data, call_params),
         "removeTableData" => cb.call(manage::remove_table_data, call_params),
+        "getTableAuditLog" => cb.call(manage::get_table_audit_log, call_params),
+        "revertTableChanges" => cb.call(manage::revert_table_changes, call_params),
 
         "runQuery" => cb.call(manage::run_query, call_params),
         "runScript" => cb.call(manage::run_script, call_params),
+        "getViewData" => cb.call(manage::get_view_data, call_params),
 
         "subscribeTo" => cb.call(pubsub::subscribe_to, call_params),
         "unsubscribeFrom" => cb.call(pubsub::unsubscribe_from, call_params),
         "unsubscribeAll" => cb.call(pubsub::unsubscribe_all, call_params),
         "getSubscribers" => cb.call(pubsub::get_subscribers, call_params),
+        "publish" => cb.call(pubsub::publish, call_params),
         "getMessages" => cb.call(pubsub::get_messages, call_params),
 
         _ => cb.error(call_params),
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use actix::prelude::*;
+use actix::fut;
+use chrono::Utc;
+
+use connection::executor::Executor;
+use view::action_wrapper::ActionWrapper;
+use model::actions::SyncDirectoryUsers;
+use state::ActionState;
+use data::claims::AuthClaims;
+use metastore::ADMIN_USER_ID;
+
+const DIRECTORY_SYNC_ISSUER: &'static str = "kakapo-directory-sync";
+
+/// Periodically syncs the users and their roles from the LDAP directory
+pub struct DirectoryWatcher {
+    executor: Addr<Executor>,
+    interval: Duration,
+}
+
+impl DirectoryWatcher {
+    pub fn new(executor: Addr<Executor>, interval: Duration) -> Self {
+        Self {
+            executor,
+            interval,
+        }
+    }
+
+    fn get_claims(&self) -> AuthClaims {
+        let now = Utc::now().timestamp();
+        AuthClaims {
+            iss: DIRECTORY_SYNC_ISSUER.to_string(),
+            sub: ADMIN_USER_ID,
+            iat: now,
+            exp: now + self.interval.as_secs() as i64,
+            username: DIRECTORY_SYNC_ISSUER.to_string(),
+            is_admin: true,
+            role: None,
+            jti: None,
+            scopes: None,
+        }
+    }
+
+    fn sync_users(&mut self, ctx: &mut Context<Self>) {
+        let action = SyncDirectoryUsers::<ActionState>::new();
+        let action_wrapper = ActionWrapper::new(Ok((None, action)))
+            .with_claims(self.get_claims());
+
+        self.executor
+            .send(action_wrapper)
+            .into_actor(self)
+            .then(|res, _, _| {
+                match res {
+                    Ok(Ok(res)) => {
+                        let result = res.get_data();
+                        info!("Synced {} users from the directory", result.synced.len());
+                        for username in result.deprovisioned {
+                            info!("Deprovisioned {:?}, they are no longer in the directory", &username);
+                        }
+                    },
+                    Ok(Err(err)) => error!("Could not sync the directory: {:?}", &err),
+                    Err(err) => error!("Could not reach the executor: {:?}", &err),
+                }
+
+                fut::ok(())
+            })
+            .spawn(ctx);
+    }
+}
+
+impl Actor for DirectoryWatcher {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.sync_users(ctx);
+        ctx.run_interval(self.interval, Self::sync_users);
+    }
+}
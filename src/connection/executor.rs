@@ -13,6 +13,14 @@ use diesel::r2d2::Pool;
 
 use connection::AppStateBuilder;
 use connection::domain::DomainCollection;
+use scripting::sandbox::ScriptRunner;
+use auth::send_mail::MailSettings;
+use auth::sessions::SessionCache;
+use auth::oidc::OidcSettings;
+use auth::ldap::LdapSettings;
+use auth::totp::MfaSettings;
+use auth::login_throttle::LoginThrottleSettings;
+use auth::password_policy::PasswordPolicy;
 
 use plugins::v1::Domain;
 use plugins::v1::Datastore;
@@ -41,6 +49,18 @@ pub struct Secrets {
 pub struct Executor {
     pool: Pool<ConnectionManager<PgConnection>>,
     script_path: PathBuf,
+    script_runner: ScriptRunner,
+    script_allowed_paths: Vec<PathBuf>,
+    script_api_url: Option<String>,
+    script_api_socket: Option<PathBuf>,
+    sync_directories: HashMap<String, PathBuf>,
+    mail_settings: MailSettings,
+    oidc_settings: Option<OidcSettings>,
+    ldap_settings: Option<LdapSettings>,
+    mfa_settings: MfaSettings,
+    login_throttle: LoginThrottleSettings,
+    password_policy: PasswordPolicy,
+    session_cache: SessionCache,
     secrets: Secrets,
 
     domains: DomainCollection,
@@ -84,14 +104,7 @@ impl Executor {
 
     pub fn create(info: &AppStateBuilder) -> Self {
 
-        let database_url = format!(
-            "postgres://{}:{}@{}:{}/{}",
-            info.user.clone().unwrap_or_default(),
-            info.pass.clone().unwrap_or_default(),
-            info.host.clone().unwrap_or_default(),
-            info.port.clone().unwrap_or_default(),
-            info.db.clone().unwrap_or_default(),
-        );
+        let database_url = info.get_database_url();
         let mut domains = DomainCollection::new();
         for (key, value) in info.domain_builders.iter() {
             domains.insert(key, value.build());
@@ -117,6 +130,18 @@ impl Executor {
         Self {
             pool,
             script_path,
+            script_runner: info.script_runner.clone(),
+            script_allowed_paths: info.script_allowed_paths.clone(),
+            script_api_url: info.script_api_url.clone(),
+            script_api_socket: info.script_api_socket.clone(),
+            sync_directories: info.sync_directories.clone(),
+            mail_settings: info.mail_settings.clone(),
+            oidc_settings: info.oidc_settings.clone(),
+            ldap_settings: info.ldap_settings.clone(),
+            mfa_settings: info.mfa_settings.clone(),
+            login_throttle: info.login_throttle.clone(),
+            password_policy: info.password_policy.clone(),
+            session_cache: info.session_cache.clone(),
             secrets,
 
             domains,
@@ -131,6 +156,56 @@ impl Executor {
         self.script_path.to_owned()
     }
 
+    pub fn get_script_runner(&self) -> ScriptRunner {
+        self.script_runner.to_owned()
+    }
+
+    pub fn get_script_allowed_paths(&self) -> Vec<PathBuf> {
+        self.script_allowed_paths.to_owned()
+    }
+
+    pub fn get_script_api_url(&self) -> Option<String> {
+        self.script_api_url.to_owned()
+    }
+
+    pub fn get_script_api_socket(&self) -> Option<PathBuf> {
+        self.script_api_socket.to_owned()
+    }
+
+    pub fn get_sync_directory(&self, domain_name: &str) -> Option<PathBuf> {
+        self.sync_directories
+            .get(domain_name)
+            .map(|sync_directory| sync_directory.to_owned())
+    }
+
+    pub fn get_mail_settings(&self) -> MailSettings {
+        self.mail_settings.to_owned()
+    }
+
+    pub fn get_oidc_settings(&self) -> Option<OidcSettings> {
+        self.oidc_settings.to_owned()
+    }
+
+    pub fn get_ldap_settings(&self) -> Option<LdapSettings> {
+        self.ldap_settings.to_owned()
+    }
+
+    pub fn get_mfa_settings(&self) -> MfaSettings {
+        self.mfa_settings.to_owned()
+    }
+
+    pub fn get_login_throttle(&self) -> LoginThrottleSettings {
+        self.login_throttle.to_owned()
+    }
+
+    pub fn get_password_policy(&self) -> PasswordPolicy {
+        self.password_policy.to_owned()
+    }
+
+    pub fn get_session_cache(&self) -> SessionCache {
+        self.session_cache.to_owned()
+    }
+
     pub fn get_token_secret(&self) -> String {
         self.secrets.token_secret.to_owned()
     }
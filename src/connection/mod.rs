@@ -1,18 +1,39 @@
 
 pub mod executor;
 pub mod domain;
+mod sync_watcher;
+mod directory_watcher;
 
 use num_cpus;
 
 use std::sync::Arc;
 use std::fmt::Debug;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::net::IpAddr;
 
 use actix::Addr;
 use actix::sync::SyncArbiter;
+use actix::Actor;
 
 use data::channels::Channels;
-
+use data::permissions::Permission;
+use scripting::sandbox::ScriptRunner;
+use auth::send_mail::MailSettings;
+use auth::send_mail::MailTransport;
+use auth::send_mail::SmtpSettings;
+use auth::send_mail::EmailTemplate;
+use auth::sessions::SessionCache;
+use auth::oidc::OidcSettings;
+use auth::ldap::LdapSettings;
+use auth::totp::MfaSettings;
+use auth::login_throttle::LoginThrottleSettings;
+use auth::password_policy::PasswordPolicy;
+use connection::sync_watcher::SyncWatcher;
+use connection::directory_watcher::DirectoryWatcher;
+
+use metastore;
 use plugins::v1::DomainBuilder;
 use plugins::v1::Domain;
 
@@ -23,6 +44,9 @@ pub trait GetSecrets {
 
 pub trait AppStateLike: GetSecrets {
     fn connect(&self) -> &Addr<executor::Executor>;
+
+    /// Forwarded headers are only believed when the request comes from one of these
+    fn get_trusted_proxies(&self) -> &[IpAddr];
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +54,8 @@ pub struct AppState {
     connections: Addr<executor::Executor>,
     token_secret: String, //This is duplicated here as well as inside the executor , because we need it both in the view (websocket) and in the model
     password_secret: String, // TODO: find a better way
+    script_api_socket: Option<PathBuf>,
+    trusted_proxies: Vec<IpAddr>,
 }
 
 /// Builder for the AppState
@@ -40,6 +66,22 @@ pub struct AppStateBuilder {
     pass: Option<String>,
     db: Option<String>,
     script_path: Option<String>,
+    script_runner: ScriptRunner,
+    script_seccomp_filter: Option<PathBuf>,
+    script_allowed_paths: Vec<PathBuf>,
+    script_api_url: Option<String>,
+    script_api_socket: Option<PathBuf>,
+    sync_directories: HashMap<String, PathBuf>,
+    sync_interval: Option<u64>,
+    mail_settings: MailSettings,
+    oidc_settings: Option<OidcSettings>,
+    ldap_settings: Option<LdapSettings>,
+    ldap_sync_interval: Option<u64>,
+    mfa_settings: MfaSettings,
+    login_throttle: LoginThrottleSettings,
+    password_policy: PasswordPolicy,
+    session_cache: SessionCache,
+    trusted_proxies: Vec<IpAddr>,
     token_secret: Option<String>,
     password_secret: Option<String>,
     jwt_issuer: Option<String>,
@@ -61,6 +103,22 @@ impl AppStateBuilder {
             pass: None,
             db: None,
             script_path: None,
+            script_runner: ScriptRunner::Local,
+            script_seccomp_filter: None,
+            script_allowed_paths: vec![],
+            script_api_url: None,
+            script_api_socket: None,
+            sync_directories: HashMap::new(),
+            sync_interval: None,
+            mail_settings: MailSettings::default(),
+            oidc_settings: None,
+            ldap_settings: None,
+            ldap_sync_interval: None,
+            mfa_settings: MfaSettings::default(),
+            login_throttle: LoginThrottleSettings::default(),
+            password_policy: PasswordPolicy::default(),
+            session_cache: SessionCache::default(),
+            trusted_proxies: vec![],
             token_secret: None,
             password_secret: None,
             jwt_issuer: None,
@@ -102,6 +160,165 @@ impl AppStateBuilder {
         self
     }
 
+    /// Run every script inside a bubblewrap sandbox, each script gets it's own uid starting from `base_uid`
+    pub fn sandbox_scripts(mut self, bwrap_path: &str, base_uid: u32) -> Self {
+        self.script_runner = ScriptRunner::Bubblewrap {
+            bwrap_path: PathBuf::from(bwrap_path),
+            base_uid,
+            seccomp_filter: self.script_seccomp_filter.to_owned(),
+        };
+        self
+    }
+
+    /// A compiled seccomp bpf program that sandboxed scripts run under
+    pub fn sandbox_seccomp_filter(mut self, seccomp_filter_path: &str) -> Self {
+        let seccomp_filter_path = PathBuf::from(seccomp_filter_path);
+        if let ScriptRunner::Bubblewrap { ref mut seccomp_filter, .. } = self.script_runner {
+            *seccomp_filter = Some(seccomp_filter_path.to_owned());
+        }
+        self.script_seccomp_filter = Some(seccomp_filter_path);
+        self
+    }
+
+    /// Scripts can mount this path, or anything inside of it, in their sandbox.
+    /// Sandboxes that mount anything else are rejected when the script is created or updated
+    pub fn allow_sandbox_path(mut self, path: &str) -> Self {
+        self.script_allowed_paths.push(PathBuf::from(path));
+        self
+    }
+
+    /// The url the kakapo client inside scripts uses to reach this server
+    pub fn script_api_url(mut self, script_api_url: &str) -> Self {
+        self.script_api_url = Some(script_api_url.to_string());
+        self
+    }
+
+    /// The server also listens on this unix socket, it is mounted in the sandbox so that scripts
+    /// without network access can still reach the api
+    pub fn script_api_socket(mut self, script_api_socket: &str) -> Self {
+        self.script_api_socket = Some(PathBuf::from(script_api_socket));
+        self
+    }
+
+    /// A reverse proxy in front of the server, only requests coming from it can set the client address
+    /// with `X-Forwarded-For`, everyone else is identified by the address of the connection
+    pub fn trusted_proxy(mut self, address: &str) -> Self {
+        let address = address.parse()
+            .expect("The trusted proxy must be an ip address");
+        self.trusted_proxies.push(address);
+        self
+    }
+
+    /// The entities of the domain can be synced with this directory (or git working tree) with `planSync` and `applySync`
+    pub fn sync_directory(mut self, domain_name: &str, sync_directory: &str) -> Self {
+        self.sync_directories.insert(domain_name.to_string(), PathBuf::from(sync_directory));
+        self
+    }
+
+    /// Applies the sync directories as the admin user whenever they change, they are checked every `interval_secs`
+    pub fn watch_sync_directories(mut self, interval_secs: u64) -> Self {
+        self.sync_interval = Some(interval_secs);
+        self
+    }
+
+    /// Send invitations and notifications through the SMTP server
+    pub fn smtp(mut self, smtp_settings: SmtpSettings) -> Self {
+        self.mail_settings.transport = MailTransport::Smtp(smtp_settings);
+        self
+    }
+
+    /// Write the emails into the directory instead of sending them, for development
+    pub fn mail_spool(mut self, spool_directory: &str) -> Self {
+        self.mail_settings.transport = MailTransport::Spool(PathBuf::from(spool_directory));
+        self
+    }
+
+    pub fn mail_from(mut self, from: &str) -> Self {
+        self.mail_settings.from = from.to_string();
+        self
+    }
+
+    /// The page where invited users accept the invitation, the token is passed as the `token` query parameter
+    pub fn invitation_url(mut self, invitation_url: &str) -> Self {
+        self.mail_settings.invitation_url = invitation_url.to_string();
+        self
+    }
+
+    /// `{{email}}`, `{{link}}`, `{{token}}` and `{{expiresAt}}` are replaced in the subject and the body
+    pub fn invitation_template(mut self, subject: &str, body: &str) -> Self {
+        self.mail_settings.invitation_template = EmailTemplate::new(subject, body);
+        self
+    }
+
+    /// The page where users choose a new password, the token is passed as the `token` query parameter
+    pub fn password_reset_url(mut self, password_reset_url: &str) -> Self {
+        self.mail_settings.password_reset_url = password_reset_url.to_string();
+        self
+    }
+
+    /// `{{email}}`, `{{link}}` and `{{expiresAt}}` are replaced in the subject and the body
+    pub fn password_reset_template(mut self, subject: &str, body: &str) -> Self {
+        self.mail_settings.password_reset_template = EmailTemplate::new(subject, body);
+        self
+    }
+
+    /// `{{email}}`, `{{subject}}` and `{{message}}` are replaced in the subject and the body
+    pub fn notification_template(mut self, subject: &str, body: &str) -> Self {
+        self.mail_settings.notification_template = EmailTemplate::new(subject, body);
+        self
+    }
+
+    /// Log in through an OpenID Connect identity provider with `oidcLogin` and `oidcCallback`
+    pub fn oidc(mut self, oidc_settings: OidcSettings) -> Self {
+        self.oidc_settings = Some(oidc_settings);
+        self
+    }
+
+    /// Check passwords with a bind against the LDAP directory, users are created the first time they log in
+    pub fn ldap(mut self, ldap_settings: LdapSettings) -> Self {
+        self.ldap_settings = Some(ldap_settings);
+        self
+    }
+
+    /// Syncs the users and their group roles from the LDAP directory every `interval_secs`,
+    /// users that are no longer in the directory are deprovisioned, they keep everything but can not log in
+    pub fn watch_ldap(mut self, interval_secs: u64) -> Self {
+        self.ldap_sync_interval = Some(interval_secs);
+        self
+    }
+
+    /// Shown as the account's issuer in authenticator apps
+    pub fn mfa_issuer(mut self, issuer: &str) -> Self {
+        self.mfa_settings.issuer = issuer.to_string();
+        self
+    }
+
+    /// Users holding the permission (and the admin) have to use two-factor authentication,
+    /// they are asked to enroll the next time they log in
+    pub fn require_mfa_for(mut self, permission: Permission) -> Self {
+        self.mfa_settings.required_for.push(permission);
+        self
+    }
+
+    /// Backoff and lockout after failed logins, `LoginThrottleSettings::default()` is used otherwise
+    pub fn login_throttle(mut self, login_throttle: LoginThrottleSettings) -> Self {
+        self.login_throttle = login_throttle;
+        self
+    }
+
+    /// Checked when users are added and when passwords are changed, no rules are enforced otherwise
+    pub fn password_policy(mut self, password_policy: PasswordPolicy) -> Self {
+        self.password_policy = password_policy;
+        self
+    }
+
+    /// How long an access token is trusted without checking that it's session still exists,
+    /// sessions revoked through this server are rejected right away regardless
+    pub fn session_check_interval(mut self, interval_secs: u64) -> Self {
+        self.session_cache = SessionCache::new(Duration::from_secs(interval_secs));
+        self
+    }
+
     pub fn token_secret(mut self, token_secret: &str) -> Self {
         self.token_secret = Some(token_secret.to_string());
         self
@@ -140,33 +357,82 @@ impl AppStateBuilder {
         self
     }
 
+    /// Creates or updates the admin user, the password has to meet the password policy of the server
+    pub fn setup_admin(&self, username: &str, email: &str, display_name: &str, password: &str) -> Result<(), String> {
+        metastore::setup_admin_with_policy(&self.get_database_url(), username, email, display_name, password, &self.password_policy)
+    }
+
+    fn get_database_url(&self) -> String {
+        format!(
+            "postgres://{}:{}@{}:{}/{}",
+            self.user.clone().unwrap_or_default(),
+            self.pass.clone().unwrap_or_default(),
+            self.host.clone().unwrap_or_default(),
+            self.port.clone().unwrap_or_default(),
+            self.db.clone().unwrap_or_default(),
+        )
+    }
+
     pub fn done(self) -> AppState {
         let token_secret = self.token_secret.clone()
             .expect("Must specify a token secret");
         let password_secret = self.password_secret.clone()
             .expect("Must specify a password secret");
         let threads = self.num_threads;
+        let sync_directories = self.sync_directories.clone();
+        let sync_interval = self.sync_interval;
+        let ldap_sync_interval = self.ldap_settings.as_ref().and(self.ldap_sync_interval);
+        let script_api_socket = self.script_api_socket.clone();
+        let trusted_proxies = self.trusted_proxies.clone();
+
+        self.script_runner.check_isolation();
 
         info!("Starting database connection");
         let connections = SyncArbiter::start(
             threads,
             move || executor::Executor::create(&self));
 
+        if let Some(interval_secs) = sync_interval {
+            for (domain_name, sync_directory) in sync_directories {
+                info!("Watching {:?} for the domain {:?}", &sync_directory, &domain_name);
+                SyncWatcher::new(connections.clone(), domain_name, sync_directory, Duration::from_secs(interval_secs))
+                    .start();
+            }
+        }
+
+        if let Some(interval_secs) = ldap_sync_interval {
+            info!("Syncing the LDAP directory every {} seconds", interval_secs);
+            DirectoryWatcher::new(connections.clone(), Duration::from_secs(interval_secs))
+                .start();
+        }
 
 
         AppState {
             connections,
             token_secret,
             password_secret,
+            script_api_socket,
+            trusted_proxies,
         }
     }
 }
 
+impl AppState {
+    /// The unix socket that the server should listen on for scripts
+    pub fn get_script_api_socket(&self) -> Option<PathBuf> {
+        self.script_api_socket.to_owned()
+    }
+}
+
 
 impl AppStateLike for AppState {
     fn connect(&self) -> &Addr<executor::Executor> {
         &self.connections
     }
+
+    fn get_trusted_proxies(&self) -> &[IpAddr] {
+        &self.trusted_proxies
+    }
 }
 
 impl GetSecrets for AppState {
@@ -0,0 +1,169 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::collections::hash_map::DefaultHasher;
+
+use actix::prelude::*;
+use actix::fut;
+use chrono::Utc;
+
+use connection::executor::Executor;
+use view::action_wrapper::ActionWrapper;
+use model::actions::ApplySync;
+use state::ActionState;
+use data::claims::AuthClaims;
+use data::utils::DependentsPolicy;
+use metastore::ADMIN_USER_ID;
+
+const SYNC_ISSUER: &'static str = "kakapo-sync";
+
+/// Polls a sync directory, and applies it to the domain whenever something in it changes
+pub struct SyncWatcher {
+    executor: Addr<Executor>,
+    domain_name: String,
+    sync_directory: PathBuf,
+    interval: Duration,
+    fingerprint: Option<u64>,
+}
+
+/// Hashes the names, sizes and modification times, hidden files (i.e. `.git`) are left out like when syncing
+fn hash_tree(dir: &Path, hasher: &mut DefaultHasher) -> io::Result<()> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    paths.sort();
+
+    for path in paths {
+        let is_hidden = path.file_name()
+            .map(|name| name.to_string_lossy().starts_with('.'))
+            .unwrap_or(false);
+        if is_hidden {
+            continue;
+        }
+
+        let metadata = fs::symlink_metadata(&path)?;
+        path.hash(hasher);
+        metadata.len().hash(hasher);
+        metadata.modified().ok().hash(hasher);
+
+        if metadata.is_dir() {
+            hash_tree(&path, hasher)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl SyncWatcher {
+    pub fn new(executor: Addr<Executor>, domain_name: String, sync_directory: PathBuf, interval: Duration) -> Self {
+        Self {
+            executor,
+            domain_name,
+            sync_directory,
+            interval,
+            fingerprint: None,
+        }
+    }
+
+    fn get_fingerprint(&self) -> io::Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        hash_tree(&self.sync_directory, &mut hasher)?;
+        Ok(hasher.finish())
+    }
+
+    /// The watcher acts as the admin, drifted entities are never overwritten and entities with dependents are never deleted
+    fn get_claims(&self) -> AuthClaims {
+        let now = Utc::now().timestamp();
+        AuthClaims {
+            iss: SYNC_ISSUER.to_string(),
+            sub: ADMIN_USER_ID,
+            iat: now,
+            exp: now + self.interval.as_secs() as i64,
+            username: SYNC_ISSUER.to_string(),
+            is_admin: true,
+            role: None,
+            jti: None,
+            scopes: None,
+        }
+    }
+
+    fn check_directory(&mut self, ctx: &mut Context<Self>) {
+        let fingerprint = match self.get_fingerprint() {
+            Ok(fingerprint) => fingerprint,
+            Err(err) => {
+                warn!("Could not read the sync directory {:?}: {:?}", &self.sync_directory, &err);
+                return;
+            },
+        };
+
+        if self.fingerprint == Some(fingerprint) {
+            return;
+        }
+
+        info!("Sync directory {:?} changed, applying it to the domain {:?}", &self.sync_directory, &self.domain_name);
+        let action = ApplySync::<ActionState>::new(false, DependentsPolicy::Restrict);
+        let action_wrapper = ActionWrapper::new(Ok((Some(self.domain_name.to_owned()), action)))
+            .with_claims(self.get_claims());
+
+        self.executor
+            .send(action_wrapper)
+            .into_actor(self)
+            .then(move |res, actor, _| {
+                match res {
+                    Ok(Ok(res)) => {
+                        // Only remembered once applied, so that a failed sync is retried on the next check
+                        actor.fingerprint = Some(fingerprint);
+                        let result = res.get_data();
+                        info!("Applied {} changes to the domain {:?}", result.applied.len(), &actor.domain_name);
+                        for change in result.skipped {
+                            warn!("Skipped {:?}, it was modified outside of the sync directory, use `force` to overwrite it", &change);
+                        }
+                    },
+                    Ok(Err(err)) => error!("Could not apply the sync directory {:?}: {:?}", &actor.sync_directory, &err),
+                    Err(err) => error!("Could not reach the executor: {:?}", &err),
+                }
+
+                fut::ok(())
+            })
+            .spawn(ctx);
+    }
+}
+
+impl Actor for SyncWatcher {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.check_directory(ctx);
+        ctx.run_interval(self.interval, Self::check_directory);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tempfile;
+
+    fn fingerprint(dir: &Path) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        hash_tree(dir, &mut hasher).unwrap();
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_hidden_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("manifest.json"), "{}").unwrap();
+        let before = fingerprint(dir.path());
+
+        fs::write(dir.path().join(".sync_lock"), "").unwrap();
+        assert_eq!(fingerprint(dir.path()), before);
+
+        fs::write(dir.path().join("manifest.json"), "{ \"version\": 1 }").unwrap();
+        assert_ne!(fingerprint(dir.path()), before);
+    }
+}
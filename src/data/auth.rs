@@ -1,6 +1,8 @@
 
 use chrono;
+use serde_json;
 use data::claims::AuthClaims;
+use data::permissions::Permission;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -29,6 +31,17 @@ pub struct NewUser {
     pub display_name: Option<String>,
 }
 
+/// The profile fields to change, the ones that are left out stay the same
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserModification {
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    /// users that change their own email have to confirm it with their password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_password: Option<String>,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Role {
@@ -57,6 +70,123 @@ pub struct InvitationToken {
     pub expires_at: chrono::NaiveDateTime,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordResetToken {
+    pub email: String,
+    pub token: String,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+/// Kept between sending the user to the identity provider and the provider redirecting back
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginRequest {
+    pub state: String,
+    pub code_verifier: String,
+    pub nonce: String,
+}
+
+/// A user as the identity provider knows them, `issuer` and `subject` identify them across logins
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalUser {
+    pub issuer: String,
+    pub subject: String,
+    pub username: String,
+    pub email: String,
+    pub display_name: Option<String>,
+    /// everything the provider said about the user, roles are mapped from these
+    pub claims: serde_json::Value,
+}
+
+/// Where a request came from, recorded when a session is started
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientInfo {
+    pub device: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub session_id: i64,
+    pub device: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub last_used_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+    /// the session of the token that made the request
+    pub is_current: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub api_key_id: i64,
+    pub name: String,
+    /// the start of the key, so that users can tell their keys apart
+    pub prefix: String,
+    /// the key can only use these permissions, as long as the user still has them, `None` is all of them
+    pub scopes: Option<Vec<Permission>>,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub use_count: i64,
+}
+
+/// The key is only ever returned when it's created, only it's hash is stored
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+/// A failed or rejected login, for auditing
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginFailure {
+    /// the username or email that was tried
+    pub user_identifier: String,
+    pub user_id: Option<i64>,
+    pub ip_address: Option<String>,
+    pub device: Option<String>,
+    /// `invalidCredentials`, `invalidMfaCode`, `throttled` or `locked`
+    pub reason: String,
+    pub failed_at: chrono::NaiveDateTime,
+}
+
+/// Returned once, when two-factor authentication is set up
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaEnrollment {
+    /// base32, for entering it by hand
+    pub secret: String,
+    pub otpauth_uri: String,
+    pub recovery_codes: Vec<String>,
+}
+
+/// The password was right but has expired, the token can only be used to set a new one with `confirmPasswordReset`
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordChangeRequired {
+    pub password_change_token: String,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+/// The password was right, the challenge token and a code from the authenticator get the session
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaChallenge {
+    pub challenge_token: String,
+    pub expires_in: u32,
+    /// the user has to enroll with the challenge token first, 2FA is required for them
+    pub enrollment_required: bool,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "tokenType")]
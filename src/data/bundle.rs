@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+
+use serde_json;
+
+use data::DataStoreEntity;
+use data::DataQueryEntity;
+use data::Script;
+use data::ScriptSandbox;
+use data::View;
+use data::Tag;
+use data::permissions::Permission;
+use data::error::BundleError;
+
+/// Bumped whenever the layout of the bundle changes, older bundles can still be read
+pub const BUNDLE_VERSION: u32 = 1;
+
+const MANIFEST_NAME: &'static str = "manifest.json";
+const QUERY_DIR: &'static str = "queries";
+const SCRIPT_DIR: &'static str = "scripts";
+const DATA_DIR: &'static str = "data";
+
+/// Everything in a domain, in a form that can be moved to another installation
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bundle {
+    pub domain: String,
+    pub tables: Vec<DataStoreEntity>,
+    pub queries: Vec<DataQueryEntity>,
+    pub scripts: Vec<Script>,
+    pub views: Vec<View>,
+    pub tags: Vec<Tag>,
+    pub entity_tags: Vec<EntityTags>,
+    pub roles: Vec<RoleBundle>,
+    /// rows of each table, only there if the data was exported
+    pub table_data: BTreeMap<String, Vec<serde_json::Value>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityTags {
+    pub type_name: String,
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleBundle {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+}
+
+/// The query statement lives in `queries/<name>.sql`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct QueryManifest {
+    name: String,
+    description: String,
+}
+
+/// The script files live in `scripts/<name>/`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScriptManifest {
+    name: String,
+    description: String,
+    entrypoint: String,
+    #[serde(default)]
+    requirements: Vec<String>,
+    #[serde(default)]
+    sandbox: ScriptSandbox,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    version: u32,
+    #[serde(default)]
+    domain: String,
+    #[serde(default)]
+    tables: Vec<DataStoreEntity>,
+    #[serde(default)]
+    queries: Vec<QueryManifest>,
+    #[serde(default)]
+    scripts: Vec<ScriptManifest>,
+    #[serde(default)]
+    views: Vec<View>,
+    #[serde(default)]
+    tags: Vec<Tag>,
+    #[serde(default)]
+    entity_tags: Vec<EntityTags>,
+    #[serde(default)]
+    roles: Vec<RoleBundle>,
+}
+
+/// names end up in file paths, so they can't point anywhere else
+fn checked_name(name: &str) -> Result<&str, BundleError> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        Err(BundleError::InvalidName(name.to_string()))
+    } else {
+        Ok(name)
+    }
+}
+
+fn query_path(name: &str) -> Result<String, BundleError> {
+    Ok(format!("{}/{}.sql", QUERY_DIR, checked_name(name)?))
+}
+
+fn script_dir(name: &str) -> Result<String, BundleError> {
+    Ok(format!("{}/{}/", SCRIPT_DIR, checked_name(name)?))
+}
+
+fn data_path(name: &str) -> Result<String, BundleError> {
+    Ok(format!("{}/{}.ndjson", DATA_DIR, checked_name(name)?))
+}
+
+fn get_file<'a>(files: &'a BTreeMap<String, String>, path: &str) -> Result<&'a String, BundleError> {
+    files.get(path)
+        .ok_or_else(|| BundleError::MissingFile(path.to_string()))
+}
+
+impl Bundle {
+    /// The files of the bundle, paths are relative to the root of the bundle
+    pub fn to_files(&self) -> Result<BTreeMap<String, String>, BundleError> {
+        let mut files = BTreeMap::new();
+
+        for query in &self.queries {
+            files.insert(query_path(&query.name)?, query.statement.to_owned());
+        }
+
+        for script in &self.scripts {
+            let dir = script_dir(&script.name)?;
+            for (file_name, contents) in script.all_files() {
+                files.insert(format!("{}{}", dir, file_name), contents);
+            }
+        }
+
+        for (table_name, rows) in &self.table_data {
+            let mut ndjson = String::new();
+            for row in rows {
+                let line = serde_json::to_string(row)
+                    .map_err(|err| BundleError::SerializationError(err.to_string()))?;
+                ndjson.push_str(&line);
+                ndjson.push('\n');
+            }
+            files.insert(data_path(table_name)?, ndjson);
+        }
+
+        let manifest = Manifest {
+            version: BUNDLE_VERSION,
+            domain: self.domain.to_owned(),
+            tables: self.tables.to_owned(),
+            queries: self.queries
+                .iter()
+                .map(|query| QueryManifest {
+                    name: query.name.to_owned(),
+                    description: query.description.to_owned(),
+                })
+                .collect(),
+            scripts: self.scripts
+                .iter()
+                .map(|script| ScriptManifest {
+                    name: script.name.to_owned(),
+                    description: script.description.to_owned(),
+                    entrypoint: script.entrypoint.to_owned(),
+                    requirements: script.requirements.to_owned(),
+                    sandbox: script.sandbox.to_owned(),
+                })
+                .collect(),
+            views: self.views.to_owned(),
+            tags: self.tags.to_owned(),
+            entity_tags: self.entity_tags.to_owned(),
+            roles: self.roles.to_owned(),
+        };
+        let manifest = serde_json::to_string_pretty(&manifest)
+            .map_err(|err| BundleError::SerializationError(err.to_string()))?;
+        files.insert(MANIFEST_NAME.to_string(), manifest);
+
+        Ok(files)
+    }
+
+    /// Reads the bundle back from it's files
+    pub fn from_files(files: &BTreeMap<String, String>) -> Result<Self, BundleError> {
+        let manifest: Manifest = serde_json::from_str(get_file(files, MANIFEST_NAME)?)
+            .map_err(|err| BundleError::SerializationError(err.to_string()))?;
+
+        if manifest.version > BUNDLE_VERSION {
+            return Err(BundleError::UnsupportedVersion(manifest.version));
+        }
+
+        let mut queries = vec![];
+        for query in manifest.queries {
+            let statement = get_file(files, &query_path(&query.name)?)?;
+            queries.push(DataQueryEntity {
+                name: query.name,
+                description: query.description,
+                statement: statement.to_owned(),
+            });
+        }
+
+        let mut scripts = vec![];
+        for script in manifest.scripts {
+            let dir = script_dir(&script.name)?;
+            let mut script_files: BTreeMap<String, String> = files
+                .iter()
+                .filter(|(path, _)| path.starts_with(&dir))
+                .map(|(path, contents)| (path[dir.len()..].to_string(), contents.to_owned()))
+                .collect();
+            let text = script_files.remove(&script.entrypoint)
+                .ok_or_else(|| BundleError::MissingFile(format!("{}{}", dir, &script.entrypoint)))?;
+
+            scripts.push(Script {
+                name: script.name,
+                description: script.description,
+                text,
+                entrypoint: script.entrypoint,
+                files: script_files,
+                requirements: script.requirements,
+                sandbox: script.sandbox,
+            });
+        }
+
+        let mut table_data = BTreeMap::new();
+        for table in &manifest.tables {
+            let ndjson = match files.get(&data_path(&table.name)?) {
+                Some(ndjson) => ndjson,
+                None => continue,
+            };
+
+            let rows = ndjson
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line)
+                    .map_err(|err| BundleError::SerializationError(err.to_string())))
+                .collect::<Result<Vec<serde_json::Value>, BundleError>>()?;
+            table_data.insert(table.name.to_owned(), rows);
+        }
+
+        Ok(Bundle {
+            domain: manifest.domain,
+            tables: manifest.tables,
+            queries,
+            scripts,
+            views: manifest.views,
+            tags: manifest.tags,
+            entity_tags: manifest.entity_tags,
+            roles: manifest.roles,
+            table_data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+
+    #[test]
+    fn test_bundle_files() {
+        let mut table_data = BTreeMap::new();
+        table_data.insert("my_table".to_string(), vec![json!({ "id": 1, "message": "hello" })]);
+
+        let bundle = Bundle {
+            domain: "staging".to_string(),
+            tables: vec![from_value(json!({
+                "name": "my_table",
+                "description": "table description",
+                "schema": { "columns": [], "constraint": [] },
+            })).unwrap()],
+            queries: vec![DataQueryEntity {
+                name: "my_query".to_string(),
+                description: "query description".to_string(),
+                statement: "SELECT * FROM my_table".to_string(),
+            }],
+            scripts: vec![from_value(json!({
+                "name": "my_script",
+                "description": "script description",
+                "text": "import lib.helpers",
+                "files": { "lib/helpers.py": "print(42)" },
+            })).unwrap()],
+            roles: vec![RoleBundle {
+                name: "readers".to_string(),
+                description: None,
+                permissions: vec![Permission::get_table_data("my_table".to_string())],
+            }],
+            table_data,
+            ..Bundle::default()
+        };
+
+        let files = bundle.to_files().unwrap();
+        assert_eq!(files.get("queries/my_query.sql"), Some(&"SELECT * FROM my_table".to_string()));
+        assert_eq!(files.get("scripts/my_script/script.py"), Some(&"import lib.helpers".to_string()));
+        assert_eq!(files.get("scripts/my_script/lib/helpers.py"), Some(&"print(42)".to_string()));
+        assert!(files.contains_key("data/my_table.ndjson"));
+
+        assert_eq!(Bundle::from_files(&files), Ok(bundle));
+    }
+
+    #[test]
+    fn test_bundle_rejects_paths() {
+        let bundle = Bundle {
+            queries: vec![DataQueryEntity {
+                name: "../my_query".to_string(),
+                description: "".to_string(),
+                statement: "".to_string(),
+            }],
+            ..Bundle::default()
+        };
+
+        assert_eq!(bundle.to_files(), Err(BundleError::InvalidName("../my_query".to_string())));
+    }
+}
@@ -1,3 +1,8 @@
+use data::permissions::Permission;
+
+/// tokens that are handed out on behalf of an api key carry the id of the key instead of a session
+const API_KEY_JTI_PREFIX: &'static str = "key:";
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthClaims {
@@ -8,6 +13,12 @@ pub struct AuthClaims {
     pub username: String,
     pub is_admin: bool,
     pub role: Option<String>, //the default role that the user is interacting with
+    /// the id of the session or the api key that the token belongs to, tokens without either can't be revoked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// api keys can be limited to some of the permissions of the user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<Permission>>,
 }
 
 impl AuthClaims {
@@ -26,4 +37,23 @@ impl AuthClaims {
     pub fn is_user_admin(&self) -> bool {
         self.is_admin
     }
+
+    pub fn get_session_id(&self) -> Option<i64> {
+        self.jti.as_ref().and_then(|jti| jti.parse().ok())
+    }
+
+    pub fn get_api_key_id(&self) -> Option<i64> {
+        self.jti
+            .as_ref()
+            .and_then(|jti| if jti.starts_with(API_KEY_JTI_PREFIX) { Some(&jti[API_KEY_JTI_PREFIX.len()..]) } else { None })
+            .and_then(|api_key_id| api_key_id.parse().ok())
+    }
+
+    pub fn api_key_jti(api_key_id: i64) -> String {
+        format!("{}{}", API_KEY_JTI_PREFIX, api_key_id)
+    }
+
+    pub fn get_scopes(&self) -> Option<Vec<Permission>> {
+        self.scopes.to_owned()
+    }
 }
\ No newline at end of file
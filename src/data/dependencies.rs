@@ -0,0 +1,357 @@
+use std::ops::Range;
+
+use serde_json;
+
+use data::DataStoreEntity;
+use data::DataQueryEntity;
+use data::View;
+use data::ViewSource;
+
+/// Why one entity depends on another
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DependencyKind {
+    /// a table with a foreign key to the table
+    ForeignKey,
+    /// a query with the table in it's statement
+    QueryStatement,
+    /// a view that gets it's data from the table or query
+    ViewSource,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dependent {
+    pub type_name: String,
+    pub name: String,
+    pub kind: DependencyKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Symbol(char),
+}
+
+/// Splits the statement into words, quoted identifiers and symbols, string literals and comments are dropped
+fn tokenize(statement: &str) -> Vec<(Token, Range<usize>)> {
+    let chars: Vec<(usize, char)> = statement.char_indices().collect();
+    let end_of = |i: usize| chars.get(i).map(|(index, _)| *index).unwrap_or(statement.len());
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (start, c) = chars[i];
+        let next = chars.get(i + 1).map(|(_, c)| *c);
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i].1 != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i].1 == '*' && chars.get(i + 1).map(|(_, c)| *c) == Some('/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '\'' || c == '"' {
+            let mut value = String::new();
+            i += 1;
+            while i < chars.len() {
+                if chars[i].1 == c {
+                    // doubled quotes are escaped quotes
+                    if chars.get(i + 1).map(|(_, next)| *next) == Some(c) {
+                        value.push(c);
+                        i += 2;
+                        continue;
+                    }
+                    break;
+                }
+                value.push(chars[i].1);
+                i += 1;
+            }
+            i += 1;
+            if c == '"' {
+                tokens.push((Token::Quoted(value), start..end_of(i)));
+            }
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut value = String::new();
+            while i < chars.len() && (chars[i].1.is_alphanumeric() || chars[i].1 == '_' || chars[i].1 == '$') {
+                value.push(chars[i].1);
+                i += 1;
+            }
+            tokens.push((Token::Word(value), start..end_of(i)));
+        } else {
+            tokens.push((Token::Symbol(c), start..end_of(i + 1)));
+            i += 1;
+        }
+    }
+
+    tokens
+}
+
+fn is_keyword(token: &Token, keyword: &str) -> bool {
+    match token {
+        Token::Word(word) => word.eq_ignore_ascii_case(keyword),
+        _ => false,
+    }
+}
+
+/// Words that can follow a table name, and so can't be it's alias
+const CLAUSE_KEYWORDS: [&'static str; 29] = [
+    "WHERE", "JOIN", "INNER", "LEFT", "RIGHT", "FULL", "CROSS", "NATURAL", "ON", "USING",
+    "GROUP", "ORDER", "LIMIT", "OFFSET", "HAVING", "UNION", "EXCEPT", "INTERSECT", "WINDOW",
+    "SET", "VALUES", "RETURNING", "SELECT", "FOR", "DEFAULT", "OUTER", "FETCH", "DO", "AS",
+];
+
+/// Identifiers that are compared to the table names, unquoted identifiers are case insensitive in sql
+fn identifier(token: &Token) -> Option<String> {
+    match token {
+        Token::Word(word) => Some(word.to_lowercase()),
+        Token::Quoted(word) => Some(word.to_owned()),
+        Token::Symbol(_) => None,
+    }
+}
+
+/// Every place in the statement where a table is named, that is after `FROM`, `JOIN`, `UPDATE`, `INTO` and `TABLE`.
+/// This is not a full sql parser, names of CTEs come out as well, so the result should be checked against the existing tables
+fn table_references(statement: &str) -> Vec<(String, Range<usize>)> {
+    let tokens = tokenize(statement);
+    let mut references = vec![];
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let starts_table_list = is_keyword(&tokens[i].0, "FROM");
+        let starts_table = starts_table_list || ["JOIN", "UPDATE", "INTO", "TABLE"]
+            .iter()
+            .any(|keyword| is_keyword(&tokens[i].0, keyword));
+        i += 1;
+        if !starts_table {
+            continue;
+        }
+
+        loop {
+            while i < tokens.len() && (is_keyword(&tokens[i].0, "ONLY") || is_keyword(&tokens[i].0, "LATERAL")) {
+                i += 1;
+            }
+
+            // schema qualified names, only the last part is the table
+            let mut reference = None;
+            while i < tokens.len() {
+                match identifier(&tokens[i].0) {
+                    Some(name) => reference = Some((name, tokens[i].1.to_owned())),
+                    None => break,
+                }
+                i += 1;
+                if i < tokens.len() && tokens[i].0 == Token::Symbol('.') {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+
+            match reference {
+                Some(reference) => references.push(reference),
+                None => break,
+            }
+
+            if i < tokens.len() && is_keyword(&tokens[i].0, "AS") {
+                i += 2;
+            } else if i < tokens.len() && identifier(&tokens[i].0).is_some()
+                && !CLAUSE_KEYWORDS.iter().any(|keyword| is_keyword(&tokens[i].0, keyword)) {
+                i += 1;
+            }
+
+            if starts_table_list && i < tokens.len() && tokens[i].0 == Token::Symbol(',') {
+                i += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    references
+}
+
+/// The tables that the statement reads from or writes to, without duplicates
+pub fn referenced_tables(statement: &str) -> Vec<String> {
+    let mut tables: Vec<String> = vec![];
+    for (name, _) in table_references(statement) {
+        if !tables.contains(&name) {
+            tables.push(name);
+        }
+    }
+
+    tables
+}
+
+fn needs_quotes(name: &str) -> bool {
+    name.is_empty()
+        || name.chars().next().map(|c| c.is_numeric()).unwrap_or(true)
+        || !name.chars().all(|c| c.is_lowercase() || c.is_numeric() || c == '_')
+}
+
+/// Points every reference of the table in the statement to the new name, everything else is left as is
+pub fn rename_table_in_statement(statement: &str, old_name: &str, new_name: &str) -> String {
+    let replacement = if needs_quotes(new_name) {
+        format!("\"{}\"", new_name.replace('"', "\"\""))
+    } else {
+        new_name.to_owned()
+    };
+
+    let mut ranges: Vec<Range<usize>> = table_references(statement)
+        .into_iter()
+        .filter(|(name, _)| name == old_name)
+        .map(|(_, range)| range)
+        .collect();
+
+    // columns qualified with the table name, i.e. `users.name`
+    let tokens = tokenize(statement);
+    for (i, (token, range)) in tokens.iter().enumerate() {
+        let is_qualifier = tokens.get(i + 1).map(|(next, _)| next == &Token::Symbol('.')).unwrap_or(false);
+        let is_qualified = i > 0 && tokens[i - 1].0 == Token::Symbol('.');
+        if is_qualifier && !is_qualified && identifier(token).as_ref().map(|x| x.as_str()) == Some(old_name)
+            && !ranges.contains(range) {
+            ranges.push(range.to_owned());
+        }
+    }
+    ranges.sort_by_key(|range| range.start);
+
+    let mut renamed = statement.to_owned();
+    // going backwards, so that the ranges stay valid
+    for range in ranges.into_iter().rev() {
+        renamed.replace_range(range, &replacement);
+    }
+
+    renamed
+}
+
+/// The foreign keys are in the constraints of the schema, i.e. `{ "reference": { "foreignTable": ... } }`
+fn foreign_tables(constraint: &serde_json::Value) -> Option<&serde_json::Value> {
+    constraint.get("reference")
+        .or_else(|| constraint.get("referenceTogether"))
+        .and_then(|reference| reference.get("foreignTable"))
+}
+
+impl DataStoreEntity {
+    /// Tables that this table has a foreign key to
+    pub fn get_referenced_tables(&self) -> Vec<String> {
+        let constraints = self.schema
+            .get("constraint")
+            .and_then(|constraints| constraints.as_array());
+
+        let mut tables: Vec<String> = vec![];
+        for constraint in constraints.into_iter().flatten() {
+            let foreign_table = foreign_tables(constraint).and_then(|table| table.as_str());
+            if let Some(foreign_table) = foreign_table {
+                if !tables.iter().any(|table| table == foreign_table) {
+                    tables.push(foreign_table.to_owned());
+                }
+            }
+        }
+
+        tables
+    }
+
+    pub fn with_renamed_reference(&self, old_name: &str, new_name: &str) -> Self {
+        let mut renamed = self.to_owned();
+        let constraints = renamed.schema
+            .get_mut("constraint")
+            .and_then(|constraints| constraints.as_array_mut());
+
+        for constraint in constraints.into_iter().flatten() {
+            let key = if constraint.get("reference").is_some() { "reference" } else { "referenceTogether" };
+            if let Some(reference) = constraint.get_mut(key) {
+                if reference.get("foreignTable").and_then(|table| table.as_str()) == Some(old_name) {
+                    reference["foreignTable"] = json!(new_name);
+                }
+            }
+        }
+
+        renamed
+    }
+}
+
+impl DataQueryEntity {
+    pub fn with_renamed_table(&self, old_name: &str, new_name: &str) -> Self {
+        let mut renamed = self.to_owned();
+        renamed.statement = rename_table_in_statement(&self.statement, old_name, new_name);
+        renamed
+    }
+}
+
+impl View {
+    /// The type and name of the table or query that the view gets it's data from
+    pub fn get_source_entity(&self) -> Option<(&'static str, &str)> {
+        match &self.source {
+            Some(ViewSource::Table { name, .. }) => Some(("table", name.as_str())),
+            Some(ViewSource::Query { name, .. }) => Some(("query", name.as_str())),
+            None => None,
+        }
+    }
+
+    pub fn with_renamed_source(&self, type_name: &str, old_name: &str, new_name: &str) -> Self {
+        let mut renamed = self.to_owned();
+        if self.get_source_entity() == Some((type_name, old_name)) {
+            match &mut renamed.source {
+                Some(ViewSource::Table { name, .. }) | Some(ViewSource::Query { name, .. }) => *name = new_name.to_owned(),
+                None => (),
+            }
+        }
+
+        renamed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+
+    #[test]
+    fn test_referenced_tables() {
+        let statement = r#"
+            SELECT * FROM users u, public."Orders" AS o
+            LEFT JOIN items ON items.id = o.item_id -- FROM comments
+            WHERE u.name = 'FROM strings' AND o.id IN (SELECT order_id FROM refunds)
+        "#;
+        assert_eq!(referenced_tables(statement), vec!["users", "Orders", "items", "refunds"]);
+
+        assert_eq!(referenced_tables("INSERT INTO logs (message) VALUES ('hello')"), vec!["logs"]);
+        assert_eq!(referenced_tables("UPDATE ONLY Counters SET count = count + 1"), vec!["counters"]);
+    }
+
+    #[test]
+    fn test_rename_table_in_statement() {
+        let statement = "SELECT users.name FROM users JOIN user_roles ON user_roles.user_id = users.id";
+        assert_eq!(
+            rename_table_in_statement(statement, "users", "members"),
+            "SELECT members.name FROM members JOIN user_roles ON user_roles.user_id = members.id");
+        assert_eq!(
+            rename_table_in_statement(statement, "user_roles", "Member Roles"),
+            "SELECT users.name FROM users JOIN \"Member Roles\" ON \"Member Roles\".user_id = users.id");
+    }
+
+    #[test]
+    fn test_table_references() {
+        let table: DataStoreEntity = from_value(json!({
+            "name": "orders",
+            "description": "",
+            "schema": {
+                "columns": [],
+                "constraint": [
+                    { "key": "id" },
+                    { "reference": { "column": "user_id", "foreignTable": "users", "foreignColumn": "id" } },
+                ],
+            },
+        })).unwrap();
+        assert_eq!(table.get_referenced_tables(), vec!["users"]);
+
+        let renamed = table.with_renamed_reference("users", "members");
+        assert_eq!(renamed.get_referenced_tables(), vec!["members"]);
+    }
+}
@@ -0,0 +1,102 @@
+use serde_json::Value;
+
+/// A single difference between two json documents, `path` is a json pointer into both of them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "op")]
+pub enum Change {
+    Added {
+        path: String,
+        new: Value,
+    },
+    Removed {
+        path: String,
+        old: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+/// Structural diff between two json values
+/// objects are compared key by key, arrays index by index, anything else as a whole
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = vec![];
+    diff_at("", old, new, &mut changes);
+
+    changes
+}
+
+fn escape(key: &str) -> String {
+    key.replace("~", "~0").replace("/", "~1")
+}
+
+fn diff_at(path: &str, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map.iter() {
+                let key_path = format!("{}/{}", path, escape(key));
+                match new_map.get(key) {
+                    Some(new_value) => diff_at(&key_path, old_value, new_value, changes),
+                    None => changes.push(Change::Removed { path: key_path, old: old_value.to_owned() }),
+                }
+            }
+            for (key, new_value) in new_map.iter() {
+                if !old_map.contains_key(key) {
+                    let key_path = format!("{}/{}", path, escape(key));
+                    changes.push(Change::Added { path: key_path, new: new_value.to_owned() });
+                }
+            }
+        },
+        (Value::Array(old_list), Value::Array(new_list)) => {
+            let max_len = old_list.len().max(new_list.len());
+            for i in 0..max_len {
+                let index_path = format!("{}/{}", path, i);
+                match (old_list.get(i), new_list.get(i)) {
+                    (Some(old_value), Some(new_value)) => diff_at(&index_path, old_value, new_value, changes),
+                    (Some(old_value), None) => changes.push(Change::Removed { path: index_path, old: old_value.to_owned() }),
+                    (None, Some(new_value)) => changes.push(Change::Added { path: index_path, new: new_value.to_owned() }),
+                    (None, None) => {},
+                }
+            }
+        },
+        _ => if old != new {
+            changes.push(Change::Changed { path: path.to_string(), old: old.to_owned(), new: new.to_owned() });
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = json!({
+            "name": "my_table",
+            "schema": {
+                "columns": [{"name": "a"}, {"name": "b"}],
+                "constraint": "x",
+            },
+        });
+        let new = json!({
+            "name": "my_table",
+            "schema": {
+                "columns": [{"name": "a"}, {"name": "c"}, {"name": "d"}],
+                "key/id": "a",
+            },
+        });
+
+        let changes = diff(&old, &new);
+
+        assert_eq!(changes, vec![
+            Change::Changed { path: "/schema/columns/1/name".to_string(), old: json!("b"), new: json!("c") },
+            Change::Added { path: "/schema/columns/2".to_string(), new: json!({"name": "d"}) },
+            Change::Removed { path: "/schema/constraint".to_string(), old: json!("x") },
+            Change::Added { path: "/schema/key~1id".to_string(), new: json!("a") },
+        ]);
+        assert_eq!(diff(&old, &old), vec![]);
+    }
+}
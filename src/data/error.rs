@@ -23,4 +23,16 @@ pub enum DatastoreError {
     DbError(String),
     #[fail(display = "An unknown error occurred")]
     Unknown,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum BundleError {
+    #[fail(display = "bundle version {} is not supported", 0)]
+    UnsupportedVersion(u32),
+    #[fail(display = "missing file {:?} in bundle", 0)]
+    MissingFile(String),
+    #[fail(display = "invalid name {:?}, names end up in file paths", 0)]
+    InvalidName(String),
+    #[fail(display = "{}", 0)]
+    SerializationError(String),
+}
@@ -1,6 +1,8 @@
 
 use serde_json;
 use linked_hash_map::LinkedHashMap;
+use std::collections::BTreeMap;
+use openssl::sha::Sha256;
 
 pub mod utils;
 pub mod auth;
@@ -8,16 +10,37 @@ pub mod claims;
 pub mod channels;
 pub mod permissions;
 pub mod error;
+pub mod diff;
+pub mod bundle;
+pub mod sync;
+pub mod dependencies;
 
 pub trait Named {
     fn my_name(&self) -> &str;
 }
 
+pub trait Rename {
+    /// the same object under a different name
+    fn renamed(&self, name: &str) -> Self;
+}
+
+macro_rules! implement_rename {
+    ($entity:ty) => {
+        impl Rename for $entity {
+            fn renamed(&self, name: &str) -> Self {
+                let mut renamed = self.to_owned();
+                renamed.name = name.to_owned();
+                renamed
+            }
+        }
+    };
+}
+
 pub trait GetDomainId {
     fn my_domain_id(&self) -> i64;
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct DataStoreEntity {
     pub name: String, //TODO: make sure this is an alphanumeric
@@ -32,13 +55,15 @@ impl Named for DataStoreEntity {
     }
 }
 
+implement_rename!(DataStoreEntity);
+
 //impl GetDomainId for DataStoreEntity {
 //    fn my_domain_id(&self) -> i64 {
 //        self.domain_id
 //    }
 //}
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct DataQueryEntity {
     pub name: String, //TODO: make sure this is an alphanumeric
@@ -53,14 +78,53 @@ impl Named for DataQueryEntity {
     }
 }
 
+implement_rename!(DataQueryEntity);
+
 pub type ScriptParam = serde_json::Value;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Script {
     pub name: String, //TODO: make sure this is an alphanumeric
     pub description: String,
+    /// contents of the entrypoint
     pub text: String,
+    #[serde(default = "default_entrypoint")]
+    pub entrypoint: String,
+    /// the rest of the script tree, relative path to contents
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// pip requirements, one requirement specifier per entry (i.e. the lines of a requirements.txt)
+    #[serde(default)]
+    pub requirements: Vec<String>,
+    #[serde(default)]
+    pub sandbox: ScriptSandbox,
+}
+
+/// Changes to a script, files mapped to `null` are removed
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptPatch {
+    #[serde(default)]
+    pub files: BTreeMap<String, Option<String>>,
+    #[serde(default)]
+    pub entrypoint: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub requirements: Option<Vec<String>>,
+}
+
+/// What a script is allowed to touch when it runs inside the sandbox
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptSandbox {
+    #[serde(default)]
+    pub network: bool,
+    #[serde(default)]
+    pub read_only_paths: Vec<String>,
+    #[serde(default)]
+    pub read_write_paths: Vec<String>,
 }
 
 impl Named for Script {
@@ -69,13 +133,91 @@ impl Named for Script {
     }
 }
 
+implement_rename!(Script);
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+fn default_entrypoint() -> String {
+    "script.py".to_string()
+}
+
+impl Script {
+    /// All the files of the script, including the entrypoint
+    pub fn all_files(&self) -> BTreeMap<String, String> {
+        let mut files = self.files.to_owned();
+        files.insert(self.entrypoint.to_owned(), self.text.to_owned());
+
+        files
+    }
+
+    /// New script with the patch applied, the rest of the script stays the same.
+    /// None if the entrypoint is not one of the files of the patched script
+    pub fn patched(&self, patch: &ScriptPatch) -> Option<Script> {
+        let mut files = self.all_files();
+        for (file_name, contents) in patch.files.iter() {
+            match contents {
+                Some(contents) => files.insert(file_name.to_owned(), contents.to_owned()),
+                None => files.remove(file_name),
+            };
+        }
+
+        let entrypoint = patch.entrypoint.to_owned().unwrap_or_else(|| self.entrypoint.to_owned());
+        let text = files.remove(&entrypoint)?;
+
+        Some(Script {
+            name: self.name.to_owned(),
+            description: patch.description.to_owned().unwrap_or_else(|| self.description.to_owned()),
+            text,
+            entrypoint,
+            files,
+            requirements: patch.requirements.to_owned().unwrap_or_else(|| self.requirements.to_owned()),
+            sandbox: self.sandbox.to_owned(),
+        })
+    }
+
+    /// Hash of the script tree, changes whenever any of the files changes
+    pub fn version(&self) -> String {
+        let mut hasher = Sha256::new();
+        for (path, contents) in self.all_files() {
+            hasher.update(path.as_bytes());
+            hasher.update(&[0]);
+            hasher.update(contents.as_bytes());
+            hasher.update(&[0]);
+        }
+
+        hasher.finish()
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect()
+    }
+}
+
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct View {
     pub name: String, //TODO: make sure this is an alphanumeric
     pub description: String,
     pub view_state: serde_json::Value,
+    #[serde(default)]
+    pub source: Option<ViewSource>,
+}
+
+/// Where the data of a view comes from
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "type")]
+pub enum ViewSource {
+    #[serde(rename_all = "camelCase")]
+    Table {
+        name: String,
+        #[serde(default)]
+        query: serde_json::Value,
+    },
+    #[serde(rename_all = "camelCase")]
+    Query {
+        name: String,
+        #[serde(default)]
+        params: serde_json::Value,
+    },
 }
 
 impl Named for View {
@@ -84,6 +226,8 @@ impl Named for View {
     }
 }
 
+implement_rename!(View);
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Message {
     pub data: serde_json::Value,
@@ -91,6 +235,169 @@ pub struct Message {
     //TODO: maybe add the user as well
 }
 
+/// A single use of an entity, i.e. a query that ran or a table that was read
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageRecord {
+    pub entity_id: i64,
+    pub user_id: i64,
+    pub action: String,
+    pub latency_ms: Option<i64>,
+    pub row_count: Option<i64>,
+}
+
+/// How much an entity was used over a time range, entities that were never used have a call count of 0
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityUsage {
+    pub type_name: String,
+    pub name: String,
+    pub call_count: i64,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub p50_latency_ms: Option<f64>,
+    pub p95_latency_ms: Option<f64>,
+    pub total_rows: Option<i64>,
+    pub top_users: Vec<UserUsage>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserUsage {
+    pub username: String,
+    pub call_count: i64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl Named for Tag {
+    fn my_name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Narrows down a listing of entities, every field that is set has to match
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityFilter {
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub name_prefix: Option<String>,
+    #[serde(default)]
+    pub created_by: Option<i64>,
+    #[serde(default)]
+    pub modified_since: Option<chrono::NaiveDateTime>,
+    /// matches anywhere in the name or the description
+    #[serde(default)]
+    pub search: Option<String>,
+}
+
+/// How a row of table data was changed
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum DataOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A single row that was changed through a datastore, inserts have no `old` value and deletes have no `new` value
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataChange {
+    pub operation: DataOperation,
+    pub keys: serde_json::Value,
+    #[serde(default)]
+    pub old: Option<serde_json::Value>,
+    #[serde(default)]
+    pub new: Option<serde_json::Value>,
+}
+
+/// What a datastore returns after it modified data
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataMutation {
+    pub data: serde_json::Value,
+    pub changes: Vec<DataChange>,
+}
+
+/// An entry in the audit log of a table
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableAuditRecord {
+    pub transaction_id: i64,
+    pub made_at: chrono::NaiveDateTime,
+    pub made_by: String,
+    #[serde(flatten)]
+    pub change: DataChange,
+}
+
+/// Narrows down the audit log of a table, every field that is set has to match
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogFilter {
+    #[serde(default)]
+    pub start: Option<chrono::NaiveDateTime>,
+    #[serde(default)]
+    pub end: Option<chrono::NaiveDateTime>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub from_transaction: Option<i64>,
+    #[serde(default)]
+    pub to_transaction: Option<i64>,
+}
+
+/// Which changes of a table to revert, either a range of transactions or everything since a point in time
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(untagged)]
+pub enum RevertRange {
+    #[serde(rename_all = "camelCase")]
+    Transactions {
+        from_transaction: i64,
+        to_transaction: i64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Since {
+        since: chrono::NaiveDateTime,
+    },
+}
+
+/// What a datastore returns after it reverted changes
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataRevert {
+    /// the inverse of every change that could be reverted, in the order they were applied
+    pub changes: Vec<DataChange>,
+    pub conflicts: Vec<DataConflict>,
+}
+
+/// A change that couldn't be reverted since the row changed again afterwards
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataConflict {
+    /// position of the change in the reverted changes
+    pub index: usize,
+    pub current: Option<serde_json::Value>,
+}
+
+/// A change from the audit log that couldn't be reverted
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RevertConflict {
+    pub transaction_id: i64,
+    pub keys: serde_json::Value,
+    pub expected: Option<serde_json::Value>,
+    pub current: Option<serde_json::Value>,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DomainInfo {
     pub name: String,
@@ -0,0 +1,20 @@
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncOperation {
+    Create,
+    Update,
+    /// soft deleted, the entity keeps it's history
+    Delete,
+}
+
+/// A change needed to bring the metastore in line with the sync directory
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncChange {
+    pub type_name: String,
+    pub name: String,
+    pub operation: SyncOperation,
+    /// the entity was modified outside of the sync directory (i.e. in the UI) since the last sync,
+    /// or it was never synced at all
+    pub drifted: bool,
+}
@@ -12,3 +12,30 @@ pub enum OnDuplicate {
     Fail,
     Update,
 }
+
+/// What happens when something being imported already exists
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OnConflict {
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+impl Default for OnConflict {
+    fn default() -> Self {
+        OnConflict::Skip
+    }
+}
+
+/// What happens to the entities that depend on one that is deleted or renamed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DependentsPolicy {
+    Restrict,
+    Cascade,
+}
+
+impl Default for DependentsPolicy {
+    fn default() -> Self {
+        DependentsPolicy::Restrict
+    }
+}
@@ -2,12 +2,18 @@ use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::PooledConnection;
 use diesel::r2d2::Pool;
 use diesel::prelude::PgConnection;
+use linked_hash_map::LinkedHashMap;
 
 use plugins::v1::Domain;
 use plugins::v1::Datastore;
 use plugins::v1::DomainBuilder;
 use plugins::v1::DataStoreEntity;
 use plugins::v1::DatastoreError;
+use plugins::v1::DataMutation;
+use plugins::v1::DataChange;
+use plugins::v1::DataOperation;
+use plugins::v1::DataRevert;
+use plugins::v1::DataConflict;
 use plugins::v1::DataQuery;
 use plugins::v1::DataQueryEntity;
 
@@ -15,6 +21,9 @@ use kakapo_postgres::data::Table;
 use kakapo_postgres::data::TableData;
 use kakapo_postgres::data::KeyedTableData;
 use kakapo_postgres::data::KeyData;
+use kakapo_postgres::data::ObjectKeys;
+use kakapo_postgres::data::ObjectValues;
+use kakapo_postgres::data::Value;
 use kakapo_postgres::KakapoPostgres;
 use kakapo_postgres::update_state::UpdateTable;
 use kakapo_postgres::update_state::UpdateTableOps;
@@ -80,6 +89,64 @@ impl Domain for KakapoPostgresDone {
     }
 }
 
+impl From<diesel::result::Error> for DatastoreError {
+    fn from(err: diesel::result::Error) -> Self {
+        DatastoreError::DbError(err.to_string())
+    }
+}
+
+enum Reverted {
+    Applied(Vec<DataChange>),
+    Conflict(Option<serde_json::Value>),
+}
+
+fn to_row(value: &serde_json::Value) -> Result<LinkedHashMap<String, Value>, DatastoreError> {
+    serde_json::from_value(value.to_owned())
+        .map_err(|_| DatastoreError::DeserializationError)
+}
+
+/// Undoes a single change, as long as the row still looks like the change left it
+fn revert_change(action: &CrudTable, change: &DataChange) -> Result<Reverted, DatastoreError> {
+    let row = match (&change.new, &change.old) {
+        (Some(row), _) => to_row(row)?,
+        (None, Some(row)) => to_row(row)?,
+        (None, None) => return Err(DatastoreError::DeserializationError),
+    };
+
+    // a row that can't be looked up again can't be checked either, so it is left as it is
+    let mut keys = LinkedHashMap::new();
+    for (name, value) in action.row_keys(&row) {
+        match value.into_indexable() {
+            Some(key) => keys.insert(name, key),
+            None => {
+                debug!("Cannot revert a row by the column {:?}", &name);
+                return Ok(Reverted::Conflict(None));
+            },
+        };
+    }
+    if keys.is_empty() {
+        return Ok(Reverted::Conflict(None));
+    }
+    let keys = ObjectKeys::new(vec![keys]);
+
+    let current = match action.retrieve_by_keys(keys.to_owned())?.to_objects().into_iter().next() {
+        Some(current) => Some(serde_json::to_value(current).map_err(|_| DatastoreError::SerializationError)?),
+        None => None,
+    };
+    if current != change.new {
+        return Ok(Reverted::Conflict(current));
+    }
+
+    let (_, changes) = match (&change.operation, &change.old) {
+        (DataOperation::Insert, _) => action.delete(keys, true)?,
+        (DataOperation::Update, Some(old)) => action.update(keys, ObjectValues::new(vec![to_row(old)?]), true)?,
+        (DataOperation::Delete, Some(old)) => action.insert(ObjectValues::new(vec![to_row(old)?]), true)?,
+        (_, None) => return Err(DatastoreError::DeserializationError),
+    };
+
+    Ok(Reverted::Applied(changes))
+}
+
 // All of this is just boilerplate -__-
 impl Datastore for KakapoPostgresConnection {
     fn retrieve(&self, data_store: &DataStoreEntity, _: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
@@ -98,7 +165,7 @@ impl Datastore for KakapoPostgresConnection {
         Ok(res)
     }
 
-    fn insert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn insert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<DataMutation, DatastoreError> {
         let table: Result<Table, DatastoreError> = data_store.into();
         let table = table?;
 
@@ -111,14 +178,14 @@ impl Datastore for KakapoPostgresConnection {
             &self.conn,
         );
 
-        let res = action.insert(data, true)?; //TODO: fail on duplicate?
-        let res = serde_json::to_value(res)
+        let (res, changes) = action.insert(data, true)?; //TODO: fail on duplicate?
+        let data = serde_json::to_value(res)
             .map_err(|_| DatastoreError::SerializationError)?;
 
-        Ok(res)
+        Ok(DataMutation { data, changes })
     }
 
-    fn upsert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn upsert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<DataMutation, DatastoreError> {
         let table: Result<Table, DatastoreError> = data_store.into();
         let table = table?;
 
@@ -131,14 +198,14 @@ impl Datastore for KakapoPostgresConnection {
             &self.conn,
         );
 
-        let res = action.upsert(data)?;
-        let res = serde_json::to_value(res)
+        let (res, changes) = action.upsert(data)?;
+        let data = serde_json::to_value(res)
             .map_err(|_| DatastoreError::SerializationError)?;
 
-        Ok(res)
+        Ok(DataMutation { data, changes })
     }
 
-    fn update(&self, data_store: &DataStoreEntity, key_values: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn update(&self, data_store: &DataStoreEntity, key_values: &serde_json::Value) -> Result<DataMutation, DatastoreError> {
         let table: Result<Table, DatastoreError> = data_store.into();
         let table = table?;
 
@@ -151,14 +218,14 @@ impl Datastore for KakapoPostgresConnection {
             &self.conn,
         );
 
-        let res = action.update(keys, data, true)?; //TODO: fail on duplicate?
-        let res = serde_json::to_value(res)
+        let (res, changes) = action.update(keys, data, true)?; //TODO: fail on duplicate?
+        let data = serde_json::to_value(res)
             .map_err(|_| DatastoreError::SerializationError)?;
 
-        Ok(res)
+        Ok(DataMutation { data, changes })
     }
 
-    fn delete(&self, data_store: &DataStoreEntity, keys: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn delete(&self, data_store: &DataStoreEntity, keys: &serde_json::Value) -> Result<DataMutation, DatastoreError> {
         let table: Result<Table, DatastoreError> = data_store.into();
         let table = table?;
 
@@ -171,11 +238,34 @@ impl Datastore for KakapoPostgresConnection {
             &self.conn,
         );
 
-        let res = action.delete(keys, true)?; //TODO: fail on duplicate?
-        let res = serde_json::to_value(res)
+        let (res, changes) = action.delete(keys, true)?; //TODO: fail on duplicate?
+        let data = serde_json::to_value(res)
             .map_err(|_| DatastoreError::SerializationError)?;
 
-        Ok(res)
+        Ok(DataMutation { data, changes })
+    }
+
+    fn revert(&self, data_store: &DataStoreEntity, changes: &[DataChange]) -> Result<DataRevert, DatastoreError> {
+        let table: Result<Table, DatastoreError> = data_store.into();
+        let table = table?;
+
+        let action = CrudTable::new(
+            &table,
+            &self.conn,
+        );
+
+        // the action runs in a transaction already, a conflict doesn't roll back the changes that could be reverted
+        let mut reverted = vec![];
+        let mut conflicts = vec![];
+
+        for (index, change) in changes.iter().enumerate() {
+            match revert_change(&action, change)? {
+                Reverted::Applied(inverse) => reverted.extend(inverse),
+                Reverted::Conflict(current) => conflicts.push(DataConflict { index, current }),
+            }
+        }
+
+        Ok(DataRevert { changes: reverted, conflicts })
     }
 
     fn on_datastore_created(&self, new: &DataStoreEntity) -> Result<(), DatastoreError> {
@@ -13,6 +13,7 @@ use kakapo_postgres::data::ObjectKeys;
 use kakapo_postgres::data::TabularKeys;
 use kakapo_postgres::data::TabularValues;
 use kakapo_postgres::data::Table;
+use kakapo_postgres::data::Constraint;
 use kakapo_postgres::data::QueryParams;
 use kakapo_postgres::utils::TableDataFormat;
 
@@ -32,6 +33,16 @@ impl IndexableValue {
     }
 }
 
+impl Value {
+    pub fn into_indexable(self) -> Option<IndexableValue> {
+        match self {
+            Value::Integer(x) => Some(IndexableValue::Integer(x)),
+            Value::String(x) => Some(IndexableValue::String(x)),
+            _ => None,
+        }
+    }
+}
+
 impl RawTableDataColumns {
 
     pub fn new(keys: Vec<String>, values: Vec<String>) -> Self {
@@ -103,6 +114,21 @@ impl RawTableData {
         Ok(())
     }
 
+    /// every row as column name to value
+    pub fn to_objects(&self) -> Vec<LinkedHashMap<String, Value>> {
+        let col_names = self.columns.value_columns();
+
+        self.data
+            .iter()
+            .map(|table_row| {
+                col_names.iter()
+                    .zip(table_row.values.iter())
+                    .map(|(col_name, value)| (col_name.to_owned(), value.to_owned()))
+                    .collect()
+            })
+            .collect()
+    }
+
     pub fn format_with(self, format: &TableDataFormat) -> TableData {
         let col_names = self.columns.get_value_columns();
 
@@ -275,6 +301,16 @@ impl Table {
     pub fn get_column_names(&self) -> Vec<String> {
         self.schema.get_column_names()
     }
+
+    pub fn get_key_names(&self) -> Vec<String> {
+        self.schema.constraint
+            .iter()
+            .filter_map(|constraint| match constraint {
+                Constraint::Key(column) => Some(column.to_owned()),
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 impl QueryParams {
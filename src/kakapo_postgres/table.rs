@@ -7,10 +7,14 @@ use kakapo_postgres::data::Value;
 use kakapo_postgres::database::error::DbError;
 use kakapo_postgres::database::DatabaseFunctions;
 
+use linked_hash_map::LinkedHashMap;
+
 use diesel::r2d2::PooledConnection;
 use diesel::r2d2::ConnectionManager;
 use diesel::prelude::PgConnection;
 use plugins::v1::DatastoreError;
+use plugins::v1::DataChange;
+use plugins::v1::DataOperation;
 
 pub struct CrudTable<'a> {
     conn: &'a PooledConnection<ConnectionManager<PgConnection>>,
@@ -21,19 +25,76 @@ impl<'a> CrudTable<'a> {
     pub fn new(table: &'a Table, conn: &'a PooledConnection<ConnectionManager<PgConnection>>) -> Self {
         Self { table, conn }
     }
+
+    /// The key columns of a row, a table without keys is identified by the whole row
+    pub fn row_keys(&self, row: &LinkedHashMap<String, Value>) -> LinkedHashMap<String, Value> {
+        keys_of(&self.table.get_key_names(), row)
+    }
+}
+
+fn keys_of(key_names: &[String], row: &LinkedHashMap<String, Value>) -> LinkedHashMap<String, Value> {
+    if key_names.is_empty() {
+        return row.to_owned();
+    }
+
+    row.iter()
+        .filter(|(name, _)| key_names.contains(*name))
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect()
+}
+
+/// Matches the rows from before an update with the rows that the update returned. The rows are matched
+/// by their keys, after the update was applied to the old row, since the update can change the keys as well
+fn pair_updated_rows(
+    key_names: &[String],
+    old_rows: Vec<LinkedHashMap<String, Value>>,
+    new_rows: Vec<LinkedHashMap<String, Value>>,
+    updated_values: &LinkedHashMap<String, Value>,
+) -> Vec<(Option<LinkedHashMap<String, Value>>, LinkedHashMap<String, Value>)> {
+    let mut old_rows: Vec<Option<LinkedHashMap<String, Value>>> = old_rows.into_iter().map(Some).collect();
+
+    new_rows
+        .into_iter()
+        .map(|new_row| {
+            let new_keys = keys_of(key_names, &new_row);
+            let old_row = old_rows
+                .iter_mut()
+                .find(|old_row| match old_row {
+                    Some(old_row) => {
+                        let updated_row: LinkedHashMap<String, Value> = old_row
+                            .iter()
+                            .map(|(name, value)| (name.to_owned(), updated_values.get(name).unwrap_or(value).to_owned()))
+                            .collect();
+                        keys_of(key_names, &updated_row) == new_keys
+                    },
+                    None => false,
+                })
+                .and_then(|old_row| old_row.take());
+
+            (old_row, new_row)
+        })
+        .collect()
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<serde_json::Value, DatastoreError> {
+    serde_json::to_value(value)
+        .map_err(|_| DatastoreError::SerializationError)
 }
 
 
 pub trait CrudTableOps {
     fn retrieve(&self) -> Result<RawTableData, DatastoreError>;
 
-    fn insert(&self, data: ObjectValues, fail_on_duplicate: bool) -> Result<RawTableData, DatastoreError>;
+    fn retrieve_by_keys(&self, keys: ObjectKeys) -> Result<RawTableData, DatastoreError>;
 
-    fn upsert(&self, data: ObjectValues) -> Result<RawTableData, DatastoreError>;
+    // modifications also return the rows that changed, so that they can be audited
+    fn insert(&self, data: ObjectValues, fail_on_duplicate: bool) -> Result<(RawTableData, Vec<DataChange>), DatastoreError>;
 
-    fn update(&self, keys: ObjectKeys, data: ObjectValues, fail_on_not_found: bool) -> Result<RawTableData, DatastoreError>;
+    fn upsert(&self, data: ObjectValues) -> Result<(RawTableData, Vec<DataChange>), DatastoreError>;
 
-    fn delete(&self, keys: ObjectKeys, fail_on_not_found: bool) -> Result<RawTableData, DatastoreError>;
+    fn update(&self, keys: ObjectKeys, data: ObjectValues, fail_on_not_found: bool) -> Result<(RawTableData, Vec<DataChange>), DatastoreError>;
+
+    fn delete(&self, keys: ObjectKeys, fail_on_not_found: bool) -> Result<(RawTableData, Vec<DataChange>), DatastoreError>;
 }
 
 impl<'a> CrudTableOps for CrudTable<'a> {
@@ -45,11 +106,45 @@ impl<'a> CrudTableOps for CrudTable<'a> {
             .or_else(|err| Err(DatastoreError::DbError(err.to_string())))
     }
 
-    fn insert(&self, data: ObjectValues, fail_on_duplicate: bool) -> Result<RawTableData, DatastoreError> {
+    fn retrieve_by_keys(&self, keys: ObjectKeys) -> Result<RawTableData, DatastoreError> {
+
+        let table_column_names = self.table.get_column_names();
+        let raw_keys = keys.as_list();
+        let mut results = RawTableData::new(vec![], table_column_names.to_owned());
+
+        for key in raw_keys {
+            let key_names: Vec<String> = key.keys().map(|x| x.to_owned()).collect();
+            let values: Vec<Value> = key.values().map(|x| x.to_owned().into_value()).collect();
+
+            let query = format!(
+                "SELECT * FROM {name} WHERE {id}", //"SELECT * FROM table WHERE id = my_id"
+                name=&self.table.name,
+                id=key_names.iter().enumerate()
+                    .map(|(i, x)| format!("{} = ${}", x, i+1))
+                    .collect::<Vec<String>>()
+                    .join(" AND "),
+            );
+
+            let rows = self.conn
+                .exec(&query, values)
+                .or_else(|err| Err(DatastoreError::DbError(err.to_string())))?;
+
+            results.append(rows)
+                .or_else(|_| {
+                    error!("columns names are mismatched");
+                    Err(DatastoreError::Unknown)
+                })?;
+        }
+
+        Ok(results)
+    }
+
+    fn insert(&self, data: ObjectValues, fail_on_duplicate: bool) -> Result<(RawTableData, Vec<DataChange>), DatastoreError> {
 
         let table_column_names = self.table.get_column_names();
         let raw_data = data.as_list();
         let mut results = RawTableData::new(vec![], table_column_names.to_owned());
+        let mut changes = vec![];
 
         for row in raw_data {
             let sql_column_names: Vec<String> = row.keys().map(|x| x.to_owned()).collect();
@@ -77,6 +172,15 @@ impl<'a> CrudTableOps for CrudTable<'a> {
                     }
                 })?;
 
+            for row in new_row.to_objects() {
+                changes.push(DataChange {
+                    operation: DataOperation::Insert,
+                    keys: to_json(&self.row_keys(&row))?,
+                    old: None,
+                    new: Some(to_json(&row)?),
+                });
+            }
+
             results.append(new_row)
                 .or_else(|_| {
                     error!("columns names are mismatched");
@@ -84,15 +188,16 @@ impl<'a> CrudTableOps for CrudTable<'a> {
                 })?;
         }
 
-        Ok(results)
+        Ok((results, changes))
     }
 
-    fn upsert(&self, data: ObjectValues) -> Result<RawTableData, DatastoreError> {
+    fn upsert(&self, data: ObjectValues) -> Result<(RawTableData, Vec<DataChange>), DatastoreError> {
         //Note: doing this because I want to know whether it was an insert or update so that I can put in the correct data in the transactions table
         // otherise, maybe ON CONFLICT with triggers would have been the proper choice
         let table_column_names = self.table.get_column_names();
         let raw_data = data.as_list();
         let mut results = RawTableData::new(vec![], table_column_names.to_owned());
+        let mut changes = vec![];
 
         for row in raw_data {
             let sql_column_names: Vec<String> = row.keys().map(|x| x.to_owned()).collect();
@@ -119,6 +224,15 @@ impl<'a> CrudTableOps for CrudTable<'a> {
                     }
                 })?;
 
+            for row in new_row.to_objects() {
+                changes.push(DataChange {
+                    operation: DataOperation::Insert,
+                    keys: to_json(&self.row_keys(&row))?,
+                    old: None,
+                    new: Some(to_json(&row)?),
+                });
+            }
+
             results.append(new_row)
                 .or_else(|_| {
                     error!("columns names are mismatched");
@@ -126,15 +240,16 @@ impl<'a> CrudTableOps for CrudTable<'a> {
                 })?;
         }
 
-        Ok(results)
+        Ok((results, changes))
     }
 
-    fn update(&self, keys: ObjectKeys, data: ObjectValues, fail_on_not_found: bool) -> Result<RawTableData, DatastoreError> {
+    fn update(&self, keys: ObjectKeys, data: ObjectValues, fail_on_not_found: bool) -> Result<(RawTableData, Vec<DataChange>), DatastoreError> {
 
         let table_column_names = self.table.get_column_names();
         let raw_keys = keys.as_list();
         let raw_data = data.as_list();
         let mut results = RawTableData::new(vec![], table_column_names.to_owned());
+        let mut changes = vec![];
 
         for (key, row) in raw_keys.iter().zip(raw_data) {
             let column_names: Vec<String> = row.keys().map(|x| x.to_owned()).collect();
@@ -144,6 +259,9 @@ impl<'a> CrudTableOps for CrudTable<'a> {
             let key_values: Vec<Value> = key.values().map(|x| x.to_owned().into_value()).collect();
             values.extend(key_values);
 
+            // the old values are needed for the audit log
+            let old_row = self.retrieve_by_keys(ObjectKeys::new(vec![key.to_owned()]))?;
+
             let val_index = 1;
             let key_index = column_names.len() + 1;
 
@@ -173,6 +291,19 @@ impl<'a> CrudTableOps for CrudTable<'a> {
                     }
                 })?;
 
+            let key_names = self.table.get_key_names();
+            for (old, new) in pair_updated_rows(&key_names, old_row.to_objects(), new_row.to_objects(), &row) {
+                changes.push(DataChange {
+                    operation: DataOperation::Update,
+                    keys: to_json(&self.row_keys(&new))?,
+                    old: match old {
+                        Some(old) => Some(to_json(&old)?),
+                        None => None,
+                    },
+                    new: Some(to_json(&new)?),
+                });
+            }
+
             results.append(new_row)
                 .or_else(|_| {
                     error!("columns names are mismatched");
@@ -180,15 +311,15 @@ impl<'a> CrudTableOps for CrudTable<'a> {
                 })?;
         }
 
-        Ok(results)
-
+        Ok((results, changes))
     }
 
-    fn delete(&self, keys: ObjectKeys, fail_on_not_found: bool) -> Result<RawTableData, DatastoreError> {
+    fn delete(&self, keys: ObjectKeys, fail_on_not_found: bool) -> Result<(RawTableData, Vec<DataChange>), DatastoreError> {
 
         let table_column_names = self.table.get_column_names();
         let raw_keys = keys.as_list();
         let mut results = RawTableData::new(vec![], table_column_names.to_owned());
+        let mut changes = vec![];
 
         for key in raw_keys {
             let key_names: Vec<String> = key.keys().map(|x| x.to_owned()).collect();
@@ -216,6 +347,15 @@ impl<'a> CrudTableOps for CrudTable<'a> {
                     }
                 })?;
 
+            for row in new_row.to_objects() {
+                changes.push(DataChange {
+                    operation: DataOperation::Delete,
+                    keys: to_json(&key)?,
+                    old: Some(to_json(&row)?),
+                    new: None,
+                });
+            }
+
             results.append(new_row)
                 .or_else(|_| {
                     error!("columns names are mismatched");
@@ -223,6 +363,45 @@ impl<'a> CrudTableOps for CrudTable<'a> {
                 })?;
         }
 
-        Ok(results)
+        Ok((results, changes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn row(id: i64, name: &str) -> LinkedHashMap<String, Value> {
+        let mut row = LinkedHashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("name".to_string(), Value::String(name.to_string()));
+        row
+    }
+
+    #[test]
+    fn test_pair_updated_rows_by_keys() {
+        let key_names = vec!["id".to_string()];
+        let mut updated_values = LinkedHashMap::new();
+        updated_values.insert("name".to_string(), Value::String("new".to_string()));
+
+        // the database doesn't return the rows in the same order
+        let pairs = pair_updated_rows(
+            &key_names,
+            vec![row(1, "a"), row(2, "b")],
+            vec![row(2, "new"), row(1, "new")],
+            &updated_values);
+        assert_eq!(pairs, vec![
+            (Some(row(2, "b")), row(2, "new")),
+            (Some(row(1, "a")), row(1, "new")),
+        ]);
+
+        // the update can change the key itself
+        let mut updated_values = LinkedHashMap::new();
+        updated_values.insert("id".to_string(), Value::Integer(3));
+        let pairs = pair_updated_rows(&key_names, vec![row(1, "a")], vec![row(3, "a")], &updated_values);
+        assert_eq!(pairs, vec![(Some(row(1, "a")), row(3, "a"))]);
+
+        let pairs = pair_updated_rows(&key_names, vec![], vec![row(3, "a")], &updated_values);
+        assert_eq!(pairs, vec![(None, row(3, "a"))]);
     }
 }
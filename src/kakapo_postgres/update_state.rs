@@ -1,5 +1,6 @@
 
 use diesel::RunQueryDsl;
+use serde_json;
 
 use diesel::r2d2::PooledConnection;
 use diesel::r2d2::ConnectionManager;
@@ -13,6 +14,7 @@ use state::user_management::UserManagementOps;
 
 use kakapo_postgres::data::DataType;
 use kakapo_postgres::data::Table;
+use kakapo_postgres::data::Constraint;
 
 use plugins::v1::DatastoreError;
 
@@ -44,6 +46,20 @@ fn get_sql_data_type(data_type: &DataType) -> String {
     }
 }
 
+/// The schema without the names of the referenced tables, postgres keeps the foreign keys when those are renamed
+fn without_foreign_tables(table: &Table) -> Result<serde_json::Value, DatastoreError> {
+    let mut schema = table.schema.to_owned();
+    for constraint in schema.constraint.iter_mut() {
+        match constraint {
+            Constraint::Reference { foreign_table, .. } |
+            Constraint::ReferenceTogether { foreign_table, .. } => foreign_table.clear(),
+            _ => (),
+        }
+    }
+
+    serde_json::to_value(&schema).or_else(|_| Err(DatastoreError::SerializationError))
+}
+
 pub struct UpdateTable<'a> {
     conn: &'a PooledConnection<ConnectionManager<PgConnection>>,
 }
@@ -95,12 +111,20 @@ impl<'a> UpdateTableOps for UpdateTable<'a> {
     }
 
     fn update_table(&self, old: &Table, new: &Table) -> Result<(), DatastoreError> {
-        unimplemented!();
-        let command = format!("ALTER TABLE \"{}\";", &old.name);
-        diesel::sql_query(command)
-            .execute(self.conn)
-            .or_else(|err|
-                Err(DatastoreError::DbError(err.to_string())))?;
+        if old.name != new.name {
+            let command = format!("ALTER TABLE \"{}\" RENAME TO \"{}\";", &old.name, &new.name);
+            info!("DSL command: `{}`", &command);
+
+            diesel::sql_query(command)
+                .execute(self.conn)
+                .or_else(|err|
+                    Err(DatastoreError::DbError(err.to_string())))?;
+        }
+
+        //TODO: alter the columns and constraints
+        if without_foreign_tables(old)? != without_foreign_tables(new)? {
+            Err(DatastoreError::NotSupported)?;
+        }
 
         Ok(())
     }
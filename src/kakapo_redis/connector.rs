@@ -4,6 +4,10 @@ use plugins::v1::Datastore;
 use plugins::v1::DomainBuilder;
 use plugins::v1::DataStoreEntity;
 use plugins::v1::DatastoreError;
+use plugins::v1::DataMutation;
+use plugins::v1::DataChange;
+use plugins::v1::DataOperation;
+use plugins::v1::DataRevert;
 use plugins::v1::DataQuery;
 use plugins::v1::DataQueryEntity;
 
@@ -87,7 +91,7 @@ impl Datastore for KakapoRedisConnection {
         Ok(res)
     }
 
-    fn insert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn insert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<DataMutation, DatastoreError> {
         let table: Result<Table, DatastoreError> = data_store.into();
         let table = table?;
         let table_name = table.get_name();
@@ -96,6 +100,7 @@ impl Datastore for KakapoRedisConnection {
             .map_err(|_| DatastoreError::SerializationError)?;
 
         let mut results = KeyValues::new();
+        let mut changes = vec![];
         for (key, value) in data {
             let key_val: Option<(String, String)> = self.conn.set(&format!("{}:{}", table_name, &key), &value) //TODO: maybe use multiset
                 .map(|res: String| {
@@ -112,28 +117,39 @@ impl Datastore for KakapoRedisConnection {
                 });
 
             if let Some((ok_key, ok_val)) = key_val {
+                // redis doesn't tell us what was there before, so everything is recorded as an insert
+                changes.push(DataChange {
+                    operation: DataOperation::Insert,
+                    keys: json!(ok_key),
+                    old: None,
+                    new: Some(json!(ok_val)),
+                });
                 results.insert(ok_key, ok_val);
             }
         }
 
-        let res = serde_json::to_value(results)
+        let data = serde_json::to_value(results)
             .map_err(|err| DatastoreError::SerializationError)?;
 
-        Ok(res)
+        Ok(DataMutation { data, changes })
     }
 
-    fn upsert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn upsert(&self, data_store: &DataStoreEntity, rows: &serde_json::Value) -> Result<DataMutation, DatastoreError> {
         self.insert(data_store, rows) // Same as insert
     }
 
-    fn update(&self, data_store: &DataStoreEntity, key_values: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn update(&self, data_store: &DataStoreEntity, key_values: &serde_json::Value) -> Result<DataMutation, DatastoreError> {
         self.insert(data_store, key_values) // Same as insert
     }
 
-    fn delete(&self, data_store: &DataStoreEntity, keys: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn delete(&self, data_store: &DataStoreEntity, keys: &serde_json::Value) -> Result<DataMutation, DatastoreError> {
         unimplemented!()
     }
 
+    fn revert(&self, data_store: &DataStoreEntity, changes: &[DataChange]) -> Result<DataRevert, DatastoreError> {
+        Err(DatastoreError::NotSupported) // the old values aren't known, so there is nothing to revert to
+    }
+
     fn on_datastore_created(&self, new: &DataStoreEntity) -> Result<(), DatastoreError> {
         unimplemented!()
     }
@@ -20,13 +20,19 @@ extern crate futures;
 extern crate inflector;
 extern crate json;
 extern crate jsonwebtoken;
+extern crate ldap3;
+extern crate lettre;
+extern crate lettre_email;
+extern crate libc;
 extern crate linked_hash_map;
 #[macro_use]
 extern crate log;
+extern crate native_tls;
 extern crate num_cpus;
 extern crate r2d2;
 extern crate r2d2_redis;
 extern crate rand;
+extern crate reqwest;
 extern crate serde;
 #[macro_use]
 extern crate serde_json;
@@ -38,6 +44,8 @@ extern crate tempfile;
 extern crate time_test;
 extern crate tokio;
 extern crate tokio_core;
+extern crate tokio_uds;
+extern crate url;
 extern crate uuid;
 
 // Mods
@@ -69,7 +77,15 @@ use actix_web::middleware::cors::CorsBuilder;
 pub use connection::AppStateBuilder;
 pub use connection::AppState;
 pub use connection::AppStateLike;
+pub use auth::send_mail::SmtpSettings;
+pub use auth::oidc::OidcSettings;
+pub use auth::ldap::LdapSettings;
+pub use auth::login_throttle::LoginThrottleSettings;
+pub use auth::password_policy::PasswordPolicy;
+pub use auth::password_policy::CharacterClass;
+pub use data::permissions::Permission;
 pub use metastore::setup_admin;
+pub use metastore::setup_admin_with_policy;
 pub use server::Server;
 
 use actix_web::test::TestApp;
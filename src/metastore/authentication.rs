@@ -17,19 +17,51 @@ use data::auth::NewUser;
 use data::auth::User;
 use data::auth::UserInfo;
 use data::auth::SessionToken;
+use data::auth::Session;
+use data::auth::ApiKey;
+use data::auth::NewApiKey;
+use data::auth::MfaEnrollment;
+use data::auth::MfaChallenge;
+use data::auth::LoginFailure;
+use data::auth::ExternalUser;
 
 use state::authentication::AuthenticationOps;
 use state::user_management::UserManagementOps;
 use state::Authentication;
+use state::Authorization;
 use state::error::UserManagementError;
 
 use metastore::schema;
 use auth::tokens::Token;
+use auth::ldap::LdapError;
+use auth::oidc::RoleMapping;
+use auth::totp;
 use metastore::dbdata;
+use metastore::user_management::find_user;
+use metastore::user_management::get_user_by_identifier;
 use metastore;
 use diesel::prelude::*;
 use diesel::result::Error;
+use serde_json;
 
+/// Makes api keys recognizable, i.e. for secret scanners
+const API_KEY_PREFIX: &'static str = "kak_";
+/// How much of the key is kept around in the clear
+const API_KEY_DISPLAY_LENGTH: usize = 12;
+/// How long the user has to enter the code after the password was checked
+const MFA_CHALLENGE_DURATION_SECS: i64 = 300;
+/// The challenge can't be used anymore after this many wrong codes, the user has to log in again
+const MAX_MFA_ATTEMPTS: i32 = 5;
+/// How many of the latest login failures are returned
+const LOGIN_FAILURE_LIMIT: i64 = 100;
+
+/// Failed logins are counted for the user and for the address they came from
+struct ThrottleKey {
+    key: String,
+    free_attempts: i32,
+    /// only users are locked, addresses are only slowed down
+    can_lock: bool,
+}
 
 impl<'a> AuthenticationOps for Authentication<'a> {
 
@@ -67,7 +99,78 @@ impl<'a> AuthenticationOps for Authentication<'a> {
         Ok(hashed_pass)
     }
 
+    fn authenticate_with_directory(&self, user_identifier: &str, password: &str) -> Result<Option<ExternalUser>, UserManagementError> {
+        let issuer = match self.directory.get_issuer() {
+            Ok(issuer) => issuer,
+            Err(LdapError::NotConfigured) => return Ok(None),
+            Err(err) => return Err(UserManagementError::AuthenticationError(err.to_string())),
+        };
+
+        // users that are already linked are looked up with their username, they might have logged in with their email
+        let user = find_user(self.conn, user_identifier)?;
+        let is_linked = match &user {
+            Some(user) => self.has_identity(user.user_id, &issuer)?,
+            None => false,
+        };
+        let username = match &user {
+            Some(user) if is_linked => user.username.to_owned(),
+            _ => user_identifier.to_owned(),
+        };
+
+        match self.directory.authenticate(&username, password) {
+            Ok(Some(directory_user)) => Ok(Some(directory_user.to_external_user(&issuer))),
+            Ok(None) if is_linked => {
+                info!("{:?} is no longer in the directory", &username);
+                Err(UserManagementError::Unauthorized)
+            },
+            Ok(None) => Ok(None),
+            Err(LdapError::InvalidCredentials) => {
+                info!("Directory authentication failed for {:?}", &username);
+                Err(UserManagementError::Unauthorized)
+            },
+            // nothing is known about users that aren't linked, they are checked locally like before the directory was added
+            Err(err) => if is_linked {
+                error!("Could not check the password of {:?} with the directory: {:?}", &username, &err);
+                Err(UserManagementError::Unauthorized)
+            } else {
+                warn!("Could not reach the directory, checking {:?} locally: {:?}", &username, &err);
+                Ok(None)
+            },
+        }
+    }
+
+    fn get_directory_role_mappings(&self) -> Vec<RoleMapping> {
+        self.directory.get_role_mappings()
+    }
+
     fn create_session(&self, user: UserInfo) -> Result<SessionToken, UserManagementError> {
+        let (is_deprovisioned, is_disabled, is_service_account) = schema::user::table
+            .filter(schema::user::columns::user_id.eq(user.user_id))
+            .select((
+                schema::user::columns::deprovisioned,
+                schema::user::columns::disabled,
+                schema::user::columns::is_service_account,
+            ))
+            .get_result::<(bool, bool, bool)>(self.conn)
+            .map_err(|err| {
+                error!("Could not get user: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        if is_deprovisioned {
+            info!("Not starting a session for {:?}, the user was removed from the directory", &user.username);
+            return Err(UserManagementError::Unauthorized);
+        }
+
+        if is_disabled {
+            info!("Not starting a session for {:?}, the user is disabled", &user.username);
+            return Err(UserManagementError::Unauthorized);
+        }
+
+        if is_service_account {
+            info!("Not starting a session for {:?}, service accounts only use api keys", &user.username);
+            return Err(UserManagementError::Unauthorized);
+        }
 
         let refresh_token = Token::new()
             .map_err(|err| {
@@ -85,6 +188,8 @@ impl<'a> AuthenticationOps for Authentication<'a> {
             token: token_string,
             created_at: NaiveDateTime::from_timestamp(now.timestamp(), 0),
             expires_at: NaiveDateTime::from_timestamp((now + Duration::seconds(refresh_duration)).timestamp(), 0),
+            device: self.client_info.device.to_owned(),
+            ip_address: self.client_info.ip_address.to_owned(),
         };
 
         let session = diesel::insert_into(schema::session::table)
@@ -96,8 +201,9 @@ impl<'a> AuthenticationOps for Authentication<'a> {
                 UserManagementError::InternalError(err.to_string())
             })?;
 
+        self.session_cache.set_active(session.session_id);
 
-        self.build_jwt_token(now, user, session_token.token)
+        self.build_jwt_token(now, user, session.token, Some(session.session_id))
     }
 
     fn refresh_session(&self, token_string: String) -> Result<SessionToken, UserManagementError> {
@@ -105,9 +211,11 @@ impl<'a> AuthenticationOps for Authentication<'a> {
         let now = Utc::now();
         let naive_datetime_now = NaiveDateTime::from_timestamp(now.timestamp(), 0);
 
-        let token = schema::session::table
-            .filter(schema::session::columns::token.eq(&token_string))
-            .filter(schema::session::columns::expires_at.gt(naive_datetime_now))
+        let token = diesel::update(
+            schema::session::table
+                .filter(schema::session::columns::token.eq(&token_string))
+                .filter(schema::session::columns::expires_at.gt(naive_datetime_now)))
+            .set(schema::session::columns::last_used_at.eq(naive_datetime_now))
             .get_result::<dbdata::RawSessionToken>(self.conn)
             .map_err(|err| match err {
                 Error::NotFound => {
@@ -128,6 +236,15 @@ impl<'a> AuthenticationOps for Authentication<'a> {
                 UserManagementError::InternalError(err.to_string())
             })?;
 
+        if user.deprovisioned {
+            info!("Not refreshing the session of {:?}, the user was removed from the directory", &user.username);
+            return Err(UserManagementError::Unauthorized);
+        }
+
+        if user.disabled {
+            info!("Not refreshing the session of {:?}, the user is disabled", &user.username);
+            return Err(UserManagementError::Unauthorized);
+        }
 
         let duration = self.jwt_duration;
 
@@ -138,11 +255,11 @@ impl<'a> AuthenticationOps for Authentication<'a> {
             display_name: user.display_name,
         };
 
-        self.build_jwt_token(now, user, token_string)
+        self.build_jwt_token(now, user, token_string, Some(token.session_id))
     }
 
     fn delete_session(&self, user_id: i64) -> Result<(), UserManagementError> {
-        let _tokens = diesel::delete(schema::session::table)
+        let tokens = diesel::delete(schema::session::table)
             .filter(schema::session::columns::user_id.eq(&user_id))
             .get_results::<dbdata::RawSessionToken>(self.conn)
             .map_err(|err| {
@@ -150,18 +267,840 @@ impl<'a> AuthenticationOps for Authentication<'a> {
                 UserManagementError::InternalError(err.to_string())
             })?;
 
+        for token in tokens {
+            self.session_cache.evict(token.session_id);
+        }
         info!("All tokens for user id {} removed", user_id);
 
         Ok(())
     }
 
+    fn get_sessions(&self, user_id: i64, current_session_id: Option<i64>) -> Result<Vec<Session>, UserManagementError> {
+        let naive_datetime_now = NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0);
+        let tokens = schema::session::table
+            .filter(schema::session::columns::user_id.eq(&user_id))
+            .filter(schema::session::columns::expires_at.gt(naive_datetime_now))
+            .order(schema::session::columns::last_used_at.desc())
+            .get_results::<dbdata::RawSessionToken>(self.conn)
+            .map_err(|err| {
+                error!("Could not get sessions: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        let sessions = tokens
+            .into_iter()
+            .map(|token| Session {
+                session_id: token.session_id,
+                device: token.device,
+                ip_address: token.ip_address,
+                created_at: token.created_at,
+                last_used_at: token.last_used_at,
+                expires_at: token.expires_at,
+                is_current: Some(token.session_id) == current_session_id,
+            })
+            .collect();
+
+        Ok(sessions)
+    }
+
+    fn revoke_session(&self, user_id: i64, session_id: i64) -> Result<(), UserManagementError> {
+        let token = diesel::delete(schema::session::table)
+            .filter(schema::session::columns::session_id.eq(&session_id))
+            .filter(schema::session::columns::user_id.eq(&user_id))
+            .get_result::<dbdata::RawSessionToken>(self.conn)
+            .map_err(|err| match err {
+                Error::NotFound => UserManagementError::NotFound,
+                _ => {
+                    error!("Could not revoke session: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                },
+            })?;
+
+        self.session_cache.evict(token.session_id);
+        info!("Session {} for user id {} removed", session_id, user_id);
+
+        Ok(())
+    }
+
+    fn revoke_user_sessions(&self, user_identifier: &str) -> Result<(), UserManagementError> {
+        let user = get_user_by_identifier(self.conn, &user_identifier)?;
+
+        self.delete_session(user.user_id)
+    }
+
+    fn is_session_active(&self, session_id: i64) -> Result<bool, UserManagementError> {
+        if self.session_cache.is_active(session_id) {
+            return Ok(true);
+        }
+
+        let naive_datetime_now = NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0);
+        let result = diesel::update(
+            schema::session::table
+                .filter(schema::session::columns::session_id.eq(&session_id))
+                .filter(schema::session::columns::expires_at.gt(naive_datetime_now)))
+            .set(schema::session::columns::last_used_at.eq(naive_datetime_now))
+            .get_result::<dbdata::RawSessionToken>(self.conn);
+
+        match result {
+            Ok(_) => {
+                self.session_cache.set_active(session_id);
+                Ok(true)
+            },
+            Err(Error::NotFound) => {
+                info!("Session {} was revoked or has expired", session_id);
+                Ok(false)
+            },
+            Err(err) => {
+                error!("Could not check session: {:?}", &err);
+                Err(UserManagementError::InternalError(err.to_string()))
+            },
+        }
+    }
+
+    fn create_scoped_token(&self, user_id: i64, session_id: Option<i64>, api_key_id: Option<i64>, scopes: Option<Vec<Permission>>, duration: i64) -> Result<String, UserManagementError> {
+        let user = schema::user::table
+            .filter(schema::user::columns::user_id.eq(user_id))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| match err {
+                Error::NotFound => UserManagementError::NotFound,
+                _ => {
+                    error!("Could not get user: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                },
+            })?;
+
+        if user.disabled || user.deprovisioned {
+            info!("Not creating a scoped token for {:?}, the user can't log in", &user.username);
+            return Err(UserManagementError::Unauthorized);
+        }
+
+        // the token is tied to whatever the caller authenticated with, so revoking that revokes the token too
+        let jti = match (session_id, api_key_id) {
+            (Some(session_id), _) => Some(session_id.to_string()),
+            (None, Some(api_key_id)) => Some(AuthClaims::api_key_jti(api_key_id)),
+            (None, None) => None,
+        };
+
+        let now = Utc::now();
+        // no refresh token, the token can't outlive the duration
+        let claims = AuthClaims {
+            iss: self.jwt_issuer.to_owned(),
+            sub: user.user_id,
+            iat: now.timestamp(),
+            exp: (now + Duration::seconds(duration)).timestamp(),
+            username: user.username,
+            is_admin: user.user_id == metastore::ADMIN_USER_ID && scopes.is_none(),
+            role: None,
+            jti,
+            scopes,
+        };
+
+        jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, self.jwt_secret.as_ref())
+            .map_err(|err| UserManagementError::AuthenticationError(err.to_string()))
+    }
+
+    fn is_api_key_active(&self, api_key_id: i64) -> Result<bool, UserManagementError> {
+        let naive_datetime_now = NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0);
+        let result = schema::api_key::table
+            .inner_join(schema::user::table)
+            .filter(schema::api_key::columns::api_key_id.eq(&api_key_id))
+            .filter(schema::api_key::columns::expires_at.is_null()
+                .or(schema::api_key::columns::expires_at.gt(naive_datetime_now)))
+            .filter(schema::user::columns::disabled.eq(false))
+            .filter(schema::user::columns::deprovisioned.eq(false))
+            .select(schema::api_key::columns::api_key_id)
+            .first::<i64>(self.conn);
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound) => {
+                info!("Api key {} was revoked or has expired", api_key_id);
+                Ok(false)
+            },
+            Err(err) => {
+                error!("Could not check api key: {:?}", &err);
+                Err(UserManagementError::InternalError(err.to_string()))
+            },
+        }
+    }
+
+    fn create_api_key(&self, user_id: i64, name: &str, scopes: Option<Vec<Permission>>, expires_at: Option<NaiveDateTime>) -> Result<NewApiKey, UserManagementError> {
+        let token = Token::new()
+            .map_err(|err| {
+                error!("could not create a random api key: {:?}", &err);
+                UserManagementError::Unknown
+            })?;
+        let key = format!("{}{}", API_KEY_PREFIX, token.as_string());
+        let scopes = match scopes {
+            Some(scopes) => Some(serde_json::to_value(scopes)
+                .map_err(|err| UserManagementError::InternalError(err.to_string()))?),
+            None => None,
+        };
+
+        let new_api_key = dbdata::NewRawApiKey {
+            user_id,
+            name: name.to_string(),
+            key_prefix: key.chars().take(API_KEY_DISPLAY_LENGTH).collect(),
+            key_hash: Token::digest(&key),
+            scopes,
+            expires_at,
+        };
+
+        let api_key = diesel::insert_into(schema::api_key::table)
+            .values(&new_api_key)
+            .get_result::<dbdata::RawApiKey>(self.conn)
+            .map_err(|err| {
+                error!("Could not create api key err: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        info!("Created api key {:?} for user id {}", &api_key.name, user_id);
+        Ok(NewApiKey {
+            key,
+            api_key: to_api_key(api_key)?,
+        })
+    }
+
+    fn get_api_keys(&self, user_id: i64) -> Result<Vec<ApiKey>, UserManagementError> {
+        schema::api_key::table
+            .filter(schema::api_key::columns::user_id.eq(&user_id))
+            .order(schema::api_key::columns::created_at.desc())
+            .get_results::<dbdata::RawApiKey>(self.conn)
+            .map_err(|err| {
+                error!("Could not get api keys: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?
+            .into_iter()
+            .map(to_api_key)
+            .collect()
+    }
+
+    fn revoke_api_key(&self, user_id: i64, api_key_id: i64) -> Result<(), UserManagementError> {
+        let api_key = diesel::delete(schema::api_key::table)
+            .filter(schema::api_key::columns::api_key_id.eq(&api_key_id))
+            .filter(schema::api_key::columns::user_id.eq(&user_id))
+            .get_result::<dbdata::RawApiKey>(self.conn)
+            .map_err(|err| match err {
+                Error::NotFound => UserManagementError::NotFound,
+                _ => {
+                    error!("Could not revoke api key: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                },
+            })?;
+
+        info!("Api key {:?} for user id {} revoked", &api_key.name, user_id);
+
+        Ok(())
+    }
+
+    fn authenticate_api_key(&self, key: &str) -> Result<AuthClaims, UserManagementError> {
+        let now = Utc::now();
+        let naive_datetime_now = NaiveDateTime::from_timestamp(now.timestamp(), 0);
+        let api_key = diesel::update(
+            schema::api_key::table
+                .filter(schema::api_key::columns::key_hash.eq(Token::digest(key)))
+                .filter(schema::api_key::columns::expires_at.is_null()
+                    .or(schema::api_key::columns::expires_at.gt(naive_datetime_now))))
+            .set((
+                schema::api_key::columns::last_used_at.eq(naive_datetime_now),
+                schema::api_key::columns::use_count.eq(schema::api_key::columns::use_count + 1),
+            ))
+            .get_result::<dbdata::RawApiKey>(self.conn)
+            .map_err(|err| match err {
+                Error::NotFound => {
+                    info!("Api key does not exist, was revoked or has expired");
+                    UserManagementError::Unauthorized
+                },
+                _ => {
+                    error!("Could not check api key: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                },
+            })?;
+
+        let user = schema::user::table
+            .filter(schema::user::columns::user_id.eq(api_key.user_id))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| {
+                error!("Could not get user: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        if user.deprovisioned {
+            info!("Not accepting the api key of {:?}, the user was removed from the directory", &user.username);
+            return Err(UserManagementError::Unauthorized);
+        }
+
+        if user.disabled {
+            info!("Not accepting the api key of {:?}, the user is disabled", &user.username);
+            return Err(UserManagementError::Unauthorized);
+        }
+
+        let api_key = to_api_key(api_key)?;
+        // a scoped key of the admin is limited like any other key
+        let is_admin = user.user_id == metastore::ADMIN_USER_ID && api_key.scopes.is_none();
+        Ok(AuthClaims {
+            iss: self.jwt_issuer.to_owned(),
+            sub: user.user_id,
+            iat: now.timestamp(),
+            exp: (now + Duration::seconds(self.jwt_duration)).timestamp(),
+            username: user.username,
+            is_admin,
+            role: None,
+            jti: Some(AuthClaims::api_key_jti(api_key.api_key_id)),
+            scopes: api_key.scopes,
+        })
+    }
+
+    fn enroll_mfa(&self, user_id: i64) -> Result<MfaEnrollment, UserManagementError> {
+        if self.is_mfa_enabled(user_id)? {
+            info!("User id {} already uses two-factor authentication", user_id);
+            return Err(UserManagementError::AlreadyExists);
+        }
+
+        let user = self.get_user_info(user_id)?;
+        let secret = totp::generate_secret()
+            .map_err(|err| {
+                error!("could not create a random mfa secret: {:?}", &err);
+                UserManagementError::Unknown
+            })?;
+        let recovery_codes = totp::generate_recovery_codes()
+            .map_err(|err| {
+                error!("could not create random recovery codes: {:?}", &err);
+                UserManagementError::Unknown
+            })?;
+
+        // an enrollment that was never confirmed is replaced
+        self.delete_mfa(user_id)?;
+
+        let user_mfa = dbdata::NewRawUserMfa {
+            user_id,
+            secret: secret.to_owned(),
+        };
+        diesel::insert_into(schema::user_mfa::table)
+            .values(&user_mfa)
+            .execute(self.conn)
+            .map_err(|err| {
+                error!("Could not create mfa secret err: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        let raw_recovery_codes: Vec<dbdata::NewRawMfaRecoveryCode> = recovery_codes
+            .iter()
+            .map(|code| dbdata::NewRawMfaRecoveryCode {
+                user_id,
+                code_hash: Token::digest(code),
+            })
+            .collect();
+        diesel::insert_into(schema::mfa_recovery_code::table)
+            .values(&raw_recovery_codes)
+            .execute(self.conn)
+            .map_err(|err| {
+                error!("Could not create recovery codes err: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        info!("Started two-factor authentication enrollment for user id {}", user_id);
+        Ok(MfaEnrollment {
+            otpauth_uri: totp::otpauth_uri(&self.mfa_settings.issuer, &user.username, &secret),
+            secret,
+            recovery_codes,
+        })
+    }
+
+    fn is_mfa_enabled(&self, user_id: i64) -> Result<bool, UserManagementError> {
+        let user_mfa = self.get_user_mfa(user_id)?;
+        Ok(user_mfa.map(|user_mfa| user_mfa.confirmed_at.is_some()).unwrap_or(false))
+    }
+
+    fn is_mfa_required(&self, user_id: i64) -> Result<bool, UserManagementError> {
+        let required_for = &self.mfa_settings.required_for;
+        if required_for.is_empty() {
+            return Ok(false);
+        }
+
+        if user_id == metastore::ADMIN_USER_ID {
+            return Ok(true);
+        }
+
+        let authorization = Authorization {
+            conn: self.conn,
+            claims: &None,
+        };
+        let permissions = authorization.get_user_permissions(user_id)?;
+
+        Ok(permissions.iter().any(|permission| required_for.contains(permission)))
+    }
+
+    fn verify_mfa_code(&self, user_id: i64, code: &str) -> Result<bool, UserManagementError> {
+        let user_mfa = match self.get_user_mfa(user_id)? {
+            Some(user_mfa) => user_mfa,
+            None => return Ok(false),
+        };
+
+        let now = Utc::now();
+        let naive_datetime_now = NaiveDateTime::from_timestamp(now.timestamp(), 0);
+        let step = totp::verify_code(&user_mfa.secret, code, now.timestamp(), user_mfa.last_used_step)
+            .map_err(|err| {
+                error!("Could not verify the code: {:?}", &err);
+                UserManagementError::InternalError(err)
+            })?;
+
+        if let Some(step) = step {
+            // the first code that is right confirms the enrollment, the step is only moved forward,
+            // so when the same code is sent twice at once only one of them gets through
+            let updated = diesel::update(
+                schema::user_mfa::table
+                    .filter(schema::user_mfa::columns::user_id.eq(user_id))
+                    .filter(schema::user_mfa::columns::last_used_step.is_null()
+                        .or(schema::user_mfa::columns::last_used_step.lt(step))))
+                .set((
+                    schema::user_mfa::columns::last_used_step.eq(step),
+                    schema::user_mfa::columns::confirmed_at.eq(user_mfa.confirmed_at.unwrap_or(naive_datetime_now)),
+                ))
+                .execute(self.conn)
+                .map_err(|err| {
+                    error!("Could not update mfa secret err: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                })?;
+
+            if updated == 0 {
+                info!("The code of user id {} was already used", user_id);
+            }
+
+            return Ok(updated > 0);
+        }
+
+        if user_mfa.confirmed_at.is_none() {
+            return Ok(false);
+        }
+
+        let used_recovery_codes = diesel::update(
+            schema::mfa_recovery_code::table
+                .filter(schema::mfa_recovery_code::columns::user_id.eq(user_id))
+                .filter(schema::mfa_recovery_code::columns::code_hash.eq(Token::digest(&code.trim().to_lowercase())))
+                .filter(schema::mfa_recovery_code::columns::used_at.is_null()))
+            .set(schema::mfa_recovery_code::columns::used_at.eq(naive_datetime_now))
+            .execute(self.conn)
+            .map_err(|err| {
+                error!("Could not check recovery code err: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        if used_recovery_codes > 0 {
+            info!("User id {} used a recovery code", user_id);
+        }
+
+        Ok(used_recovery_codes > 0)
+    }
+
+    fn disable_mfa(&self, user_identifier: &str) -> Result<(), UserManagementError> {
+        let user = get_user_by_identifier(self.conn, &user_identifier)?;
+
+        self.delete_mfa(user.user_id)?;
+        info!("Two-factor authentication disabled for {:?}", &user.username);
+
+        Ok(())
+    }
+
+    fn create_mfa_challenge(&self, user_id: i64) -> Result<MfaChallenge, UserManagementError> {
+        let token = Token::new()
+            .map_err(|err| {
+                error!("could not create a random challenge token: {:?}", &err);
+                UserManagementError::Unknown
+            })?;
+        let now = Utc::now();
+        let challenge_token = token.as_string();
+
+        let challenge = dbdata::NewRawMfaChallenge {
+            challenge_hash: Token::digest(&challenge_token),
+            user_id,
+            expires_at: NaiveDateTime::from_timestamp((now + Duration::seconds(MFA_CHALLENGE_DURATION_SECS)).timestamp(), 0),
+        };
+        diesel::insert_into(schema::mfa_challenge::table)
+            .values(&challenge)
+            .execute(self.conn)
+            .map_err(|err| {
+                error!("Could not create mfa challenge err: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        Ok(MfaChallenge {
+            challenge_token,
+            expires_in: MFA_CHALLENGE_DURATION_SECS as u32,
+            enrollment_required: !self.is_mfa_enabled(user_id)?,
+        })
+    }
+
+    fn get_mfa_challenge_user(&self, challenge_token: &str) -> Result<UserInfo, UserManagementError> {
+        let challenge = self.get_mfa_challenge(challenge_token)?;
+        self.get_user_info(challenge.user_id)
+    }
+
+    fn verify_mfa_challenge(&self, challenge_token: &str, code: &str) -> Result<UserInfo, UserManagementError> {
+        let challenge = self.count_mfa_challenge_attempt(challenge_token)?;
+
+        if self.verify_mfa_code(challenge.user_id, code)? {
+            diesel::delete(schema::mfa_challenge::table)
+                .filter(schema::mfa_challenge::columns::challenge_hash.eq(&challenge.challenge_hash))
+                .execute(self.conn)
+                .map_err(|err| {
+                    error!("Could not remove mfa challenge err: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                })?;
+
+            return self.get_user_info(challenge.user_id);
+        }
+
+        info!("Wrong two-factor code for user id {}", challenge.user_id);
+
+        Err(UserManagementError::Unauthorized)
+    }
+
+    fn check_login_throttle(&self, user_identifier: &str) -> Result<(), UserManagementError> {
+        let user_id = self.find_user_id(user_identifier)?;
+        let naive_datetime_now = NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0);
+
+        for throttle_key in self.get_throttle_keys(user_id, user_identifier) {
+            let throttle = match self.get_login_throttle(&throttle_key.key)? {
+                Some(throttle) => throttle,
+                None => continue,
+            };
+
+            match throttle.blocked_until {
+                Some(blocked_until) if blocked_until > naive_datetime_now => {
+                    let reason = if throttle.is_locked { "locked" } else { "throttled" };
+                    info!("Rejected login for {:?}, {:?} is {} until {}", user_identifier, &throttle_key.key, reason, blocked_until);
+                    self.add_login_failure(user_identifier, user_id, reason)?;
+
+                    return Err(if throttle.is_locked {
+                        UserManagementError::Locked
+                    } else {
+                        UserManagementError::TooManyAttempts((blocked_until - naive_datetime_now).num_seconds().max(1))
+                    });
+                },
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn record_failed_login(&self, user_identifier: &str) -> Result<(), UserManagementError> {
+        self.record_failure(user_identifier, "invalidCredentials")
+    }
+
+    fn record_failed_mfa_code(&self, user_identifier: &str) -> Result<(), UserManagementError> {
+        self.record_failure(user_identifier, "invalidMfaCode")
+    }
+
+    fn clear_failed_logins(&self, user_identifier: &str) -> Result<(), UserManagementError> {
+        let user_id = self.find_user_id(user_identifier)?;
+        let user_key = self.get_user_throttle_key(user_id, user_identifier);
+
+        diesel::delete(schema::login_throttle::table)
+            .filter(schema::login_throttle::columns::throttle_key.eq(&user_key))
+            .execute(self.conn)
+            .map_err(|err| {
+                error!("Could not clear failed logins err: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        Ok(())
+    }
+
+    fn unlock_user(&self, user_identifier: &str) -> Result<(), UserManagementError> {
+        let user_id = self
+            .find_user_id(user_identifier)?
+            .ok_or(UserManagementError::NotFound)?;
+
+        self.clear_failed_logins(user_identifier)?;
+        info!("Failed logins for user id {} cleared", user_id);
+
+        Ok(())
+    }
+
+    fn get_login_failures(&self, user_identifier: Option<String>) -> Result<Vec<LoginFailure>, UserManagementError> {
+        let mut query = schema::login_failure::table.into_boxed();
+        if let Some(user_identifier) = user_identifier {
+            query = match self.find_user_id(&user_identifier)? {
+                Some(user_id) => query.filter(schema::login_failure::columns::user_id.eq(user_id)),
+                None => query.filter(schema::login_failure::columns::user_identifier.eq(user_identifier)),
+            };
+        }
+
+        let login_failures = query
+            .order((
+                schema::login_failure::columns::failed_at.desc(),
+                schema::login_failure::columns::login_failure_id.desc(),
+            ))
+            .limit(LOGIN_FAILURE_LIMIT)
+            .get_results::<dbdata::RawLoginFailure>(self.conn)
+            .map_err(|err| {
+                error!("Could not get login failures: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?
+            .into_iter()
+            .map(|login_failure| LoginFailure {
+                user_identifier: login_failure.user_identifier,
+                user_id: login_failure.user_id,
+                ip_address: login_failure.ip_address,
+                device: login_failure.device,
+                reason: login_failure.reason,
+                failed_at: login_failure.failed_at,
+            })
+            .collect();
+
+        Ok(login_failures)
+    }
+
 }
 
 
+fn to_api_key(api_key: dbdata::RawApiKey) -> Result<ApiKey, UserManagementError> {
+    let scopes = match api_key.scopes {
+        Some(scopes) => Some(serde_json::from_value(scopes)
+            .map_err(|err| UserManagementError::InternalError(err.to_string()))?),
+        None => None,
+    };
+
+    Ok(ApiKey {
+        api_key_id: api_key.api_key_id,
+        name: api_key.name,
+        prefix: api_key.key_prefix,
+        scopes,
+        created_at: api_key.created_at,
+        expires_at: api_key.expires_at,
+        last_used_at: api_key.last_used_at,
+        use_count: api_key.use_count,
+    })
+}
+
 impl<'a> Authentication<'a>  {
-    fn build_jwt_token(&self, now: chrono::DateTime<Utc>, user: UserInfo, refresh_token_string: String) -> Result<SessionToken, UserManagementError> {
+    fn has_identity(&self, user_id: i64, issuer: &str) -> Result<bool, UserManagementError> {
+        diesel::select(diesel::dsl::exists(schema::user_identity::table
+            .filter(schema::user_identity::columns::user_id.eq(user_id))
+            .filter(schema::user_identity::columns::issuer.eq(issuer))))
+            .get_result::<bool>(self.conn)
+            .map_err(|err| {
+                error!("Could not get the identities of user id {}: {:?}", user_id, &err);
+                UserManagementError::InternalError(err.to_string())
+            })
+    }
+
+    fn get_user_info(&self, user_id: i64) -> Result<UserInfo, UserManagementError> {
+        let user = schema::user::table
+            .filter(schema::user::columns::user_id.eq(user_id))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| match err {
+                Error::NotFound => UserManagementError::NotFound,
+                _ => {
+                    error!("Could not get user: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                },
+            })?;
+
+        Ok(UserInfo {
+            user_id: user.user_id,
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
+
+    fn get_user_mfa(&self, user_id: i64) -> Result<Option<dbdata::RawUserMfa>, UserManagementError> {
+        schema::user_mfa::table
+            .filter(schema::user_mfa::columns::user_id.eq(user_id))
+            .get_result::<dbdata::RawUserMfa>(self.conn)
+            .optional()
+            .map_err(|err| {
+                error!("Could not get mfa secret: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })
+    }
+
+    fn delete_mfa(&self, user_id: i64) -> Result<(), UserManagementError> {
+        diesel::delete(schema::mfa_recovery_code::table)
+            .filter(schema::mfa_recovery_code::columns::user_id.eq(user_id))
+            .execute(self.conn)
+            .and_then(|_| diesel::delete(schema::user_mfa::table)
+                .filter(schema::user_mfa::columns::user_id.eq(user_id))
+                .execute(self.conn))
+            .map_err(|err| {
+                error!("Could not remove mfa secret err: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        Ok(())
+    }
+
+    fn find_user_id(&self, user_identifier: &str) -> Result<Option<i64>, UserManagementError> {
+        find_user(self.conn, user_identifier)
+            .map(|user| user.map(|user| user.user_id))
+    }
+
+    /// Existing users are counted by id, so trying their username and their email doesn't give twice the attempts
+    fn get_user_throttle_key(&self, user_id: Option<i64>, user_identifier: &str) -> String {
+        match user_id {
+            Some(user_id) => format!("user:{}", user_id),
+            None => format!("user:{}", user_identifier.to_lowercase()),
+        }
+    }
+
+    fn get_throttle_keys(&self, user_id: Option<i64>, user_identifier: &str) -> Vec<ThrottleKey> {
+        let mut throttle_keys = vec![ThrottleKey {
+            key: self.get_user_throttle_key(user_id, user_identifier),
+            free_attempts: self.login_throttle.user_free_attempts,
+            can_lock: true,
+        }];
+
+        if let Some(ip_address) = &self.client_info.ip_address {
+            throttle_keys.push(ThrottleKey {
+                key: format!("ip:{}", ip_address),
+                free_attempts: self.login_throttle.ip_free_attempts,
+                can_lock: false,
+            });
+        }
+
+        throttle_keys
+    }
+
+    fn get_login_throttle(&self, throttle_key: &str) -> Result<Option<dbdata::RawLoginThrottle>, UserManagementError> {
+        schema::login_throttle::table
+            .filter(schema::login_throttle::columns::throttle_key.eq(throttle_key))
+            .get_result::<dbdata::RawLoginThrottle>(self.conn)
+            .optional()
+            .map_err(|err| {
+                error!("Could not get failed logins: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })
+    }
+
+    /// Counts the failure for the user and the address, and records it for auditing
+    fn record_failure(&self, user_identifier: &str, reason: &str) -> Result<(), UserManagementError> {
+        let user_id = self.find_user_id(user_identifier)?;
+        let now = NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0);
+        let settings = &self.login_throttle;
+
+        for throttle_key in self.get_throttle_keys(user_id, user_identifier) {
+            // counted in the database, so that failures at the same time don't overwrite each other's counts
+            let throttle: dbdata::RawLoginThrottle = diesel::sql_query(r#"
+                INSERT INTO "login_throttle" ("throttle_key", "failed_attempts", "last_failed_at") VALUES ($1, 1, $2)
+                ON CONFLICT ("throttle_key") DO UPDATE SET
+                    "failed_attempts" = CASE
+                        WHEN "login_throttle"."last_failed_at" > $3 THEN "login_throttle"."failed_attempts" + 1
+                        ELSE 1
+                    END,
+                    "last_failed_at" = EXCLUDED."last_failed_at"
+                RETURNING *;
+                "#)
+                .bind::<diesel::sql_types::Text, _>(throttle_key.key.to_owned())
+                .bind::<diesel::sql_types::Timestamp, _>(now)
+                .bind::<diesel::sql_types::Timestamp, _>(now - Duration::seconds(settings.reset_after_secs))
+                .get_result(self.conn)
+                .map_err(|err| {
+                    error!("Could not record failed login err: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                })?;
+
+            let failed_attempts = throttle.failed_attempts;
+            let is_locked = throttle_key.can_lock && settings.is_lockout(failed_attempts);
+            let blocked_until = if is_locked {
+                warn!("Locking {:?} after {} failed logins", user_identifier, failed_attempts);
+                Some(now + Duration::seconds(settings.lockout_secs))
+            } else {
+                settings
+                    .get_delay(failed_attempts, throttle_key.free_attempts)
+                    .map(|delay| now + Duration::seconds(delay))
+            };
+
+            // a failure that was counted after this one has the higher count, and is left alone
+            diesel::update(
+                schema::login_throttle::table
+                    .filter(schema::login_throttle::columns::throttle_key.eq(&throttle_key.key))
+                    .filter(schema::login_throttle::columns::failed_attempts.eq(failed_attempts)))
+                .set((
+                    schema::login_throttle::columns::blocked_until.eq(blocked_until),
+                    schema::login_throttle::columns::is_locked.eq(is_locked),
+                ))
+                .execute(self.conn)
+                .map_err(|err| {
+                    error!("Could not record failed login err: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                })?;
+        }
+
+        self.add_login_failure(user_identifier, user_id, reason)
+    }
+
+    fn add_login_failure(&self, user_identifier: &str, user_id: Option<i64>, reason: &str) -> Result<(), UserManagementError> {
+        let login_failure = dbdata::NewRawLoginFailure {
+            user_identifier: user_identifier.to_string(),
+            user_id,
+            ip_address: self.client_info.ip_address.to_owned(),
+            device: self.client_info.device.to_owned(),
+            reason: reason.to_string(),
+        };
+
+        diesel::insert_into(schema::login_failure::table)
+            .values(&login_failure)
+            .execute(self.conn)
+            .map_err(|err| {
+                error!("Could not record login failure err: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        Ok(())
+    }
+
+    /// Expired challenges and challenges with too many wrong codes are rejected
+    fn get_mfa_challenge(&self, challenge_token: &str) -> Result<dbdata::RawMfaChallenge, UserManagementError> {
+        let naive_datetime_now = NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0);
+        schema::mfa_challenge::table
+            .filter(schema::mfa_challenge::columns::challenge_hash.eq(Token::digest(challenge_token)))
+            .filter(schema::mfa_challenge::columns::expires_at.gt(naive_datetime_now))
+            .filter(schema::mfa_challenge::columns::failed_attempts.lt(MAX_MFA_ATTEMPTS))
+            .get_result::<dbdata::RawMfaChallenge>(self.conn)
+            .map_err(|err| match err {
+                Error::NotFound => {
+                    info!("Mfa challenge does not exist, has expired or had too many attempts");
+                    UserManagementError::Unauthorized
+                },
+                _ => {
+                    error!("Could not get mfa challenge: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                },
+            })
+    }
+
+    /// The attempt is counted before the code is checked, in the same statement as the limit,
+    /// so that codes sent at the same time can't get past it. A right code removes the challenge
+    fn count_mfa_challenge_attempt(&self, challenge_token: &str) -> Result<dbdata::RawMfaChallenge, UserManagementError> {
+        let naive_datetime_now = NaiveDateTime::from_timestamp(Utc::now().timestamp(), 0);
+        diesel::update(
+            schema::mfa_challenge::table
+                .filter(schema::mfa_challenge::columns::challenge_hash.eq(Token::digest(challenge_token)))
+                .filter(schema::mfa_challenge::columns::expires_at.gt(naive_datetime_now))
+                .filter(schema::mfa_challenge::columns::failed_attempts.lt(MAX_MFA_ATTEMPTS)))
+            .set(schema::mfa_challenge::columns::failed_attempts.eq(schema::mfa_challenge::columns::failed_attempts + 1))
+            .get_result::<dbdata::RawMfaChallenge>(self.conn)
+            .map_err(|err| match err {
+                Error::NotFound => {
+                    info!("Mfa challenge does not exist, has expired or had too many attempts");
+                    UserManagementError::Unauthorized
+                },
+                _ => {
+                    error!("Could not update mfa challenge err: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                },
+            })
+    }
+
+    fn build_jwt_token(&self, now: chrono::DateTime<Utc>, user: UserInfo, refresh_token_string: String, session_id: Option<i64>) -> Result<SessionToken, UserManagementError> {
         let duration = self.jwt_duration;
-        let refresh_duration = self.jwt_refresh_duration;
+        self.build_jwt_token_with_duration(now, user, refresh_token_string, session_id, duration)
+    }
+
+    fn build_jwt_token_with_duration(&self, now: chrono::DateTime<Utc>, user: UserInfo, refresh_token_string: String, session_id: Option<i64>, duration: i64) -> Result<SessionToken, UserManagementError> {
 
         let is_admin = user.user_id == metastore::ADMIN_USER_ID;
         let claims = AuthClaims {
@@ -172,6 +1111,8 @@ impl<'a> Authentication<'a>  {
             username: user.username,
             is_admin: is_admin,
             role: None, //TODO: make sure the role is here
+            jti: session_id.map(|session_id| session_id.to_string()),
+            scopes: None,
         };
 
         let jwt = jsonwebtoken::encode(&jsonwebtoken::Header::default(), &claims, self.jwt_secret.as_ref())
@@ -8,6 +8,7 @@ use metastore::dbdata::RawPermission;
 use diesel::sql_types::BigInt;
 use diesel::RunQueryDsl;
 use connection::executor::Conn;
+use metastore;
 
 use state::Authorization;
 use state::authorization::AuthorizationOps;
@@ -46,7 +47,16 @@ impl<'a> AuthorizationOps for Authorization<'a> {
             }
         };
 
-        HashSet::from_iter(raw_permissions)
+        let permissions: HashSet<Permission> = HashSet::from_iter(raw_permissions);
+        match self.scopes() {
+            // the admin holds every permission without having them through a role
+            Some(scopes) if user_id == metastore::ADMIN_USER_ID => HashSet::from_iter(scopes),
+            Some(scopes) => permissions
+                .intersection(&HashSet::from_iter(scopes))
+                .cloned()
+                .collect(),
+            None => permissions,
+        }
     }
 
     fn all_permissions(&self) -> HashSet<Permission> {
@@ -65,10 +75,22 @@ impl<'a> AuthorizationOps for Authorization<'a> {
     fn username(&self) -> Option<String> {
         self.claims.to_owned().map(|x| x.get_username())
     }
+
+    fn session_id(&self) -> Option<i64> {
+        self.claims.to_owned().and_then(|x| x.get_session_id())
+    }
+
+    fn api_key_id(&self) -> Option<i64> {
+        self.claims.to_owned().and_then(|x| x.get_api_key_id())
+    }
+
+    fn scopes(&self) -> Option<Vec<Permission>> {
+        self.claims.to_owned().and_then(|x| x.get_scopes())
+    }
 }
 
 impl<'a> Authorization<'a> {
-    fn get_user_permissions(&self, user_id: i64) -> Result<Vec<Permission>, UserManagementError> {
+    pub(super) fn get_user_permissions(&self, user_id: i64) -> Result<Vec<Permission>, UserManagementError> {
         let query = r#"
         SELECT
             DISTINCT ON("permission"."permission_id")
@@ -2,6 +2,7 @@
 use std::fmt::Debug;
 
 use serde_json;
+use chrono::NaiveDateTime;
 use serde::Serialize;
 
 use metastore::dbdata;
@@ -19,6 +20,7 @@ use metastore::dbdata::NewRawScript;
 use metastore::dbdata::RawView;
 use metastore::dbdata::NewRawView;
 use model::entity::ConvertRaw;
+use model::entity::RawRevision;
 use model::entity::GenerateRaw;
 use model::entity::RawEntityTypes;
 use data::channels::GetEntityChannel;
@@ -51,6 +53,23 @@ impl ConvertRaw<data::Script> for dbdata::RawScript {
             name: self.my_name().to_owned(),
             description: self.description.to_owned(),
             text: self.script_text.to_owned(),
+            entrypoint: self.script_info
+                .get("entrypoint")
+                .and_then(|x| x.as_str())
+                .unwrap_or("script.py")
+                .to_string(),
+            files: self.script_info
+                .get("files")
+                .and_then(|x| serde_json::from_value(x.to_owned()).ok())
+                .unwrap_or_default(),
+            requirements: self.script_info
+                .get("requirements")
+                .and_then(|x| serde_json::from_value(x.to_owned()).ok())
+                .unwrap_or_default(),
+            sandbox: self.script_info
+                .get("sandbox")
+                .and_then(|x| serde_json::from_value(x.to_owned()).ok())
+                .unwrap_or_default(),
         }
     }
 }
@@ -61,10 +80,29 @@ impl ConvertRaw<data::View> for dbdata::RawView {
             name: self.my_name().to_owned(),
             description: self.description.to_owned(),
             view_state: self.view_state.to_owned(),
+            source: self.view_info
+                .get("source")
+                .and_then(|x| serde_json::from_value(x.to_owned()).ok()),
         }
     }
 }
 
+macro_rules! implement_raw_revision {
+    ($raw:ty, $id:ident) => {
+        impl RawRevision for $raw {
+            fn entity_id(&self) -> i64 { self.entity_id }
+            fn revision_id(&self) -> i64 { self.$id }
+            fn modified_at(&self) -> NaiveDateTime { self.modified_at }
+            fn modified_by(&self) -> i64 { self.modified_by }
+            fn is_deleted(&self) -> bool { self.is_deleted }
+        }
+    };
+}
+
+implement_raw_revision!(dbdata::RawTable, table_schema_id);
+implement_raw_revision!(dbdata::RawQuery, query_id);
+implement_raw_revision!(dbdata::RawScript, script_id);
+implement_raw_revision!(dbdata::RawView, view_id);
 
 impl GenerateRaw<data::DataStoreEntity> for dbdata::NewRawTable {
     fn new(data: &data::DataStoreEntity, entity_id: i64, modified_by: i64) -> Self {
@@ -124,7 +162,13 @@ impl GenerateRaw<data::Script> for dbdata::NewRawScript {
             description: data.description.to_owned(),
             script_language: "Python".to_string(), //Only Python is supported right now
             script_text: data.text.to_owned(),
-            script_info: serde_json::to_value(json!({})).unwrap_or_default(),
+            script_info: serde_json::to_value(json!({
+                "entrypoint": data.entrypoint,
+                "files": data.files,
+                "version": data.version(),
+                "requirements": data.requirements,
+                "sandbox": data.sandbox,
+            })).unwrap_or_default(),
             is_deleted: false,
             modified_by,
         }
@@ -151,7 +195,9 @@ impl GenerateRaw<data::View> for dbdata::NewRawView {
             name: data.my_name().to_owned(),
             description: data.description.to_owned(),
             view_state: data.view_state.to_owned(),
-            view_info: serde_json::to_value(json!({})).unwrap_or_default(),
+            view_info: json!({
+                "source": data.source,
+            }),
             is_deleted: false,
             modified_by,
         }
@@ -4,6 +4,12 @@ use std::fmt::Debug;
 use serde::Serialize;
 use chrono::NaiveDateTime;
 use serde_json;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Text;
+use diesel::sql_types::Double;
+use diesel::sql_types::Timestamp;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Json;
 
 use metastore::schema::entity;
 use metastore::schema::table_schema;
@@ -15,10 +21,22 @@ use metastore::schema::permission;
 use metastore::schema::role;
 use metastore::schema::invitation;
 use metastore::schema::session;
+use metastore::schema::password_reset;
+use metastore::schema::login_request;
+use metastore::schema::user_identity;
 use metastore::schema::channel;
 use metastore::schema::user_channel;
 use metastore::schema::domain;
 use metastore::schema::message;
+use metastore::schema::tag;
+use metastore::schema::entity_tag;
+use metastore::schema::entity_usage;
+use metastore::schema::api_key;
+use metastore::schema::user_mfa;
+use metastore::schema::mfa_recovery_code;
+use metastore::schema::mfa_challenge;
+use metastore::schema::login_throttle;
+use metastore::schema::login_failure;
 
 use data::permissions::Permission;
 use data::Named;
@@ -210,6 +228,7 @@ pub struct NewRawUser {
     pub email: String,
     pub password: String,
     pub display_name: String,
+    pub user_info: serde_json::Value,
 }
 
 #[derive(Debug, Identifiable, Queryable, QueryableByName)]
@@ -223,6 +242,9 @@ pub struct RawUser {
     pub display_name: String,
     pub user_info: serde_json::Value,
     pub joined_at: chrono::NaiveDateTime,
+    pub deprovisioned: bool,
+    pub is_service_account: bool,
+    pub disabled: bool,
 }
 
 #[derive(Debug, Deserialize, Insertable)]
@@ -248,6 +270,88 @@ pub struct RawRole {
     pub role_info: serde_json::Value,
 }
 
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "tag"]
+pub struct NewRawTag {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[primary_key(tag_id)]
+#[table_name = "tag"]
+pub struct RawTag {
+    pub tag_id: i64,
+    pub name: String,
+    pub description: String,
+    pub tag_info: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "entity_tag"]
+pub struct NewRawEntityTag {
+    pub entity_id: i64,
+    pub tag_id: i64,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "entity_usage"]
+pub struct NewRawEntityUsage {
+    pub entity_id: i64,
+    pub used_by: i64,
+    pub action: String,
+    pub latency_ms: Option<i64>,
+    pub row_count: Option<i64>,
+}
+
+#[derive(Debug, QueryableByName)]
+pub struct RawEntityUsageSummary {
+    #[sql_type = "Text"]
+    pub type_name: String,
+    #[sql_type = "Text"]
+    pub name: String,
+    #[sql_type = "BigInt"]
+    pub entity_id: i64,
+    #[sql_type = "BigInt"]
+    pub call_count: i64,
+    #[sql_type = "Nullable<Timestamp>"]
+    pub last_used_at: Option<NaiveDateTime>,
+    #[sql_type = "Nullable<Double>"]
+    pub p50_latency_ms: Option<f64>,
+    #[sql_type = "Nullable<Double>"]
+    pub p95_latency_ms: Option<f64>,
+    #[sql_type = "Nullable<BigInt>"]
+    pub total_rows: Option<i64>,
+}
+
+#[derive(Debug, QueryableByName)]
+pub struct RawTableAuditRecord {
+    #[sql_type = "BigInt"]
+    pub transaction_id: i64,
+    #[sql_type = "Json"]
+    pub action_data: serde_json::Value,
+    #[sql_type = "Timestamp"]
+    pub made_at: NaiveDateTime,
+    #[sql_type = "Text"]
+    pub username: String,
+}
+
+#[derive(Debug, QueryableByName)]
+pub struct RawEntitySync {
+    #[sql_type = "BigInt"]
+    pub revision_id: i64,
+}
+
+#[derive(Debug, QueryableByName)]
+pub struct RawEntityUserUsage {
+    #[sql_type = "BigInt"]
+    pub entity_id: i64,
+    #[sql_type = "Text"]
+    pub username: String,
+    #[sql_type = "BigInt"]
+    pub call_count: i64,
+}
+
 #[derive(Debug, Deserialize, Insertable)]
 #[table_name = "invitation"]
 pub struct NewRawInvitation {
@@ -280,6 +384,8 @@ pub struct NewRawSessionToken {
     pub user_id: i64,
     pub created_at: chrono::NaiveDateTime,
     pub expires_at: chrono::NaiveDateTime,
+    pub device: Option<String>,
+    pub ip_address: Option<String>,
 }
 
 #[derive(Debug, Identifiable, Queryable, QueryableByName)]
@@ -291,6 +397,173 @@ pub struct RawSessionToken {
     pub user_id: i64,
     pub created_at: chrono::NaiveDateTime,
     pub expires_at: chrono::NaiveDateTime,
+    pub device: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_used_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "api_key"]
+pub struct NewRawApiKey {
+    pub user_id: i64,
+    pub name: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub scopes: Option<serde_json::Value>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[primary_key(api_key_id)]
+#[table_name = "api_key"]
+pub struct RawApiKey {
+    pub api_key_id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub scopes: Option<serde_json::Value>,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub use_count: i64,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "user_mfa"]
+pub struct NewRawUserMfa {
+    pub user_id: i64,
+    pub secret: String,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[primary_key(user_id)]
+#[table_name = "user_mfa"]
+pub struct RawUserMfa {
+    pub user_id: i64,
+    pub secret: String,
+    pub confirmed_at: Option<chrono::NaiveDateTime>,
+    pub last_used_step: Option<i64>,
+    pub created_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "mfa_recovery_code"]
+pub struct NewRawMfaRecoveryCode {
+    pub user_id: i64,
+    pub code_hash: String,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "mfa_challenge"]
+pub struct NewRawMfaChallenge {
+    pub challenge_hash: String,
+    pub user_id: i64,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[primary_key(challenge_hash)]
+#[table_name = "mfa_challenge"]
+pub struct RawMfaChallenge {
+    pub challenge_hash: String,
+    pub user_id: i64,
+    pub failed_attempts: i32,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[primary_key(throttle_key)]
+#[table_name = "login_throttle"]
+pub struct RawLoginThrottle {
+    pub throttle_key: String,
+    pub failed_attempts: i32,
+    pub last_failed_at: chrono::NaiveDateTime,
+    pub blocked_until: Option<chrono::NaiveDateTime>,
+    pub is_locked: bool,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "login_failure"]
+pub struct NewRawLoginFailure {
+    pub user_identifier: String,
+    pub user_id: Option<i64>,
+    pub ip_address: Option<String>,
+    pub device: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[primary_key(login_failure_id)]
+#[table_name = "login_failure"]
+pub struct RawLoginFailure {
+    pub login_failure_id: i64,
+    pub user_identifier: String,
+    pub user_id: Option<i64>,
+    pub ip_address: Option<String>,
+    pub device: Option<String>,
+    pub reason: String,
+    pub failed_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "password_reset"]
+pub struct NewRawPasswordReset {
+    pub user_id: i64,
+    pub token_hash: String,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[primary_key(password_reset_id)]
+#[table_name = "password_reset"]
+pub struct RawPasswordReset {
+    pub password_reset_id: i64,
+    pub user_id: i64,
+    pub token_hash: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+    pub used_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "login_request"]
+pub struct NewRawLoginRequest {
+    pub state: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[primary_key(state)]
+#[table_name = "login_request"]
+pub struct RawLoginRequest {
+    pub state: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub created_at: chrono::NaiveDateTime,
+    pub expires_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize, Insertable)]
+#[table_name = "user_identity"]
+pub struct NewRawUserIdentity {
+    pub user_id: i64,
+    pub issuer: String,
+    pub subject: String,
+}
+
+#[derive(Debug, Identifiable, Queryable, QueryableByName)]
+#[primary_key(user_identity_id)]
+#[table_name = "user_identity"]
+pub struct RawUserIdentity {
+    pub user_identity_id: i64,
+    pub user_id: i64,
+    pub issuer: String,
+    pub subject: String,
+    pub created_at: chrono::NaiveDateTime,
 }
 
 #[derive(Debug, Deserialize, Insertable)]
@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::Path;
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+use diesel;
+
+use state::EntitySync;
+use state::entity_sync::EntitySyncOps;
+use state::error::EntitySyncError;
+use metastore::dbdata;
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().starts_with('.'))
+        .unwrap_or(false)
+}
+
+fn read_tree(root: &Path, dir: &Path, files: &mut BTreeMap<String, String>) -> Result<(), EntitySyncError> {
+    let entries = fs::read_dir(dir)
+        .map_err(|err| EntitySyncError::ReadError(err.to_string()))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|err| EntitySyncError::ReadError(err.to_string()))?
+            .path();
+        if is_hidden(&path) {
+            continue;
+        }
+
+        let metadata = fs::symlink_metadata(&path)
+            .map_err(|err| EntitySyncError::ReadError(err.to_string()))?;
+
+        if metadata.is_dir() {
+            read_tree(root, &path, files)?;
+        } else if metadata.is_file() {
+            let relative_path = path.strip_prefix(root)
+                .map_err(|err| EntitySyncError::ReadError(err.to_string()))?
+                .to_string_lossy()
+                .to_string();
+            let contents = fs::read_to_string(&path)
+                .map_err(|_| EntitySyncError::ReadError(format!("{} is not a text file, only text files are supported", &relative_path)))?;
+            files.insert(relative_path, contents);
+        } else {
+            warn!("skipping {:?} in the sync directory, only files and directories are supported", &path);
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> EntitySyncOps for EntitySync<'a> {
+    fn read_directory(&self) -> Result<BTreeMap<String, String>, EntitySyncError> {
+        let directory = self.directory
+            .to_owned()
+            .ok_or_else(|| EntitySyncError::NoSyncDirectory)?;
+        debug!("Reading the sync directory {:?}", &directory);
+
+        let mut files = BTreeMap::new();
+        read_tree(&directory, &directory, &mut files)?;
+
+        Ok(files)
+    }
+
+    fn get_synced_revision(&self, entity_id: i64) -> Result<Option<i64>, EntitySyncError> {
+        let raw_syncs: Vec<dbdata::RawEntitySync> = diesel::sql_query(r#"
+            SELECT revision_id FROM entity_sync WHERE entity_id = $1;
+            "#)
+            .bind::<diesel::sql_types::BigInt, _>(entity_id)
+            .load(self.conn)
+            .map_err(|err| EntitySyncError::InternalError(err.to_string()))?;
+
+        Ok(raw_syncs.into_iter().next().map(|raw_sync| raw_sync.revision_id))
+    }
+
+    fn mark_synced(&self, entity_id: i64, revision_id: i64) -> Result<(), EntitySyncError> {
+        debug!("Marking the entity {:?} as synced at revision {:?}", entity_id, revision_id);
+
+        diesel::sql_query(r#"
+            INSERT INTO entity_sync (entity_id, revision_id) VALUES ($1, $2)
+            ON CONFLICT (entity_id) DO UPDATE SET revision_id = EXCLUDED.revision_id, synced_at = NOW();
+            "#)
+            .bind::<diesel::sql_types::BigInt, _>(entity_id)
+            .bind::<diesel::sql_types::BigInt, _>(revision_id)
+            .execute(self.conn)
+            .map_err(|err| EntitySyncError::InternalError(err.to_string()))?;
+
+        Ok(())
+    }
+
+    fn unmark_synced(&self, entity_id: i64) -> Result<(), EntitySyncError> {
+        diesel::sql_query(r#"
+            DELETE FROM entity_sync WHERE entity_id = $1;
+            "#)
+            .bind::<diesel::sql_types::BigInt, _>(entity_id)
+            .execute(self.conn)
+            .map_err(|err| EntitySyncError::InternalError(err.to_string()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tempfile;
+
+    #[test]
+    fn test_read_tree_skips_hidden_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("queries")).unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join("manifest.json"), "{}").unwrap();
+        fs::write(dir.path().join("queries/my_query.sql"), "SELECT 1").unwrap();
+        fs::write(dir.path().join(".git/HEAD"), "ref: refs/heads/master").unwrap();
+
+        let mut files = BTreeMap::new();
+        read_tree(dir.path(), dir.path(), &mut files).unwrap();
+
+        assert_eq!(files.keys().collect::<Vec<_>>(), vec!["manifest.json", "queries/my_query.sql"]);
+    }
+}
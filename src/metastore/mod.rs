@@ -1,6 +1,10 @@
 
 pub mod user_management;
 pub mod domain_management;
+pub mod tag_management;
+pub mod usage_tracking;
+pub mod table_audit;
+pub mod entity_sync;
 pub mod authorization;
 pub mod authentication;
 pub mod pub_sub;
@@ -14,6 +18,11 @@ use std::fmt::Debug;
 use diesel::prelude::*;
 use chrono::Utc;
 use argonautica::Hasher;
+use argonautica::Verifier;
+
+use auth::password_policy::PasswordPolicy;
+use auth::password_policy::PasswordViolation;
+use metastore::user_management::PasswordInfo;
 
 use data;
 
@@ -24,6 +33,7 @@ use metastore::dbdata::NewRawEntity;
 use model::entity::RawEntityTypes;
 use model::entity::GenerateRaw;
 use model::entity::ConvertRaw;
+use model::entity::RawRevision;
 
 
 use model::entity::error::EntityError;
@@ -40,10 +50,14 @@ use plugins::v1::Domain;
 pub trait EntityCrudOps
     where Self: Sized + Debug,
 {
-    fn get_all(state: &EntityRetrieverController) -> Result<Vec<Self>, EntityError>;
+    fn get_all(state: &EntityRetrieverController, filter: &data::EntityFilter) -> Result<Vec<Self>, EntityError>;
+
+    fn get_entity_id(state: &EntityRetrieverController, name: &str) -> Result<Option<i64>, EntityError>;
 
     fn get_one(state: &EntityRetrieverController, name: &str) -> Result<Option<Self>, EntityError>;
 
+    fn get_history(state: &EntityRetrieverController, name: &str) -> Result<Vec<Revision<Self>>, EntityError>;
+
     fn create(state: &EntityModifierController, object: Self) -> Result<Created<Self>, EntityError>;
 
     fn upsert(state: &EntityModifierController, object: Self) -> Result<Upserted<Self>, EntityError>;
@@ -51,6 +65,8 @@ pub trait EntityCrudOps
     fn update(state: &EntityModifierController, name_object: (&str, Self)) -> Result<Updated<Self>, EntityError>;
 
     fn delete(state: &EntityModifierController, name: &str) -> Result<Deleted<Self>, EntityError>;
+
+    fn restore(state: &EntityModifierController, name_object: (&str, Self)) -> Result<Created<Self>, EntityError>;
 }
 
 // Meta store helpers
@@ -77,7 +93,21 @@ pub fn sync_domains(database_url: &str, domains: &HashMap<String, Box<Domain>>)
     Ok(())
 }
 
+/// Without any password policy, `AppStateBuilder::setup_admin` applies the one that the server is configured with
 pub fn setup_admin(database_url: &str, username: &str, email: &str, display_name: &str, password: &str) -> Result<(), String> {
+    setup_admin_with_policy(database_url, username, email, display_name, password, &PasswordPolicy::default())
+}
+
+/// Same as `setup_admin`, the password has to meet the policy. Starting again with the same password
+/// only updates the profile of the admin, the policy and the password history only apply when the password changes
+pub fn setup_admin_with_policy(
+    database_url: &str,
+    username: &str,
+    email: &str,
+    display_name: &str,
+    password: &str,
+    password_policy: &PasswordPolicy,
+) -> Result<(), String> {
 
     let id = ADMIN_USER_ID;
 
@@ -89,7 +119,71 @@ pub fn setup_admin(database_url: &str, username: &str, email: &str, display_name
 
     let password_secret = "Hello World Hello Wold";
 
-    //TODO: test password complexity
+    let existing_admin = schema::user::table
+        .filter(schema::user::columns::user_id.eq(id))
+        .get_result::<dbdata::RawUser>(&conn)
+        .optional()
+        .map_err(|err| {
+            error!("Could not create user, couldn't get the existing admin: {:?}", &err);
+            err.to_string()
+        })?;
+
+    let verify_password = |hashed_password: &str| {
+        let mut verifier = Verifier::default();
+        verifier
+            .with_hash(hashed_password)
+            .with_password(password)
+            .with_secret_key(password_secret)
+            .verify()
+            .map_err(|err| {
+                error!("Could not create user, couldn't verify the previous passwords: {:?}", &err);
+                err.to_string()
+            })
+    };
+
+    if let Some(admin) = &existing_admin {
+        if verify_password(&admin.password)? {
+            diesel::update(schema::user::table.filter(schema::user::columns::user_id.eq(id)))
+                .set((
+                    schema::user::columns::username.eq(username),
+                    schema::user::columns::email.eq(email),
+                    schema::user::columns::display_name.eq(display_name),
+                ))
+                .execute(&conn)
+                .map_err(|err| err.to_string())?;
+
+            info!("Admin user has been set up, the password is unchanged");
+            return Ok(());
+        }
+    }
+
+    // the current password is the latest one in the history, like for any other password change
+    let (previous_hashes, previous_user_info) = match existing_admin {
+        Some(admin) => {
+            let mut previous_hashes = vec![admin.password];
+            previous_hashes.extend(PasswordInfo::from_user_info(&admin.user_info).password_history);
+            (previous_hashes, admin.user_info)
+        },
+        None => (vec![], json!({})),
+    };
+    let previous_hashes: Vec<String> = previous_hashes
+        .into_iter()
+        .take(password_policy.history_size)
+        .collect();
+
+    let mut violations = password_policy.validate(password);
+    for hashed_password in &previous_hashes {
+        if verify_password(hashed_password)? {
+            violations.push(PasswordViolation::Reused { history_size: password_policy.history_size });
+            break;
+        }
+    }
+
+    if !violations.is_empty() {
+        error!("Could not create user, the password does not meet the password policy: {:?}", &violations);
+        return Err(format!("The password does not meet the password policy: {:?}", &violations));
+    }
+
     let mut hasher = Hasher::default();
     let password = hasher
         .with_password(password)
@@ -100,6 +194,11 @@ pub fn setup_admin(database_url: &str, username: &str, email: &str, display_name
             err.to_string()
         })?;
 
+    let user_info = PasswordInfo {
+        password_history: previous_hashes,
+        password_changed_at: Some(Utc::now().naive_utc()),
+    }.merge_into(&previous_user_info);
+
     let result = diesel::insert_into(schema::user::table)
         .values((
             schema::user::columns::user_id.eq(id),
@@ -107,7 +206,7 @@ pub fn setup_admin(database_url: &str, username: &str, email: &str, display_name
             schema::user::columns::email.eq(email),
             schema::user::columns::display_name.eq(display_name),
             schema::user::columns::password.eq(&password),
-            schema::user::columns::user_info.eq(json!({})),
+            schema::user::columns::user_info.eq(&user_info),
             schema::user::columns::joined_at.eq(Utc::now().naive_utc()),
         ))
         .on_conflict(schema::user::columns::user_id)
@@ -117,7 +216,7 @@ pub fn setup_admin(database_url: &str, username: &str, email: &str, display_name
             schema::user::columns::email.eq(email),
             schema::user::columns::display_name.eq(display_name),
             schema::user::columns::password.eq(&password),
-            schema::user::columns::user_info.eq(json!({})),
+            schema::user::columns::user_info.eq(&user_info),
             schema::user::columns::joined_at.eq(Utc::now().naive_utc()),
         ))
         .execute(&conn)
@@ -128,7 +227,7 @@ pub fn setup_admin(database_url: &str, username: &str, email: &str, display_name
     Ok(())
 }
 
-const ADMIN_USER_ID: i64 = 1;
+pub const ADMIN_USER_ID: i64 = 1;
 
 fn get_user_id(controller: &EntityModifierController) -> Option<i64> {
     match controller.claims {
@@ -185,9 +284,14 @@ macro_rules! make_crud_ops {
 
         impl EntityCrudOps for $EntityType {
 
-            fn get_all(state: &EntityRetrieverController) -> Result<Vec<$EntityType>, EntityError> {
+            fn get_all(state: &EntityRetrieverController, filter: &data::EntityFilter) -> Result<Vec<$EntityType>, EntityError> {
+                let domain_id = get_controller_domain_id(state).ok_or_else(|| EntityError::Unknown)?;
+                $entity::get_all::<$EntityType>(state.conn, domain_id, filter)
+            }
+
+            fn get_entity_id(state: &EntityRetrieverController, name: &str) -> Result<Option<i64>, EntityError> {
                 let domain_id = get_controller_domain_id(state).ok_or_else(|| EntityError::Unknown)?;
-                $entity::get_all::<$EntityType>(state.conn, domain_id)
+                $entity::get_entity_id(state.conn, domain_id, name)
             }
 
             fn get_one(state: &EntityRetrieverController, name: &str) -> Result<Option<$EntityType>, EntityError> {
@@ -195,6 +299,11 @@ macro_rules! make_crud_ops {
                 $entity::get_one::<$EntityType>(state.conn, domain_id, name)
             }
 
+            fn get_history(state: &EntityRetrieverController, name: &str) -> Result<Vec<Revision<$EntityType>>, EntityError> {
+                let domain_id = get_controller_domain_id(state).ok_or_else(|| EntityError::Unknown)?;
+                $entity::get_history::<$EntityType>(state.conn, domain_id, name)
+            }
+
             fn create(state: &EntityModifierController, object: $EntityType) -> Result<Created<$EntityType>, EntityError> {
                 info!("create object: {:?}", &object);
                 let user_id = get_user_id(state).ok_or_else(|| EntityError::Unknown)?;
@@ -222,6 +331,13 @@ macro_rules! make_crud_ops {
                 let domain_id = get_domain_id(state).ok_or_else(|| EntityError::Unknown)?;
                 $entity::delete::<$EntityType>(state.conn, user_id, domain_id, name)
             }
+
+            fn restore(state: &EntityModifierController, name_object: (&str, $EntityType)) -> Result<Created<$EntityType>, EntityError> {
+                info!("restore object: {:?}", &name_object);
+                let user_id = get_user_id(state).ok_or_else(|| EntityError::Unknown)?;
+                let domain_id = get_domain_id(state).ok_or_else(|| EntityError::Unknown)?;
+                $entity::restore::<$EntityType>(state.conn, user_id, domain_id, name_object)
+            }
         }
     );
 }
@@ -250,7 +366,7 @@ macro_rules! implement_retriever_and_modifier {
                     ON entity_list.entity_id = entity.entity_id
                 INNER JOIN domain
                     ON entity.domain_id = domain.domain_id
-                WHERE rn = 1 AND domain_id = $1 AND is_deleted = false AND entity_list.name = $2 AND
+                WHERE rn = 1 AND entity.domain_id = $1 AND is_deleted = false AND entity_list.name = $2
                 ORDER BY entity_list.name ASC;
                 "#, stringify!($data_table));
 
@@ -267,7 +383,41 @@ macro_rules! implement_retriever_and_modifier {
             Ok(result.first().map(|x: &RD| x.to_owned()))
         }
 
-        fn query_all_entities(conn: &Conn, domain_id: i64, show_deleted: bool) -> Result<Vec<RD>, EntityError> {
+        /// All the revisions of the entity that most recently had this name, newest first
+        fn query_entity_history(conn: &Conn, domain_id: i64, name: String) -> Result<Vec<RD>, EntityError> {
+            let query = format!(r#"
+                WITH named_entity AS (
+                    SELECT m.entity_id FROM {table} AS m
+                    INNER JOIN entity
+                        ON m.entity_id = entity.entity_id
+                    WHERE entity.domain_id = $1 AND m.name = $2
+                    ORDER BY m.modified_at DESC
+                    LIMIT 1
+                )
+                SELECT m.* FROM {table} AS m
+                INNER JOIN named_entity
+                    ON m.entity_id = named_entity.entity_id
+                ORDER BY m.modified_at DESC;
+                "#, table=stringify!($data_table));
+
+            let result = diesel::sql_query(query)
+                .bind::<diesel::sql_types::BigInt, _>(domain_id)
+                .bind::<diesel::sql_types::Text, _>(name)
+                .load(conn)
+                .or_else(|err| Err(EntityError::InternalError(err.description().to_string())))?;
+
+            Ok(result)
+        }
+
+        /// escape the wildcards of a LIKE pattern
+        fn like_pattern(value: &str) -> String {
+            value
+                .replace("\\", "\\\\")
+                .replace("%", "\\%")
+                .replace("_", "\\_")
+        }
+
+        fn query_all_entities(conn: &Conn, domain_id: i64, show_deleted: bool, filter: &data::EntityFilter) -> Result<Vec<RD>, EntityError> {
             let query = format!(r#"
                 WITH entity_list AS (
                     SELECT m.*, ROW_NUMBER() OVER (PARTITION BY entity_id ORDER BY modified_at DESC) AS rn
@@ -278,12 +428,27 @@ macro_rules! implement_retriever_and_modifier {
                     ON entity_list.entity_id = entity.entity_id
                 INNER JOIN domain
                     ON entity.domain_id = domain.domain_id
-                WHERE rn = 1 AND domain_id = $1 {}
+                WHERE rn = 1 AND entity.domain_id = $1 {}
+                    AND ($2::VARCHAR IS NULL OR EXISTS (
+                        SELECT 1 FROM entity_tag
+                        INNER JOIN tag
+                            ON entity_tag.tag_id = tag.tag_id
+                        WHERE entity_tag.entity_id = entity.entity_id AND tag.name = $2
+                    ))
+                    AND ($3::VARCHAR IS NULL OR entity_list.name LIKE $3 || '%')
+                    AND ($4::BIGINT IS NULL OR entity.created_by = $4)
+                    AND ($5::TIMESTAMP IS NULL OR entity_list.modified_at >= $5)
+                    AND ($6::VARCHAR IS NULL OR entity_list.name ILIKE '%' || $6 || '%' OR entity_list.description ILIKE '%' || $6 || '%')
                 ORDER BY entity_list.name ASC;
                 "#, stringify!($data_table), if show_deleted { "" } else { "AND is_deleted = false" });
 
             let result = diesel::sql_query(query)
                 .bind::<diesel::sql_types::BigInt, _>(domain_id)
+                .bind::<diesel::sql_types::Nullable<diesel::sql_types::Text>, _>(filter.tag.to_owned())
+                .bind::<diesel::sql_types::Nullable<diesel::sql_types::Text>, _>(filter.name_prefix.as_ref().map(|x| like_pattern(x)))
+                .bind::<diesel::sql_types::Nullable<diesel::sql_types::BigInt>, _>(filter.created_by)
+                .bind::<diesel::sql_types::Nullable<diesel::sql_types::Timestamp>, _>(filter.modified_since)
+                .bind::<diesel::sql_types::Nullable<diesel::sql_types::Text>, _>(filter.search.as_ref().map(|x| like_pattern(x)))
                 .load(conn)
                 .or_else(|err| Err(EntityError::InternalError(err.description().to_string())))?;
 
@@ -293,13 +458,12 @@ macro_rules! implement_retriever_and_modifier {
         pub fn get_all<O>(
             conn: &Conn,
             domain_id: i64,
+            filter: &data::EntityFilter,
         ) -> Result<Vec<O>, EntityError>
         where
             RD: ConvertRaw<O>,
         {
-            let entities: Vec<RD> = query_all_entities(conn, domain_id, false)?;
-
-            unimplemented!(); //TODO: should filter by the domain name
+            let entities: Vec<RD> = query_all_entities(conn, domain_id, false, filter)?;
 
             let ok_result = entities
                 .into_iter()
@@ -327,6 +491,43 @@ macro_rules! implement_retriever_and_modifier {
             Ok(ok_result)
         }
 
+        pub fn get_entity_id(
+            conn: &Conn,
+            domain_id: i64,
+            name: &str,
+        ) -> Result<Option<i64>, EntityError>
+        where
+            RD: RawRevision,
+        {
+            let entity: Option<RD> = query_entities_by_name(conn, domain_id, name.to_string())?;
+
+            Ok(entity.map(|x| x.entity_id()))
+        }
+
+        pub fn get_history<O>(
+            conn: &Conn,
+            domain_id: i64,
+            name: &str,
+        ) -> Result<Vec<Revision<O>>, EntityError>
+        where
+            O: Debug,
+            RD: ConvertRaw<O> + RawRevision,
+        {
+            let entities: Vec<RD> = query_entity_history(conn, domain_id, name.to_string())?;
+
+            let ok_result = entities
+                .into_iter()
+                .map(|entity| Revision {
+                    revision_id: entity.revision_id(),
+                    modified_at: entity.modified_at(),
+                    modified_by: entity.modified_by(),
+                    is_deleted: entity.is_deleted(),
+                    data: entity.convert(),
+                })
+                .collect();
+
+            Ok(ok_result)
+        }
 
         fn create_internal<O>(
             conn: &Conn,
@@ -516,6 +717,43 @@ macro_rules! implement_retriever_and_modifier {
                 }
             }
         }
+
+        /// Brings back the entity that most recently had the name, with the same entity id, so that
+        /// the tags and history stay with it. Creates a new entity if there never was one with this name
+        pub fn restore<O>(
+            conn: &Conn,
+            user_id: i64,
+            domain_id: i64,
+            name_object: (&str, O),
+        ) -> Result<Created<O>, EntityError>
+        where
+            O: RawEntityTypes,
+            NRD: GenerateRaw<O>,
+            RD: ConvertRaw<O> + RawRevision,
+        {
+            let (object_name, object) = name_object;
+            let entities: Option<RD> = query_entities_by_name(conn, domain_id, object.my_name().to_owned())?;
+            if let Some(entity) = entities {
+                return Ok(Created::Fail {
+                    existing: entity.convert()
+                });
+            }
+
+            let history: Vec<RD> = query_entity_history(conn, domain_id, object_name.to_string())?;
+            let new_val = match history.first() {
+                Some(latest) if latest.is_deleted() => update_internal(conn, user_id, domain_id, latest.entity_id(), object)?,
+                Some(latest) => {
+                    // the entity was renamed, and something else was created with the old name
+                    debug!("entity {:?} is not deleted, creating a new one instead", latest.entity_id());
+                    create_internal(conn, user_id, domain_id, object)?
+                },
+                None => create_internal(conn, user_id, domain_id, object)?,
+            };
+
+            Ok(Created::Success {
+                new: new_val.convert(),
+            })
+        }
     }
 }
 
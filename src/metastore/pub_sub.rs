@@ -120,6 +120,47 @@ impl<'a> PubSubOps for PublishCallback<'a> {
         Ok(users)
     }
 
+    fn rename_channel(&self, old_channel: Channels, new_channel: Channels) -> Result<(), BroadcastError> {
+        info!("renaming channel {:?} to {:?}", &old_channel, &new_channel);
+
+        let old_raw_channel = match get_channel(self.conn, &old_channel) {
+            Ok(raw_channel) => raw_channel,
+            Err(BroadcastError::NotSubscribed) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let new_raw_channel = get_or_create_channel(self.conn, &new_channel)?;
+
+        // users that were subscribed to both stay subscribed only once
+        let query = r#"
+        UPDATE "user_channel"
+            SET "channel_id" = $2
+        WHERE "channel_id" = $1 AND "user_id" NOT IN (
+            SELECT "user_id" FROM "user_channel" WHERE "channel_id" = $2
+        );
+        "#;
+
+        diesel::sql_query(query)
+            .bind::<types::BigInt, _>(old_raw_channel.channel_id)
+            .bind::<types::BigInt, _>(new_raw_channel.channel_id)
+            .execute(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+
+        diesel::update(schema::message::table.filter(schema::message::columns::channel_id.eq(old_raw_channel.channel_id)))
+            .set(schema::message::columns::channel_id.eq(new_raw_channel.channel_id))
+            .execute(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+
+        diesel::delete(schema::user_channel::table.filter(schema::user_channel::columns::channel_id.eq(old_raw_channel.channel_id)))
+            .execute(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+
+        diesel::delete(schema::channel::table.filter(schema::channel::columns::channel_id.eq(old_raw_channel.channel_id)))
+            .execute(self.conn)
+            .map_err(|err| BroadcastError::InternalError(err.to_string()))?;
+
+        Ok(())
+    }
+
     fn get_messages(
         &self,
         user_id: i64,
@@ -1,3 +1,18 @@
+table! {
+    api_key (api_key_id) {
+        api_key_id -> Int8,
+        user_id -> Int8,
+        name -> Varchar,
+        key_prefix -> Varchar,
+        key_hash -> Varchar,
+        scopes -> Nullable<Json>,
+        created_at -> Timestamp,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        use_count -> Int8,
+    }
+}
+
 table! {
     channel (channel_id) {
         channel_id -> Int8,
@@ -41,6 +56,9 @@ table! {
         entity_id -> Int8,
         used_at -> Timestamp,
         used_by -> Int8,
+        action -> Varchar,
+        latency_ms -> Nullable<Int8>,
+        row_count -> Nullable<Int8>,
     }
 }
 
@@ -55,6 +73,38 @@ table! {
     }
 }
 
+table! {
+    login_failure (login_failure_id) {
+        login_failure_id -> Int8,
+        user_identifier -> Varchar,
+        user_id -> Nullable<Int8>,
+        ip_address -> Nullable<Varchar>,
+        device -> Nullable<Varchar>,
+        reason -> Varchar,
+        failed_at -> Timestamp,
+    }
+}
+
+table! {
+    login_request (state) {
+        state -> Varchar,
+        code_verifier -> Varchar,
+        nonce -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    login_throttle (throttle_key) {
+        throttle_key -> Varchar,
+        failed_attempts -> Int4,
+        last_failed_at -> Timestamp,
+        blocked_until -> Nullable<Timestamp>,
+        is_locked -> Bool,
+    }
+}
+
 table! {
     message (message_id) {
         message_id -> Int8,
@@ -64,6 +114,36 @@ table! {
     }
 }
 
+table! {
+    mfa_challenge (challenge_hash) {
+        challenge_hash -> Varchar,
+        user_id -> Int8,
+        failed_attempts -> Int4,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    mfa_recovery_code (mfa_recovery_code_id) {
+        mfa_recovery_code_id -> Int8,
+        user_id -> Int8,
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    password_reset (password_reset_id) {
+        password_reset_id -> Int8,
+        user_id -> Int8,
+        token_hash -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     permission (permission_id) {
         permission_id -> Int8,
@@ -133,6 +213,9 @@ table! {
         user_id -> Int8,
         created_at -> Timestamp,
         expires_at -> Timestamp,
+        device -> Nullable<Varchar>,
+        ip_address -> Nullable<Varchar>,
+        last_used_at -> Timestamp,
     }
 }
 
@@ -178,6 +261,9 @@ table! {
         display_name -> Varchar,
         user_info -> Json,
         joined_at -> Timestamp,
+        deprovisioned -> Bool,
+        is_service_account -> Bool,
+        disabled -> Bool,
     }
 }
 
@@ -190,6 +276,26 @@ table! {
     }
 }
 
+table! {
+    user_identity (user_identity_id) {
+        user_identity_id -> Int8,
+        user_id -> Int8,
+        issuer -> Varchar,
+        subject -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    user_mfa (user_id) {
+        user_id -> Int8,
+        secret -> Varchar,
+        confirmed_at -> Nullable<Timestamp>,
+        last_used_step -> Nullable<Int8>,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     user_role (user_role_id) {
         user_role_id -> Int8,
@@ -220,6 +326,7 @@ table! {
     }
 }
 
+joinable!(api_key -> user (user_id));
 joinable!(entity -> domain (domain_id));
 joinable!(entity -> scope (scope_id));
 joinable!(entity -> user (created_by));
@@ -227,7 +334,11 @@ joinable!(entity_tag -> entity (entity_id));
 joinable!(entity_tag -> tag (tag_id));
 joinable!(entity_usage -> entity (entity_id));
 joinable!(entity_usage -> user (used_by));
+joinable!(login_failure -> user (user_id));
 joinable!(message -> channel (channel_id));
+joinable!(mfa_challenge -> user (user_id));
+joinable!(mfa_recovery_code -> user (user_id));
+joinable!(password_reset -> user (user_id));
 joinable!(query -> entity (entity_id));
 joinable!(query -> user (modified_by));
 joinable!(role_permission -> permission (permission_id));
@@ -241,19 +352,28 @@ joinable!(table_schema_transaction -> table_schema (table_schema_id));
 joinable!(table_schema_transaction -> user (made_by));
 joinable!(user_channel -> channel (channel_id));
 joinable!(user_channel -> user (user_id));
+joinable!(user_identity -> user (user_id));
+joinable!(user_mfa -> user (user_id));
 joinable!(user_role -> role (role_id));
 joinable!(user_role -> user (user_id));
 joinable!(view -> entity (entity_id));
 joinable!(view -> user (modified_by));
 
 allow_tables_to_appear_in_same_query!(
+    api_key,
     channel,
     domain,
     entity,
     entity_tag,
     entity_usage,
     invitation,
+    login_failure,
+    login_request,
+    login_throttle,
     message,
+    mfa_challenge,
+    mfa_recovery_code,
+    password_reset,
     permission,
     query,
     role,
@@ -266,6 +386,8 @@ allow_tables_to_appear_in_same_query!(
     tag,
     user,
     user_channel,
+    user_identity,
+    user_mfa,
     user_role,
     version,
     view,
@@ -0,0 +1,89 @@
+use diesel::prelude::*;
+use diesel;
+
+use state::TableAudit;
+use state::table_audit::TableAuditOps;
+
+use data::DataChange;
+use data::TableAuditRecord;
+use data::AuditLogFilter;
+use state::error::TableAuditError;
+use metastore::dbdata;
+
+impl<'a> TableAuditOps for TableAudit<'a> {
+    fn record_changes(&self, entity_id: i64, user_id: i64, changes: &[DataChange]) -> Result<(), TableAuditError> {
+        debug!("Recording {} changes to the table {:?}", changes.len(), entity_id);
+
+        for change in changes {
+            let action_data = serde_json::to_value(change)
+                .map_err(|err| TableAuditError::InternalError(err.to_string()))?;
+
+            let inserted = diesel::sql_query(r#"
+                INSERT INTO table_schema_transaction (action_data, table_schema_id, made_by)
+                SELECT $1, table_schema_id, $2 FROM table_schema
+                WHERE entity_id = $3
+                ORDER BY modified_at DESC
+                LIMIT 1;
+                "#)
+                .bind::<diesel::sql_types::Json, _>(action_data)
+                .bind::<diesel::sql_types::BigInt, _>(user_id)
+                .bind::<diesel::sql_types::BigInt, _>(entity_id)
+                .execute(self.conn)
+                .map_err(|err| TableAuditError::InternalError(err.to_string()))?;
+
+            if inserted == 0 {
+                return Err(TableAuditError::InternalError(format!("no schema found for the table {:?}", entity_id)));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_audit_log(&self, entity_id: i64, filter: &AuditLogFilter) -> Result<Vec<TableAuditRecord>, TableAuditError> {
+        debug!("Getting the audit log of the table {:?} with {:?}", entity_id, filter);
+
+        // every revision of the table shares the same entity, so renaming or altering the table keeps the log
+        let raw_records: Vec<dbdata::RawTableAuditRecord> = diesel::sql_query(r#"
+            SELECT
+                table_schema_transaction.transaction_id,
+                table_schema_transaction.action_data,
+                table_schema_transaction.made_at,
+                "user".username
+            FROM table_schema_transaction
+            INNER JOIN table_schema
+                ON table_schema.table_schema_id = table_schema_transaction.table_schema_id
+            INNER JOIN "user"
+                ON "user".user_id = table_schema_transaction.made_by
+            WHERE table_schema.entity_id = $1
+                AND ($2::TIMESTAMP IS NULL OR table_schema_transaction.made_at >= $2)
+                AND ($3::TIMESTAMP IS NULL OR table_schema_transaction.made_at < $3)
+                AND ($4::VARCHAR IS NULL OR "user".username = $4)
+                AND ($5::BIGINT IS NULL OR table_schema_transaction.transaction_id >= $5)
+                AND ($6::BIGINT IS NULL OR table_schema_transaction.transaction_id <= $6)
+            ORDER BY table_schema_transaction.transaction_id ASC;
+            "#)
+            .bind::<diesel::sql_types::BigInt, _>(entity_id)
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Timestamp>, _>(filter.start)
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Timestamp>, _>(filter.end)
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Text>, _>(filter.username.to_owned())
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::BigInt>, _>(filter.from_transaction)
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::BigInt>, _>(filter.to_transaction)
+            .load(self.conn)
+            .map_err(|err| TableAuditError::InternalError(err.to_string()))?;
+
+        raw_records
+            .into_iter()
+            .map(|raw_record| {
+                let change: DataChange = serde_json::from_value(raw_record.action_data)
+                    .map_err(|err| TableAuditError::InternalError(err.to_string()))?;
+
+                Ok(TableAuditRecord {
+                    transaction_id: raw_record.transaction_id,
+                    made_at: raw_record.made_at,
+                    made_by: raw_record.username,
+                    change,
+                })
+            })
+            .collect()
+    }
+}
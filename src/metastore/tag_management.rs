@@ -0,0 +1,122 @@
+use diesel::prelude::*;
+use diesel;
+use diesel::result::Error as DbError;
+use diesel::result::DatabaseErrorKind as DbErrKind;
+
+use state::TagManagement;
+use state::tag_management::TagManagementOps;
+
+use data::Tag;
+use state::error::TagManagementError;
+use metastore::schema;
+use metastore::dbdata;
+
+fn to_tag(raw_tag: dbdata::RawTag) -> Tag {
+    Tag {
+        name: raw_tag.name,
+        description: raw_tag.description,
+    }
+}
+
+impl<'a> TagManagement<'a> {
+    fn get_raw_tag(&self, name: &str) -> Result<dbdata::RawTag, TagManagementError> {
+        schema::tag::table
+            .filter(schema::tag::columns::name.eq(name))
+            .get_result::<dbdata::RawTag>(self.conn)
+            .map_err(|err| match err {
+                DbError::NotFound => TagManagementError::NotFound,
+                _ => TagManagementError::InternalError(err.to_string()),
+            })
+    }
+}
+
+impl<'a> TagManagementOps for TagManagement<'a> {
+    fn get_all_tags(&self) -> Result<Vec<Tag>, TagManagementError> {
+        debug!("Getting all the tags");
+        let tags = schema::tag::table
+            .order(schema::tag::columns::name.asc())
+            .get_results::<dbdata::RawTag>(self.conn)
+            .map_err(|err| TagManagementError::InternalError(err.to_string()))?;
+
+        Ok(tags.into_iter().map(to_tag).collect())
+    }
+
+    fn create_tag(&self, tag: &Tag) -> Result<Tag, TagManagementError> {
+        info!("Adding new tag {:?}", &tag.name);
+        let raw_tag = dbdata::NewRawTag {
+            name: tag.name.to_owned(),
+            description: tag.description.to_owned(),
+        };
+
+        let tag = diesel::insert_into(schema::tag::table)
+            .values(&raw_tag)
+            .get_result::<dbdata::RawTag>(self.conn)
+            .map_err(|err| match err {
+                DbError::DatabaseError(DbErrKind::UniqueViolation, _) => TagManagementError::AlreadyExists,
+                _ => TagManagementError::InternalError(err.to_string()),
+            })?;
+
+        Ok(to_tag(tag))
+    }
+
+    fn delete_tag(&self, name: &str) -> Result<Tag, TagManagementError> {
+        info!("Deleting tag {:?}", name);
+        let tag = diesel::delete(schema::tag::table)
+            .filter(schema::tag::columns::name.eq(name))
+            .get_result::<dbdata::RawTag>(self.conn)
+            .map_err(|err| match err {
+                DbError::NotFound => TagManagementError::NotFound,
+                _ => TagManagementError::InternalError(err.to_string()),
+            })?;
+
+        Ok(to_tag(tag))
+    }
+
+    fn get_entity_tags(&self, entity_id: i64) -> Result<Vec<Tag>, TagManagementError> {
+        let tags = schema::tag::table
+            .inner_join(schema::entity_tag::table)
+            .filter(schema::entity_tag::columns::entity_id.eq(entity_id))
+            .order(schema::tag::columns::name.asc())
+            .select(schema::tag::all_columns)
+            .get_results::<dbdata::RawTag>(self.conn)
+            .map_err(|err| TagManagementError::InternalError(err.to_string()))?;
+
+        Ok(tags.into_iter().map(to_tag).collect())
+    }
+
+    fn attach_tag(&self, entity_id: i64, name: &str) -> Result<Tag, TagManagementError> {
+        info!("Attaching tag {:?} to entity {:?}", name, entity_id);
+        let tag = self.get_raw_tag(name)?;
+
+        let raw_entity_tag = dbdata::NewRawEntityTag {
+            entity_id,
+            tag_id: tag.tag_id,
+        };
+        diesel::insert_into(schema::entity_tag::table)
+            .values(&raw_entity_tag)
+            .execute(self.conn)
+            .map_err(|err| match err {
+                DbError::DatabaseError(DbErrKind::UniqueViolation, _) => TagManagementError::AlreadyExists,
+                _ => TagManagementError::InternalError(err.to_string()),
+            })?;
+
+        Ok(to_tag(tag))
+    }
+
+    fn detach_tag(&self, entity_id: i64, name: &str) -> Result<Tag, TagManagementError> {
+        info!("Detaching tag {:?} from entity {:?}", name, entity_id);
+        let tag = self.get_raw_tag(name)?;
+
+        let removed = diesel::delete(schema::entity_tag::table)
+            .filter(schema::entity_tag::columns::entity_id.eq(entity_id))
+            .filter(schema::entity_tag::columns::tag_id.eq(tag.tag_id))
+            .execute(self.conn)
+            .map_err(|err| TagManagementError::InternalError(err.to_string()))?;
+
+        if removed == 0 {
+            return Err(TagManagementError::NotFound);
+        }
+
+        Ok(to_tag(tag))
+    }
+}
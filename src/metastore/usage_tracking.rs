@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use diesel;
+
+use state::UsageTracking;
+use state::usage_tracking::UsageTrackingOps;
+
+use data::UsageRecord;
+use data::EntityUsage;
+use data::UserUsage;
+use state::error::UsageTrackingError;
+use metastore::schema;
+use metastore::dbdata;
+
+/// Number of users returned per entity
+const TOP_USERS: usize = 5;
+
+/// The latest name of every entity in the domain, together with it's type
+const ENTITY_NAMES: &'static str = r#"
+    entity_names AS (
+        SELECT latest.* FROM (
+            (SELECT DISTINCT ON (entity_id) entity_id, name, is_deleted, 'table' AS type_name
+                FROM table_schema ORDER BY entity_id, modified_at DESC)
+            UNION ALL
+            (SELECT DISTINCT ON (entity_id) entity_id, name, is_deleted, 'query' AS type_name
+                FROM query ORDER BY entity_id, modified_at DESC)
+            UNION ALL
+            (SELECT DISTINCT ON (entity_id) entity_id, name, is_deleted, 'script' AS type_name
+                FROM script ORDER BY entity_id, modified_at DESC)
+            UNION ALL
+            (SELECT DISTINCT ON (entity_id) entity_id, name, is_deleted, 'view' AS type_name
+                FROM view ORDER BY entity_id, modified_at DESC)
+        ) AS latest
+        INNER JOIN entity
+            ON latest.entity_id = entity.entity_id
+        INNER JOIN domain
+            ON entity.domain_id = domain.domain_id
+        WHERE domain.name = $1 AND latest.is_deleted = false
+    ),
+    usage_in_range AS (
+        SELECT * FROM entity_usage
+        WHERE used_at >= $2 AND used_at < $3
+    )
+"#;
+
+impl<'a> UsageTrackingOps for UsageTracking<'a> {
+    fn record_usage(&self, record: &UsageRecord) -> Result<(), UsageTrackingError> {
+        let raw_usage = dbdata::NewRawEntityUsage {
+            entity_id: record.entity_id,
+            used_by: record.user_id,
+            action: record.action.to_owned(),
+            latency_ms: record.latency_ms,
+            row_count: record.row_count,
+        };
+
+        diesel::insert_into(schema::entity_usage::table)
+            .values(&raw_usage)
+            .execute(self.conn)
+            .map_err(|err| UsageTrackingError::InternalError(err.to_string()))?;
+
+        Ok(())
+    }
+
+    fn get_entity_usage(
+        &self,
+        domain_name: &str,
+        start_time: chrono::NaiveDateTime,
+        end_time: chrono::NaiveDateTime,
+    ) -> Result<Vec<EntityUsage>, UsageTrackingError> {
+        debug!("Getting the entity usage of {:?} from {:?} to {:?}", domain_name, start_time, end_time);
+
+        let summary_query = format!(r#"
+            WITH {}
+            SELECT
+                entity_names.type_name,
+                entity_names.name,
+                entity_names.entity_id,
+                COUNT(usage_in_range.entity_usage_id) AS call_count,
+                MAX(usage_in_range.used_at) AS last_used_at,
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY usage_in_range.latency_ms) AS p50_latency_ms,
+                percentile_cont(0.95) WITHIN GROUP (ORDER BY usage_in_range.latency_ms) AS p95_latency_ms,
+                SUM(usage_in_range.row_count)::BIGINT AS total_rows
+            FROM entity_names
+            LEFT JOIN usage_in_range
+                ON entity_names.entity_id = usage_in_range.entity_id
+            GROUP BY entity_names.type_name, entity_names.name, entity_names.entity_id
+            ORDER BY call_count DESC, entity_names.name ASC;
+            "#, ENTITY_NAMES);
+
+        let summaries: Vec<dbdata::RawEntityUsageSummary> = diesel::sql_query(summary_query)
+            .bind::<diesel::sql_types::Text, _>(domain_name)
+            .bind::<diesel::sql_types::Timestamp, _>(start_time)
+            .bind::<diesel::sql_types::Timestamp, _>(end_time)
+            .load(self.conn)
+            .map_err(|err| UsageTrackingError::InternalError(err.to_string()))?;
+
+        let user_query = format!(r#"
+            WITH {}
+            SELECT
+                usage_in_range.entity_id,
+                "user".username,
+                COUNT(*) AS call_count
+            FROM usage_in_range
+            INNER JOIN entity_names
+                ON entity_names.entity_id = usage_in_range.entity_id
+            INNER JOIN "user"
+                ON "user".user_id = usage_in_range.used_by
+            GROUP BY usage_in_range.entity_id, "user".username
+            ORDER BY call_count DESC, "user".username ASC;
+            "#, ENTITY_NAMES);
+
+        let user_usages: Vec<dbdata::RawEntityUserUsage> = diesel::sql_query(user_query)
+            .bind::<diesel::sql_types::Text, _>(domain_name)
+            .bind::<diesel::sql_types::Timestamp, _>(start_time)
+            .bind::<diesel::sql_types::Timestamp, _>(end_time)
+            .load(self.conn)
+            .map_err(|err| UsageTrackingError::InternalError(err.to_string()))?;
+
+        let mut top_users: HashMap<i64, Vec<UserUsage>> = HashMap::new();
+        for user_usage in user_usages {
+            let users = top_users.entry(user_usage.entity_id).or_insert_with(|| vec![]);
+            if users.len() < TOP_USERS {
+                users.push(UserUsage {
+                    username: user_usage.username,
+                    call_count: user_usage.call_count,
+                });
+            }
+        }
+
+        let result = summaries
+            .into_iter()
+            .map(|summary| EntityUsage {
+                top_users: top_users.remove(&summary.entity_id).unwrap_or_default(),
+                type_name: summary.type_name,
+                name: summary.name,
+                call_count: summary.call_count,
+                last_used_at: summary.last_used_at,
+                p50_latency_ms: summary.p50_latency_ms,
+                p95_latency_ms: summary.p95_latency_ms,
+                total_rows: summary.total_rows,
+            })
+            .collect();
+
+        Ok(result)
+    }
+}
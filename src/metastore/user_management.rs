@@ -5,16 +5,23 @@ use diesel::result::Error as DbError;
 use diesel::result::DatabaseErrorKind as DbErrKind;
 
 use chrono::Utc;
+use chrono::Duration;
+use chrono::NaiveDateTime;
 use serde_json;
 
 use auth::tokens::Token;
+use auth::password_policy::PasswordViolation;
 
 use data::auth::InvitationToken;
+use data::auth::PasswordResetToken;
+use data::auth::LoginRequest;
+use data::auth::ExternalUser;
 use data::auth::Role;
 use data::permissions::Permission;
 use data::auth::NewUser;
 use data::auth::UserInfo;
 use data::auth::User;
+use data::auth::UserModification;
 use metastore::schema;
 
 use metastore::dbdata;
@@ -25,89 +32,69 @@ use state::authentication::AuthenticationOps;
 use state::user_management::UserManagementOps;
 use state::UserManagement;
 
+/// Service accounts don't have an email, but it has to be unique
+const SERVICE_ACCOUNT_EMAIL_DOMAIN: &'static str = "service-account.invalid";
+/// How long a password reset link stays valid
+const PASSWORD_RESET_DURATION_HOURS: i64 = 1;
+/// How long the user has to log in at the identity provider
+const LOGIN_REQUEST_DURATION_MINUTES: i64 = 10;
 
 impl<'a> UserManagementOps for UserManagement<'a> {
     fn get_user(&self, user_identifier: &str, password: &str) -> Result<UserInfo, UserManagementError> {
-        debug!("Authenticating user: {:?}", user_identifier);
-        let user = schema::user::table
-            .filter(schema::user::columns::username.eq(&user_identifier))
-            .or_filter(schema::user::columns::email.eq(&user_identifier))
-            .get_result::<dbdata::RawUser>(self.conn)
-            .map_err(|err| {
-                info!("Could not find user: {:?}", &user_identifier);
-                //TODO: a timining attack possible here?
-                match err {
-                    DbError::NotFound => UserManagementError::Unauthorized,
-                    _ => UserManagementError::InternalError(err.to_string()),
-                }
-            })?;
+        let user = self.authenticate_user(user_identifier, password)?;
 
-        let is_valid = self
-            .authentication
-            .verify_password(&user.password, password)?;
-
-        if is_valid {
-            info!("Password authentication passed for {:?}", &user.username);
-            Ok(UserInfo {
-                user_id: user.user_id,
-                username: user.username,
-                email: user.email,
-                display_name: user.display_name,
-            })
-        } else {
-            info!("Password authentication failed for {:?}", &user.username);
-            Err(UserManagementError::Unauthorized)
+        let password_changed_at = PasswordInfo::from_user_info(&user.user_info).password_changed_at;
+        if self.authentication.password_policy.is_expired(password_changed_at, Utc::now().naive_utc()) {
+            info!("The password of {:?} has expired", &user.username);
+            return Err(UserManagementError::PasswordExpired);
         }
-    }
 
-    fn add_user(&self, user: &NewUser) -> Result<User, UserManagementError> {
-        info!("Creating new user {:?}", &user);
-
-        //TODO: test password complexity
-        let hashed_pass = self
-            .authentication
-            .hash_password(&user.password)?;
+        Ok(UserInfo {
+            user_id: user.user_id,
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
 
-        let raw_user = dbdata::NewRawUser {
-            username: user.username.to_owned(),
-            email: user.email.to_owned(),
-            display_name: user.display_name.to_owned()
-                .unwrap_or_else(|| user.username.to_owned()),
-            password: hashed_pass,
-        };
+    fn verify_user_password(&self, user_identifier: &str, password: &str) -> Result<UserInfo, UserManagementError> {
+        let user = self.authenticate_user(user_identifier, password)?;
 
-        let user = diesel::insert_into(schema::user::table)
-            .values(&raw_user)
-            .get_result::<dbdata::RawUser>(self.conn)
-            .map_err(|err| {
-                error!("Could not insert new user {}[{}] {} err: {:?}", &raw_user.username, &raw_user.display_name, &raw_user.email, &err);
+        Ok(UserInfo {
+            user_id: user.user_id,
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
 
-                match err {
-                    DbError::DatabaseError(DbErrKind::UniqueViolation, _) => UserManagementError::AlreadyExists,
-                    _ => UserManagementError::InternalError(err.to_string()),
-                }
-            })?;
+    fn get_user_info(&self, user_identifier: &str) -> Result<UserInfo, UserManagementError> {
+        let user = get_user_by_identifier(self.conn, &user_identifier)?;
 
-        info!("inserted new user {}[{}] {}", &user.username, &user.display_name, &user.email);
-        Ok(User {
+        Ok(UserInfo {
+            user_id: user.user_id,
             username: user.username,
             email: user.email,
             display_name: user.display_name,
         })
+    }
 
+    fn add_user(&self, user: &NewUser) -> Result<User, UserManagementError> {
+        info!("Creating new user {:?}", &user.username);
+
+        if !is_valid_email(&user.email) {
+            info!("Not creating {:?}, {:?} is not a valid email", &user.username, &user.email);
+            return Err(UserManagementError::InvalidEmail);
+        }
+        self.check_password_policy(&user.password, &[])?;
+
+        self.insert_user(user)
     }
 
     fn remove_user(&self, user_identifier: &str) -> Result<User, UserManagementError> {
         info!("deleting user: {:?}", &user_identifier); //TODO: doesn't work since user does not cascade, put in a flag instead
-        /* FIXME: .or_filter not working for diesel */
-        /*
-        let result = diesel::delete(schema::user::table)
-            .filter(schema::user::columns::username.eq(&user_identifier))
-            .or_filter(schema::user::columns::email.eq(&user_identifier))
-        */
-        let user = diesel::sql_query(r#"DELETE FROM "user" WHERE "username" = $1 OR "email" = $2 RETURNING *;"#)
-            .bind::<diesel::sql_types::Text, _>(user_identifier)
-            .bind::<diesel::sql_types::Text, _>(user_identifier)
+        let user = get_user_by_identifier(self.conn, &user_identifier)?;
+        let user = diesel::delete(schema::user::table.filter(schema::user::columns::user_id.eq(user.user_id)))
             .get_result::<dbdata::RawUser>(self.conn)
             .map_err(|err| {
                 info!("Could not delete user: {:?}", &user_identifier);
@@ -126,6 +113,49 @@ impl<'a> UserManagementOps for UserManagement<'a> {
         })
     }
 
+    fn add_service_account(&self, username: &str, display_name: Option<String>) -> Result<User, UserManagementError> {
+        info!("Creating service account {:?}", &username);
+        // nobody knows the password, so it can't be used to log in
+        let password = Token::new()
+            .map_err(|err| UserManagementError::InternalError(err.to_string()))?
+            .as_string();
+        self.insert_user(&NewUser {
+            username: username.to_string(),
+            email: format!("{}@{}", username, SERVICE_ACCOUNT_EMAIL_DOMAIN),
+            password,
+            display_name,
+        })?;
+
+        let user = diesel::update(schema::user::table.filter(schema::user::columns::username.eq(username)))
+            .set(schema::user::columns::is_service_account.eq(true))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| UserManagementError::InternalError(err.to_string()))?;
+
+        Ok(User {
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
+
+    fn get_service_account(&self, username: &str) -> Result<UserInfo, UserManagementError> {
+        let user = schema::user::table
+            .filter(schema::user::columns::username.eq(username))
+            .filter(schema::user::columns::is_service_account.eq(true))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| match err {
+                DbError::NotFound => UserManagementError::NotFound,
+                _ => UserManagementError::InternalError(err.to_string()),
+            })?;
+
+        Ok(UserInfo {
+            user_id: user.user_id,
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
+
     fn create_user_token(&self, email: &str) -> Result<InvitationToken, UserManagementError> {
         info!("Creating token for: {}", email);
         let token = Token::new()
@@ -156,9 +186,340 @@ impl<'a> UserManagementOps for UserManagement<'a> {
         Ok(token)
     }
 
-    //TODO: check with old password
+    fn modify_user(&self, user_identifier: &str, modification: &UserModification) -> Result<User, UserManagementError> {
+        info!("Modifying user: {:?}", &user_identifier);
+        let user = get_user_by_identifier(self.conn, &user_identifier)?;
+
+        if let Some(email) = &modification.email {
+            if !is_valid_email(email) {
+                info!("Not modifying {:?}, {:?} is not a valid email", &user_identifier, email);
+                return Err(UserManagementError::InvalidEmail);
+            }
+            self.check_identifier_available(email, Some(user.user_id))?;
+        }
+
+        let email = modification.email.to_owned().unwrap_or(user.email);
+        let display_name = modification.display_name.to_owned().unwrap_or(user.display_name);
+
+        let user = diesel::update(schema::user::table.filter(schema::user::columns::user_id.eq(user.user_id)))
+            .set((
+                schema::user::columns::email.eq(&email),
+                schema::user::columns::display_name.eq(&display_name),
+            ))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| {
+                info!("Could not modify user: {:?} err: {:?}", &user_identifier, &err);
+
+                match err {
+                    DbError::DatabaseError(DbErrKind::UniqueViolation, _) => UserManagementError::AlreadyExists,
+                    _ => UserManagementError::InternalError(err.to_string()),
+                }
+            })?;
+
+        info!("modified user {}[{}] {}", &user.username, &user.display_name, &user.email);
+        Ok(User {
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
+
     fn modify_user_password(&self, user_identifier: &str, password: &str) -> Result<User, UserManagementError> {
-        unimplemented!()
+        info!("Changing the password for: {:?}", &user_identifier);
+        let user = get_user_by_identifier(self.conn, &user_identifier)?;
+
+        // the current password is the latest one in the history
+        let mut previous_hashes = vec![user.password.to_owned()];
+        previous_hashes.extend(PasswordInfo::from_user_info(&user.user_info).password_history);
+        self.check_password_policy(password, &previous_hashes)?;
+
+        let hashed_pass = self
+            .authentication
+            .hash_password(password)?;
+
+        let password_info = PasswordInfo {
+            password_history: previous_hashes
+                .into_iter()
+                .take(self.authentication.password_policy.history_size)
+                .collect(),
+            password_changed_at: Some(Utc::now().naive_utc()),
+        };
+
+        let user = diesel::update(schema::user::table.filter(schema::user::columns::user_id.eq(user.user_id)))
+            .set((
+                schema::user::columns::password.eq(&hashed_pass),
+                schema::user::columns::user_info.eq(password_info.merge_into(&user.user_info)),
+            ))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| {
+                info!("Could not change the password for: {:?}", &user_identifier);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        info!("changed the password for {}", &user.username);
+        Ok(User {
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
+
+    fn set_user_disabled(&self, user_identifier: &str, disabled: bool) -> Result<User, UserManagementError> {
+        info!("Setting disabled to {:?} for: {:?}", disabled, &user_identifier);
+        let user = get_user_by_identifier(self.conn, &user_identifier)?;
+        let user = diesel::update(schema::user::table.filter(schema::user::columns::user_id.eq(user.user_id)))
+            .set(schema::user::columns::disabled.eq(disabled))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| {
+                info!("Could not change disabled for: {:?}", &user_identifier);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        // a reset link or a half finished login would get the user back in once they are enabled again
+        if disabled {
+            diesel::delete(schema::password_reset::table)
+                .filter(schema::password_reset::columns::user_id.eq(user.user_id))
+                .execute(self.conn)
+                .map_err(|err| {
+                    error!("Could not remove password reset tokens err: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                })?;
+
+            diesel::delete(schema::mfa_challenge::table)
+                .filter(schema::mfa_challenge::columns::user_id.eq(user.user_id))
+                .execute(self.conn)
+                .map_err(|err| {
+                    error!("Could not remove mfa challenges err: {:?}", &err);
+                    UserManagementError::InternalError(err.to_string())
+                })?;
+        }
+
+        Ok(User {
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
+
+    fn set_user_deprovisioned(&self, user_identifier: &str, deprovisioned: bool) -> Result<User, UserManagementError> {
+        info!("Setting deprovisioned to {:?} for: {:?}", deprovisioned, &user_identifier);
+        let user = get_user_by_identifier(self.conn, &user_identifier)?;
+        let user = diesel::update(schema::user::table.filter(schema::user::columns::user_id.eq(user.user_id)))
+            .set(schema::user::columns::deprovisioned.eq(deprovisioned))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| {
+                info!("Could not change deprovisioned for: {:?}", &user_identifier);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        Ok(User {
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
+
+    fn create_password_reset_token(&self, user_identifier: &str) -> Result<PasswordResetToken, UserManagementError> {
+        info!("Creating password reset token for: {:?}", &user_identifier);
+        let user = get_user_by_identifier(self.conn, &user_identifier)?;
+
+        // the identity provider or the directory has the password, a local one would be a way around it
+        let has_identity = diesel::select(diesel::dsl::exists(schema::user_identity::table
+            .filter(schema::user_identity::columns::user_id.eq(user.user_id))))
+            .get_result::<bool>(self.conn)
+            .map_err(|err| UserManagementError::InternalError(err.to_string()))?;
+        if has_identity {
+            info!("{:?} logs in through an identity provider, not resetting the password", &user.username);
+            return Err(UserManagementError::Unauthorized);
+        }
+
+        let token = Token::new()
+            .map_err(|err| UserManagementError::InternalError(err.to_string()))?
+            .as_string();
+
+        diesel::delete(schema::password_reset::table)
+            .filter(schema::password_reset::columns::user_id.eq(user.user_id))
+            .filter(schema::password_reset::columns::used_at.is_null())
+            .execute(self.conn)
+            .map_err(|err| UserManagementError::InternalError(err.to_string()))?;
+
+        let password_reset = dbdata::NewRawPasswordReset {
+            user_id: user.user_id,
+            token_hash: Token::digest(&token),
+            expires_at: (Utc::now() + Duration::hours(PASSWORD_RESET_DURATION_HOURS)).naive_utc(),
+        };
+        let password_reset = diesel::insert_into(schema::password_reset::table)
+            .values(&password_reset)
+            .get_result::<dbdata::RawPasswordReset>(self.conn)
+            .map_err(|err| {
+                error!("Encountered error: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        info!("created password reset token for {:?}[{:?}]", &user.username, password_reset.password_reset_id);
+        Ok(PasswordResetToken {
+            email: user.email,
+            token,
+            expires_at: password_reset.expires_at,
+        })
+    }
+
+    fn use_password_reset_token(&self, token: &str) -> Result<UserInfo, UserManagementError> {
+        let now = Utc::now().naive_utc();
+        let password_reset = diesel::update(
+            schema::password_reset::table
+                .filter(schema::password_reset::columns::token_hash.eq(Token::digest(token)))
+                .filter(schema::password_reset::columns::used_at.is_null())
+                .filter(schema::password_reset::columns::expires_at.gt(now)))
+            .set(schema::password_reset::columns::used_at.eq(now))
+            .get_result::<dbdata::RawPasswordReset>(self.conn)
+            .map_err(|err| match err {
+                DbError::NotFound => {
+                    info!("Password reset token is invalid, used or expired");
+                    UserManagementError::Unauthorized
+                },
+                _ => UserManagementError::InternalError(err.to_string()),
+            })?;
+
+        let user = schema::user::table
+            .filter(schema::user::columns::user_id.eq(password_reset.user_id))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| match err {
+                DbError::NotFound => UserManagementError::NotFound,
+                _ => UserManagementError::InternalError(err.to_string()),
+            })?;
+
+        info!("used password reset token for {:?}", &user.username);
+        Ok(UserInfo {
+            user_id: user.user_id,
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
+
+    fn create_login_request(&self, login_request: &LoginRequest) -> Result<(), UserManagementError> {
+        let raw_login_request = dbdata::NewRawLoginRequest {
+            state: login_request.state.to_owned(),
+            code_verifier: login_request.code_verifier.to_owned(),
+            nonce: login_request.nonce.to_owned(),
+            expires_at: (Utc::now() + Duration::minutes(LOGIN_REQUEST_DURATION_MINUTES)).naive_utc(),
+        };
+
+        diesel::insert_into(schema::login_request::table)
+            .values(&raw_login_request)
+            .execute(self.conn)
+            .map_err(|err| {
+                error!("Could not create login request: {:?}", &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        Ok(())
+    }
+
+    fn take_login_request(&self, state: &str) -> Result<LoginRequest, UserManagementError> {
+        let login_request = diesel::delete(schema::login_request::table)
+            .filter(schema::login_request::columns::state.eq(state))
+            .get_result::<dbdata::RawLoginRequest>(self.conn)
+            .map_err(|err| match err {
+                DbError::NotFound => {
+                    info!("Login request does not exist or was already used");
+                    UserManagementError::Unauthorized
+                },
+                _ => UserManagementError::InternalError(err.to_string()),
+            })?;
+
+        if login_request.expires_at < Utc::now().naive_utc() {
+            info!("Login request has expired");
+            return Err(UserManagementError::Unauthorized);
+        }
+
+        Ok(LoginRequest {
+            state: login_request.state,
+            code_verifier: login_request.code_verifier,
+            nonce: login_request.nonce,
+        })
+    }
+
+    fn get_or_create_external_user(&self, external_user: &ExternalUser) -> Result<UserInfo, UserManagementError> {
+        let identity = schema::user_identity::table
+            .filter(schema::user_identity::columns::issuer.eq(&external_user.issuer))
+            .filter(schema::user_identity::columns::subject.eq(&external_user.subject))
+            .get_result::<dbdata::RawUserIdentity>(self.conn)
+            .optional()
+            .map_err(|err| UserManagementError::InternalError(err.to_string()))?;
+
+        let user_id = match identity {
+            Some(identity) => identity.user_id,
+            None => {
+                info!("First login of {:?} from {:?}, creating the user", &external_user.username, &external_user.issuer);
+                // the password is never given out, these users can only log in through the provider
+                let password = Token::new()
+                    .map_err(|err| UserManagementError::InternalError(err.to_string()))?
+                    .as_string();
+                // an existing user with the same name or email is not taken over, that would trust the provider with every account
+                self.insert_user(&NewUser {
+                    username: external_user.username.to_owned(),
+                    email: external_user.email.to_owned(),
+                    password,
+                    display_name: external_user.display_name.to_owned(),
+                })?;
+
+                let user = schema::user::table
+                    .filter(schema::user::columns::username.eq(&external_user.username))
+                    .get_result::<dbdata::RawUser>(self.conn)
+                    .map_err(|err| UserManagementError::InternalError(err.to_string()))?;
+
+                let identity = dbdata::NewRawUserIdentity {
+                    user_id: user.user_id,
+                    issuer: external_user.issuer.to_owned(),
+                    subject: external_user.subject.to_owned(),
+                };
+                diesel::insert_into(schema::user_identity::table)
+                    .values(&identity)
+                    .execute(self.conn)
+                    .map_err(|err| UserManagementError::InternalError(err.to_string()))?;
+
+                user.user_id
+            },
+        };
+
+        let user = schema::user::table
+            .filter(schema::user::columns::user_id.eq(user_id))
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| match err {
+                DbError::NotFound => UserManagementError::NotFound,
+                _ => UserManagementError::InternalError(err.to_string()),
+            })?;
+
+        Ok(UserInfo {
+            user_id: user.user_id,
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+    }
+
+    fn get_active_external_users(&self, issuer: &str) -> Result<Vec<(String, UserInfo)>, UserManagementError> {
+        let users = schema::user_identity::table
+            .inner_join(schema::user::table)
+            .filter(schema::user_identity::columns::issuer.eq(issuer))
+            .filter(schema::user::columns::deprovisioned.eq(false))
+            .get_results::<(dbdata::RawUserIdentity, dbdata::RawUser)>(self.conn)
+            .map_err(|err| UserManagementError::InternalError(err.to_string()))?
+            .into_iter()
+            .map(|(identity, user)| {
+                let user = UserInfo {
+                    user_id: user.user_id,
+                    username: user.username,
+                    email: user.email,
+                    display_name: user.display_name,
+                };
+                (identity.subject, user)
+            })
+            .collect();
+
+        Ok(users)
     }
 
     fn get_all_users(&self) -> Result<Vec<User>, UserManagementError> {
@@ -254,6 +615,29 @@ impl<'a> UserManagementOps for UserManagement<'a> {
         Ok(roles)
     }
 
+    fn get_role_permissions(&self, rolename: &str) -> Result<Vec<Permission>, UserManagementError> {
+        info!("listing permissions of role [{}]", &rolename);
+        let raw_permissions = schema::permission::table
+            .inner_join(schema::role_permission::table.inner_join(schema::role::table))
+            .filter(schema::role::columns::name.eq(&rolename))
+            .select(schema::permission::all_columns)
+            .distinct()
+            .get_results::<dbdata::RawPermission>(self.conn)
+            .map_err(|err| {
+                error!("Could not list the permissions of role {} err: {:?}", rolename, &err);
+                UserManagementError::InternalError(err.to_string())
+            })?;
+
+        raw_permissions
+            .into_iter()
+            .map(|raw_permission| serde_json::from_value(raw_permission.data)
+                .map_err(|err| {
+                    error!("Could not deserialize error: {:?}", &err);
+                    UserManagementError::Unknown
+                }))
+            .collect()
+    }
+
     fn add_permission(&self, permission: &Permission) -> Result<Permission, UserManagementError> {
         let permission_json = serde_json::to_value(permission)
             .map_err(|err| {
@@ -437,17 +821,7 @@ impl<'a> UserManagementOps for UserManagement<'a> {
         info!("attaching role [{}] for user [{}]", &rolename, &user_identifier);
 
         //Get the user
-        let raw_user = schema::user::table
-            .filter(schema::user::columns::username.eq(&user_identifier))
-            .or_filter(schema::user::columns::email.eq(&user_identifier))
-            .get_result::<dbdata::RawUser>(self.conn)
-            .map_err(|err| {
-                info!("Could not find user: {:?}", &user_identifier);
-                match err {
-                    DbError::NotFound => UserManagementError::NotFound,
-                    _ => UserManagementError::InternalError(err.to_string()),
-                }
-            })?;
+        let raw_user = get_user_by_identifier(self.conn, &user_identifier)?;
 
         //Get the role
         let raw_role = schema::role::table
@@ -487,17 +861,7 @@ impl<'a> UserManagementOps for UserManagement<'a> {
         info!("detaching role [{}] for user [{}]", &rolename, &user_identifier);
 
         //Get the user
-        let raw_user = schema::user::table
-            .filter(schema::user::columns::username.eq(&user_identifier))
-            .or_filter(schema::user::columns::email.eq(&user_identifier))
-            .get_result::<dbdata::RawUser>(self.conn)
-            .map_err(|err| {
-                info!("Could not find user: {:?}", &user_identifier);
-                match err {
-                    DbError::NotFound => UserManagementError::NotFound,
-                    _ => UserManagementError::InternalError(err.to_string()),
-                }
-            })?;
+        let raw_user = get_user_by_identifier(self.conn, &user_identifier)?;
 
         //Get the role
         let raw_role = schema::role::table
@@ -529,6 +893,172 @@ impl<'a> UserManagementOps for UserManagement<'a> {
     }
 }
 
+/// What is kept about the password in `user_info`, next to anything else that is in there
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(super) struct PasswordInfo {
+    /// hashes of the previous passwords, latest first
+    pub password_history: Vec<String>,
+    pub password_changed_at: Option<NaiveDateTime>,
+}
+
+impl PasswordInfo {
+    pub(super) fn from_user_info(user_info: &serde_json::Value) -> Self {
+        serde_json::from_value(user_info.to_owned()).unwrap_or_default()
+    }
+
+    pub(super) fn merge_into(&self, user_info: &serde_json::Value) -> serde_json::Value {
+        let mut user_info = user_info.to_owned();
+        if let (Some(fields), Ok(serde_json::Value::Object(password_fields))) = (user_info.as_object_mut(), serde_json::to_value(self)) {
+            fields.extend(password_fields);
+        }
+        user_info
+    }
+}
+
+impl<'a> UserManagement<'a> {
+    fn authenticate_user(&self, user_identifier: &str, password: &str) -> Result<dbdata::RawUser, UserManagementError> {
+        debug!("Authenticating user: {:?}", user_identifier);
+        let user = find_user(self.conn, &user_identifier)?
+            .ok_or_else(|| {
+                info!("Could not find user: {:?}", &user_identifier);
+                //TODO: a timining attack possible here?
+                UserManagementError::Unauthorized
+            })?;
+
+        let is_valid = self
+            .authentication
+            .verify_password(&user.password, password)?;
+
+        if is_valid {
+            info!("Password authentication passed for {:?}", &user.username);
+            Ok(user)
+        } else {
+            info!("Password authentication failed for {:?}", &user.username);
+            Err(UserManagementError::Unauthorized)
+        }
+    }
+
+    /// Without checking the password policy, for generated passwords that nobody knows
+    fn insert_user(&self, user: &NewUser) -> Result<User, UserManagementError> {
+        self.check_identifier_available(&user.username, None)?;
+        self.check_identifier_available(&user.email, None)?;
+
+        let hashed_pass = self
+            .authentication
+            .hash_password(&user.password)?;
+
+        let raw_user = dbdata::NewRawUser {
+            username: user.username.to_owned(),
+            email: user.email.to_owned(),
+            display_name: user.display_name.to_owned()
+                .unwrap_or_else(|| user.username.to_owned()),
+            password: hashed_pass,
+            user_info: PasswordInfo {
+                password_history: vec![],
+                password_changed_at: Some(Utc::now().naive_utc()),
+            }.merge_into(&json!({})),
+        };
+
+        let user = diesel::insert_into(schema::user::table)
+            .values(&raw_user)
+            .get_result::<dbdata::RawUser>(self.conn)
+            .map_err(|err| {
+                error!("Could not insert new user {}[{}] {} err: {:?}", &raw_user.username, &raw_user.display_name, &raw_user.email, &err);
+
+                match err {
+                    DbError::DatabaseError(DbErrKind::UniqueViolation, _) => UserManagementError::AlreadyExists,
+                    _ => UserManagementError::InternalError(err.to_string()),
+                }
+            })?;
+
+        info!("inserted new user {}[{}] {}", &user.username, &user.display_name, &user.email);
+        Ok(User {
+            username: user.username,
+            email: user.email,
+            display_name: user.display_name,
+        })
+
+    }
+
+    /// Users are looked up by their username or their email, so neither can be the username or the email of someone else
+    fn check_identifier_available(&self, identifier: &str, user_id: Option<i64>) -> Result<(), UserManagementError> {
+        match find_user(self.conn, identifier) {
+            Ok(Some(ref user)) if Some(user.user_id) == user_id => Ok(()),
+            Ok(None) => Ok(()),
+            Ok(Some(_)) | Err(UserManagementError::AmbiguousIdentifier) => {
+                info!("{:?} is already used by another user", identifier);
+                Err(UserManagementError::AlreadyExists)
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    /// `previous_hashes` are the hashes of the current password and the ones before it, latest first
+    fn check_password_policy(&self, password: &str, previous_hashes: &[String]) -> Result<(), UserManagementError> {
+        let policy = &self.authentication.password_policy;
+        let mut violations = policy.validate(password);
+
+        for hashed_password in previous_hashes.iter().take(policy.history_size) {
+            if self.authentication.verify_password(hashed_password, password)? {
+                violations.push(PasswordViolation::Reused { history_size: policy.history_size });
+                break;
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            info!("The password does not meet the password policy: {:?}", &violations);
+            Err(UserManagementError::PasswordPolicy(violations))
+        }
+    }
+}
+
+/// Only the shape is checked, whether the address exists is up to the mail server
+fn is_valid_email(email: &str) -> bool {
+    let mut parts = email.split('@');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(local_part), Some(domain), None) => {
+            !local_part.is_empty() &&
+                domain.contains('.') &&
+                !domain.starts_with('.') &&
+                !domain.ends_with('.') &&
+                !email.chars().any(|c| c.is_whitespace())
+        },
+        _ => false,
+    }
+}
+
+/// Users can be referred to by their username or their email, an identifier that matches
+/// more than one user is refused instead of acting on whichever row comes first
+pub(super) fn find_user(conn: &Conn, user_identifier: &str) -> Result<Option<dbdata::RawUser>, UserManagementError> {
+    let mut users = schema::user::table
+        .filter(schema::user::columns::username.eq(&user_identifier))
+        .or_filter(schema::user::columns::email.eq(&user_identifier))
+        .limit(2)
+        .load::<dbdata::RawUser>(conn)
+        .map_err(|err| {
+            error!("Could not get user: {:?}", &err);
+            UserManagementError::InternalError(err.to_string())
+        })?;
+
+    if users.len() > 1 {
+        warn!("{:?} is the username of one user and the email of another", &user_identifier);
+        return Err(UserManagementError::AmbiguousIdentifier);
+    }
+
+    Ok(users.pop())
+}
+
+pub(super) fn get_user_by_identifier(conn: &Conn, user_identifier: &str) -> Result<dbdata::RawUser, UserManagementError> {
+    find_user(conn, user_identifier)?
+        .ok_or_else(|| {
+            info!("Could not find user: {:?}", &user_identifier);
+            UserManagementError::NotFound
+        })
+}
+
 fn get_or_create_permission(conn: &Conn, permission: &Permission) -> Result<dbdata::RawPermission, UserManagementError> {
     let permission_json = serde_json::to_value(permission)
         .map_err(|err| {
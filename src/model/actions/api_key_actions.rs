@@ -0,0 +1,286 @@
+use std::marker::PhantomData;
+
+use chrono::NaiveDateTime;
+
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+use state::ActionState;
+use state::StateFunctions;
+use state::user_management::UserManagementOps;
+use state::authentication::AuthenticationOps;
+use state::authorization::AuthorizationOps;
+
+use connection::GetSecrets;
+
+/// Keys belong to the logged in user, or to a service account when a user admin manages them
+fn get_key_owner<S>(state: &S, service_account: &Option<String>) -> Result<i64, Error>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    let authorization = state.get_authorization();
+    match service_account {
+        Some(username) => {
+            let is_user_admin = authorization.is_admin() ||
+                authorization.permissions().contains(&Permission::user_admin());
+            if !is_user_admin {
+                return Err(Error::Unauthorized);
+            }
+
+            state
+                .get_user_management()
+                .get_service_account(username)
+                .map(|user| user.user_id)
+                .map_err(Error::UserManagement)
+        },
+        None => authorization
+            .user_id()
+            .ok_or_else(|| {
+                error!("This is unexpected. the user should already be logged in at this point");
+                Error::Unknown
+            }),
+    }
+}
+
+/// User Auth: Create a user for automation, it can only authenticate with api keys
+#[derive(Debug)]
+pub struct AddServiceAccount<S = ActionState> {
+    username: String,
+    display_name: Option<String>,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> AddServiceAccount<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(username: String, display_name: Option<String>) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            username,
+            display_name,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for AddServiceAccount<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = UserResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling AddServiceAccount");
+
+        state
+            .get_user_management()
+            .add_service_account(&self.username, self.display_name.to_owned())
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("addServiceAccount", UserResult(res)))
+    }
+}
+
+/// User Auth: Create a long lived api key, optionally limited to some permissions and with an expiry
+#[derive(Debug)]
+pub struct CreateApiKey<S = ActionState> {
+    name: String,
+    scopes: Option<Vec<Permission>>,
+    expires_at: Option<NaiveDateTime>,
+    service_account: Option<String>,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> CreateApiKey<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(
+        name: String,
+        scopes: Option<Vec<Permission>>,
+        expires_at: Option<NaiveDateTime>,
+        service_account: Option<String>,
+    ) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            name,
+            scopes,
+            expires_at,
+            service_account,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for CreateApiKey<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = NewApiKeyResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling CreateApiKey");
+
+        let authorization = state.get_authorization();
+        // otherwise a scoped key could hand out keys with more permissions than it has
+        if authorization.scopes().is_some() {
+            debug!("Api keys can't be created with a scoped api key");
+            return Err(Error::Unauthorized);
+        }
+
+        let user_id = get_key_owner(state, &self.service_account)?;
+
+        // the keys of service accounts are limited by the service account's permissions when they are used
+        if let (Some(scopes), None) = (&self.scopes, &self.service_account) {
+            let permissions = authorization.permissions();
+            let is_permitted = authorization.is_admin() || scopes.iter().all(|scope| permissions.contains(scope));
+            if !is_permitted {
+                debug!("Api key scopes {:?} are not a subset of the user's permissions", scopes);
+                return Err(Error::Unauthorized);
+            }
+        }
+
+        state
+            .get_authentication()
+            .create_api_key(user_id, &self.name, self.scopes.to_owned(), self.expires_at)
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("createApiKey", NewApiKeyResult(res)))
+    }
+}
+
+/// User Auth: The api keys of the logged in user or a service account, with when they were last used
+#[derive(Debug)]
+pub struct ListApiKeys<S = ActionState> {
+    service_account: Option<String>,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ListApiKeys<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(service_account: Option<String>) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            service_account,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ListApiKeys<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = ApiKeysResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ListApiKeys");
+
+        let user_id = get_key_owner(state, &self.service_account)?;
+
+        state
+            .get_authentication()
+            .get_api_keys(user_id)
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("listApiKeys", ApiKeysResult(res)))
+    }
+}
+
+/// User Auth: The api key stops working right away
+#[derive(Debug)]
+pub struct RevokeApiKey<S = ActionState> {
+    api_key_id: i64,
+    service_account: Option<String>,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RevokeApiKey<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(api_key_id: i64, service_account: Option<String>) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            api_key_id,
+            service_account,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for RevokeApiKey<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = ();
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RevokeApiKey");
+
+        let user_id = get_key_owner(state, &self.service_account)?;
+
+        state
+            .get_authentication()
+            .revoke_api_key(user_id, self.api_key_id)
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("revokeApiKey", res))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+    use state::error::UserManagementError;
+
+    #[test]
+    fn test_service_account_api_key() {
+        with_state(|state| {
+            let username = format!("ci_{}", random_identifier());
+            AddServiceAccount::<MockState>::new(username.to_owned(), None).call(&state).unwrap();
+
+            let scopes = vec![Permission::user_admin()];
+            let result = CreateApiKey::<MockState>::new("deploys".to_string(), Some(scopes.to_owned()), None, Some(username.to_owned()))
+                .call(&state)
+                .unwrap();
+            let new_api_key = result.get_data().0;
+            assert!(new_api_key.key.starts_with(&new_api_key.api_key.prefix));
+
+            let authentication = state.get_authentication();
+            let claims = authentication.authenticate_api_key(&new_api_key.key).unwrap();
+            assert_eq!(claims.username, username);
+            assert_eq!(claims.scopes, Some(scopes));
+            assert!(!claims.is_admin);
+            assert_eq!(claims.get_api_key_id(), Some(new_api_key.api_key.api_key_id));
+            assert!(authentication.is_api_key_active(new_api_key.api_key.api_key_id).unwrap());
+
+            let api_keys = ListApiKeys::<MockState>::new(Some(username.to_owned())).call(&state).unwrap().get_data().0;
+            assert_eq!(api_keys.len(), 1);
+            assert_eq!(api_keys[0].use_count, 1);
+            assert!(api_keys[0].last_used_at.is_some());
+
+            let service_account = state.get_user_management().get_service_account(&username).unwrap();
+            let session = authentication.create_session(service_account);
+            assert_eq!(session.unwrap_err(), UserManagementError::Unauthorized);
+
+            RevokeApiKey::<MockState>::new(new_api_key.api_key.api_key_id, Some(username.to_owned())).call(&state).unwrap();
+            let result = authentication.authenticate_api_key(&new_api_key.key);
+            assert_eq!(result.unwrap_err(), UserManagementError::Unauthorized);
+            // tokens handed out for the key stop working with it
+            assert!(!authentication.is_api_key_active(new_api_key.api_key.api_key_id).unwrap());
+        });
+    }
+}
@@ -0,0 +1,650 @@
+use std::result::Result::Ok;
+use std::marker::PhantomData;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use data;
+use data::Named;
+use data::Rename;
+use data::auth::Role;
+use data::bundle::Bundle;
+use data::bundle::EntityTags;
+use data::bundle::RoleBundle;
+use data::bundle::BUNDLE_VERSION;
+use data::utils::OnConflict;
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+use model::actions::tag_actions::get_entity_id;
+use model::entity::RetrieverFunctions;
+use model::entity::ModifierFunctions;
+use model::entity::RawEntityTypes;
+use model::entity::results::Created;
+use model::entity::results::Updated;
+use model::entity::update_state::UpdateActionFunctions;
+use model::table::DatastoreActionOps;
+
+use scripting::ScriptFunctions;
+use scripting::pack_archive;
+use scripting::unpack_archive;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::user_management::UserManagementOps;
+use state::tag_management::TagManagementOps;
+use state::error::TagManagementError;
+use state::error::UserManagementError;
+
+/// Bundles can carry the data of the tables, so they can be a lot larger than a script archive
+const MAX_BUNDLE_SIZE: u64 = 256 * 1024 * 1024;
+const MAX_UNPACKED_BUNDLE_SIZE: u64 = 1024 * 1024 * 1024;
+
+// Export Action
+#[derive(Debug)]
+pub struct ExportDomain<S = ActionState>  {
+    pub domain_name: String,
+    pub include_data: bool,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ExportDomain<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    /// The roles come along as well, so only admins can do this
+    pub fn new(domain_name: String, include_data: bool) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            domain_name,
+            include_data,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+fn export_entity_tags<T, S>(state: &S, entities: &Vec<T>) -> Result<Vec<EntityTags>, Error>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    let mut entity_tags = vec![];
+    for entity in entities {
+        let entity_id = state
+            .get_entity_retreiver_functions()
+            .get_entity_id::<T>(entity.my_name())
+            .map_err(Error::Entity)?
+            .ok_or_else(|| Error::NotFound)?;
+
+        let tags = state
+            .get_tag_management()
+            .get_entity_tags(entity_id)
+            .map_err(Error::TagManagement)?;
+
+        if !tags.is_empty() {
+            entity_tags.push(EntityTags {
+                type_name: T::TYPE_NAME.to_string(),
+                name: entity.my_name().to_owned(),
+                tags: tags.into_iter().map(|tag| tag.name).collect(),
+            });
+        }
+    }
+
+    Ok(entity_tags)
+}
+
+/// The entity that the permission is about, as the type name and the entity name
+fn permission_entity(permission: &Permission) -> Option<(String, String)> {
+    match permission {
+        Permission::GetEntity { type_name, entity_name } | Permission::ModifyEntity { type_name, entity_name } =>
+            Some((type_name.to_owned(), entity_name.to_owned())),
+        Permission::GetTableData { table_name } | Permission::ModifyTableData { table_name } =>
+            Some((data::DataStoreEntity::TYPE_NAME.to_string(), table_name.to_owned())),
+        Permission::RunQuery { query_name } =>
+            Some((data::DataQueryEntity::TYPE_NAME.to_string(), query_name.to_owned())),
+        Permission::RunScript { script_name } =>
+            Some((data::Script::TYPE_NAME.to_string(), script_name.to_owned())),
+        _ => None,
+    }
+}
+
+fn entity_names<T>(entities: &Vec<T>) -> Vec<(String, String)>
+    where
+        T: RawEntityTypes,
+{
+    entities
+        .iter()
+        .map(|entity| (T::TYPE_NAME.to_string(), entity.my_name().to_owned()))
+        .collect()
+}
+
+/// The rows of a retrieved table as objects, so that they can be inserted again
+fn to_rows(dataset: &serde_json::Value) -> Vec<serde_json::Value> {
+    let columns: Vec<&str> = dataset
+        .pointer("/columns/values")
+        .and_then(|columns| columns.as_array())
+        .map(|columns| columns.iter().filter_map(|column| column.as_str()).collect())
+        .unwrap_or_default();
+
+    dataset
+        .get("data")
+        .and_then(|rows| rows.as_array())
+        .map(|rows| rows
+            .iter()
+            .filter_map(|row| row.get("values").and_then(|values| values.as_array()))
+            .map(|values| {
+                let row: serde_json::Map<String, serde_json::Value> = columns
+                    .iter()
+                    .zip(values)
+                    .map(|(column, value)| (column.to_string(), value.to_owned()))
+                    .collect();
+                serde_json::Value::Object(row)
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+impl<S> Action<S> for ExportDomain<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ExportDomainResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ExportDomain");
+
+        let retriever = state.get_entity_retreiver_functions();
+        let tables: Vec<data::DataStoreEntity> = retriever.get_all().map_err(Error::Entity)?;
+        let queries: Vec<data::DataQueryEntity> = retriever.get_all().map_err(Error::Entity)?;
+        let scripts: Vec<data::Script> = retriever.get_all().map_err(Error::Entity)?;
+        let views: Vec<data::View> = retriever.get_all().map_err(Error::Entity)?;
+
+        let mut entity_tags = vec![];
+        entity_tags.extend(export_entity_tags(state, &tables)?);
+        entity_tags.extend(export_entity_tags(state, &queries)?);
+        entity_tags.extend(export_entity_tags(state, &scripts)?);
+        entity_tags.extend(export_entity_tags(state, &views)?);
+
+        let tags = state
+            .get_tag_management()
+            .get_all_tags()
+            .map_err(Error::TagManagement)?;
+
+        let mut exported_entities = BTreeSet::new();
+        exported_entities.extend(entity_names(&tables));
+        exported_entities.extend(entity_names(&queries));
+        exported_entities.extend(entity_names(&scripts));
+        exported_entities.extend(entity_names(&views));
+
+        // the permissions are read as they are now, the ones left behind by deleted
+        // entities, or entities of other domains, would be granted on whatever is created under that name
+        let user_management = state.get_user_management();
+        let mut roles = vec![];
+        for role in user_management.get_all_roles().map_err(Error::UserManagement)? {
+            let permissions = user_management
+                .get_role_permissions(&role.name)
+                .map_err(Error::UserManagement)?
+                .into_iter()
+                .filter(|permission| match permission_entity(permission) {
+                    Some(entity) => exported_entities.contains(&entity),
+                    None => true,
+                })
+                .collect();
+            roles.push(RoleBundle {
+                name: role.name,
+                description: role.description,
+                permissions,
+            });
+        }
+
+        let mut table_data = BTreeMap::new();
+        if self.include_data {
+            for table in &tables {
+                let dataset = state
+                    .get_table_controller()
+                    .query(table, &json!({}))
+                    .map_err(Error::Datastore)?;
+                table_data.insert(table.name.to_owned(), to_rows(&dataset));
+            }
+        }
+
+        let bundle = Bundle {
+            domain: self.domain_name.to_owned(),
+            tables,
+            queries,
+            scripts,
+            views,
+            tags,
+            entity_tags,
+            roles,
+            table_data,
+        };
+
+        let files = bundle.to_files().map_err(Error::Bundle)?;
+        let archive = pack_archive(&files).map_err(Error::Script)?;
+
+        ActionRes::new("exportDomain", ExportDomainResult {
+            version: BUNDLE_VERSION,
+            archive: base64::encode(&archive),
+        })
+    }
+}
+
+// Import Action
+#[derive(Debug)]
+pub struct ImportDomain<S = ActionState>  {
+    pub archive: String,
+    pub dry_run: bool,
+    pub on_conflict: OnConflict,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ImportDomain<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    /// `archive` is a base64 encoded gzipped tarball from `ExportDomain`
+    /// with `dry_run`, nothing is changed and the result is what would have happened
+    pub fn new(archive: String, dry_run: bool, on_conflict: OnConflict) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            archive,
+            dry_run,
+            on_conflict,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+/// The first name that isn't taken yet, i.e. `name_1`, `name_2`, ...
+fn free_name<F>(name: &str, is_taken: F) -> Result<String, Error>
+    where
+        F: Fn(&str) -> Result<bool, Error>,
+{
+    let mut index = 1;
+    loop {
+        let candidate = format!("{}_{}", name, index);
+        if !is_taken(&candidate)? {
+            return Ok(candidate);
+        }
+        index += 1;
+    }
+}
+
+/// Where the entity from the bundle ended up, if it was imported
+fn imported_name(steps: &[ImportStep], type_name: &str, name: &str) -> Option<String> {
+    steps
+        .iter()
+        .find(|step| step.type_name == type_name && step.name == name)
+        .and_then(|step| match &step.action {
+            ImportAction::Create | ImportAction::Overwrite => Some(step.name.to_owned()),
+            ImportAction::Rename { new_name } => Some(new_name.to_owned()),
+            _ => None,
+        })
+}
+
+/// Permissions follow the entities they are about when those are renamed
+fn imported_permission(steps: &[ImportStep], permission: &Permission) -> Permission {
+    let renamed = |type_name: &str, name: &String| imported_name(steps, type_name, name)
+        .unwrap_or_else(|| name.to_owned());
+
+    match permission {
+        Permission::GetEntity { type_name, entity_name } => Permission::GetEntity {
+            type_name: type_name.to_owned(),
+            entity_name: renamed(type_name, entity_name),
+        },
+        Permission::ModifyEntity { type_name, entity_name } => Permission::ModifyEntity {
+            type_name: type_name.to_owned(),
+            entity_name: renamed(type_name, entity_name),
+        },
+        Permission::GetTableData { table_name } =>
+            Permission::get_table_data(renamed(data::DataStoreEntity::TYPE_NAME, table_name)),
+        Permission::ModifyTableData { table_name } =>
+            Permission::modify_table_data(renamed(data::DataStoreEntity::TYPE_NAME, table_name)),
+        Permission::RunQuery { query_name } =>
+            Permission::run_query(renamed(data::DataQueryEntity::TYPE_NAME, query_name)),
+        Permission::RunScript { script_name } =>
+            Permission::run_script(renamed(data::Script::TYPE_NAME, script_name)),
+        _ => permission.to_owned(),
+    }
+}
+
+/// Creation goes through the modifier functions, so that the tables, script files, etc. are created as well
+fn import_entities<T, S>(state: &S, entities: Vec<T>, on_conflict: &OnConflict, dry_run: bool, steps: &mut Vec<ImportStep>) -> Result<(), Error>
+    where
+        T: RawEntityTypes + UpdateActionFunctions + Rename,
+        for<'a> S: StateFunctions<'a>,
+{
+    let is_taken = |name: &str| state
+        .get_entity_retreiver_functions()
+        .get_one::<T>(name)
+        .map(|entity: Option<T>| entity.is_some())
+        .map_err(Error::Entity);
+
+    for entity in entities {
+        let name = entity.my_name().to_owned();
+        let action = match (is_taken(&name)?, on_conflict) {
+            (false, _) => ImportAction::Create,
+            (true, OnConflict::Skip) => ImportAction::Skip,
+            (true, OnConflict::Overwrite) => ImportAction::Overwrite,
+            (true, OnConflict::Rename) => ImportAction::Rename { new_name: free_name(&name, &is_taken)? },
+        };
+
+        if !dry_run {
+            let modifier = state.get_entity_modifier_function();
+            let created = match &action {
+                ImportAction::Create => Some(modifier.create(entity).map_err(Error::Entity)?),
+                ImportAction::Rename { new_name } => Some(modifier.create(entity.renamed(new_name)).map_err(Error::Entity)?),
+                ImportAction::Overwrite => match modifier.update((&name, entity)).map_err(Error::Entity)? {
+                    Updated::Success { .. } => None,
+                    Updated::Fail => return Err(Error::NotFound),
+                },
+                _ => None,
+            };
+
+            if let Some(Created::Fail { .. }) = created {
+                return Err(Error::AlreadyExists);
+            }
+        }
+
+        steps.push(ImportStep {
+            type_name: T::TYPE_NAME.to_string(),
+            name,
+            action,
+        });
+    }
+
+    Ok(())
+}
+
+/// Rows only go into tables that were created by the import, existing data is left alone
+fn import_table_data<S>(state: &S, table_data: BTreeMap<String, Vec<serde_json::Value>>, dry_run: bool, steps: &mut Vec<ImportStep>) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    for (table_name, rows) in table_data {
+        let created_name = steps
+            .iter()
+            .find(|step| step.type_name == data::DataStoreEntity::TYPE_NAME && step.name == table_name)
+            .and_then(|step| match &step.action {
+                ImportAction::Create => Some(table_name.to_owned()),
+                ImportAction::Rename { new_name } => Some(new_name.to_owned()),
+                _ => None,
+            });
+
+        let created_name = match created_name {
+            Some(created_name) => created_name,
+            None => continue,
+        };
+
+        if rows.is_empty() {
+            continue;
+        }
+
+        if !dry_run {
+            let table: data::DataStoreEntity = state
+                .get_entity_retreiver_functions()
+                .get_one(&created_name)
+                .map_err(Error::Entity)?
+                .ok_or_else(|| Error::NotFound)?;
+
+            state
+                .get_table_controller()
+                .insert_row(&table, &serde_json::Value::Array(rows.to_owned()), true)
+                .map_err(Error::Datastore)?;
+        }
+
+        steps.push(ImportStep {
+            type_name: data::DataStoreEntity::TYPE_NAME.to_string(),
+            name: table_name,
+            action: ImportAction::InsertRows { row_count: rows.len() },
+        });
+    }
+
+    Ok(())
+}
+
+fn import_tags<S>(state: &S, bundle: &Bundle, dry_run: bool, steps: &mut Vec<ImportStep>) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let tag_management = state.get_tag_management();
+    let existing_tags = tag_management
+        .get_all_tags()
+        .map_err(Error::TagManagement)?;
+
+    for tag in &bundle.tags {
+        let action = if existing_tags.iter().any(|existing| existing.name == tag.name) {
+            ImportAction::Skip
+        } else {
+            if !dry_run {
+                tag_management.create_tag(tag).map_err(Error::TagManagement)?;
+            }
+            ImportAction::Create
+        };
+
+        steps.push(ImportStep {
+            type_name: "tag".to_string(),
+            name: tag.name.to_owned(),
+            action,
+        });
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    for entity_tags in &bundle.entity_tags {
+        let name = match imported_name(steps, &entity_tags.type_name, &entity_tags.name) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let entity_id = get_entity_id(state, &entity_tags.type_name, &name)?;
+
+        for tag in &entity_tags.tags {
+            match tag_management.attach_tag(entity_id, tag) {
+                Ok(_) | Err(TagManagementError::AlreadyExists) => (),
+                Err(err) => return Err(Error::TagManagement(err)),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn import_roles<S>(state: &S, roles: Vec<RoleBundle>, on_conflict: &OnConflict, dry_run: bool, steps: &mut Vec<ImportStep>) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let user_management = state.get_user_management();
+    let existing_roles = user_management
+        .get_all_roles()
+        .map_err(Error::UserManagement)?;
+    let is_taken = |name: &str| -> Result<bool, Error> {
+        Ok(existing_roles.iter().any(|role| role.name == name))
+    };
+
+    for role in roles {
+        let action = match (is_taken(&role.name)?, on_conflict) {
+            (false, _) => ImportAction::Create,
+            (true, OnConflict::Skip) => ImportAction::Skip,
+            (true, OnConflict::Overwrite) => ImportAction::Overwrite,
+            (true, OnConflict::Rename) => ImportAction::Rename { new_name: free_name(&role.name, &is_taken)? },
+        };
+
+        let rolename = match &action {
+            ImportAction::Create | ImportAction::Overwrite => Some(role.name.to_owned()),
+            ImportAction::Rename { new_name } => Some(new_name.to_owned()),
+            _ => None,
+        };
+
+        if let (false, Some(rolename)) = (dry_run, rolename) {
+            if let ImportAction::Overwrite = action {
+                let old_permissions = user_management
+                    .get_role_permissions(&rolename)
+                    .map_err(Error::UserManagement)?;
+                for permission in old_permissions {
+                    user_management
+                        .detach_permission_for_role(&permission, &rolename)
+                        .map_err(Error::UserManagement)?;
+                }
+            } else {
+                user_management
+                    .add_role(&Role { name: rolename.to_owned(), description: role.description.to_owned() })
+                    .map_err(Error::UserManagement)?;
+            }
+
+            for permission in &role.permissions {
+                let permission = imported_permission(steps, permission);
+                match user_management.add_permission(&permission) {
+                    Ok(_) | Err(UserManagementError::AlreadyExists) => (),
+                    Err(err) => return Err(Error::UserManagement(err)),
+                }
+                user_management
+                    .attach_permission_for_role(&permission, &rolename)
+                    .map_err(Error::UserManagement)?;
+            }
+        }
+
+        steps.push(ImportStep {
+            type_name: "role".to_string(),
+            name: role.name,
+            action,
+        });
+    }
+
+    Ok(())
+}
+
+impl<S> Action<S> for ImportDomain<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ImportDomainResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ImportDomain");
+
+        let archive = base64::decode(&self.archive)
+            .map_err(|err| Error::SerializationError(err.to_string()))?;
+        let files = unpack_archive(&archive, MAX_BUNDLE_SIZE, MAX_UNPACKED_BUNDLE_SIZE)
+            .map_err(Error::Script)?;
+        let bundle = Bundle::from_files(&files).map_err(Error::Bundle)?;
+
+        // checked up front, so that a dry run shows it as well
+        for script in &bundle.scripts {
+            state
+                .get_script_runner()
+                .check_sandbox(&script.sandbox)
+                .map_err(Error::Script)?;
+        }
+
+        let mut steps = vec![];
+        import_entities(state, bundle.tables.to_owned(), &self.on_conflict, self.dry_run, &mut steps)?;
+        import_entities(state, bundle.queries.to_owned(), &self.on_conflict, self.dry_run, &mut steps)?;
+        import_entities(state, bundle.scripts.to_owned(), &self.on_conflict, self.dry_run, &mut steps)?;
+        import_entities(state, bundle.views.to_owned(), &self.on_conflict, self.dry_run, &mut steps)?;
+        import_table_data(state, bundle.table_data.to_owned(), self.dry_run, &mut steps)?;
+        import_tags(state, &bundle, self.dry_run, &mut steps)?;
+        import_roles(state, bundle.roles.to_owned(), &self.on_conflict, self.dry_run, &mut steps)?;
+
+        ActionRes::new("importDomain", ImportDomainResult {
+            dry_run: self.dry_run,
+            steps,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+    use model::actions::entity_actions::*;
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+    use scripting::error::ScriptError;
+
+    #[test]
+    fn test_export_and_import_domain() {
+        with_state(|state| {
+            let name = format!("my_query_{}", random_identifier());
+            let new_query: data::DataQueryEntity = from_value(json!({
+                "name": name,
+                "description": "exported query",
+                "statement": "SELECT * FROM a_table"
+            })).unwrap();
+            CreateEntity::<data::DataQueryEntity, MockState>::new(new_query).call(&state).unwrap();
+
+            let exported = ExportDomain::<MockState>::new("test".to_string(), false)
+                .call(&state)
+                .unwrap()
+                .get_data();
+            assert_eq!(exported.version, BUNDLE_VERSION);
+
+            let result = ImportDomain::<MockState>::new(exported.archive.to_owned(), true, OnConflict::Rename)
+                .call(&state)
+                .unwrap()
+                .get_data();
+            assert!(result.dry_run);
+            let step = result.steps
+                .iter()
+                .find(|step| step.type_name == "query" && step.name == name)
+                .unwrap();
+            assert_eq!(step.action, ImportAction::Rename { new_name: format!("{}_1", name) });
+
+            // nothing was created in the dry run
+            let renamed: Option<data::DataQueryEntity> = state
+                .get_entity_retreiver_functions()
+                .get_one(&format!("{}_1", name))
+                .unwrap();
+            assert_eq!(renamed, None);
+
+            let result = ImportDomain::<MockState>::new(exported.archive, false, OnConflict::Rename)
+                .call(&state)
+                .unwrap()
+                .get_data();
+            assert!(!result.dry_run);
+            let renamed: Option<data::DataQueryEntity> = state
+                .get_entity_retreiver_functions()
+                .get_one(&format!("{}_1", name))
+                .unwrap();
+            assert_eq!(renamed.map(|query| query.statement), Some("SELECT * FROM a_table".to_string()));
+        });
+    }
+
+    #[test]
+    fn test_import_checks_sandbox_paths() {
+        with_state(|state| {
+            let script: data::Script = from_value(json!({
+                "name": format!("my_script_{}", random_identifier()),
+                "description": "mounts everything",
+                "text": "print('Hello World')",
+                "sandbox": { "readWritePaths": ["/"] }
+            })).unwrap();
+            let bundle = Bundle {
+                domain: "test".to_string(),
+                scripts: vec![script],
+                ..Bundle::default()
+            };
+            let archive = pack_archive(&bundle.to_files().unwrap()).unwrap();
+
+            let result = ImportDomain::<MockState>::new(base64::encode(&archive), true, OnConflict::Skip).call(&state);
+            assert_eq!(result.err(), Some(Error::Script(ScriptError::InvalidPath("/".to_string()))));
+        });
+    }
+}
@@ -3,20 +3,29 @@ use std::result::Result::Ok;
 use std::marker::PhantomData;
 use std::fmt;
 use std::collections::HashSet;
+use std::time::Instant;
 
 use data::channels::Channels;
 use data::permissions::*;
+use data::UsageRecord;
 
 use model::actions::error::Error;
 use model::actions::Action;
 use model::actions::ActionResult;
 use model::actions::OkAction;
+use model::actions::ActionRes;
+use model::actions::results::ScriptModifiedResult;
+use model::entity::RetrieverFunctions;
+use model::entity::RawEntityTypes;
 
 use state::StateFunctions;
 use state::authorization::AuthorizationOps;
 use state::PubSubOps;
+use state::usage_tracking::UsageTrackingOps;
 use state::ActionState;
 
+use scripting::ScriptFunctions;
+
 #[derive(Debug, Clone)]
 enum Requirements {
     AllOf(Vec<Permission>),
@@ -353,3 +362,160 @@ impl<A, S> Action<S> for WithDispatch<A, S>
         Ok(result)
     }
 }
+
+///decorator for recording the usage of an entity
+/// the usage is only recorded if the action succeeded, failing to record it doesn't fail the action
+#[derive(Clone)]
+pub struct WithUsageRecorded<A, T, S = ActionState>
+    where
+        A: Action<S>,
+        T: RawEntityTypes,
+{
+    action: A,
+    entity_name: String,
+    phantom_data: PhantomData<(T, S)>,
+}
+
+impl<A, T, S> fmt::Debug for WithUsageRecorded<A, T, S>
+    where
+        A: Action<S>,
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WithUsageRecorded({:?})", &self.action)
+    }
+}
+
+impl<A, T, S> WithUsageRecorded<A, T, S>
+    where
+        A: Action<S>,
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(action: A, entity_name: String) -> Self {
+        Self {
+            action,
+            entity_name,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+/// Number of rows in a result, if the result looks like table data
+fn row_count(value: &serde_json::Value) -> Option<i64> {
+    match value {
+        serde_json::Value::Array(rows) => Some(rows.len() as i64),
+        serde_json::Value::Object(object) => object
+            .get("data")
+            .and_then(|data| data.as_array())
+            .map(|rows| rows.len() as i64),
+        _ => None,
+    }
+}
+
+impl<A, T, S> Action<S> for WithUsageRecorded<A, T, S>
+    where
+        A: Action<S>,
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = A::Ret;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let start = Instant::now();
+        let result = self.action.call(state)?;
+        let elapsed = start.elapsed();
+        let latency_ms = elapsed.as_secs() as i64 * 1000 + elapsed.subsec_millis() as i64;
+
+        let user_id = match state.get_authorization().user_id() {
+            Some(user_id) => user_id,
+            None => return Ok(result),
+        };
+
+        let entity_id = match state.get_entity_retreiver_functions().get_entity_id::<T>(&self.entity_name) {
+            Ok(Some(entity_id)) => entity_id,
+            Ok(None) => return Ok(result),
+            Err(err) => {
+                warn!("could not find the entity {:?} to record the usage: {:?}", &self.entity_name, err);
+                return Ok(result);
+            },
+        };
+
+        let record = UsageRecord {
+            entity_id,
+            user_id,
+            action: result.get_name(),
+            latency_ms: Some(latency_ms),
+            row_count: serde_json::to_value(result.get_data_ref())
+                .ok()
+                .and_then(|value| row_count(&value)),
+        };
+
+        let _ = state
+            .get_usage_tracking()
+            .record_usage(&record)
+            .map_err(|err| warn!("could not record the usage of {:?}: {:?}", &self.entity_name, err));
+
+        Ok(result)
+    }
+}
+
+/// Adds the install log of the script's environment to the result, the build log would be lost otherwise when it succeeds.
+/// The log is only there if the action rebuilt the environment
+pub struct WithInstallLog<A, S = ActionState>
+    where
+        A: Action<S>,
+{
+    action: A,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<A, S> fmt::Debug for WithInstallLog<A, S>
+    where
+        A: Action<S>,
+        for<'a> S: StateFunctions<'a>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WithInstallLog({:?})", &self.action)
+    }
+}
+
+impl<A, S> WithInstallLog<A, S>
+    where
+        A: Action<S>,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(action: A) -> Self {
+        Self {
+            action,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<A, S> Action<S> for WithInstallLog<A, S>
+    where
+        A: Action<S>,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ScriptModifiedResult<A::Ret>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let result = self.action.call(state)?;
+        let install_log = state.take_install_log();
+
+        let name = result.get_name();
+        ActionRes::new(&name, ScriptModifiedResult { result: result.get_data(), install_log })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_row_count() {
+        assert_eq!(row_count(&json!([{"a": 1}, {"a": 2}])), Some(2));
+        assert_eq!(row_count(&json!({"columns": ["a"], "data": [[1], [2], [3]]})), Some(3));
+        assert_eq!(row_count(&json!({"stdout": ""})), None);
+    }
+}
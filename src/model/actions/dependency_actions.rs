@@ -0,0 +1,291 @@
+use std::result::Result::Ok;
+use std::marker::PhantomData;
+
+use data;
+use data::Named;
+use data::dependencies::Dependent;
+use data::dependencies::DependencyKind;
+use data::dependencies::referenced_tables;
+use data::permissions::Permission;
+use data::utils::DependentsPolicy;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+use model::entity::RetrieverFunctions;
+use model::entity::ModifierFunctions;
+use model::entity::RawEntityTypes;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::authorization::AuthorizationOps;
+
+/// Tables, queries and views that would break if the entity was deleted or renamed
+pub(super) fn get_dependents<S>(state: &S, type_name: &str, name: &str) -> Result<Vec<Dependent>, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let retriever = state.get_entity_retreiver_functions();
+    let dependent = |type_name: &str, dependent_name: &str, kind: DependencyKind| Dependent {
+        type_name: type_name.to_owned(),
+        name: dependent_name.to_owned(),
+        kind,
+    };
+    let mut dependents = vec![];
+
+    if type_name == data::DataStoreEntity::TYPE_NAME {
+        let tables: Vec<data::DataStoreEntity> = retriever.get_all().map_err(Error::Entity)?;
+        for table in tables {
+            if table.my_name() != name && table.get_referenced_tables().iter().any(|x| x == name) {
+                dependents.push(dependent(data::DataStoreEntity::TYPE_NAME, table.my_name(), DependencyKind::ForeignKey));
+            }
+        }
+
+        let queries: Vec<data::DataQueryEntity> = retriever.get_all().map_err(Error::Entity)?;
+        for query in queries {
+            if referenced_tables(&query.statement).iter().any(|x| x == name) {
+                dependents.push(dependent(data::DataQueryEntity::TYPE_NAME, query.my_name(), DependencyKind::QueryStatement));
+            }
+        }
+    }
+
+    if type_name == data::DataStoreEntity::TYPE_NAME || type_name == data::DataQueryEntity::TYPE_NAME {
+        let views: Vec<data::View> = retriever.get_all().map_err(Error::Entity)?;
+        for view in views {
+            if view.get_source_entity() == Some((type_name, name)) {
+                dependents.push(dependent(data::View::TYPE_NAME, view.my_name(), DependencyKind::ViewSource));
+            }
+        }
+    }
+
+    Ok(dependents)
+}
+
+/// Cascading touches entities that weren't named in the request, so their permissions are checked as well
+fn check_can_modify<S>(state: &S, dependent: &Dependent) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let authorization = state.get_authorization();
+    let permission = Permission::ModifyEntity {
+        type_name: dependent.type_name.to_owned(),
+        entity_name: dependent.name.to_owned(),
+    };
+
+    if authorization.is_admin() || authorization.permissions().contains(&permission) {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized)
+    }
+}
+
+fn delete_dependent<S>(state: &S, dependent: &Dependent) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let modifier = state.get_entity_modifier_function();
+    match dependent.type_name.as_str() {
+        x if x == data::DataStoreEntity::TYPE_NAME => modifier.delete::<data::DataStoreEntity>(&dependent.name).map(|_| ()),
+        x if x == data::DataQueryEntity::TYPE_NAME => modifier.delete::<data::DataQueryEntity>(&dependent.name).map(|_| ()),
+        x if x == data::View::TYPE_NAME => modifier.delete::<data::View>(&dependent.name).map(|_| ()),
+        _ => return Err(Error::NotFound),
+    }.map_err(Error::Entity)
+}
+
+/// Deletes the dependents before the entities they depend on, `visited` keeps cycles of foreign keys from looping
+fn cascade_delete<S>(state: &S, dependents: Vec<Dependent>, visited: &mut Vec<(String, String)>) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    for dependent in dependents {
+        let key = (dependent.type_name.to_owned(), dependent.name.to_owned());
+        if visited.contains(&key) {
+            continue;
+        }
+        visited.push(key);
+
+        check_can_modify(state, &dependent)?;
+        let next_dependents = get_dependents(state, &dependent.type_name, &dependent.name)?;
+        cascade_delete(state, next_dependents, visited)?;
+        delete_dependent(state, &dependent)?;
+    }
+
+    Ok(())
+}
+
+/// Called before the entity is deleted
+pub(super) fn delete_dependents<S>(state: &S, type_name: &str, name: &str, policy: &DependentsPolicy) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let dependents = get_dependents(state, type_name, name)?;
+    if dependents.is_empty() {
+        return Ok(());
+    }
+
+    match policy {
+        DependentsPolicy::Restrict => Err(Error::HasDependents(dependents)),
+        DependentsPolicy::Cascade => {
+            let mut visited = vec![(type_name.to_owned(), name.to_owned())];
+            cascade_delete(state, dependents, &mut visited)
+        },
+    }
+}
+
+fn rename_reference<S>(state: &S, dependent: &Dependent, type_name: &str, old_name: &str, new_name: &str) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let retriever = state.get_entity_retreiver_functions();
+    let modifier = state.get_entity_modifier_function();
+
+    match dependent.kind {
+        DependencyKind::ForeignKey => {
+            let table: Option<data::DataStoreEntity> = retriever.get_one(&dependent.name).map_err(Error::Entity)?;
+            if let Some(table) = table {
+                modifier.update((&dependent.name, table.with_renamed_reference(old_name, new_name))).map_err(Error::Entity)?;
+            }
+        },
+        DependencyKind::QueryStatement => {
+            let query: Option<data::DataQueryEntity> = retriever.get_one(&dependent.name).map_err(Error::Entity)?;
+            if let Some(query) = query {
+                modifier.update((&dependent.name, query.with_renamed_table(old_name, new_name))).map_err(Error::Entity)?;
+            }
+        },
+        DependencyKind::ViewSource => {
+            let view: Option<data::View> = retriever.get_one(&dependent.name).map_err(Error::Entity)?;
+            if let Some(view) = view {
+                modifier.update((&dependent.name, view.with_renamed_source(type_name, old_name, new_name))).map_err(Error::Entity)?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Called before the entity is renamed, the dependents are pointed to the new name
+pub(super) fn rename_dependents<S>(state: &S, type_name: &str, old_name: &str, new_name: &str, policy: &DependentsPolicy) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let dependents = get_dependents(state, type_name, old_name)?;
+    if dependents.is_empty() {
+        return Ok(());
+    }
+
+    match policy {
+        DependentsPolicy::Restrict => Err(Error::HasDependents(dependents)),
+        DependentsPolicy::Cascade => rename_references(state, type_name, old_name, new_name, true),
+    }
+}
+
+/// Views and foreign keys always follow the renamed entity, query statements only if `rewrite_queries` is set
+pub(super) fn rename_references<S>(state: &S, type_name: &str, old_name: &str, new_name: &str, rewrite_queries: bool) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    for dependent in get_dependents(state, type_name, old_name)? {
+        if dependent.kind == DependencyKind::QueryStatement && !rewrite_queries {
+            continue;
+        }
+
+        check_can_modify(state, &dependent)?;
+        rename_reference(state, &dependent, type_name, old_name, new_name)?;
+    }
+
+    Ok(())
+}
+
+// Dependency Actions
+#[derive(Debug)]
+pub struct GetDependents<S = ActionState>  {
+    pub type_name: String,
+    pub name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetDependents<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(type_name: String, name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let permission = Permission::GetEntity {
+            type_name: type_name.to_owned(),
+            entity_name: name.to_owned(),
+        };
+        let action = Self {
+            type_name,
+            name,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithPermissionRequired::new(action_with_transaction, permission);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetDependents<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = DependentsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetDependents");
+
+        get_dependents(state, &self.type_name, &self.name)
+            .and_then(|res| ActionRes::new("getDependents", DependentsResult(res)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+    use model::actions::CreateEntity;
+    use model::actions::DeleteEntity;
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+
+    #[test]
+    fn test_delete_query_with_dependents() {
+        with_state(|state| {
+            let query_name = format!("my_query_{}", random_identifier());
+            let view_name = format!("my_view_{}", random_identifier());
+            let query: data::DataQueryEntity = from_value(json!({
+                "name": query_name,
+                "description": "",
+                "statement": "SELECT * FROM a_table"
+            })).unwrap();
+            let view: data::View = from_value(json!({
+                "name": view_name,
+                "description": "",
+                "viewState": {},
+                "source": { "type": "query", "name": query_name }
+            })).unwrap();
+            CreateEntity::<data::DataQueryEntity, MockState>::new(query).call(&state).unwrap();
+            CreateEntity::<data::View, MockState>::new(view).call(&state).unwrap();
+
+            let result = GetDependents::<MockState>::new("query".to_string(), query_name.to_owned()).call(&state);
+            let DependentsResult(dependents) = result.unwrap().get_data();
+            assert_eq!(dependents, vec![Dependent {
+                type_name: "view".to_string(),
+                name: view_name.to_owned(),
+                kind: DependencyKind::ViewSource,
+            }]);
+
+            let result = DeleteEntity::<data::DataQueryEntity, MockState>::new(query_name.to_owned(), DependentsPolicy::Restrict).call(&state);
+            assert_eq!(result.unwrap_err(), Error::HasDependents(dependents));
+
+            DeleteEntity::<data::DataQueryEntity, MockState>::new(query_name.to_owned(), DependentsPolicy::Cascade).call(&state).unwrap();
+            let view: Option<data::View> = state.get_entity_retreiver_functions().get_one(&view_name).unwrap();
+            assert!(view.is_none());
+        });
+    }
+}
@@ -4,7 +4,11 @@ use std::marker::PhantomData;
 use data::utils::OnDuplicate;
 
 use data::utils::OnNotFound;
+use data::utils::DependentsPolicy;
+use data;
 use data::Named;
+use data::Rename;
+use data::EntityFilter;
 use data::channels::Channels;
 use data::permissions::*;
 
@@ -16,6 +20,9 @@ use model::actions::decorator::*;
 use model::actions::Action;
 use model::actions::ActionRes;
 use model::actions::ActionResult;
+use model::actions::dependency_actions::delete_dependents;
+use model::actions::dependency_actions::rename_dependents;
+use model::actions::dependency_actions::rename_references;
 
 use model::entity::RetrieverFunctions;
 use model::entity::ModifierFunctions;
@@ -29,6 +36,7 @@ use model::entity::update_state::UpdateActionFunctions;
 use state::StateFunctions;
 use state::ActionState;
 use state::authorization::AuthorizationOps;
+use state::PubSubOps;
 
 ///decorator for permission in listing items
 /// Only defined for GetAllEntities
@@ -105,6 +113,7 @@ pub struct GetAllEntities<T, S = ActionState>
         T: RawEntityTypes,
 {
     pub show_deleted: bool,
+    pub filter: EntityFilter,
     pub phantom_data: PhantomData<(T, S)>,
 }
 
@@ -113,9 +122,10 @@ impl<T, S> GetAllEntities<T, S>
         T: RawEntityTypes,
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(show_deleted: bool) -> WithFilterListByPermission<WithTransaction<Self, S>, T, S> {
+    pub fn new(show_deleted: bool, filter: EntityFilter) -> WithFilterListByPermission<WithTransaction<Self, S>, T, S> {
         let action = Self {
             show_deleted,
+            filter,
             phantom_data: PhantomData,
         };
 
@@ -135,7 +145,7 @@ impl<T, S> Action<S> for GetAllEntities<T, S>
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
         let entities: Vec<T> = state
             .get_entity_retreiver_functions()
-            .get_all()
+            .get_all_filtered(&self.filter)
             .or_else(|err| Err(Error::Entity(err)))?;
 
         let action_name =  format!("getAll{}", T::TYPE_NAME_PLURAL.to_pascal_case());
@@ -312,6 +322,7 @@ pub struct UpdateEntity<T, S = ActionState>
     pub name: String,
     pub data: T,
     pub on_not_found: OnNotFound,
+    pub on_dependents: DependentsPolicy,
     pub phantom_data: PhantomData<(S)>,
 }
 
@@ -320,12 +331,13 @@ impl<T, S> UpdateEntity<T, S>
         T: RawEntityTypes + UpdateActionFunctions,
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(name: String, data: T) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(name: String, data: T, on_dependents: DependentsPolicy) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
         let channel = Channels::entity::<T>(&name);
         let action = Self {
             name: name.to_owned(),
             data,
             on_not_found: OnNotFound::Ignore,
+            on_dependents,
             phantom_data: PhantomData,
         };
 
@@ -347,6 +359,10 @@ impl<T, S> Action<S> for UpdateEntity<T, S>
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
         let action_name =  format!("update{}", T::TYPE_NAME.to_pascal_case());
 
+        if self.data.my_name() != self.name {
+            rename_dependents(state, T::TYPE_NAME, &self.name, self.data.my_name(), &self.on_dependents)?;
+        }
+
         match &self.on_not_found {
             OnNotFound::Ignore => {
                 state
@@ -391,6 +407,7 @@ pub struct DeleteEntity<T, S = ActionState>
 {
     pub name: String,
     pub on_not_found: OnNotFound,
+    pub on_dependents: DependentsPolicy,
     pub phantom_data: PhantomData<(T, S)>,
 }
 
@@ -399,11 +416,12 @@ impl<T, S> DeleteEntity<T, S>
         T: RawEntityTypes + UpdateActionFunctions,
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(name: String) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(name: String, on_dependents: DependentsPolicy) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
         let channel = Channels::entity::<T>(&name);
         let action = Self {
             name: name.to_owned(),
             on_not_found: OnNotFound::Ignore,
+            on_dependents,
             phantom_data: PhantomData,
         };
 
@@ -425,6 +443,8 @@ impl<T, S> Action<S> for DeleteEntity<T, S>
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
         let action_name =  format!("delete{}", T::TYPE_NAME.to_pascal_case());
 
+        delete_dependents(state, T::TYPE_NAME, &self.name, &self.on_dependents)?;
+
         match &self.on_not_found {
             OnNotFound::Ignore => {
                 state
@@ -459,6 +479,89 @@ impl<T, S> Action<S> for DeleteEntity<T, S>
     }
 }
 
+///rename table, and everything that refers to it by name
+#[derive(Debug, Clone)]
+pub struct RenameEntity<T, S = ActionState>
+    where
+        T: RawEntityTypes + UpdateActionFunctions + Rename,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub name: String,
+    pub new_name: String,
+    pub rewrite_queries: bool,
+    pub phantom_data: PhantomData<(T, S)>,
+}
+
+impl<T, S> RenameEntity<T, S>
+    where
+        T: RawEntityTypes + UpdateActionFunctions + Rename,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(name: String, new_name: String, rewrite_queries: bool) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+        let channel = Channels::entity::<T>(&new_name);
+        let action = Self {
+            name: name.to_owned(),
+            new_name,
+            rewrite_queries,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_dispatch, Permission::modify_entity::<T>(name));
+
+        action_with_permission
+    }
+}
+
+impl<T, S> Action<S> for RenameEntity<T, S>
+    where
+        T: RawEntityTypes + UpdateActionFunctions + Rename,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = UpdateEntityResult<T>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let action_name =  format!("rename{}", T::TYPE_NAME.to_pascal_case());
+
+        let retriever = state.get_entity_retreiver_functions();
+        let existing: Option<T> = retriever.get_one(&self.new_name).map_err(Error::Entity)?;
+        if existing.is_some() {
+            return Err(Error::AlreadyExists);
+        }
+        let entity: T = retriever
+            .get_one(&self.name)
+            .map_err(Error::Entity)?
+            .ok_or_else(|| Error::NotFound)?;
+
+        rename_references(state, T::TYPE_NAME, &self.name, &self.new_name, self.rewrite_queries)?;
+
+        let pub_sub = state.get_pub_sub();
+        pub_sub
+            .rename_channel(Channels::entity::<T>(&self.name), Channels::entity::<T>(&self.new_name))
+            .map_err(Error::PublishError)?;
+        if T::TYPE_NAME == data::DataStoreEntity::TYPE_NAME {
+            pub_sub
+                .rename_channel(Channels::table(&self.name), Channels::table(&self.new_name))
+                .map_err(Error::PublishError)?;
+        }
+
+        // the datastore object, the script directory and the permissions are renamed when the entity is updated,
+        // this goes last since only the database changes are rolled back if something fails
+        let res = state
+            .get_entity_modifier_function()
+            .update((&self.name, entity.renamed(&self.new_name)))
+            .map_err(Error::Entity)?;
+        info!("rename result: {:?}", &res);
+
+        match res {
+            Updated::Success { old, new } =>
+                ActionRes::new(&action_name, UpdateEntityResult::Updated { id: self.new_name.to_owned(), old, new }),
+            Updated::Fail => Err(Error::NotFound),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -536,7 +639,7 @@ mod test {
             let result = create_action.call(&state);
             let data = result.unwrap().get_data();
 
-            let delete_action = DeleteEntity::<data::DataQueryEntity, MockState>::new(name.to_owned());
+            let delete_action = DeleteEntity::<data::DataQueryEntity, MockState>::new(name.to_owned(), DependentsPolicy::Restrict);
             let result = delete_action.call(&state);
             let data = result.unwrap().get_data();
 
@@ -550,4 +653,44 @@ mod test {
             }
         });
     }
+
+    #[test]
+    fn test_rename_entity() {
+        with_state(|state| {
+            let name = format!("my_query_{}", random_identifier());
+            let new_name = format!("my_renamed_query_{}", random_identifier());
+            let view_name = format!("my_view_{}", random_identifier());
+            let new_query: data::DataQueryEntity = from_value(json!({
+                "name": name,
+                "description": "blah blah blah",
+                "statement": "SELECT * FROM a_table"
+            })).unwrap();
+            let new_view: data::View = from_value(json!({
+                "name": view_name,
+                "description": "",
+                "viewState": {},
+                "source": { "type": "query", "name": name }
+            })).unwrap();
+            CreateEntity::<data::DataQueryEntity, MockState>::new(new_query).call(&state).unwrap();
+            CreateEntity::<data::View, MockState>::new(new_view).call(&state).unwrap();
+
+            let rename_action = RenameEntity::<data::DataQueryEntity, MockState>::new(name.to_owned(), new_name.to_owned(), false);
+            let data = rename_action.call(&state).unwrap().get_data();
+
+            if let UpdateEntityResult::Updated { id, old, new } = data {
+                assert_eq!(id, new_name);
+                assert_eq!(old.my_name(), name);
+                assert_eq!(new.my_name(), new_name);
+                assert_eq!(new.statement, "SELECT * FROM a_table");
+            } else {
+                panic!("expected an updated result");
+            }
+
+            let retriever = state.get_entity_retreiver_functions();
+            let old: Option<data::DataQueryEntity> = retriever.get_one(&name).unwrap();
+            assert!(old.is_none());
+            let view: data::View = retriever.get_one(&view_name).unwrap().unwrap();
+            assert_eq!(view.get_source_entity(), Some(("query", new_name.as_str())));
+        });
+    }
 }
\ No newline at end of file
@@ -1,11 +1,21 @@
 use model::entity::error::EntityError;
 use state::error::UserManagementError;
 use auth::send_mail::EmailError;
+use auth::oidc::OidcError;
+use auth::ldap::LdapError;
 
 use scripting::error::ScriptError;
 use state::error::BroadcastError;
 use data::error::DatastoreError;
 use state::error::DomainManagementError;
+use state::error::TagManagementError;
+use state::error::UsageTrackingError;
+use state::error::TableAuditError;
+use state::error::EntitySyncError;
+use data::error::BundleError;
+use data::dependencies::Dependent;
+
+use serde_json;
 
 #[derive(Debug, Fail, PartialEq, Eq)]
 pub enum Error {
@@ -14,12 +24,26 @@ pub enum Error {
     #[fail(display = "{}", 0)]
     DomainManagement(DomainManagementError),
     #[fail(display = "{}", 0)]
+    TagManagement(TagManagementError),
+    #[fail(display = "{}", 0)]
+    UsageTracking(UsageTrackingError),
+    #[fail(display = "{}", 0)]
+    TableAudit(TableAuditError),
+    #[fail(display = "{}", 0)]
+    Bundle(BundleError),
+    #[fail(display = "{}", 0)]
+    EntitySync(EntitySyncError),
+    #[fail(display = "{}", 0)]
     Datastore(DatastoreError),
     #[fail(display = "{}", 0)]
     Script(ScriptError),
     #[fail(display = "{}", 0)]
     EmailError(EmailError),
     #[fail(display = "{}", 0)]
+    Oidc(OidcError),
+    #[fail(display = "{}", 0)]
+    Ldap(LdapError),
+    #[fail(display = "{}", 0)]
     UserManagement(UserManagementError),
     #[fail(display = "Not authorized")]
     Unauthorized,
@@ -27,10 +51,22 @@ pub enum Error {
     NotFound,
     #[fail(display = "Already exists")]
     AlreadyExists,
+    #[fail(display = "Other entities depend on this: {:?}", 0)]
+    HasDependents(Vec<Dependent>),
     #[fail(display = "{}", 0)]
     SerializationError(String),
     #[fail(display = "{}", 0)]
     PublishError(BroadcastError),
     #[fail(display = "An unknown error occurred")]
     Unknown,
-}
\ No newline at end of file
+}
+
+impl Error {
+    /// More than the message, for errors that clients can act on
+    pub fn get_details(&self) -> Option<serde_json::Value> {
+        match self {
+            Error::UserManagement(UserManagementError::PasswordPolicy(violations)) => serde_json::to_value(violations).ok(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::marker::PhantomData;
+
+use data::channels::Channels;
+use data::permissions::*;
+use data::diff::diff;
+
+use inflector::Inflector;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+
+use model::entity::RetrieverFunctions;
+use model::entity::ModifierFunctions;
+use model::entity::RawEntityTypes;
+use model::entity::results::Revision;
+use model::entity::results::Created;
+use model::entity::results::Updated;
+use model::entity::update_state::UpdateActionFunctions;
+
+use state::StateFunctions;
+use state::ActionState;
+
+fn get_revision<T, S>(state: &S, name: &str, revision_id: i64) -> Result<Revision<T>, Error>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    state
+        .get_entity_retreiver_functions()
+        .get_revision::<T>(name, revision_id)
+        .map_err(Error::Entity)
+        .and_then(|res| match res {
+            Some(revision) => Ok(revision),
+            None => Err(Error::NotFound),
+        })
+}
+
+///get all the revisions of an entity
+#[derive(Debug, Clone)]
+pub struct GetEntityHistory<T, S = ActionState>
+    where
+        T: RawEntityTypes,
+{
+    pub name: String,
+    pub phantom_data: PhantomData<(T, S)>,
+}
+
+impl<T, S> GetEntityHistory<T, S>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            name: name.to_owned(),
+            phantom_data: PhantomData,
+        };
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<T>(name));
+
+        action_with_permission
+    }
+}
+
+impl<T, S> Action<S> for GetEntityHistory<T, S>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = GetEntityHistoryResult<T>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let history: Vec<Revision<T>> = state
+            .get_entity_retreiver_functions()
+            .get_history(&self.name)
+            .map_err(Error::Entity)?;
+
+        if history.is_empty() {
+            return Err(Error::NotFound);
+        }
+
+        let action_name = format!("get{}History", T::TYPE_NAME.to_pascal_case());
+        ActionRes::new(&action_name, GetEntityHistoryResult::<T>(history))
+    }
+}
+
+///get a single revision of an entity
+#[derive(Debug, Clone)]
+pub struct GetEntityRevision<T, S = ActionState>
+    where
+        T: RawEntityTypes,
+{
+    pub name: String,
+    pub revision_id: i64,
+    pub phantom_data: PhantomData<(T, S)>,
+}
+
+impl<T, S> GetEntityRevision<T, S>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(name: String, revision_id: i64) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            name: name.to_owned(),
+            revision_id,
+            phantom_data: PhantomData,
+        };
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<T>(name));
+
+        action_with_permission
+    }
+}
+
+impl<T, S> Action<S> for GetEntityRevision<T, S>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = GetEntityRevisionResult<T>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let revision = get_revision::<T, S>(state, &self.name, self.revision_id)?;
+
+        let action_name = format!("get{}Revision", T::TYPE_NAME.to_pascal_case());
+        ActionRes::new(&action_name, GetEntityRevisionResult::<T>(revision))
+    }
+}
+
+///structured diff between two revisions of an entity
+#[derive(Debug, Clone)]
+pub struct DiffEntityRevisions<T, S = ActionState>
+    where
+        T: RawEntityTypes,
+{
+    pub name: String,
+    pub from_revision: i64,
+    pub to_revision: i64,
+    pub phantom_data: PhantomData<(T, S)>,
+}
+
+impl<T, S> DiffEntityRevisions<T, S>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(name: String, from_revision: i64, to_revision: i64) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            name: name.to_owned(),
+            from_revision,
+            to_revision,
+            phantom_data: PhantomData,
+        };
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::read_entity::<T>(name));
+
+        action_with_permission
+    }
+}
+
+impl<T, S> Action<S> for DiffEntityRevisions<T, S>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = DiffEntityResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let from = get_revision::<T, S>(state, &self.name, self.from_revision)?;
+        let to = get_revision::<T, S>(state, &self.name, self.to_revision)?;
+
+        let from_value = serde_json::to_value(&from.data)
+            .map_err(|err| Error::SerializationError(err.to_string()))?;
+        let to_value = serde_json::to_value(&to.data)
+            .map_err(|err| Error::SerializationError(err.to_string()))?;
+
+        let action_name = format!("diff{}Revisions", T::TYPE_NAME.to_pascal_case());
+        ActionRes::new(&action_name, DiffEntityResult {
+            from_revision: self.from_revision,
+            to_revision: self.to_revision,
+            changes: diff(&from_value, &to_value),
+        })
+    }
+}
+
+///bring an entity back to an older revision
+/// this goes through the modifier, so that the table / script is also brought back
+#[derive(Debug, Clone)]
+pub struct RevertEntity<T, S = ActionState>
+    where
+        T: RawEntityTypes + UpdateActionFunctions,
+{
+    pub name: String,
+    pub revision_id: i64,
+    pub phantom_data: PhantomData<(T, S)>,
+}
+
+impl<T, S> RevertEntity<T, S>
+    where
+        T: RawEntityTypes + UpdateActionFunctions,
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(name: String, revision_id: i64) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+        let channel = Channels::entity::<T>(&name);
+        let action = Self {
+            name: name.to_owned(),
+            revision_id,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_dispatch, Permission::modify_entity::<T>(name));
+
+        action_with_permission
+    }
+}
+
+impl<T, S> Action<S> for RevertEntity<T, S>
+    where
+        T: RawEntityTypes + UpdateActionFunctions,
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = CreateEntityResult<T>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let action_name = format!("revert{}", T::TYPE_NAME.to_pascal_case());
+
+        let revision = get_revision::<T, S>(state, &self.name, self.revision_id)?;
+        if revision.is_deleted {
+            // a deletion has nothing to go back to, use delete instead
+            return Err(Error::NotFound);
+        }
+
+        let current: Option<T> = state
+            .get_entity_retreiver_functions()
+            .get_one(&self.name)
+            .map_err(Error::Entity)?;
+
+        let modifier = state.get_entity_modifier_function();
+        match current {
+            Some(_) => modifier
+                .update((&self.name, revision.data))
+                .map_err(Error::Entity)
+                .and_then(|res| match res {
+                    Updated::Success { old, new } => ActionRes::new(&action_name, CreateEntityResult::Updated { old, new }),
+                    Updated::Fail => Err(Error::NotFound),
+                }),
+            None => modifier
+                .restore((&self.name, revision.data))
+                .map_err(Error::Entity)
+                .and_then(|res| match res {
+                    Created::Success { new } => ActionRes::new(&action_name, CreateEntityResult::Created { new }),
+                    Created::Fail { .. } => Err(Error::AlreadyExists),
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+    use data;
+    use data::Named;
+    use data::utils::DependentsPolicy;
+    use model::actions::entity_actions::*;
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+
+    #[test]
+    fn test_revert_entity() {
+        with_state(|state| {
+            let name = format!("my_query_{}", random_identifier());
+            let first_query: data::DataQueryEntity = from_value(json!({
+                "name": name,
+                "description": "first",
+                "statement": "SELECT * FROM a_table"
+            })).unwrap();
+            let second_query: data::DataQueryEntity = from_value(json!({
+                "name": name,
+                "description": "second",
+                "statement": "SELECT * FROM b_table"
+            })).unwrap();
+
+            CreateEntity::<data::DataQueryEntity, MockState>::new(first_query).call(&state).unwrap();
+            UpdateEntity::<data::DataQueryEntity, MockState>::new(name.to_owned(), second_query, DependentsPolicy::Restrict).call(&state).unwrap();
+
+            let history_action = GetEntityHistory::<data::DataQueryEntity, MockState>::new(name.to_owned());
+            let GetEntityHistoryResult(history) = history_action.call(&state).unwrap().get_data();
+            assert_eq!(history.len(), 2);
+            assert_eq!(history[0].data.description, "second");
+            assert_eq!(history[1].data.description, "first");
+
+            let first_revision = history[1].revision_id;
+            let second_revision = history[0].revision_id;
+
+            let diff_action = DiffEntityRevisions::<data::DataQueryEntity, MockState>::new(name.to_owned(), first_revision, second_revision);
+            let data = diff_action.call(&state).unwrap().get_data();
+            assert_eq!(data.changes.len(), 2);
+
+            let revert_action = RevertEntity::<data::DataQueryEntity, MockState>::new(name.to_owned(), first_revision);
+            match revert_action.call(&state).unwrap().get_data() {
+                CreateEntityResult::Updated { new, .. } => {
+                    assert_eq!(new.my_name(), name);
+                    assert_eq!(new.description, "first");
+                    assert_eq!(new.statement, "SELECT * FROM a_table");
+                },
+                _ => panic!("expected an updated result"),
+            }
+
+            let entity_id = state
+                .get_entity_retreiver_functions()
+                .get_entity_id::<data::DataQueryEntity>(&name)
+                .unwrap();
+            DeleteEntity::<data::DataQueryEntity, MockState>::new(name.to_owned(), DependentsPolicy::Restrict).call(&state).unwrap();
+
+            let revert_action = RevertEntity::<data::DataQueryEntity, MockState>::new(name.to_owned(), second_revision);
+            match revert_action.call(&state).unwrap().get_data() {
+                CreateEntityResult::Created { new } => assert_eq!(new.description, "second"),
+                _ => panic!("expected a created result"),
+            }
+
+            // the deleted entity comes back, not a new one with the same name
+            let restored_entity_id = state
+                .get_entity_retreiver_functions()
+                .get_entity_id::<data::DataQueryEntity>(&name)
+                .unwrap();
+            assert!(entity_id.is_some());
+            assert_eq!(restored_entity_id, entity_id);
+        });
+    }
+}
@@ -0,0 +1,327 @@
+use std::marker::PhantomData;
+
+use data::auth::SessionToken;
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+use state::ActionState;
+use state::StateFunctions;
+use state::authentication::AuthenticationOps;
+use state::authorization::AuthorizationOps;
+use state::error::UserManagementError;
+
+use connection::GetSecrets;
+
+/// User Auth: Set up an authenticator, either logged in or with the challenge token from `login` when 2FA is required
+#[derive(Debug)]
+pub struct EnrollMfa<S = ActionState> {
+    challenge_token: Option<String>,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> EnrollMfa<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(challenge_token: Option<String>) -> WithTransaction<Self, S> {
+        let action = Self {
+            challenge_token,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+
+        action_with_transaction
+    }
+}
+
+impl<S> Action<S> for EnrollMfa<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = MfaEnrollmentResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling EnrollMfa");
+
+        let authentication = state.get_authentication();
+        let user_id = match &self.challenge_token {
+            Some(challenge_token) => authentication
+                .get_mfa_challenge_user(challenge_token)
+                .map_err(Error::UserManagement)?
+                .user_id,
+            None => {
+                let authorization = state.get_authorization();
+                if authorization.scopes().is_some() {
+                    debug!("Two-factor authentication can't be set up with a scoped api key");
+                    return Err(Error::Unauthorized);
+                }
+
+                authorization.user_id().ok_or(Error::Unauthorized)?
+            },
+        };
+
+        authentication
+            .enroll_mfa(user_id)
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("enrollMfa", MfaEnrollmentResult(res)))
+    }
+}
+
+/// User Auth: The second step of `login`, the code is from the authenticator or one of the recovery codes
+#[derive(Debug)]
+pub struct VerifyMfa<S = ActionState> {
+    challenge_token: String,
+    code: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> VerifyMfa<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    /// Not in a transaction, the wrong codes have to be counted even though the action fails
+    pub fn new(challenge_token: String, code: String) -> Self {
+        Self {
+            challenge_token,
+            code,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<S> Action<S> for VerifyMfa<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = SessionToken;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling VerifyMfa");
+
+        // the code is throttled like the password, otherwise a known password gives unlimited challenges to guess codes with
+        let authentication = state.get_authentication();
+        let username = authentication
+            .get_mfa_challenge_user(&self.challenge_token)
+            .map_err(Error::UserManagement)?
+            .username;
+        authentication
+            .check_login_throttle(&username)
+            .map_err(Error::UserManagement)?;
+
+        let user = match authentication.verify_mfa_challenge(&self.challenge_token, &self.code) {
+            Ok(user) => user,
+            Err(UserManagementError::Unauthorized) => {
+                authentication
+                    .record_failed_mfa_code(&username)
+                    .map_err(Error::UserManagement)?;
+
+                return Err(Error::UserManagement(UserManagementError::Unauthorized));
+            },
+            Err(err) => return Err(Error::UserManagement(err)),
+        };
+
+        authentication
+            .clear_failed_logins(&username)
+            .map_err(Error::UserManagement)?;
+
+        authentication
+            .create_session(user)
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("verifyMfa", res))
+    }
+}
+
+/// User Auth: Stop using two-factor authentication, takes a current code
+#[derive(Debug)]
+pub struct DisableMfa<S = ActionState> {
+    code: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> DisableMfa<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    /// Not wrapped in a transaction, the wrong codes have to be counted even though the action fails,
+    /// two-factor authentication is disabled in one
+    pub fn new(code: String) -> WithLoginRequired<Self, S> {
+        let action = Self {
+            code,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_permission = WithLoginRequired::new(action);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for DisableMfa<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = ();
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling DisableMfa");
+
+        let authorization = state.get_authorization();
+        if authorization.scopes().is_some() {
+            debug!("Two-factor authentication can't be disabled with a scoped api key");
+            return Err(Error::Unauthorized);
+        }
+
+        let (user_id, username) = match (authorization.user_id(), authorization.username()) {
+            (Some(user_id), Some(username)) => (user_id, username),
+            _ => {
+                error!("This is unexpected. the user should already be logged in at this point");
+                return Err(Error::Unknown);
+            },
+        };
+
+        // throttled like `VerifyMfa`, a session alone isn't enough to guess the code
+        let authentication = state.get_authentication();
+        authentication
+            .check_login_throttle(&username)
+            .map_err(Error::UserManagement)?;
+
+        let is_valid = authentication
+            .verify_mfa_code(user_id, &self.code)
+            .map_err(Error::UserManagement)?;
+        if !is_valid {
+            authentication
+                .record_failed_mfa_code(&username)
+                .map_err(Error::UserManagement)?;
+
+            return Err(Error::UserManagement(UserManagementError::Unauthorized));
+        }
+
+        state
+            .transaction::<(), Error, _>(|| authentication
+                .disable_mfa(&username)
+                .map_err(Error::UserManagement))
+            .and_then(|res| ActionRes::new("disableMfa", res))
+    }
+}
+
+/// User Auth: For users that lost their authenticator and their recovery codes, they can enroll again
+#[derive(Debug)]
+pub struct ResetMfa<S = ActionState> {
+    user_identifier: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ResetMfa<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(user_identifier: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            user_identifier,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ResetMfa<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = ();
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ResetMfa");
+
+        state
+            .get_authentication()
+            .disable_mfa(&self.user_identifier)
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("resetMfa", res))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+    use chrono::Utc;
+
+    use data;
+    use auth::totp;
+    use model::actions::user_actions::AddUser;
+    use model::actions::user_actions::Login;
+    use state::user_management::UserManagementOps;
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+
+    fn current_code(secret: &str) -> String {
+        let secret = totp::base32_decode(secret).unwrap();
+        let step = totp::time_step(Utc::now().timestamp()) as u64;
+        format!("{:06}", totp::hotp(&secret, step).unwrap())
+    }
+
+    #[test]
+    fn test_disable_mfa_wrong_code() {
+        with_state(|state| {
+            let result = DisableMfa::<MockState>::new("000000".to_string()).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+
+            // counted like a wrong code at login
+            let username = state.get_authorization().username().unwrap();
+            let login_failures = state.get_authentication().get_login_failures(Some(username)).unwrap();
+            assert_eq!(login_failures[0].reason, "invalidMfaCode");
+        });
+    }
+
+    #[test]
+    fn test_mfa_login() {
+        with_state(|state| {
+            let name = format!("Bobby_{}", random_identifier());
+            let new_user: data::auth::NewUser = from_value(json!({
+                "username": name,
+                "email": format!("stuff{}@example.com", random_identifier()),
+                "password": "hunter2"
+            })).unwrap();
+            AddUser::<MockState>::new(new_user).call(&state).unwrap();
+            let user = state.get_user_management().get_user(&name, "hunter2").unwrap();
+
+            let authentication = state.get_authentication();
+            let enrollment = authentication.enroll_mfa(user.user_id).unwrap();
+            assert!(enrollment.otpauth_uri.contains(&enrollment.secret));
+            // not in use until a code was verified
+            assert!(!authentication.is_mfa_enabled(user.user_id).unwrap());
+            assert!(authentication.verify_mfa_code(user.user_id, &current_code(&enrollment.secret)).unwrap());
+            assert!(authentication.is_mfa_enabled(user.user_id).unwrap());
+            // codes are only accepted once
+            assert!(!authentication.verify_mfa_code(user.user_id, &current_code(&enrollment.secret)).unwrap());
+
+            let result = Login::<MockState>::new(name.to_owned(), "hunter2".to_string()).call(&state).unwrap();
+            let challenge = match result.get_data() {
+                LoginResult::MfaChallenge(challenge) => challenge,
+                _ => panic!("expected an mfa challenge"),
+            };
+            assert!(!challenge.enrollment_required);
+
+            let result = VerifyMfa::<MockState>::new(challenge.challenge_token.to_owned(), "000000".to_string()).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+            let login_failures = authentication.get_login_failures(Some(name.to_owned())).unwrap();
+            assert_eq!(login_failures[0].reason, "invalidMfaCode");
+
+            let recovery_code = enrollment.recovery_codes[0].to_owned();
+            VerifyMfa::<MockState>::new(challenge.challenge_token.to_owned(), recovery_code.to_owned()).call(&state).unwrap();
+
+            // both the challenge and the recovery code are used up
+            let result = VerifyMfa::<MockState>::new(challenge.challenge_token.to_owned(), recovery_code).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+
+            ResetMfa::<MockState>::new(name.to_owned()).call(&state).unwrap();
+            let result = Login::<MockState>::new(name.to_owned(), "hunter2".to_string()).call(&state).unwrap();
+            match result.get_data() {
+                LoginResult::Session(_) => (),
+                _ => panic!("two-factor authentication was reset"),
+            }
+        });
+    }
+}
@@ -5,10 +5,20 @@ mod decorator;
 mod domain_actions;
 mod user_actions;
 mod entity_actions;
+mod history_actions;
 mod table_actions;
 mod query_actions;
 mod script_actions;
+mod view_actions;
+mod tag_actions;
+mod usage_actions;
 mod pub_sub_actions;
+mod bundle_actions;
+mod sync_actions;
+mod dependency_actions;
+mod sso_actions;
+mod api_key_actions;
+mod mfa_actions;
 
 
 use std::result::Result;
@@ -24,10 +34,21 @@ use state::ActionState;
 pub use model::actions::domain_actions::*;
 pub use model::actions::user_actions::*;
 pub use model::actions::entity_actions::*;
+pub use model::actions::history_actions::*;
 pub use model::actions::table_actions::*;
 pub use model::actions::query_actions::*;
 pub use model::actions::script_actions::*;
+pub use model::actions::view_actions::*;
+pub use model::actions::tag_actions::*;
+pub use model::actions::usage_actions::*;
 pub use model::actions::pub_sub_actions::*;
+pub use model::actions::bundle_actions::*;
+pub use model::actions::sync_actions::*;
+pub use model::actions::dependency_actions::*;
+pub use model::actions::sso_actions::*;
+pub use model::actions::api_key_actions::*;
+pub use model::actions::mfa_actions::*;
+pub use model::actions::decorator::WithInstallLog;
 
 
 #[derive(Debug, Clone)]
@@ -21,6 +21,8 @@ use data::channels::Defaults;
 use data::channels::Sub;
 
 use state::PubSubOps;
+use auth::send_mail::EmailError;
+use auth::send_mail::EmailOps;
 use state::ActionState;
 use state::StateFunctions;
 use state::authorization::AuthorizationOps;
@@ -256,7 +258,112 @@ impl<S> Action<S> for GetMessages<S>
     }
 }
 
+#[derive(Debug)]
+pub struct Publish<S = ActionState>  {
+    pub channel: Channels,
+    pub data: serde_json::Value,
+    pub notify: bool,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> Publish<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    /// With `notify` set, the subscribers of the channel get the message by email as well
+    pub fn new(channel: Channels, data: serde_json::Value, notify: bool) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        debug!("new action Publish");
+
+        let permission = channel.required_publish_permission();
+        let action = Self {
+            channel,
+            data,
+            notify,
+            phantom_data: PhantomData,
+        };
+
+        let action = WithTransaction::new(action);
+        let action =
+            WithPermissionRequired::new(action, permission);
+
+        action
+    }
+}
+
+impl<S> Action<S> for Publish<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = PublishResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling Publish");
+
+        let pub_sub = state.get_pub_sub();
+        pub_sub
+            .publish(self.channel.to_owned(), "publish".to_string(), &self.data)
+            .map_err(|err| Error::PublishError(err))?;
+
+        if self.notify {
+            let subscribers = pub_sub
+                .get_subscribers(self.channel.to_owned())
+                .map_err(|err| Error::PublishError(err))?;
+            notify_subscribers(state, &self.channel, &self.data, subscribers)?;
+        }
+
+        ActionRes::new("publish", PublishResult {
+            channel: self.channel.to_owned(),
+            data: self.data.to_owned(),
+        })
+    }
+}
+
+/// The message is already published, so an email that can't be sent is only logged. Without any mail
+/// transport nobody could be notified, which fails the publish
+fn notify_subscribers<S>(state: &S, channel: &Channels, data: &serde_json::Value, subscribers: Vec<data::auth::User>) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let subject = format!("New message on {}", channel.display_name());
+    let message = match data {
+        serde_json::Value::String(message) => message.to_owned(),
+        _ => serde_json::to_string_pretty(data).unwrap_or_default(),
+    };
+
+    let email_sender = state.get_email_sender();
+    for subscriber in subscribers {
+        match email_sender.send_notification(&subscriber.email, &subject, &message) {
+            Ok(()) => (),
+            Err(EmailError::NotConfigured) => return Err(Error::EmailError(EmailError::NotConfigured)),
+            Err(err) => warn!("Could not send the notification to {:?}: {:?}", &subscriber.email, &err),
+        }
+    }
+
+    Ok(())
+}
+
 impl Channels {
+    fn display_name(&self) -> String {
+        match self {
+            Channels::Defaults(Defaults::Table(name)) => format!("table {}", name),
+            Channels::Defaults(Defaults::Query(name)) => format!("query {}", name),
+            Channels::Defaults(Defaults::Script(name)) => format!("script {}", name),
+            Channels::Defaults(Defaults::View(name)) => format!("view {}", name),
+            Channels::Defaults(Defaults::TableData(name)) => format!("table data {}", name),
+            Channels::Subscribers(Sub::Subscribers(channel)) => Channels::Defaults(channel.to_owned()).display_name(),
+        }
+    }
+
+    fn required_publish_permission(&self) -> Permission {
+        match self {
+            Channels::Defaults(Defaults::Table(name)) => Permission::modify_entity::<data::DataStoreEntity>(name.to_owned()),
+            Channels::Defaults(Defaults::Query(name)) => Permission::modify_entity::<data::DataQueryEntity>(name.to_owned()),
+            Channels::Defaults(Defaults::Script(name)) => Permission::modify_entity::<data::Script>(name.to_owned()),
+            Channels::Defaults(Defaults::View(name)) => Permission::modify_entity::<data::View>(name.to_owned()),
+            Channels::Defaults(Defaults::TableData(name)) => Permission::modify_table_data(name.to_owned()),
+            Channels::Subscribers(Sub::Subscribers(channel)) => Channels::Defaults(channel.to_owned()).required_publish_permission(),
+        }
+    }
+
     fn required_permission(&self) -> Permission {
         match self {
             Channels::Defaults(Defaults::Table(name)) => Permission::read_entity::<data::DataStoreEntity>(name.to_owned()), //TODO: not right, this should be the responsiblity of raw types
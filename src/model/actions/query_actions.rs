@@ -32,7 +32,7 @@ impl<S> RunQuery<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(query_name: String, params: serde_json::Value) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(query_name: String, params: serde_json::Value) -> WithPermissionRequired<WithUsageRecorded<WithTransaction<Self, S>, data::DataQueryEntity, S>, S> {
         let action = Self {
             query_name: query_name.to_owned(),
             params,
@@ -41,8 +41,9 @@ impl<S> RunQuery<S>
         };
 
         let action_with_transaction = WithTransaction::new(action);
+        let action_with_usage = WithUsageRecorded::new(action_with_transaction, query_name.to_owned());
         let action_with_permission =
-            WithPermissionRequired::new(action_with_transaction, Permission::run_query(query_name));
+            WithPermissionRequired::new(action_with_usage, Permission::run_query(query_name));
 
         action_with_permission
     }
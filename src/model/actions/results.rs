@@ -1,9 +1,14 @@
 
 
+use std::fmt::Debug;
+
 use data;
+use data::diff::Change;
+use data::dependencies::Dependent;
 use data::auth::Invitation;
 use data::channels::Channels;
 use data::channels::Subscription;
+use model::entity::results::Revision;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct GetAllEntitiesResult<T>(pub Vec<T>);
@@ -28,6 +33,15 @@ pub enum CreateEntityResult<T> {
     },
 }
 
+/// The result of creating or updating a script, with the log of building its environment
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptModifiedResult<R> {
+    #[serde(flatten)]
+    pub result: R,
+    pub install_log: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "result")]
@@ -84,6 +98,46 @@ pub struct AllUsersResult(pub Vec<data::auth::User>);
 #[derive(Debug, Clone, Serialize)]
 pub struct InvitationResult(pub Invitation);
 
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionsResult(pub Vec<data::auth::Session>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoginFailuresResult(pub Vec<data::auth::LoginFailure>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeysResult(pub Vec<data::auth::ApiKey>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewApiKeyResult(pub data::auth::NewApiKey);
+
+/// The session right away, a challenge when the user has to enter a code from their authenticator,
+/// or a token for setting a new password when theirs has expired
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum LoginResult {
+    Session(data::auth::SessionToken),
+    MfaChallenge(data::auth::MfaChallenge),
+    PasswordChangeRequired(data::auth::PasswordChangeRequired),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MfaEnrollmentResult(pub data::auth::MfaEnrollment);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcLoginResult {
+    /// where the frontend sends the user to log in
+    pub authorization_url: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectorySyncResult {
+    pub synced: Vec<String>,
+    /// users that are no longer in the directory
+    pub deprovisioned: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct RoleResult(pub data::auth::Role);
 
@@ -97,4 +151,121 @@ pub enum SubscriptionResult {
     Subscribed(Subscription),
     Unsubscribed(Subscription),
     UnsubscribedAll,
-}
\ No newline at end of file
+}
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishResult {
+    pub channel: Channels,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GetEntityHistoryResult<T: Debug>(pub Vec<Revision<T>>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GetEntityRevisionResult<T: Debug>(pub Revision<T>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffEntityResult {
+    pub from_revision: i64,
+    pub to_revision: i64,
+    pub changes: Vec<Change>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetViewDataResult {
+    pub source: data::ViewSource,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagResult(pub data::Tag);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AllTagsResult(pub Vec<data::Tag>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchEntitiesResult {
+    pub tables: Vec<data::DataStoreEntity>,
+    pub queries: Vec<data::DataQueryEntity>,
+    pub scripts: Vec<data::Script>,
+    pub views: Vec<data::View>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DependentsResult(pub Vec<Dependent>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EntityUsageResult(pub Vec<data::EntityUsage>);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableAuditLogResult(pub Vec<data::TableAuditRecord>);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevertTableChangesResult {
+    /// transactions that were undone
+    pub reverted: Vec<i64>,
+    /// the changes that undid them, these are in the audit log as well
+    pub changes: Vec<data::DataChange>,
+    pub conflicts: Vec<data::RevertConflict>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportDomainResult {
+    pub version: u32,
+    /// base64 encoded gzipped tarball
+    pub archive: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(tag = "action")]
+pub enum ImportAction {
+    Create,
+    Overwrite,
+    Skip,
+    #[serde(rename_all = "camelCase")]
+    Rename {
+        new_name: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    InsertRows {
+        row_count: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportStep {
+    pub type_name: String,
+    /// the name in the bundle
+    pub name: String,
+    #[serde(flatten)]
+    pub action: ImportAction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportDomainResult {
+    pub dry_run: bool,
+    pub steps: Vec<ImportStep>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPlanResult {
+    pub changes: Vec<data::sync::SyncChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplySyncResult {
+    pub applied: Vec<data::sync::SyncChange>,
+    /// drifted entities, these need `force` to be overwritten
+    pub skipped: Vec<data::sync::SyncChange>,
+}
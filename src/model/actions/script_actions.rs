@@ -1,5 +1,9 @@
 use std::result::Result::Ok;
 use std::marker::PhantomData;
+use std::cell::Cell;
+use std::cell::RefCell;
+
+use serde_json;
 
 use data;
 use data::Named;
@@ -13,12 +17,85 @@ use model::actions::Action;
 use model::actions::ActionRes;
 use model::actions::ActionResult;
 use model::entity::RetrieverFunctions;
+use model::entity::ModifierFunctions;
+use model::entity::results::Updated;
 
 use scripting::ScriptFunctions;
+use scripting::REQUIREMENTS_NAME;
+use scripting::error::ScriptError;
 use scripting::ScriptResult;
+use scripting::ScriptOutput;
+use scripting::ScriptOutputBatch;
+
+use data::channels::Channels;
 
 use state::StateFunctions;
 use state::ActionState;
+use state::authentication::AuthenticationOps;
+use state::authorization::AuthorizationOps;
+use state::PubSubOps;
+
+/// How long the token handed to the kakapo client of a script is valid for, in seconds
+const SCRIPT_TOKEN_DURATION: i64 = 15 * 60;
+/// Lines of output that are published together in one message
+const OUTPUT_BATCH_SIZE: usize = 100;
+/// Lines of output that are published for one run, the rest is only in the result of the run
+const MAX_PUBLISHED_OUTPUT_LINES: usize = 10000;
+
+/// Publishes the output of a run in batches, so that every line isn't stored as it's own message
+struct OutputPublisher<'a, S: 'a> {
+    state: &'a S,
+    channel: Channels,
+    pending: RefCell<Vec<ScriptOutput>>,
+    line_count: Cell<usize>,
+}
+
+impl<'a, S> OutputPublisher<'a, S>
+    where
+        for<'b> S: StateFunctions<'b>,
+{
+    fn new(state: &'a S, channel: Channels) -> Self {
+        Self {
+            state,
+            channel,
+            pending: RefCell::new(vec![]),
+            line_count: Cell::new(0),
+        }
+    }
+
+    fn push(&self, output: &ScriptOutput) {
+        self.line_count.set(self.line_count.get() + 1);
+        if self.line_count.get() > MAX_PUBLISHED_OUTPUT_LINES {
+            return;
+        }
+
+        self.pending.borrow_mut().push(output.to_owned());
+        if self.pending.borrow().len() >= OUTPUT_BATCH_SIZE {
+            self.publish(false);
+        }
+    }
+
+    /// Publishes the rest of the output, has to be called once the script is done
+    fn finish(&self) {
+        let is_truncated = self.line_count.get() > MAX_PUBLISHED_OUTPUT_LINES;
+        if is_truncated || !self.pending.borrow().is_empty() {
+            self.publish(is_truncated);
+        }
+    }
+
+    fn publish(&self, truncated: bool) {
+        let lines: Vec<ScriptOutput> = self.pending.borrow_mut().drain(..).collect();
+        let batch = ScriptOutputBatch { lines, truncated };
+
+        let _ = serde_json::to_value(&batch)
+            .map_err(|err| Error::SerializationError(err.to_string()))
+            .and_then(|value| self.state
+                .get_pub_sub()
+                .publish(self.channel.to_owned(), "scriptOutput".to_string(), &value)
+                .map_err(Error::PublishError))
+            .map_err(|err| warn!("could not publish script output: {:?}", err));
+    }
+}
 
 // Script Action
 #[derive(Debug)]
@@ -32,16 +109,17 @@ impl<S> RunScript<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(script_name: String, param: data::ScriptParam) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(script_name: String, param: data::ScriptParam) -> WithPermissionRequired<WithUsageRecorded<Self, data::Script, S>, S> {
         let action = Self {
             script_name: script_name.to_owned(),
             param,
             phantom_data: PhantomData,
         };
 
-        let action_with_transaction = WithTransaction::new(action);
+        // No transaction here, scripts can run for a long time, and the output has to be published while it runs
+        let action_with_usage = WithUsageRecorded::new(action, script_name.to_owned());
         let action_with_permission =
-            WithPermissionRequired::new(action_with_transaction, Permission::run_script(script_name));
+            WithPermissionRequired::new(action_with_usage, Permission::run_script(script_name));
 
         action_with_permission
     }
@@ -64,15 +142,198 @@ impl<S> Action<S> for RunScript<S>
                 None => Err(Error::NotFound),
             })
             .and_then(|script| {
-                state
+                // the script can't do more than the caller, and loses access when the caller does
+                let authorization = state.get_authorization();
+                let token = match authorization.user_id() {
+                    Some(user_id) => Some(state
+                        .get_authentication()
+                        .create_scoped_token(
+                            user_id,
+                            authorization.session_id(),
+                            authorization.api_key_id(),
+                            authorization.scopes(),
+                            SCRIPT_TOKEN_DURATION)
+                        .map_err(Error::UserManagement)?),
+                    None => None,
+                };
+
+                let channel = Channels::entity::<data::Script>(&self.script_name);
+                let publisher = OutputPublisher::new(state, channel.to_owned());
+                let on_output = |output: &ScriptOutput| publisher.push(output);
+
+                let result = state
                     .get_script_runner()
-                    .run(&script, &self.param)
-                    .map_err(Error::Script)
+                    .run(&script, &self.param, token, &on_output);
+                publisher.finish();
+                let result = result.map_err(Error::Script)?;
+
+                // the script already ran, so it's result is returned even if the subscribers can't be told
+                let _ = serde_json::to_value(&result)
+                    .map_err(|err| Error::SerializationError(err.to_string()))
+                    .and_then(|result_value| state
+                        .get_pub_sub()
+                        .publish(channel, "scriptFinished".to_string(), &result_value)
+                        .map_err(Error::PublishError))
+                    .map_err(|err| warn!("could not publish that the script finished: {:?}", err));
+
+                Ok(result)
             })
             .and_then(|res| ActionRes::new("runScript", res))
     }
 }
 
+// Patch Script Action
+#[derive(Debug)]
+pub struct PatchScript<S = ActionState>  {
+    pub script_name: String,
+    pub patch: data::ScriptPatch,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> PatchScript<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(script_name: String, patch: data::ScriptPatch) -> WithInstallLog<WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S>, S> {
+        let channel = Channels::entity::<data::Script>(&script_name);
+        let action = Self {
+            script_name: script_name.to_owned(),
+            patch,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_dispatch, Permission::modify_entity::<data::Script>(script_name.to_owned()));
+        let action_with_install_log = WithInstallLog::new(action_with_permission);
+
+        action_with_install_log
+    }
+}
+
+impl<S> Action<S> for PatchScript<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = UpdateEntityResult<data::Script>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling PatchScript");
+
+        let script = get_script(state, &self.script_name)?;
+        let new_script = patched_script(&script, &self.patch)?;
+
+        update_script(state, &self.script_name, new_script)
+            .and_then(|res| ActionRes::new("patchScript", res))
+    }
+}
+
+// Upload Script Archive Action
+#[derive(Debug)]
+pub struct UploadScriptArchive<S = ActionState>  {
+    pub script_name: String,
+    pub archive: String,
+    pub entrypoint: Option<String>,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> UploadScriptArchive<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    /// `archive` is a base64 encoded gzipped tarball, it replaces all of the files of the script
+    pub fn new(script_name: String, archive: String, entrypoint: Option<String>) -> WithInstallLog<WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S>, S> {
+        let channel = Channels::entity::<data::Script>(&script_name);
+        let action = Self {
+            script_name: script_name.to_owned(),
+            archive,
+            entrypoint,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_dispatch, Permission::modify_entity::<data::Script>(script_name.to_owned()));
+        let action_with_install_log = WithInstallLog::new(action_with_permission);
+
+        action_with_install_log
+    }
+}
+
+impl<S> Action<S> for UploadScriptArchive<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = UpdateEntityResult<data::Script>;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling UploadScriptArchive");
+
+        let script = get_script(state, &self.script_name)?;
+
+        let archive = base64::decode(&self.archive)
+            .map_err(|err| Error::SerializationError(err.to_string()))?;
+        let mut files = state
+            .get_script_runner()
+            .unpack_archive(&archive)
+            .map_err(Error::Script)?;
+
+        // the requirements of the script are managed by kakapo, a requirements.txt in the archive replaces them
+        let requirements = files
+            .remove(REQUIREMENTS_NAME)
+            .map(|text| text.lines().map(|line| line.to_owned()).collect());
+
+        // every file that isn't in the archive is removed
+        let mut patch = data::ScriptPatch {
+            files: script.all_files().keys().map(|x| (x.to_owned(), None)).collect(),
+            entrypoint: self.entrypoint.to_owned(),
+            description: None,
+            requirements,
+        };
+        patch.files.extend(files.into_iter().map(|(file_name, contents)| (file_name, Some(contents))));
+
+        let new_script = patched_script(&script, &patch)?;
+
+        update_script(state, &self.script_name, new_script)
+            .and_then(|res| ActionRes::new("uploadScriptArchive", res))
+    }
+}
+
+fn patched_script(script: &data::Script, patch: &data::ScriptPatch) -> Result<data::Script, Error> {
+    let entrypoint = patch.entrypoint.to_owned().unwrap_or_else(|| script.entrypoint.to_owned());
+    script
+        .patched(patch)
+        .ok_or_else(|| Error::Script(ScriptError::MissingEntrypoint(entrypoint)))
+}
+
+fn get_script<S>(state: &S, script_name: &str) -> Result<data::Script, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    state
+        .get_entity_retreiver_functions()
+        .get_one::<data::Script>(script_name)
+        .map_err(Error::Entity)
+        .and_then(|res| match res {
+            Some(script) => Ok(script),
+            None => Err(Error::NotFound),
+        })
+}
+
+fn update_script<S>(state: &S, script_name: &str, new_script: data::Script) -> Result<UpdateEntityResult<data::Script>, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    state
+        .get_entity_modifier_function()
+        .update((script_name, new_script))
+        .map_err(Error::Entity)
+        .and_then(|res| match res {
+            Updated::Success { old, new } => Ok(UpdateEntityResult::Updated { id: script_name.to_owned(), old, new }),
+            Updated::Fail => Err(Error::NotFound),
+        })
+}
+
 
 
 #[cfg(test)]
@@ -84,6 +345,58 @@ mod test {
     use test_common::*;
     use model::actions::entity_actions;
 
+    #[test]
+    fn test_patch_script() {
+        with_state(|state| {
+            let script_name = format!("my_script{}", random_identifier());
+            let script: data::Script = from_value(json!({
+                "name": script_name.to_owned(),
+                "description": "script description",
+                "text": "from helpers import greeting\nprint(greeting)\n",
+                "files": {
+                    "helpers.py": "greeting = 'Hello World'\n",
+                    "unused.py": "",
+                },
+            })).unwrap();
+
+            let create_action = entity_actions::CreateEntity::<data::Script, MockState>::new(script);
+            let result = create_action.call(&state);
+            let data = result.unwrap().get_data();
+
+            let patch: data::ScriptPatch = from_value(json!({
+                "files": {
+                    "helpers.py": "greeting = 'Bye World'\n",
+                    "unused.py": null,
+                },
+            })).unwrap();
+            let patch_action = PatchScript::<MockState>::new(script_name.to_owned(), patch);
+            let result = patch_action.call(&state).unwrap().get_data();
+            // no requirements, so there is no environment to build
+            assert_eq!(result.install_log, None);
+            match result.result {
+                UpdateEntityResult::Updated { new, .. } => {
+                    assert_eq!(new.entrypoint, "script.py");
+                    assert_eq!(new.files.len(), 1);
+                    assert_eq!(new.files["helpers.py"], "greeting = 'Bye World'\n");
+                },
+                _ => panic!("script should be updated"),
+            }
+
+            let patch: data::ScriptPatch = from_value(json!({
+                "files": { "script.py": null },
+            })).unwrap();
+            let patch_action = PatchScript::<MockState>::new(script_name.to_owned(), patch);
+            let result = patch_action.call(&state);
+            assert_eq!(result.err(), Some(Error::Script(ScriptError::MissingEntrypoint("script.py".to_string()))));
+
+            let run_action = RunScript::<MockState>::new(script_name, json!({}));
+            let result = run_action.call(&state);
+            let data = result.unwrap().get_data();
+            assert_eq!(data.successful, true);
+            assert_eq!(data.stdout, "Bye World\n");
+        });
+    }
+
     #[test]
     fn test_run_script() {
         with_state(|state| {
@@ -120,4 +433,41 @@ with open(filename, 'w') as f:
             assert_eq!(data.output, json!({"bye": "world"}));
         });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_run_script_publishes_output_in_batches() {
+        with_state(|state| {
+            let script_name = format!("my_script{}", random_identifier());
+            let script: data::Script = from_value(json!({
+                "name": script_name.to_owned(),
+                "description": "script description",
+                "text": "for i in range(250):\n    print(i)\n",
+            })).unwrap();
+
+            let create_action = entity_actions::CreateEntity::<data::Script, MockState>::new(script);
+            create_action.call(&state).unwrap();
+
+            let channel = Channels::entity::<data::Script>(&script_name);
+            state.get_pub_sub().subscribe(1, channel).unwrap();
+
+            let start_time = chrono::Utc::now().naive_utc() - chrono::Duration::days(1);
+            let run_action = RunScript::<MockState>::new(script_name, json!({}));
+            let data = run_action.call(&state).unwrap().get_data();
+            assert_eq!(data.successful, true);
+            assert_eq!(data.stdout.lines().count(), 250);
+
+            let end_time = chrono::Utc::now().naive_utc() + chrono::Duration::days(1);
+            let messages = state.get_pub_sub().get_messages(1, start_time, end_time).unwrap();
+            // the messages are sent in the same transaction, so they can't be ordered by the time they were sent
+            let mut batch_sizes: Vec<usize> = messages
+                .iter()
+                .filter_map(|message| message.data.get("lines"))
+                .map(|lines| lines.as_array().unwrap().len())
+                .collect();
+            batch_sizes.sort();
+
+            assert_eq!(batch_sizes, vec![50, 100, 100]);
+            assert!(messages.iter().any(|message| message.data["successful"] == json!(true)));
+        });
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+use data::auth::ExternalUser;
+use data::auth::UserInfo;
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+use model::actions::user_actions::create_session_or_challenge;
+use state::ActionState;
+use state::StateFunctions;
+use state::user_management::UserManagementOps;
+use state::authentication::AuthenticationOps;
+use state::error::UserManagementError;
+
+use auth::oidc::OidcOps;
+use auth::oidc::RoleMapping;
+use auth::ldap::DirectoryOps;
+use connection::GetSecrets;
+
+/// Roles that are in the mappings are given or taken away on every login, all other roles are left alone
+fn sync_mapped_roles<S>(state: &S, user: &UserInfo, external_user: &ExternalUser, mappings: Vec<RoleMapping>) -> Result<(), Error>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    let mut mapped_roles: Vec<(String, bool)> = vec![];
+    for mapping in mappings {
+        let is_granted = mapping.matches(&external_user.claims);
+        match mapped_roles.iter_mut().find(|(role, _)| role == &mapping.role) {
+            Some((_, granted)) => *granted = *granted || is_granted,
+            None => mapped_roles.push((mapping.role, is_granted)),
+        }
+    }
+
+    let user_management = state.get_user_management();
+    for (role, is_granted) in mapped_roles {
+        // detaching first keeps the role from being attached twice
+        if let Err(err) = user_management.detach_role_for_user(&role, &user.username) {
+            debug!("Could not detach role {:?} from {:?}: {:?}", &role, &user.username, &err);
+        }
+
+        if is_granted {
+            match user_management.attach_role_for_user(&role, &user.username) {
+                Ok(_) => info!("Attached role {:?} to {:?}", &role, &user.username),
+                Err(UserManagementError::NotFound) => warn!("The mapped role {:?} does not exist", &role),
+                Err(err) => return Err(Error::UserManagement(err)),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// User Auth: Start logging in through the identity provider
+#[derive(Debug)]
+pub struct StartOidcLogin<S = ActionState> {
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> StartOidcLogin<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    /// Not wrapped in a transaction, the provider is asked for it's configuration and the login request is a single insert
+    pub fn new() -> Self {
+        Self {
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<S> Action<S> for StartOidcLogin<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = OidcLoginResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling StartOidcLogin");
+
+        let (authorization_url, login_request) = state
+            .get_oidc_client()
+            .authorization_request()
+            .map_err(Error::Oidc)?;
+
+        state
+            .get_user_management()
+            .create_login_request(&login_request)
+            .map_err(Error::UserManagement)?;
+
+        ActionRes::new("oidcLogin", OidcLoginResult { authorization_url })
+    }
+}
+
+/// User Auth: Finish logging in with the code that the identity provider redirected back with
+#[derive(Debug)]
+pub struct FinishOidcLogin<S = ActionState> {
+    code: String,
+    state: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> FinishOidcLogin<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    /// Not wrapped in a transaction, the login request is used up even if the code exchange fails,
+    /// and nothing is held open while the identity provider is asked. The user is updated in one
+    pub fn new(code: String, state: String) -> Self {
+        Self {
+            code,
+            state,
+            phantom_data: PhantomData,
+        }
+    }
+
+    fn login(&self, state: &S, external_user: &ExternalUser) -> Result<LoginResult, Error> {
+        let user = state
+            .get_user_management()
+            .get_or_create_external_user(external_user)
+            .map_err(Error::UserManagement)?;
+
+        let mappings = state.get_oidc_client().get_role_mappings();
+        sync_mapped_roles(state, &user, external_user, mappings)?;
+
+        create_session_or_challenge(state, user)
+    }
+}
+
+impl<S> Action<S> for FinishOidcLogin<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = LoginResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling FinishOidcLogin");
+
+        let login_request = state
+            .get_user_management()
+            .take_login_request(&self.state)
+            .map_err(Error::UserManagement)?;
+
+        let external_user = state
+            .get_oidc_client()
+            .exchange_code(&self.code, &login_request)
+            .map_err(Error::Oidc)?;
+
+        let login_result = state.transaction::<LoginResult, Error, _>(|| self.login(state, &external_user))?;
+
+        ActionRes::new("oidcCallback", login_result)
+    }
+}
+
+/// Users of the directory are created on their first login and get their roles on every login,
+/// `None` means that the password has to be checked locally, i.e. LDAP is not configured or the directory does not know the user
+pub(super) fn login_with_directory<S, A>(state: &S, authentication: &A, user_identifier: &str, password: &str) -> Result<Option<UserInfo>, Error>
+    where
+        for<'a> S: GetSecrets + StateFunctions<'a>,
+        A: AuthenticationOps,
+{
+    let external_user = match authentication.authenticate_with_directory(user_identifier, password).map_err(Error::UserManagement)? {
+        Some(external_user) => external_user,
+        None => return Ok(None),
+    };
+
+    let user = match state.get_user_management().get_or_create_external_user(&external_user) {
+        Ok(user) => user,
+        Err(UserManagementError::AlreadyExists) => {
+            warn!("{:?} from the directory is already taken by a local user", &external_user.username);
+            return Err(Error::UserManagement(UserManagementError::Unauthorized));
+        },
+        Err(err) => return Err(Error::UserManagement(err)),
+    };
+
+    sync_mapped_roles(state, &user, &external_user, authentication.get_directory_role_mappings())?;
+
+    Ok(Some(user))
+}
+
+/// Creates the users of the directory and syncs their roles, and disables the ones that were removed from it
+fn sync_directory_users<S, D>(state: &S, directory: &D) -> Result<DirectorySyncResult, Error>
+    where
+        for<'a> S: GetSecrets + StateFunctions<'a>,
+        D: DirectoryOps,
+{
+    let issuer = directory.get_issuer().map_err(Error::Ldap)?;
+    let directory_users = directory.get_users().map_err(Error::Ldap)?;
+    let mappings = directory.get_role_mappings();
+    let user_management = state.get_user_management();
+
+    let mut synced = vec![];
+    let mut subjects = HashSet::new();
+    for directory_user in directory_users {
+        let external_user = directory_user.to_external_user(&issuer);
+        let user = match user_management.get_or_create_external_user(&external_user) {
+            Ok(user) => user,
+            Err(UserManagementError::AlreadyExists) => {
+                warn!("{:?} from the directory is already taken by a local user", &external_user.username);
+                continue;
+            },
+            Err(err) => return Err(Error::UserManagement(err)),
+        };
+
+        sync_mapped_roles(state, &user, &external_user, mappings.to_owned())?;
+        // the directory decides who can log in, so users that come back can log in again
+        user_management
+            .set_user_deprovisioned(&user.username, false)
+            .map_err(Error::UserManagement)?;
+
+        subjects.insert(external_user.subject);
+        synced.push(user.username);
+    }
+
+    // an empty result is more likely a wrong base dn or filter than everyone having left
+    if synced.is_empty() {
+        warn!("The directory did not return any users, not disabling anyone");
+        return Ok(DirectorySyncResult { synced, deprovisioned: vec![] });
+    }
+
+    let mut deprovisioned = vec![];
+    for (subject, user) in user_management.get_active_external_users(&issuer).map_err(Error::UserManagement)? {
+        if subjects.contains(&subject) {
+            continue;
+        }
+
+        user_management
+            .set_user_deprovisioned(&user.username, true)
+            .map_err(Error::UserManagement)?;
+        state
+            .get_authentication()
+            .revoke_user_sessions(&user.username)
+            .map_err(Error::UserManagement)?;
+        deprovisioned.push(user.username);
+    }
+
+    Ok(DirectorySyncResult { synced, deprovisioned })
+}
+
+/// User Auth: Sync the users and their roles from the LDAP directory
+#[derive(Debug)]
+pub struct SyncDirectoryUsers<S = ActionState> {
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> SyncDirectoryUsers<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new() -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for SyncDirectoryUsers<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = DirectorySyncResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling SyncDirectoryUsers");
+
+        let result = sync_directory_users(state, &state.get_directory())?;
+
+        ActionRes::new("syncDirectory", result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+    use auth::ldap::DirectoryUser;
+    use auth::ldap::StaticDirectory;
+    use data::auth::Role;
+    use model::actions::DisableUser;
+
+    #[test]
+    fn test_unknown_login_request() {
+        with_state(|state| {
+            let result = FinishOidcLogin::<MockState>::new("a_code".to_string(), random_identifier()).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+        });
+    }
+
+    #[test]
+    fn test_provision_external_user() {
+        with_state(|state| {
+            let id = random_identifier();
+            let external_user = ExternalUser {
+                issuer: "https://idp.example.com".to_string(),
+                subject: format!("subject_{}", id),
+                username: format!("sso_{}", id),
+                email: format!("sso{}@example.com", id),
+                display_name: None,
+                claims: json!({}),
+            };
+
+            let user_management = state.get_user_management();
+            let user = user_management.get_or_create_external_user(&external_user).unwrap();
+            assert_eq!(user.username, external_user.username);
+
+            let same_user = user_management.get_or_create_external_user(&external_user).unwrap();
+            assert_eq!(same_user.user_id, user.user_id);
+
+            let other_subject = ExternalUser {
+                subject: format!("other_subject_{}", id),
+                ..external_user
+            };
+            let result = user_management.get_or_create_external_user(&other_subject);
+            assert_eq!(result.unwrap_err(), UserManagementError::AlreadyExists);
+
+            // the identity provider has the password
+            let result = user_management.create_password_reset_token(&user.username);
+            assert_eq!(result.unwrap_err(), UserManagementError::Unauthorized);
+        });
+    }
+
+    #[test]
+    fn test_directory_login_and_sync() {
+        with_state(|state| {
+            let id = random_identifier();
+            let role_name = format!("developer_{}", id);
+            state.get_user_management()
+                .add_role(&Role { name: role_name.to_owned(), description: None })
+                .unwrap();
+
+            let directory_user = DirectoryUser {
+                dn: format!("uid=ldap_{},ou=people,dc=example,dc=com", id),
+                username: format!("ldap_{}", id),
+                email: format!("ldap{}@example.com", id),
+                display_name: None,
+                groups: vec!["engineering".to_string()],
+            };
+            let other_user = DirectoryUser {
+                dn: format!("uid=other_{},ou=people,dc=example,dc=com", id),
+                username: format!("other_{}", id),
+                email: format!("other{}@example.com", id),
+                display_name: None,
+                groups: vec![],
+            };
+            let issuer = format!("ldap://ldap-{}.example.com", id);
+            let directory = StaticDirectory::new(&issuer)
+                .user(directory_user.clone(), "secret")
+                .user(other_user.clone(), "secret")
+                .map_group("engineering", &role_name);
+
+            let authentication = state.get_authentication().with_directory(directory.clone());
+            let user = login_with_directory(state, &authentication, &directory_user.username, "secret").unwrap().unwrap();
+            assert_eq!(user.username, directory_user.username);
+            let other = login_with_directory(state, &authentication, &other_user.username, "secret").unwrap().unwrap();
+
+            // the directory has the last word on it's users, they never fall back to a local password
+            let result = login_with_directory(state, &authentication, &directory_user.username, "wrong");
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+            let result = login_with_directory(state, &authentication, &directory_user.email, "wrong");
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+            assert!(login_with_directory(state, &authentication, "nobody", "secret").unwrap().is_none());
+
+            // disabling is up to the admin, the sync doesn't enable the user again
+            DisableUser::<MockState>::new(other_user.username.to_owned()).call(&state).unwrap();
+
+            let result = sync_directory_users(state, &directory).unwrap();
+            assert_eq!(result.synced.len(), 2);
+            assert!(result.deprovisioned.is_empty());
+
+            let directory = StaticDirectory::new(&issuer)
+                .user(other_user.clone(), "secret");
+            let result = sync_directory_users(state, &directory).unwrap();
+            assert_eq!(result.deprovisioned, vec![directory_user.username.to_owned()]);
+
+            let session = state.get_authentication().create_session(user);
+            assert_eq!(session.unwrap_err(), UserManagementError::Unauthorized);
+            let session = state.get_authentication().create_session(other);
+            assert_eq!(session.unwrap_err(), UserManagementError::Unauthorized);
+        });
+    }
+}
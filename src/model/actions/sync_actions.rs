@@ -0,0 +1,359 @@
+use std::result::Result::Ok;
+use std::marker::PhantomData;
+
+use data;
+use data::Named;
+use data::dependencies::Dependent;
+use data::utils::DependentsPolicy;
+use data::bundle::Bundle;
+use data::sync::SyncChange;
+use data::sync::SyncOperation;
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+use model::actions::dependency_actions::get_dependents;
+use model::actions::dependency_actions::delete_dependents;
+use model::entity::RetrieverFunctions;
+use model::entity::ModifierFunctions;
+use model::entity::RawEntityTypes;
+use model::entity::results::Created;
+use model::entity::results::Updated;
+use model::entity::results::Deleted;
+use model::entity::results::Revision;
+use model::entity::update_state::UpdateActionFunctions;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::entity_sync::EntitySyncOps;
+
+fn get_entity_id<T, S>(state: &S, name: &str) -> Result<i64, Error>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    state
+        .get_entity_retreiver_functions()
+        .get_entity_id::<T>(name)
+        .map_err(Error::Entity)?
+        .ok_or_else(|| Error::NotFound)
+}
+
+fn latest_revision<T, S>(state: &S, name: &str) -> Result<Option<i64>, Error>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    let history: Vec<Revision<T>> = state
+        .get_entity_retreiver_functions()
+        .get_history(name)
+        .map_err(Error::Entity)?;
+
+    Ok(history.iter().map(|revision| revision.revision_id).max())
+}
+
+/// Entities that were never synced count as drifted as well, so that the directory doesn't silently take them over
+fn is_drifted<T, S>(state: &S, name: &str) -> Result<bool, Error>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    let entity_id = get_entity_id::<T, S>(state, name)?;
+    let synced_revision = state
+        .get_entity_sync()
+        .get_synced_revision(entity_id)
+        .map_err(Error::EntitySync)?;
+
+    Ok(synced_revision.is_none() || synced_revision != latest_revision::<T, S>(state, name)?)
+}
+
+fn mark_synced<T, S>(state: &S, name: &str) -> Result<(), Error>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    let entity_id = get_entity_id::<T, S>(state, name)?;
+    let revision_id = latest_revision::<T, S>(state, name)?
+        .ok_or_else(|| Error::NotFound)?;
+
+    state
+        .get_entity_sync()
+        .mark_synced(entity_id, revision_id)
+        .map_err(Error::EntitySync)
+}
+
+/// Creates and updates one type of entity from the directory, the flag says whether the change was (or would be) applied
+fn sync_entities<T, S>(state: &S, desired: Vec<T>, dry_run: bool, force: bool) -> Result<Vec<(SyncChange, bool)>, Error>
+    where
+        T: RawEntityTypes + UpdateActionFunctions + PartialEq,
+        for<'a> S: StateFunctions<'a>,
+{
+    let current: Vec<T> = state
+        .get_entity_retreiver_functions()
+        .get_all()
+        .map_err(Error::Entity)?;
+    let modifier = state.get_entity_modifier_function();
+    let mut changes = vec![];
+
+    for entity in desired {
+        let name = entity.my_name().to_owned();
+        let operation = match current.iter().find(|existing| existing.my_name() == name) {
+            None => Some(SyncOperation::Create),
+            Some(existing) if existing != &entity => Some(SyncOperation::Update),
+            Some(_) => None,
+        };
+        let drifted = match operation {
+            Some(SyncOperation::Update) => is_drifted::<T, S>(state, &name)?,
+            _ => false,
+        };
+        let apply = !drifted || force;
+
+        if let Some(operation) = operation {
+            if !dry_run && apply {
+                match operation {
+                    SyncOperation::Create => match modifier.create(entity).map_err(Error::Entity)? {
+                        Created::Success { .. } => (),
+                        Created::Fail { .. } => return Err(Error::AlreadyExists),
+                    },
+                    SyncOperation::Update => match modifier.update((&name, entity)).map_err(Error::Entity)? {
+                        Updated::Success { .. } => (),
+                        Updated::Fail => return Err(Error::NotFound),
+                    },
+                    SyncOperation::Delete => (),
+                }
+            }
+
+            changes.push((SyncChange {
+                type_name: T::TYPE_NAME.to_string(),
+                name: name.to_owned(),
+                operation,
+                drifted,
+            }, apply));
+        }
+
+        if !dry_run && apply {
+            mark_synced::<T, S>(state, &name)?;
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Deletes the entities of one type that were removed from the directory.
+/// Only entities that came from the directory are ever deleted, the ones created in the UI are left alone.
+/// `removed` holds the entities deleted so far, so that a dry run doesn't count them as dependents
+fn remove_entities<T, S>(
+    state: &S,
+    desired_names: &[String],
+    dry_run: bool,
+    force: bool,
+    on_dependents: &DependentsPolicy,
+    removed: &mut Vec<(String, String)>,
+) -> Result<Vec<(SyncChange, bool)>, Error>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    let current: Vec<T> = state
+        .get_entity_retreiver_functions()
+        .get_all()
+        .map_err(Error::Entity)?;
+    let modifier = state.get_entity_modifier_function();
+    let mut changes = vec![];
+
+    for existing in current {
+        let name = existing.my_name().to_owned();
+        if desired_names.contains(&name) {
+            continue;
+        }
+
+        let entity_id = get_entity_id::<T, S>(state, &name)?;
+        let synced_revision = state
+            .get_entity_sync()
+            .get_synced_revision(entity_id)
+            .map_err(Error::EntitySync)?;
+        if synced_revision.is_none() {
+            continue;
+        }
+
+        let drifted = synced_revision != latest_revision::<T, S>(state, &name)?;
+        let apply = !drifted || force;
+
+        if apply {
+            if dry_run {
+                let dependents: Vec<Dependent> = get_dependents(state, T::TYPE_NAME, &name)?
+                    .into_iter()
+                    .filter(|dependent| !removed.contains(&(dependent.type_name.to_owned(), dependent.name.to_owned())))
+                    .collect();
+                if let DependentsPolicy::Restrict = on_dependents {
+                    if !dependents.is_empty() {
+                        return Err(Error::HasDependents(dependents));
+                    }
+                }
+            } else {
+                delete_dependents(state, T::TYPE_NAME, &name, on_dependents)?;
+                match modifier.delete::<T>(&name).map_err(Error::Entity)? {
+                    Deleted::Success { .. } => (),
+                    Deleted::Fail => return Err(Error::NotFound),
+                };
+                state
+                    .get_entity_sync()
+                    .unmark_synced(entity_id)
+                    .map_err(Error::EntitySync)?;
+            }
+            removed.push((T::TYPE_NAME.to_owned(), name.to_owned()));
+        }
+
+        changes.push((SyncChange {
+            type_name: T::TYPE_NAME.to_string(),
+            name,
+            operation: SyncOperation::Delete,
+            drifted,
+        }, apply));
+    }
+
+    Ok(changes)
+}
+
+fn names_of<T: Named>(entities: &[T]) -> Vec<String> {
+    entities.iter().map(|entity| entity.my_name().to_owned()).collect()
+}
+
+/// Tags, roles and table data in the directory are not synced, only the entities.
+/// Deletes run after everything else and in reverse, so that views and queries go before the tables they use
+fn sync_directory<S>(state: &S, dry_run: bool, force: bool, on_dependents: &DependentsPolicy) -> Result<Vec<(SyncChange, bool)>, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let files = state
+        .get_entity_sync()
+        .read_directory()
+        .map_err(Error::EntitySync)?;
+    let bundle = Bundle::from_files(&files).map_err(Error::Bundle)?;
+    let table_names = names_of(&bundle.tables);
+    let query_names = names_of(&bundle.queries);
+    let script_names = names_of(&bundle.scripts);
+    let view_names = names_of(&bundle.views);
+
+    let mut changes = vec![];
+    changes.extend(sync_entities(state, bundle.tables, dry_run, force)?);
+    changes.extend(sync_entities(state, bundle.queries, dry_run, force)?);
+    changes.extend(sync_entities(state, bundle.scripts, dry_run, force)?);
+    changes.extend(sync_entities(state, bundle.views, dry_run, force)?);
+
+    let mut removed = vec![];
+    changes.extend(remove_entities::<data::View, S>(state, &view_names, dry_run, force, on_dependents, &mut removed)?);
+    changes.extend(remove_entities::<data::Script, S>(state, &script_names, dry_run, force, on_dependents, &mut removed)?);
+    changes.extend(remove_entities::<data::DataQueryEntity, S>(state, &query_names, dry_run, force, on_dependents, &mut removed)?);
+    changes.extend(remove_entities::<data::DataStoreEntity, S>(state, &table_names, dry_run, force, on_dependents, &mut removed)?);
+
+    Ok(changes)
+}
+
+// Sync Actions
+#[derive(Debug)]
+pub struct PlanSync<S = ActionState>  {
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> PlanSync<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for PlanSync<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = SyncPlanResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling PlanSync");
+
+        let changes = sync_directory(state, true, false, &DependentsPolicy::Restrict)?
+            .into_iter()
+            .map(|(change, _)| change)
+            .collect();
+
+        ActionRes::new("planSync", SyncPlanResult { changes })
+    }
+}
+
+#[derive(Debug)]
+pub struct ApplySync<S = ActionState>  {
+    pub force: bool,
+    pub on_dependents: DependentsPolicy,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ApplySync<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    /// Drifted entities are skipped, unless `force` is set, in which case the directory wins.
+    /// Entities depending on a removed one are handled by `on_dependents`, like when deleting it
+    pub fn new(force: bool, on_dependents: DependentsPolicy) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            force,
+            on_dependents,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ApplySync<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = ApplySyncResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ApplySync");
+
+        let (applied, skipped): (Vec<_>, Vec<_>) = sync_directory(state, false, self.force, &self.on_dependents)?
+            .into_iter()
+            .partition(|(_, apply)| *apply);
+
+        ActionRes::new("applySync", ApplySyncResult {
+            applied: applied.into_iter().map(|(change, _)| change).collect(),
+            skipped: skipped.into_iter().map(|(change, _)| change).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use state::error::EntitySyncError;
+    use test_common::with_state;
+    use test_common::MockState;
+
+    #[test]
+    fn test_plan_sync_without_directory() {
+        with_state(|state| {
+            let result = PlanSync::<MockState>::new().call(&state);
+            assert_eq!(result.unwrap_err(), Error::EntitySync(EntitySyncError::NoSyncDirectory));
+        });
+    }
+}
@@ -23,6 +23,33 @@ use model::table::DatastoreActionOps;
 
 use state::ActionState;
 use state::StateFunctions;
+use state::authorization::AuthorizationOps;
+use state::table_audit::TableAuditOps;
+
+/// Every change to the table data ends up in the audit log of the table
+fn record_changes<S>(state: &S, table_name: &str, changes: &[data::DataChange]) -> Result<(), Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let user_id = match state.get_authorization().user_id() {
+        Some(user_id) => user_id,
+        None => {
+            warn!("changes to {:?} were made without a user, they are not audited", table_name);
+            return Ok(());
+        },
+    };
+
+    let entity_id = state
+        .get_entity_retreiver_functions()
+        .get_entity_id::<data::DataStoreEntity>(table_name)
+        .map_err(Error::Entity)?
+        .ok_or_else(|| Error::NotFound)?;
+
+    state
+        .get_table_audit()
+        .record_changes(entity_id, user_id, changes)
+        .map_err(Error::TableAudit)
+}
 
 // Table Actions
 #[derive(Debug)]
@@ -37,7 +64,7 @@ impl<S> QueryTableData<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(table_name: String, query: serde_json::Value) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+    pub fn new(table_name: String, query: serde_json::Value) -> WithPermissionRequired<WithUsageRecorded<WithTransaction<Self, S>, data::DataStoreEntity, S>, S> {
         let action = Self {
             table_name: table_name.to_owned(),
             query,
@@ -46,8 +73,9 @@ impl<S> QueryTableData<S>
         };
 
         let action_with_transaction = WithTransaction::new(action);
+        let action_with_usage = WithUsageRecorded::new(action_with_transaction, table_name.to_owned());
         let action_with_permission =
-            WithPermissionRequired::new(action_with_transaction, Permission::get_table_data(table_name));
+            WithPermissionRequired::new(action_with_usage, Permission::get_table_data(table_name));
 
         action_with_permission
     }
@@ -95,7 +123,7 @@ impl<S> InsertTableData<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(table_name: String, data: serde_json::Value) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(table_name: String, data: serde_json::Value) -> WithPermissionRequired<WithUsageRecorded<WithDispatch<WithTransaction<Self, S>, S>, data::DataStoreEntity, S>, S> {
         let channel = Channels::table(&table_name);
         let action = Self {
             table_name: table_name.to_owned(),
@@ -107,8 +135,9 @@ impl<S> InsertTableData<S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_usage = WithUsageRecorded::new(action_with_dispatch, table_name.to_owned());
         let action_with_permission =
-            WithPermissionRequired::new(action_with_dispatch, Permission::modify_table_data(table_name));
+            WithPermissionRequired::new(action_with_usage, Permission::modify_table_data(table_name));
 
         action_with_permission
     }
@@ -140,6 +169,10 @@ impl<S> Action<S> for InsertTableData<S>
                     OnDuplicate::Fail => table_controller.insert_row(&table, &self.data, true)
                 }.or_else(|err| Err(Error::Datastore(err)))
             })
+            .and_then(|mutation| {
+                record_changes(state, &self.table_name, &mutation.changes)?;
+                Ok(mutation.data)
+            })
             .and_then(|res| ActionRes::new("insertTableData", InsertTableDataResult(res)))
     }
 }
@@ -157,7 +190,7 @@ impl<S> ModifyTableData<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(table_name: String, keyed_data: serde_json::Value) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(table_name: String, keyed_data: serde_json::Value) -> WithPermissionRequired<WithUsageRecorded<WithDispatch<WithTransaction<Self, S>, S>, data::DataStoreEntity, S>, S> {
         let channel = Channels::table(&table_name);
         let action = Self {
             table_name: table_name.to_owned(),
@@ -169,8 +202,9 @@ impl<S> ModifyTableData<S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_usage = WithUsageRecorded::new(action_with_dispatch, table_name.to_owned());
         let action_with_permission =
-            WithPermissionRequired::new(action_with_dispatch, Permission::modify_table_data(table_name));
+            WithPermissionRequired::new(action_with_usage, Permission::modify_table_data(table_name));
 
         action_with_permission
     }
@@ -201,6 +235,10 @@ impl<S> Action<S> for ModifyTableData<S>
                     OnNotFound::Fail => table_controller.update_row(&table, &self.keyed_data, true)
                 }.or_else(|err| Err(Error::Datastore(err)))
             })
+            .and_then(|mutation| {
+                record_changes(state, &self.table_name, &mutation.changes)?;
+                Ok(mutation.data)
+            })
             .and_then(|res| ActionRes::new("modifyTableData", ModifyTableDataResult(res)))
     }
 }
@@ -218,7 +256,7 @@ impl<S> RemoveTableData<S>
     where
         for<'a> S: StateFunctions<'a>,
 {
-    pub fn new(table_name: String, keys: serde_json::Value) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+    pub fn new(table_name: String, keys: serde_json::Value) -> WithPermissionRequired<WithUsageRecorded<WithDispatch<WithTransaction<Self, S>, S>, data::DataStoreEntity, S>, S> {
         let channel = Channels::table(&table_name);
         let action = Self {
             table_name: table_name.to_owned(),
@@ -230,8 +268,9 @@ impl<S> RemoveTableData<S>
 
         let action_with_transaction = WithTransaction::new(action);
         let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_usage = WithUsageRecorded::new(action_with_dispatch, table_name.to_owned());
         let action_with_permission =
-            WithPermissionRequired::new(action_with_dispatch, Permission::modify_table_data(table_name));
+            WithPermissionRequired::new(action_with_usage, Permission::modify_table_data(table_name));
 
         action_with_permission
     }
@@ -262,10 +301,161 @@ impl<S> Action<S> for RemoveTableData<S>
                     OnNotFound::Fail => table_controller.delete_row(&table, &self.keys, true)
                 }.or_else(|err| Err(Error::Datastore(err)))
             })
+            .and_then(|mutation| {
+                record_changes(state, &self.table_name, &mutation.changes)?;
+                Ok(mutation.data)
+            })
             .and_then(|res| ActionRes::new("removeTableData", RemoveTableDataResult(res)))
     }
 }
 
+#[derive(Debug)]
+pub struct GetTableAuditLog<S = ActionState>  {
+    pub table_name: String,
+    pub filter: data::AuditLogFilter,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetTableAuditLog<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String, filter: data::AuditLogFilter) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            table_name: table_name.to_owned(),
+            filter,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::get_table_data(table_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetTableAuditLog<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = TableAuditLogResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetTableAuditLog");
+
+        let entity_id = state
+            .get_entity_retreiver_functions()
+            .get_entity_id::<data::DataStoreEntity>(&self.table_name)
+            .map_err(Error::Entity)?
+            .ok_or_else(|| Error::NotFound)?;
+
+        state
+            .get_table_audit()
+            .get_audit_log(entity_id, &self.filter)
+            .map_err(Error::TableAudit)
+            .and_then(|res| ActionRes::new("getTableAuditLog", TableAuditLogResult(res)))
+    }
+}
+
+#[derive(Debug)]
+pub struct RevertTableChanges<S = ActionState>  {
+    pub table_name: String,
+    pub range: data::RevertRange,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RevertTableChanges<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(table_name: String, range: data::RevertRange) -> WithPermissionRequired<WithDispatch<WithTransaction<Self, S>, S>, S> {
+        let channel = Channels::table(&table_name);
+        let action = Self {
+            table_name: table_name.to_owned(),
+            range,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_dispatch = WithDispatch::new(action_with_transaction, channel);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_dispatch, Permission::modify_table_data(table_name));
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for RevertTableChanges<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = RevertTableChangesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RevertTableChanges");
+
+        let table: data::DataStoreEntity = state
+            .get_entity_retreiver_functions()
+            .get_one(&self.table_name)
+            .map_err(Error::Entity)?
+            .ok_or_else(|| Error::NotFound)?;
+
+        let entity_id = state
+            .get_entity_retreiver_functions()
+            .get_entity_id::<data::DataStoreEntity>(&self.table_name)
+            .map_err(Error::Entity)?
+            .ok_or_else(|| Error::NotFound)?;
+
+        let filter = match &self.range {
+            data::RevertRange::Transactions { from_transaction, to_transaction } => data::AuditLogFilter {
+                from_transaction: Some(*from_transaction),
+                to_transaction: Some(*to_transaction),
+                ..data::AuditLogFilter::default()
+            },
+            data::RevertRange::Since { since } => data::AuditLogFilter {
+                start: Some(*since),
+                ..data::AuditLogFilter::default()
+            },
+        };
+
+        // undo the newest change first, so that every row goes back through the states it was in
+        let mut records = state
+            .get_table_audit()
+            .get_audit_log(entity_id, &filter)
+            .map_err(Error::TableAudit)?;
+        records.reverse();
+
+        let changes: Vec<data::DataChange> = records.iter().map(|record| record.change.to_owned()).collect();
+        let reverted = state
+            .get_table_controller()
+            .revert_changes(&table, &changes)
+            .map_err(Error::Datastore)?;
+
+        record_changes(state, &self.table_name, &reverted.changes)?;
+
+        let conflicts: Vec<data::RevertConflict> = reverted.conflicts
+            .into_iter()
+            .filter_map(|conflict| records.get(conflict.index).map(|record| data::RevertConflict {
+                transaction_id: record.transaction_id,
+                keys: record.change.keys.to_owned(),
+                expected: record.change.new.to_owned(),
+                current: conflict.current,
+            }))
+            .collect();
+
+        let reverted_transactions = records
+            .iter()
+            .map(|record| record.transaction_id)
+            .filter(|transaction_id| !conflicts.iter().any(|conflict| conflict.transaction_id == *transaction_id))
+            .collect();
+
+        ActionRes::new("revertTableChanges", RevertTableChangesResult {
+            reverted: reverted_transactions,
+            changes: reverted.changes,
+            conflicts,
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -318,4 +508,141 @@ mod test {
             println!("result: {:?}", &result);
         });
     }
+
+    #[test]
+    fn test_table_audit_log() {
+        with_state(|state| {
+            let table_name = format!("my_table{}", random_identifier());
+            let table: data::DataStoreEntity = from_value(json!({
+                "name": table_name,
+                "description": "table description",
+                "schema": {
+                    "columns": [
+                        {
+                            "name": "col_a",
+                            "dataType": "integer"
+                        },
+                        {
+                            "name": "col_b",
+                            "dataType": "integer"
+                        }
+                    ],
+                    "constraint": [
+                        { "key": "col_a" }
+                    ]
+                }
+            })).unwrap();
+
+            entity_actions::CreateEntity::<data::DataStoreEntity, MockState>::new(table)
+                .call(&state)
+                .unwrap();
+
+            InsertTableData::<MockState>::new(table_name.to_owned(), json!([{ "col_a": 42, "col_b": 43 }]))
+                .call(&state)
+                .unwrap();
+            RemoveTableData::<MockState>::new(table_name.to_owned(), json!([{ "col_a": 42 }]))
+                .call(&state)
+                .unwrap();
+
+            let TableAuditLogResult(records) = GetTableAuditLog::<MockState>::new(table_name, data::AuditLogFilter::default())
+                .call(&state)
+                .unwrap()
+                .get_data();
+
+            let operations: Vec<data::DataOperation> = records.iter().map(|x| x.change.operation.to_owned()).collect();
+            assert_eq!(operations, vec![data::DataOperation::Insert, data::DataOperation::Delete]);
+            assert_eq!(records[0].change.keys, json!({ "col_a": 42 }));
+            assert_eq!(records[1].change.old, Some(json!({ "col_a": 42, "col_b": 43 })));
+            assert_eq!(records[1].change.new, None);
+        });
+    }
+
+    #[test]
+    fn test_revert_table_changes() {
+        with_state(|state| {
+            let table_name = format!("my_table{}", random_identifier());
+            let table: data::DataStoreEntity = from_value(json!({
+                "name": table_name,
+                "description": "table description",
+                "schema": {
+                    "columns": [
+                        {
+                            "name": "col_a",
+                            "dataType": "integer"
+                        },
+                        {
+                            "name": "col_b",
+                            "dataType": "integer"
+                        }
+                    ],
+                    "constraint": [
+                        { "key": "col_a" }
+                    ]
+                }
+            })).unwrap();
+
+            entity_actions::CreateEntity::<data::DataStoreEntity, MockState>::new(table)
+                .call(&state)
+                .unwrap();
+
+            InsertTableData::<MockState>::new(table_name.to_owned(), json!([{ "col_a": 42, "col_b": 43 }]))
+                .call(&state)
+                .unwrap();
+            ModifyTableData::<MockState>::new(table_name.to_owned(), json!({
+                "columns": { "keys": ["col_a"], "values": ["col_b"] },
+                "data": [{ "keys": [42], "values": [44] }]
+            }))
+                .call(&state)
+                .unwrap();
+
+            let TableAuditLogResult(records) = GetTableAuditLog::<MockState>::new(table_name.to_owned(), data::AuditLogFilter::default())
+                .call(&state)
+                .unwrap()
+                .get_data();
+            let update_transaction = records[1].transaction_id;
+
+            let range = data::RevertRange::Transactions {
+                from_transaction: update_transaction,
+                to_transaction: update_transaction,
+            };
+            let result = RevertTableChanges::<MockState>::new(table_name.to_owned(), range)
+                .call(&state)
+                .unwrap()
+                .get_data();
+
+            assert_eq!(result.reverted, vec![update_transaction]);
+            assert_eq!(result.conflicts, vec![]);
+            assert_eq!(result.changes[0].new, Some(json!({ "col_a": 42, "col_b": 43 })));
+
+            // the row changed since, so reverting the insert is a conflict
+            InsertTableData::<MockState>::new(table_name.to_owned(), json!([{ "col_a": 50, "col_b": 51 }]))
+                .call(&state)
+                .unwrap();
+            ModifyTableData::<MockState>::new(table_name.to_owned(), json!({
+                "columns": { "keys": ["col_a"], "values": ["col_b"] },
+                "data": [{ "keys": [50], "values": [52] }]
+            }))
+                .call(&state)
+                .unwrap();
+
+            let TableAuditLogResult(records) = GetTableAuditLog::<MockState>::new(table_name.to_owned(), data::AuditLogFilter::default())
+                .call(&state)
+                .unwrap()
+                .get_data();
+            let insert_transaction = records[records.len() - 2].transaction_id;
+
+            let range = data::RevertRange::Transactions {
+                from_transaction: insert_transaction,
+                to_transaction: insert_transaction,
+            };
+            let result = RevertTableChanges::<MockState>::new(table_name, range)
+                .call(&state)
+                .unwrap()
+                .get_data();
+
+            assert_eq!(result.reverted, Vec::<i64>::new());
+            assert_eq!(result.conflicts[0].transaction_id, insert_transaction);
+            assert_eq!(result.conflicts[0].current, Some(json!({ "col_a": 50, "col_b": 52 })));
+        });
+    }
 }
\ No newline at end of file
@@ -0,0 +1,411 @@
+use std::result::Result::Ok;
+use std::marker::PhantomData;
+
+use data;
+use data::Named;
+use data::EntityFilter;
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+use model::entity::RetrieverFunctions;
+use model::entity::RawEntityTypes;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::authorization::AuthorizationOps;
+use state::tag_management::TagManagementOps;
+
+/// Looks up the entity that the tag is attached to, tags belong to the entity, not to a revision
+pub(super) fn get_entity_id<S>(state: &S, type_name: &str, name: &str) -> Result<i64, Error>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    let retriever = state.get_entity_retreiver_functions();
+    let entity_id = match type_name {
+        x if x == data::DataStoreEntity::TYPE_NAME => retriever.get_entity_id::<data::DataStoreEntity>(name),
+        x if x == data::DataQueryEntity::TYPE_NAME => retriever.get_entity_id::<data::DataQueryEntity>(name),
+        x if x == data::Script::TYPE_NAME => retriever.get_entity_id::<data::Script>(name),
+        x if x == data::View::TYPE_NAME => retriever.get_entity_id::<data::View>(name),
+        _ => return Err(Error::NotFound),
+    };
+
+    entity_id
+        .map_err(Error::Entity)?
+        .ok_or_else(|| Error::NotFound)
+}
+
+// Tag Actions
+#[derive(Debug)]
+pub struct GetAllTags<S = ActionState>  {
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetAllTags<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new() -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetAllTags<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = AllTagsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        state
+            .get_tag_management()
+            .get_all_tags()
+            .map_err(Error::TagManagement)
+            .and_then(|res| ActionRes::new("getAllTags", AllTagsResult(res)))
+    }
+}
+
+#[derive(Debug)]
+pub struct CreateTag<S = ActionState>  {
+    pub tag: data::Tag,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> CreateTag<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(tag: data::Tag) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            tag,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for CreateTag<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = TagResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        state
+            .get_tag_management()
+            .create_tag(&self.tag)
+            .map_err(Error::TagManagement)
+            .and_then(|res| ActionRes::new("createTag", TagResult(res)))
+    }
+}
+
+#[derive(Debug)]
+pub struct DeleteTag<S = ActionState>  {
+    pub name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> DeleteTag<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    /// The tag is removed from every entity, so only admins can do this
+    pub fn new(name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            name,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for DeleteTag<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = TagResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        state
+            .get_tag_management()
+            .delete_tag(&self.name)
+            .map_err(Error::TagManagement)
+            .and_then(|res| ActionRes::new("deleteTag", TagResult(res)))
+    }
+}
+
+#[derive(Debug)]
+pub struct GetEntityTags<S = ActionState>  {
+    pub type_name: String,
+    pub name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetEntityTags<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(type_name: String, name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let permission = Permission::GetEntity {
+            type_name: type_name.to_owned(),
+            entity_name: name.to_owned(),
+        };
+        let action = Self {
+            type_name,
+            name,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithPermissionRequired::new(action_with_transaction, permission);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetEntityTags<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = AllTagsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let entity_id = get_entity_id(state, &self.type_name, &self.name)?;
+
+        state
+            .get_tag_management()
+            .get_entity_tags(entity_id)
+            .map_err(Error::TagManagement)
+            .and_then(|res| ActionRes::new("getEntityTags", AllTagsResult(res)))
+    }
+}
+
+#[derive(Debug)]
+pub struct AttachTag<S = ActionState>  {
+    pub type_name: String,
+    pub name: String,
+    pub tag_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> AttachTag<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(type_name: String, name: String, tag_name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let permission = Permission::ModifyEntity {
+            type_name: type_name.to_owned(),
+            entity_name: name.to_owned(),
+        };
+        let action = Self {
+            type_name,
+            name,
+            tag_name,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithPermissionRequired::new(action_with_transaction, permission);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for AttachTag<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = TagResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let entity_id = get_entity_id(state, &self.type_name, &self.name)?;
+
+        state
+            .get_tag_management()
+            .attach_tag(entity_id, &self.tag_name)
+            .map_err(Error::TagManagement)
+            .and_then(|res| ActionRes::new("attachTag", TagResult(res)))
+    }
+}
+
+#[derive(Debug)]
+pub struct DetachTag<S = ActionState>  {
+    pub type_name: String,
+    pub name: String,
+    pub tag_name: String,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> DetachTag<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(type_name: String, name: String, tag_name: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let permission = Permission::ModifyEntity {
+            type_name: type_name.to_owned(),
+            entity_name: name.to_owned(),
+        };
+        let action = Self {
+            type_name,
+            name,
+            tag_name,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithPermissionRequired::new(action_with_transaction, permission);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for DetachTag<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = TagResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let entity_id = get_entity_id(state, &self.type_name, &self.name)?;
+
+        state
+            .get_tag_management()
+            .detach_tag(entity_id, &self.tag_name)
+            .map_err(Error::TagManagement)
+            .and_then(|res| ActionRes::new("detachTag", TagResult(res)))
+    }
+}
+
+// Search Action
+#[derive(Debug)]
+pub struct SearchEntities<S = ActionState>  {
+    pub filter: EntityFilter,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> SearchEntities<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(filter: EntityFilter) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            filter,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+/// Same as `WithFilterListByPermission`, but for a single type out of many
+fn search_type<T, S>(state: &S, filter: &EntityFilter) -> Result<Vec<T>, Error>
+    where
+        T: RawEntityTypes,
+        for<'a> S: StateFunctions<'a>,
+{
+    let entities: Vec<T> = state
+        .get_entity_retreiver_functions()
+        .get_all_filtered(filter)
+        .map_err(Error::Entity)?;
+
+    let authorization = state.get_authorization();
+    if authorization.is_admin() {
+        return Ok(entities);
+    }
+
+    let user_permissions = authorization.permissions();
+    let filtered_entities = entities
+        .into_iter()
+        .filter(|x| {
+            let required = Permission::read_entity::<T>(x.my_name().to_owned());
+            user_permissions.contains(&required)
+        })
+        .collect();
+
+    Ok(filtered_entities)
+}
+
+impl<S> Action<S> for SearchEntities<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = SearchEntitiesResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        let result = SearchEntitiesResult {
+            tables: search_type(state, &self.filter)?,
+            queries: search_type(state, &self.filter)?,
+            scripts: search_type(state, &self.filter)?,
+            views: search_type(state, &self.filter)?,
+        };
+
+        ActionRes::new("searchEntities", result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+    use model::actions::entity_actions::*;
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+
+    #[test]
+    fn test_tag_and_search_entity() {
+        with_state(|state| {
+            let name = format!("my_query_{}", random_identifier());
+            let tag_name = format!("my_tag_{}", random_identifier());
+            let new_query: data::DataQueryEntity = from_value(json!({
+                "name": name,
+                "description": "a very searchable description",
+                "statement": "SELECT * FROM a_table"
+            })).unwrap();
+            CreateEntity::<data::DataQueryEntity, MockState>::new(new_query).call(&state).unwrap();
+
+            let tag = data::Tag { name: tag_name.to_owned(), description: "".to_string() };
+            CreateTag::<MockState>::new(tag).call(&state).unwrap();
+            AttachTag::<MockState>::new("query".to_string(), name.to_owned(), tag_name.to_owned()).call(&state).unwrap();
+
+            let AllTagsResult(tags) = GetEntityTags::<MockState>::new("query".to_string(), name.to_owned())
+                .call(&state)
+                .unwrap()
+                .get_data();
+            assert_eq!(tags.len(), 1);
+            assert_eq!(tags[0].name, tag_name);
+
+            let filter = EntityFilter { tag: Some(tag_name.to_owned()), ..EntityFilter::default() };
+            let GetAllEntitiesResult(queries) = GetAllEntities::<data::DataQueryEntity, MockState>::new(false, filter)
+                .call(&state)
+                .unwrap()
+                .get_data();
+            assert_eq!(queries.len(), 1);
+            assert_eq!(queries[0].my_name(), name);
+
+            let filter = EntityFilter { search: Some("searchable".to_string()), ..EntityFilter::default() };
+            let data = SearchEntities::<MockState>::new(filter).call(&state).unwrap().get_data();
+            assert!(data.queries.iter().any(|x| x.my_name() == name));
+
+            DetachTag::<MockState>::new("query".to_string(), name.to_owned(), tag_name.to_owned()).call(&state).unwrap();
+            DeleteTag::<MockState>::new(tag_name).call(&state).unwrap();
+        });
+    }
+}
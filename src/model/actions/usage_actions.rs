@@ -0,0 +1,60 @@
+use std::result::Result::Ok;
+use std::marker::PhantomData;
+
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::usage_tracking::UsageTrackingOps;
+
+// Usage Action
+#[derive(Debug)]
+pub struct GetEntityUsage<S = ActionState>  {
+    pub domain_name: String,
+    pub start_time: chrono::NaiveDateTime,
+    pub end_time: chrono::NaiveDateTime,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetEntityUsage<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(domain_name: String, start_time: chrono::NaiveDateTime, end_time: chrono::NaiveDateTime) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            domain_name,
+            start_time,
+            end_time,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetEntityUsage<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = EntityUsageResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetEntityUsage");
+
+        state
+            .get_usage_tracking()
+            .get_entity_usage(&self.domain_name, self.start_time, self.end_time)
+            .map_err(Error::UsageTracking)
+            .and_then(|res| ActionRes::new("getEntityUsage", EntityUsageResult(res)))
+    }
+}
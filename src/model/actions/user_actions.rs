@@ -4,6 +4,7 @@ use std::marker::PhantomData;
 use data;
 use data::permissions::*;
 use data::auth::SessionToken;
+use data::auth::UserInfo;
 
 use model::actions::results::*;
 use model::actions::error::Error;
@@ -11,11 +12,13 @@ use model::actions::decorator::*;
 use model::actions::Action;
 use model::actions::ActionRes;
 use model::actions::ActionResult;
+use model::actions::sso_actions::login_with_directory;
 use state::ActionState;
 use state::StateFunctions;
 use state::user_management::UserManagementOps;
 use state::authentication::AuthenticationOps;
 use state::PubSubOps;
+use state::error::UserManagementError;
 use state::authorization::AuthorizationOps;
 
 use auth::send_mail::EmailOps;
@@ -31,37 +34,98 @@ pub struct Login<S = ActionState> {
 impl<S> Login<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    pub fn new(user_identifier: String, password: String) -> WithTransaction<Self, S> {
-        let action = Self {
+    /// Not wrapped in a transaction, the failed logins have to be recorded even though the action fails,
+    /// the login itself is run in one
+    pub fn new(user_identifier: String, password: String) -> Self {
+        Self {
             user_identifier,
             password,
             phantom_data: PhantomData,
+        }
+    }
+
+    fn login(&self, state: &S) -> Result<LoginResult, Error> {
+        let directory_user = login_with_directory(state, &state.get_authentication(), &self.user_identifier, &self.password)?;
+        let user_management = state.get_user_management(); //TODO: this should be the responsibility of the authorization
+        let user = match directory_user {
+            Some(user) => user,
+            None => match user_management.get_user(&self.user_identifier, &self.password) {
+                Ok(user) => user,
+                // the password was right, but it's only good for setting a new one
+                Err(UserManagementError::PasswordExpired) => {
+                    let password_reset_token = user_management
+                        .create_password_reset_token(&self.user_identifier)
+                        .map_err(Error::UserManagement)?;
+
+                    return Ok(LoginResult::PasswordChangeRequired(data::auth::PasswordChangeRequired {
+                        password_change_token: password_reset_token.token,
+                        expires_at: password_reset_token.expires_at,
+                    }));
+                },
+                Err(err) => return Err(Error::UserManagement(err)),
+            },
         };
 
-        let action_with_transaction = WithTransaction::new(action);
+        create_session_or_challenge(state, user)
+    }
+}
 
-        action_with_transaction
+/// Every kind of login ends here, users that have to use two-factor authentication get a challenge instead of a session
+pub(super) fn create_session_or_challenge<S>(state: &S, user: UserInfo) -> Result<LoginResult, Error>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    let authentication = state.get_authentication();
+    let is_mfa_needed = authentication.is_mfa_enabled(user.user_id).map_err(Error::UserManagement)? ||
+        authentication.is_mfa_required(user.user_id).map_err(Error::UserManagement)?;
+    if is_mfa_needed {
+        let challenge = authentication
+            .create_mfa_challenge(user.user_id)
+            .map_err(Error::UserManagement)?;
+
+        return Ok(LoginResult::MfaChallenge(challenge));
     }
+
+    let session_token = authentication
+        .create_session(user)
+        .map_err(Error::UserManagement)?;
+
+    Ok(LoginResult::Session(session_token))
 }
 
 impl<S> Action<S> for Login<S>
     where for<'a> S: GetSecrets + StateFunctions<'a>,
 {
-    type Ret = SessionToken;
+    type Ret = LoginResult;
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
         debug!("Calling Login");
 
-        let user = state
-            .get_user_management() //TODO: this should be the responsibility of the authorization
-            .get_user(&self.user_identifier, &self.password)
+        // checked before the password, so that throttled logins don't cost a password hash
+        let authentication = state.get_authentication();
+        authentication
+            .check_login_throttle(&self.user_identifier)
             .map_err(Error::UserManagement)?;
 
-        let session_token = state
-            .get_authentication()
-            .create_session(user)
-            .map_err(Error::UserManagement)?;
-
-        ActionRes::new("login", session_token)
+        let result = state.transaction::<LoginResult, Error, _>(|| self.login(state));
+        match result {
+            Ok(login_result) => {
+                // with two-factor authentication the login isn't done until the code was checked
+                if let LoginResult::Session(_) = login_result {
+                    authentication
+                        .clear_failed_logins(&self.user_identifier)
+                        .map_err(Error::UserManagement)?;
+                }
+
+                ActionRes::new("login", login_result)
+            },
+            Err(Error::UserManagement(UserManagementError::Unauthorized)) => {
+                authentication
+                    .record_failed_login(&self.user_identifier)
+                    .map_err(Error::UserManagement)?;
+
+                Err(Error::UserManagement(UserManagementError::Unauthorized))
+            },
+            Err(err) => Err(err),
+        }
     }
 }
 
@@ -129,6 +193,101 @@ impl<S> Action<S> for Logout<S>
     fn call(&self, state: &S) -> ActionResult<Self::Ret> {
         debug!("Calling Logout");
 
+        let authorization = state.get_authorization();
+        let user_id = authorization
+            .user_id()
+            .ok_or_else(|| {
+                error!("This is unexpected. the user should already be logged in at this point");
+                Error::Unknown
+            })?;
+
+        // tokens from before sessions were tracked end all of the user's sessions
+        let authentication = state.get_authentication();
+        match authorization.session_id() {
+            Some(session_id) => authentication.revoke_session(user_id, session_id),
+            None => authentication.delete_session(user_id),
+        }.map_err(Error::UserManagement)?;
+
+        ActionRes::new("logout", ())
+    }
+}
+
+
+
+/// User Auth: List the sessions of the logged in user
+#[derive(Debug)]
+pub struct ListMySessions<S = ActionState> {
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ListMySessions<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new() -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ListMySessions<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = SessionsResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ListMySessions");
+
+        let authorization = state.get_authorization();
+        let user_id = authorization
+            .user_id()
+            .ok_or_else(|| {
+                error!("This is unexpected. the user should already be logged in at this point");
+                Error::Unknown
+            })?;
+
+        state
+            .get_authentication()
+            .get_sessions(user_id, authorization.session_id())
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("listMySessions", SessionsResult(res)))
+    }
+}
+
+/// User Auth: End one of the sessions of the logged in user, i.e. on a lost device
+#[derive(Debug)]
+pub struct RevokeSession<S = ActionState> {
+    session_id: i64,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RevokeSession<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(session_id: i64) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            session_id,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for RevokeSession<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = ();
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RevokeSession");
+
         let user_id = state
             .get_authorization()
             .user_id()
@@ -136,16 +295,134 @@ impl<S> Action<S> for Logout<S>
                 error!("This is unexpected. the user should already be logged in at this point");
                 Error::Unknown
             })?;
-        let session_token = state
+
+        state
             .get_authentication()
-            .delete_session(user_id)
+            .revoke_session(user_id, self.session_id)
             .map_err(Error::UserManagement)?;
 
-        ActionRes::new("logout", ())
+        ActionRes::new("revokeSession", ())
+    }
+}
+
+/// User Auth: Log the user out of every device
+#[derive(Debug)]
+pub struct ForceLogout<S = ActionState> {
+    user_identifier: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ForceLogout<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(user_identifier: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            user_identifier,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ForceLogout<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = ();
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ForceLogout");
+
+        state
+            .get_authentication()
+            .revoke_user_sessions(&self.user_identifier)
+            .map_err(Error::UserManagement)?;
+
+        ActionRes::new("forceLogout", ())
+    }
+}
+
+/// User Auth: Lift the lockout after too many failed logins
+#[derive(Debug)]
+pub struct UnlockUser<S = ActionState> {
+    user_identifier: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> UnlockUser<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(user_identifier: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            user_identifier,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for UnlockUser<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = ();
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling UnlockUser");
+
+        state
+            .get_authentication()
+            .unlock_user(&self.user_identifier)
+            .map_err(Error::UserManagement)?;
+
+        ActionRes::new("unlockUser", ())
     }
 }
 
+/// User Auth: The latest failed logins, of one user or of everyone
+#[derive(Debug)]
+pub struct GetLoginFailures<S = ActionState> {
+    user_identifier: Option<String>,
+    phantom_data: PhantomData<(S)>,
+}
 
+impl<S> GetLoginFailures<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(user_identifier: Option<String>) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            user_identifier,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for GetLoginFailures<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = LoginFailuresResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetLoginFailures");
+
+        state
+            .get_authentication()
+            .get_login_failures(self.user_identifier.to_owned())
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("getLoginFailures", LoginFailuresResult(res)))
+    }
+}
 
 /// User Auth: Get All users
 #[derive(Debug)]
@@ -271,6 +548,177 @@ impl<S> Action<S> for RemoveUser<S>
     }
 }
 
+/// User Auth: Change the email or display name, of the logged in user or, for user admins, of anyone else
+#[derive(Debug)]
+pub struct ModifyUser<S = ActionState> {
+    user_identifier: Option<String>,
+    modification: data::auth::UserModification,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ModifyUser<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(user_identifier: Option<String>, modification: data::auth::UserModification) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            user_identifier,
+            modification,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ModifyUser<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = UserResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ModifyUser");
+
+        let authorization = state.get_authorization();
+        let username = authorization
+            .username()
+            .ok_or_else(|| {
+                error!("This is unexpected. the user should already be logged in at this point");
+                Error::Unknown
+            })?;
+
+        let is_user_admin = authorization.is_admin() ||
+            authorization.permissions().contains(&Permission::user_admin());
+        let user_management = state.get_user_management();
+        let user_info = match &self.user_identifier {
+            Some(user_identifier) => user_management
+                .get_user_info(user_identifier)
+                .map_err(|err| match err {
+                    // others can't find out which users exist
+                    UserManagementError::NotFound if !is_user_admin => Error::Unauthorized,
+                    _ => Error::UserManagement(err),
+                })?,
+            None => user_management
+                .get_user_info(&username)
+                .map_err(Error::UserManagement)?,
+        };
+
+        if authorization.user_id() == Some(user_info.user_id) {
+            // the email is where password resets go, so a stolen session isn't enough to change it
+            if self.modification.email.is_some() {
+                let current_password = self.modification.current_password
+                    .to_owned()
+                    .ok_or(Error::UserManagement(UserManagementError::Unauthorized))?;
+                user_management
+                    .verify_user_password(&user_info.username, &current_password)
+                    .map_err(Error::UserManagement)?;
+            }
+        } else if !is_user_admin {
+            return Err(Error::Unauthorized);
+        }
+
+        user_management
+            .modify_user(&user_info.username, &self.modification)
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("modifyUser", UserResult(res)))
+    }
+}
+
+/// User Auth: Suspend a user, they are logged out and can't log in until they are enabled again
+#[derive(Debug)]
+pub struct DisableUser<S = ActionState> {
+    user_identifier: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> DisableUser<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(user_identifier: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            user_identifier,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for DisableUser<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = UserResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling DisableUser");
+
+        let user_management = state.get_user_management();
+        let user_info = user_management
+            .get_user_info(&self.user_identifier)
+            .map_err(Error::UserManagement)?;
+
+        // nobody would be left to enable them again
+        if state.get_authorization().user_id() == Some(user_info.user_id) {
+            debug!("Users can't disable themselves");
+            return Err(Error::Unauthorized);
+        }
+
+        let user = user_management
+            .set_user_disabled(&user_info.username, true)
+            .map_err(Error::UserManagement)?;
+
+        state
+            .get_authentication()
+            .revoke_user_sessions(&user_info.username)
+            .map_err(Error::UserManagement)?;
+
+        ActionRes::new("disableUser", UserResult(user))
+    }
+}
+
+/// User Auth: Let a disabled user log in again
+#[derive(Debug)]
+pub struct EnableUser<S = ActionState> {
+    user_identifier: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> EnableUser<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(user_identifier: String) -> WithPermissionRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            user_identifier,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_transaction, Permission::user_admin());
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for EnableUser<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = UserResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling EnableUser");
+
+        state
+            .get_user_management()
+            .set_user_disabled(&self.user_identifier, false)
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("enableUser", UserResult(res)))
+    }
+}
+
 /// User Auth: Email user for invitation
 #[derive(Debug)]
 pub struct InviteUser<S = ActionState> {
@@ -404,7 +852,156 @@ impl<S> Action<S> for SetUserPassword<S>
     }
 }
 
-//TODO: Change user password / image
+/// User Auth: Change the password of the logged in user
+#[derive(Debug)]
+pub struct ChangeMyPassword<S = ActionState> {
+    old_password: String,
+    new_password: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ChangeMyPassword<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(old_password: String, new_password: String) -> WithLoginRequired<WithTransaction<Self, S>, S> {
+        let action = Self {
+            old_password,
+            new_password,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_permission = WithLoginRequired::new(action_with_transaction);
+
+        action_with_permission
+    }
+}
+
+impl<S> Action<S> for ChangeMyPassword<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = UserResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ChangeMyPassword");
+
+        let username = state
+            .get_authorization()
+            .username()
+            .ok_or_else(|| {
+                error!("This is unexpected. the user should already be logged in at this point");
+                Error::Unknown
+            })?;
+
+        let user_management = state.get_user_management();
+        user_management
+            .verify_user_password(&username, &self.old_password)
+            .map_err(Error::UserManagement)?;
+
+        user_management
+            .modify_user_password(&username, &self.new_password)
+            .map_err(Error::UserManagement)
+            .and_then(|res| ActionRes::new("changeMyPassword", UserResult(res)))
+    }
+}
+
+/// User Auth: Email a password reset link, doesn't need a login
+#[derive(Debug)]
+pub struct RequestPasswordReset<S = ActionState> {
+    user_identifier: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> RequestPasswordReset<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(user_identifier: String) -> WithTransaction<Self, S> {
+        let action = Self {
+            user_identifier,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+
+        action_with_transaction
+    }
+}
+
+impl<S> Action<S> for RequestPasswordReset<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = ();
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling RequestPasswordReset");
+
+        let password_reset_token = state
+            .get_user_management()
+            .create_password_reset_token(&self.user_identifier);
+
+        // the response is the same whether the user exists or not, so that it can't be used to look up users
+        match password_reset_token {
+            Ok(password_reset_token) => state
+                .get_email_sender()
+                .send_password_reset(password_reset_token)
+                .map_err(Error::EmailError)?,
+            Err(UserManagementError::NotFound) => info!("No user {:?} for the password reset", &self.user_identifier),
+            Err(UserManagementError::Unauthorized) => info!("The password of {:?} can not be reset", &self.user_identifier),
+            Err(err) => return Err(Error::UserManagement(err)),
+        };
+
+        ActionRes::new("requestPasswordReset", ())
+    }
+}
+
+/// User Auth: Set a new password with the emailed token, all of the user's sessions are revoked
+#[derive(Debug)]
+pub struct ConfirmPasswordReset<S = ActionState> {
+    token: String,
+    new_password: String,
+    phantom_data: PhantomData<(S)>,
+}
+
+impl<S> ConfirmPasswordReset<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    pub fn new(token: String, new_password: String) -> WithTransaction<Self, S> {
+        let action = Self {
+            token,
+            new_password,
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+
+        action_with_transaction
+    }
+}
+
+impl<S> Action<S> for ConfirmPasswordReset<S>
+    where for<'a> S: GetSecrets + StateFunctions<'a>,
+{
+    type Ret = UserResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling ConfirmPasswordReset");
+
+        let user_management = state.get_user_management();
+        let user = user_management
+            .use_password_reset_token(&self.token)
+            .map_err(Error::UserManagement)?;
+
+        let result = user_management
+            .modify_user_password(&user.username, &self.new_password)
+            .map_err(Error::UserManagement)?;
+
+        state
+            .get_authentication()
+            .delete_session(user.user_id)
+            .map_err(Error::UserManagement)?;
+
+        ActionRes::new("confirmPasswordReset", UserResult(result))
+    }
+}
+
+//TODO: Change user image
 
 /// Role Auth: Add Role
 #[derive(Debug)]
@@ -932,8 +1529,10 @@ mod test {
             let create_action
             = Login::<MockState>::new(name.to_owned(), "hunter2".to_string());
             let result = create_action.call(&state);
-            let data = result.unwrap().get_data();
-            //assert_eq!(data, Some(()));
+            let data = match result.unwrap().get_data() {
+                LoginResult::Session(session_token) => session_token,
+                _ => panic!("the user doesn't use two-factor authentication"),
+            };
             let SessionToken::Bearer { access_token, expires_in, refresh_token } = data;
             let auth: AuthClaims = jsonwebtoken::decode(&access_token, "A".as_ref(), &jsonwebtoken::Validation::default())
                 .unwrap().claims;
@@ -1120,4 +1719,250 @@ mod test {
         });
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_password_reset() {
+        with_state(|state| {
+            let name = format!("bob_{}", random_identifier());
+            let email = format!("stuff{}@example.com", random_identifier());
+            let new_user: data::auth::NewUser = from_value(json!({
+                "username": name,
+                "email": email,
+                "password": "hunter2"
+            })).unwrap();
+            AddUser::<MockState>::new(new_user).call(&state).unwrap();
+
+            let unknown_user = format!("nobody_{}", random_identifier());
+            RequestPasswordReset::<MockState>::new(unknown_user).call(&state).unwrap();
+
+            let password_reset_token = state
+                .get_user_management()
+                .create_password_reset_token(&email)
+                .unwrap();
+            assert_eq!(password_reset_token.email, email);
+
+            let result = ConfirmPasswordReset::<MockState>::new(password_reset_token.token.to_owned(), "correct horse".to_string()).call(&state);
+            let UserResult(data) = result.unwrap().get_data();
+            assert_eq!(data.username, name);
+
+            let user = state.get_user_management().get_user(&name, "correct horse");
+            assert!(user.is_ok());
+
+            let result = ConfirmPasswordReset::<MockState>::new(password_reset_token.token, "hunter3".to_string()).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+        });
+    }
+
+    #[test]
+    fn test_revoke_session() {
+        with_state(|state| {
+            let name = format!("bob_{}", random_identifier());
+            let email = format!("stuff{}@example.com", random_identifier());
+            let new_user: data::auth::NewUser = from_value(json!({
+                "username": name,
+                "email": email,
+                "password": "hunter2"
+            })).unwrap();
+            AddUser::<MockState>::new(new_user).call(&state).unwrap();
+
+            Login::<MockState>::new(name.to_owned(), "hunter2".to_string()).call(&state).unwrap();
+            Login::<MockState>::new(name.to_owned(), "hunter2".to_string()).call(&state).unwrap();
+
+            let user = state.get_user_management().get_user(&name, "hunter2").unwrap();
+            let authentication = state.get_authentication();
+            let sessions = authentication.get_sessions(user.user_id, None).unwrap();
+            assert_eq!(sessions.len(), 2);
+
+            let session_id = sessions[0].session_id;
+            assert!(authentication.is_session_active(session_id).unwrap());
+            authentication.revoke_session(user.user_id, session_id).unwrap();
+            assert!(!authentication.is_session_active(session_id).unwrap());
+            assert_eq!(authentication.revoke_session(user.user_id, session_id), Err(UserManagementError::NotFound));
+
+            ForceLogout::<MockState>::new(name.to_owned()).call(&state).unwrap();
+            let sessions = authentication.get_sessions(user.user_id, None).unwrap();
+            assert!(sessions.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_modify_and_disable_user() {
+        with_state(|state| {
+            let name = format!("bob_{}", random_identifier());
+            let new_user: data::auth::NewUser = from_value(json!({
+                "username": name,
+                "email": format!("stuff{}@example.com", random_identifier()),
+                "password": "hunter2"
+            })).unwrap();
+            AddUser::<MockState>::new(new_user).call(&state).unwrap();
+
+            let email = format!("other{}@example.com", random_identifier());
+            let modification: data::auth::UserModification = from_value(json!({
+                "email": email,
+            })).unwrap();
+            let user = ModifyUser::<MockState>::new(Some(name.to_owned()), modification.to_owned()).call(&state).unwrap().get_data().0;
+            assert_eq!(user.email, email);
+            assert_eq!(user.display_name, name);
+
+            let other_name = format!("carol_{}", random_identifier());
+            let new_user: data::auth::NewUser = from_value(json!({
+                "username": other_name,
+                "email": format!("stuff{}@example.com", random_identifier()),
+                "password": "hunter2"
+            })).unwrap();
+            AddUser::<MockState>::new(new_user).call(&state).unwrap();
+
+            // the email is taken now
+            let result = ModifyUser::<MockState>::new(Some(other_name.to_owned()), modification.to_owned()).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::AlreadyExists));
+
+            let modification_by_name: data::auth::UserModification = from_value(json!({ "email": name })).unwrap();
+            let result = ModifyUser::<MockState>::new(Some(other_name.to_owned()), modification_by_name).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::InvalidEmail));
+
+            // naming yourself is the same as editing your own user, so the password is still needed
+            let result = ModifyUser::<MockState>::new(Some("admin".to_string()), modification).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+
+            // an email can't be the username of someone else either
+            let modification: data::auth::UserModification = from_value(json!({ "email": format!("{}@example.com", name) })).unwrap();
+            ModifyUser::<MockState>::new(Some(name.to_owned()), modification).call(&state).unwrap();
+            let new_user: data::auth::NewUser = from_value(json!({
+                "username": format!("{}@example.com", name),
+                "email": format!("someone{}@example.com", random_identifier()),
+                "password": "hunter2"
+            })).unwrap();
+            let result = AddUser::<MockState>::new(new_user).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::AlreadyExists));
+
+            // changing your own email takes your password
+            let modification: data::auth::UserModification = from_value(json!({
+                "email": format!("admin{}@example.com", random_identifier()),
+                "currentPassword": "wrong_password",
+            })).unwrap();
+            let result = ModifyUser::<MockState>::new(None, modification).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+
+            // the admin is the same user by username and by email
+            let result = DisableUser::<MockState>::new("admin".to_string()).call(&state);
+            assert_eq!(result.unwrap_err(), Error::Unauthorized);
+
+            Login::<MockState>::new(name.to_owned(), "hunter2".to_string()).call(&state).unwrap();
+            let user_id = state.get_user_management().get_user(&name, "hunter2").unwrap().user_id;
+            let password_reset_token = state.get_user_management().create_password_reset_token(&name).unwrap();
+            let authentication = state.get_authentication();
+
+            DisableUser::<MockState>::new(format!("{}@example.com", name)).call(&state).unwrap();
+            assert!(authentication.get_sessions(user_id, None).unwrap().is_empty());
+            let result = state.get_user_management().use_password_reset_token(&password_reset_token.token);
+            assert_eq!(result.unwrap_err(), UserManagementError::Unauthorized);
+            let result = Login::<MockState>::new(name.to_owned(), "hunter2".to_string()).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+
+            EnableUser::<MockState>::new(name.to_owned()).call(&state).unwrap();
+            Login::<MockState>::new(name.to_owned(), "hunter2".to_string()).call(&state).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_ambiguous_user_identifier() {
+        with_state(|state| {
+            let name = format!("bob_{}@example.com", random_identifier());
+            let new_user: data::auth::NewUser = from_value(json!({
+                "username": name,
+                "email": format!("stuff{}@example.com", random_identifier()),
+                "password": "hunter2"
+            })).unwrap();
+            AddUser::<MockState>::new(new_user).call(&state).unwrap();
+
+            // someone else's email can't be the username of the first user, lookups would match both of them
+            let other_user: data::auth::NewUser = from_value(json!({
+                "username": format!("alice_{}", random_identifier()),
+                "email": name,
+                "password": "hunter2"
+            })).unwrap();
+            let result = AddUser::<MockState>::new(other_user).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::AlreadyExists));
+
+            let result = AddUser::<MockState>::new(from_value(json!({
+                "username": format!("alice_{}", random_identifier()),
+                "email": "not an email",
+                "password": "hunter2"
+            })).unwrap()).call(&state);
+            assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::InvalidEmail));
+        });
+    }
+
+    #[test]
+    fn test_login_throttle() {
+        with_state(|state| {
+            let name = format!("bob_{}", random_identifier());
+            let email = format!("stuff{}@example.com", random_identifier());
+            let new_user: data::auth::NewUser = from_value(json!({
+                "username": name,
+                "email": email,
+                "password": "hunter2"
+            })).unwrap();
+            AddUser::<MockState>::new(new_user).call(&state).unwrap();
+
+            // the username and the email are counted together
+            for user_identifier in &[&name, &email, &name, &email] {
+                let result = Login::<MockState>::new(user_identifier.to_string(), "wrong_password".to_string()).call(&state);
+                assert_eq!(result.unwrap_err(), Error::UserManagement(UserManagementError::Unauthorized));
+            }
+
+            // even the right password has to wait
+            let result = Login::<MockState>::new(name.to_owned(), "hunter2".to_string()).call(&state);
+            match result.unwrap_err() {
+                Error::UserManagement(UserManagementError::TooManyAttempts(_)) => (),
+                err => panic!("expected the login to be throttled, got {:?}", err),
+            }
+
+            UnlockUser::<MockState>::new(email.to_owned()).call(&state).unwrap();
+            Login::<MockState>::new(name.to_owned(), "hunter2".to_string()).call(&state).unwrap();
+
+            let login_failures = GetLoginFailures::<MockState>::new(Some(name.to_owned())).call(&state).unwrap().get_data().0;
+            assert_eq!(login_failures.len(), 5);
+            assert_eq!(login_failures[0].reason, "throttled");
+        });
+    }
+
+    #[test]
+    fn test_password_policy() {
+        use auth::password_policy::PasswordPolicy;
+        use auth::password_policy::PasswordViolation;
+
+        with_state(|state| {
+            let mut user_management = state.get_user_management();
+            user_management.authentication.password_policy = PasswordPolicy::new()
+                .min_length(8)
+                .reject_common_passwords()
+                .history_size(2);
+
+            let name = format!("bob_{}", random_identifier());
+            let mut new_user: data::auth::NewUser = from_value(json!({
+                "username": name,
+                "email": format!("stuff{}@example.com", random_identifier()),
+                "password": "hunter2"
+            })).unwrap();
+            let violations = vec![PasswordViolation::TooShort { min_length: 8 }, PasswordViolation::Common];
+            assert_eq!(user_management.add_user(&new_user).unwrap_err(), UserManagementError::PasswordPolicy(violations));
+
+            new_user.password = "correct horse".to_string();
+            user_management.add_user(&new_user).unwrap();
+            user_management.modify_user_password(&name, "battery staple").unwrap();
+
+            // the current and the previous password can't be used again
+            let reused = UserManagementError::PasswordPolicy(vec![PasswordViolation::Reused { history_size: 2 }]);
+            assert_eq!(user_management.modify_user_password(&name, "battery staple").unwrap_err(), reused);
+            assert_eq!(user_management.modify_user_password(&name, "correct horse").unwrap_err(), reused);
+            user_management.modify_user_password(&name, "tr0ub4dor&3").unwrap();
+            user_management.modify_user_password(&name, "correct horse").unwrap();
+
+            user_management.authentication.password_policy = PasswordPolicy::new().max_age_days(-1);
+            let result = user_management.get_user(&name, "correct horse");
+            assert_eq!(result.unwrap_err(), UserManagementError::PasswordExpired);
+            // it can still be changed
+            user_management.verify_user_password(&name, "correct horse").unwrap();
+        });
+    }
+}
@@ -0,0 +1,148 @@
+use std::result::Result::Ok;
+use std::marker::PhantomData;
+
+use data;
+use data::ViewSource;
+use data::permissions::Permission;
+
+use model::actions::results::*;
+use model::actions::error::Error;
+use model::actions::decorator::*;
+use model::actions::Action;
+use model::actions::ActionRes;
+use model::actions::ActionResult;
+use model::entity::RetrieverFunctions;
+use model::table::DatastoreActionOps;
+use model::query::QueryActionOps;
+
+use state::StateFunctions;
+use state::ActionState;
+use state::authorization::AuthorizationOps;
+
+// View Action
+#[derive(Debug)]
+pub struct GetViewData<S = ActionState>  {
+    pub view_name: String,
+    pub format: serde_json::Value,
+    pub phantom_data: PhantomData<(S)>,
+}
+
+impl<S> GetViewData<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    pub fn new(view_name: String) -> WithPermissionRequired<WithUsageRecorded<WithTransaction<Self, S>, data::View, S>, S> {
+        let action = Self {
+            view_name: view_name.to_owned(),
+            format: json!({}), //TODO:...
+            phantom_data: PhantomData,
+        };
+
+        let action_with_transaction = WithTransaction::new(action);
+        let action_with_usage = WithUsageRecorded::new(action_with_transaction, view_name.to_owned());
+        let action_with_permission =
+            WithPermissionRequired::new(action_with_usage, Permission::read_entity::<data::View>(view_name));
+
+        action_with_permission
+    }
+}
+
+/// Reading the view is not enough, the user also needs to be allowed to read whatever is behind it
+fn required_source_permission(source: &ViewSource) -> Permission {
+    match source {
+        ViewSource::Table { name, .. } => Permission::get_table_data(name.to_owned()),
+        ViewSource::Query { name, .. } => Permission::run_query(name.to_owned()),
+    }
+}
+
+impl<S> Action<S> for GetViewData<S>
+    where
+        for<'a> S: StateFunctions<'a>,
+{
+    type Ret = GetViewDataResult;
+    fn call(&self, state: &S) -> ActionResult<Self::Ret> {
+        debug!("Calling GetViewData");
+
+        let view: data::View = state
+            .get_entity_retreiver_functions()
+            .get_one(&self.view_name)
+            .map_err(Error::Entity)
+            .and_then(|res| match res {
+                Some(view) => Ok(view),
+                None => Err(Error::NotFound),
+            })?;
+
+        let source = match view.source {
+            Some(source) => source,
+            None => return Err(Error::NotFound),
+        };
+
+        let authorization = state.get_authorization();
+        if !authorization.is_admin() && !authorization.permissions().contains(&required_source_permission(&source)) {
+            return Err(Error::Unauthorized);
+        }
+
+        let data = match &source {
+            ViewSource::Table { name, query } => {
+                let table: data::DataStoreEntity = state
+                    .get_entity_retreiver_functions()
+                    .get_one(name)
+                    .map_err(Error::Entity)?
+                    .ok_or_else(|| Error::NotFound)?;
+
+                state
+                    .get_table_controller()
+                    .query(&table, query)
+                    .map_err(Error::Datastore)?
+            },
+            ViewSource::Query { name, params } => {
+                let query: data::DataQueryEntity = state
+                    .get_entity_retreiver_functions()
+                    .get_one(name)
+                    .map_err(Error::Entity)?
+                    .ok_or_else(|| Error::NotFound)?;
+
+                state
+                    .get_query_controller()
+                    .run_query(&query, params, &self.format)
+                    .map_err(Error::Datastore)?
+            },
+        };
+
+        ActionRes::new("getViewData", GetViewDataResult { source, data })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::from_value;
+    use model::actions::entity_actions::*;
+    use test_common::random_identifier;
+    use test_common::with_state;
+    use test_common::MockState;
+
+    #[test]
+    fn test_get_view_without_source() {
+        with_state(|state| {
+            let name = format!("my_view_{}", random_identifier());
+            let view: data::View = from_value(json!({
+                "name": name,
+                "description": "a view without a source",
+                "viewState": {},
+            })).unwrap();
+
+            CreateEntity::<data::View, MockState>::new(view).call(&state).unwrap();
+
+            let GetEntityResult(view) = GetEntity::<data::View, MockState>::new(name.to_owned())
+                .call(&state)
+                .unwrap()
+                .get_data();
+            assert_eq!(view.source, None);
+
+            let result = GetViewData::<MockState>::new(name).call(&state);
+            assert_eq!(result.unwrap_err(), Error::NotFound);
+        });
+    }
+}
@@ -9,6 +9,10 @@ pub enum EntityError {
     SerializationError,
     #[fail(display = "File System Error {:?}", 0)]
     FileSystemError(String),
+    #[fail(display = "Could not build the script environment {:?}", 0)]
+    ScriptEnvironmentError(String), //returns back the install log
+    #[fail(display = "The script sandbox is not allowed {:?}", 0)]
+    InvalidSandbox(String),
     #[fail(display = "Invalid state, something is really weird with the database")]
     InvalidState,
     #[fail(display = "No Columns found, every table must have at least one column")]
@@ -5,6 +5,7 @@ pub mod results;
 pub mod update_state;
 
 use std::fmt::Debug;
+use std::cell::RefCell;
 
 use connection::executor::Conn;
 
@@ -12,6 +13,7 @@ use serde::Serialize;
 
 use data::claims::AuthClaims;
 use data::Named;
+use data::EntityFilter;
 use data::channels::GetEntityChannel;
 
 use model::entity::error::EntityError;
@@ -31,7 +33,7 @@ use connection::executor::DomainError;
 pub trait RawEntityTypes
     where
         Self: Clone + Send + Debug + Serialize,
-        Self::Data: ConvertRaw<Self>,
+        Self::Data: ConvertRaw<Self> + RawRevision,
         Self::NewData: GenerateRaw<Self>,
         Self: EntityCrudOps,
         Self: Named,
@@ -48,6 +50,15 @@ pub trait ConvertRaw<T> {
     fn convert(&self) -> T;
 }
 
+/// Metadata of a row in one of the append only entity tables
+pub trait RawRevision {
+    fn entity_id(&self) -> i64;
+    fn revision_id(&self) -> i64;
+    fn modified_at(&self) -> chrono::NaiveDateTime;
+    fn modified_by(&self) -> i64;
+    fn is_deleted(&self) -> bool;
+}
+
 pub trait GenerateRaw<T> {
     fn new(data: &T, entity_id: i64, modified_by: i64) -> Self;
     fn tombstone(name: String, entity_id: i64, modified_by: i64) -> Self;
@@ -64,6 +75,7 @@ pub struct EntityModifierController<'a> {
     pub domain_conn: &'a Result<Box<Datastore>, DomainError>,
     pub claims: &'a Option<AuthClaims>,
     pub scripting: &'a Scripting,
+    pub install_log: &'a RefCell<Option<String>>, //the environment built for a script is reported back with the action
     pub user_management: UserManagement<'a>, //Entities need to get access to user management for updating data
     pub domain_name: &'a Option<String>,
 }
@@ -96,11 +108,31 @@ pub trait RetrieverFunctions {
         where
             O: RawEntityTypes;
 
+    /// same as get_all, but only returns the values that match the filter
+    fn get_all_filtered<O>(&self, filter: &EntityFilter) -> Result<Vec<O>, EntityError>
+        where
+            O: RawEntityTypes;
+
+    /// the id that stays the same over all the revisions of the value, used for tagging
+    fn get_entity_id<O>(&self, name: &str) -> Result<Option<i64>, EntityError>
+        where
+            O: RawEntityTypes;
+
     /// filters the values by the name, and returns the value if it exists
     /// if it doesn't exist it retuns none
     fn get_one<O>(&self, name: &str) -> Result<Option<O>, EntityError>
         where
             O: RawEntityTypes;
+
+    /// every revision of the value, newest first, including the deletions
+    fn get_history<O>(&self, name: &str) -> Result<Vec<Revision<O>>, EntityError>
+        where
+            O: RawEntityTypes;
+
+    /// a single revision of the value, if it exists
+    fn get_revision<O>(&self, name: &str, revision_id: i64) -> Result<Option<Revision<O>>, EntityError>
+        where
+            O: RawEntityTypes;
 }
 
 pub trait ModifierFunctions {
@@ -124,6 +156,10 @@ pub trait ModifierFunctions {
     /// if name not found, returns nothing
     fn delete<O>(&self, name: &str) -> Result<Deleted<O>, EntityError>
         where O: RawEntityTypes + UpdateActionFunctions;
+
+    /// like create, but a deleted value with the name comes back with the id that it had before
+    fn restore<O>(&self, name_object: (&str, O)) -> Result<Created<O>, EntityError>
+        where O: RawEntityTypes + UpdateActionFunctions;
 }
 
 
@@ -132,7 +168,21 @@ impl<'a> RetrieverFunctions for EntityRetrieverController<'a> {
         where
             O: RawEntityTypes,
     {
-        O::get_all(self)
+        O::get_all(self, &EntityFilter::default())
+    }
+
+    fn get_all_filtered<O>(&self, filter: &EntityFilter) -> Result<Vec<O>, EntityError>
+        where
+            O: RawEntityTypes,
+    {
+        O::get_all(self, filter)
+    }
+
+    fn get_entity_id<O>(&self, name: &str) -> Result<Option<i64>, EntityError>
+        where
+            O: RawEntityTypes,
+    {
+        O::get_entity_id(self, name)
     }
 
     fn get_one<O>(&self, name: &str) -> Result<Option<O>, EntityError>
@@ -141,6 +191,24 @@ impl<'a> RetrieverFunctions for EntityRetrieverController<'a> {
     {
         O::get_one(self, name)
     }
+
+    fn get_history<O>(&self, name: &str) -> Result<Vec<Revision<O>>, EntityError>
+        where
+            O: RawEntityTypes,
+    {
+        O::get_history(self, name)
+    }
+
+    fn get_revision<O>(&self, name: &str, revision_id: i64) -> Result<Option<Revision<O>>, EntityError>
+        where
+            O: RawEntityTypes,
+    {
+        let revision = O::get_history(self, name)?
+            .into_iter()
+            .find(|revision| revision.revision_id == revision_id);
+
+        Ok(revision)
+    }
 }
 
 impl<'a> ModifierFunctions for EntityModifierController<'a> {
@@ -183,4 +251,14 @@ impl<'a> ModifierFunctions for EntityModifierController<'a> {
                 res.update_state(self)
             })
     }
+
+    fn restore<O>(&self, name_object: (&str, O)) -> Result<Created<O>, EntityError>
+        where O: RawEntityTypes + UpdateActionFunctions
+    {
+        O::restore(self, name_object)
+            .and_then(|res| {
+                debug!("result in table, now updating state: {:?}", res);
+                res.update_state(self)
+            })
+    }
 }
\ No newline at end of file
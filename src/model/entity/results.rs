@@ -1,5 +1,7 @@
 use std::fmt::Debug;
 
+use chrono::NaiveDateTime;
+
 #[derive(Debug)]
 pub enum Upserted<T: Debug> {
     Update {
@@ -38,3 +40,14 @@ pub enum Deleted<T: Debug> {
     Fail
 }
 
+
+/// A single revision of an entity, revisions are never modified
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision<T: Debug> {
+    pub revision_id: i64,
+    pub modified_at: NaiveDateTime,
+    pub modified_by: i64,
+    pub is_deleted: bool,
+    pub data: T,
+}
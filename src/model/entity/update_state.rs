@@ -10,6 +10,7 @@ use data::permissions::Permission;
 use data::Named;
 
 use state::user_management::UserManagementOps;
+use state::error::UserManagementError;
 
 pub trait UpdateActionFunctions
     where Self: UpdatePermissionFunctions
@@ -61,8 +62,8 @@ impl<T> UpdateState<T> for Upserted<T>
     fn update_state(self, state: &EntityModifierController) -> Result<Self, EntityError> {
         let res = match &self {
             Upserted::Update { old, new } => {
-                T::update_entity(&state, &old, &new)?;
                 T::update_permission(&state, &old, &new)?;
+                T::update_entity(&state, &old, &new)?;
             },
             Upserted::Create { new } => {
                 T::create_entity(&state, &new)?;
@@ -75,14 +76,15 @@ impl<T> UpdateState<T> for Upserted<T>
 }
 
 //Updated
+/// The permissions are renamed first, they are rolled back with the transaction while the entity changes are not
 impl<T> UpdateState<T> for Updated<T>
     where T: Debug + RawEntityTypes + UpdateActionFunctions
 {
     fn update_state(self, state: &EntityModifierController) -> Result<Self, EntityError> {
         let res = match &self {
             Updated::Success { old, new } => {
-                T::update_entity(&state, &old, &new)?;
                 T::update_permission(&state, &old, &new)?;
+                T::update_entity(&state, &old, &new)?;
             },
             _ => (),
         };
@@ -108,6 +110,20 @@ impl<T> UpdateState<T> for Deleted<T>
     }
 }
 
+/// Points the permissions (and so the roles that have them) to the new name,
+/// permissions that were never created are skipped
+pub fn rename_permissions(controller: &EntityModifierController, permission_list: Vec<(Permission, Permission)>) -> Result<(), EntityError> {
+    for (old_permission, new_permission) in permission_list {
+        match controller.user_management.rename_permission(&old_permission, &new_permission) {
+            Ok(_) => (),
+            Err(UserManagementError::NotFound) => debug!("Permission {:?} was never created, skipping", &old_permission),
+            Err(err) => return Err(EntityError::InternalError(err.to_string())),
+        }
+    }
+
+    Ok(())
+}
+
 ///Nothing needed here
 ///maybe have stored procedures here for some speedup
 impl UpdateActionFunctions for data::DataQueryEntity {
@@ -212,32 +228,28 @@ impl UpdatePermissionFunctions for data::DataQueryEntity {
     }
 
     fn update_permission(controller: &EntityModifierController, old: &data::DataQueryEntity, new: &data::DataQueryEntity) -> Result<(), EntityError> {
-        /* TODO:...
         let old_name = old.my_name().to_owned();
         let new_name = new.my_name().to_owned();
+        if old_name == new_name {
+            return Ok(());
+        }
 
         let permission_list = vec![
-           (
-               Permission::read_entity::<Query>(old_name.to_owned()),
-               Permission::read_entity::<Query>(new_name.to_owned()),
-           ),
-           (
-               Permission::modify_entity::<Query>(old_name.to_owned()),
-               Permission::modify_entity::<Query>(new_name.to_owned()),
-           ),
-           (
-               Permission::run_query(old_name.to_owned()),
-               Permission::run_query(new_name.to_owned()),
-           )
+            (
+                Permission::read_entity::<data::DataQueryEntity>(old_name.to_owned()),
+                Permission::read_entity::<data::DataQueryEntity>(new_name.to_owned()),
+            ),
+            (
+                Permission::modify_entity::<data::DataQueryEntity>(old_name.to_owned()),
+                Permission::modify_entity::<data::DataQueryEntity>(new_name.to_owned()),
+            ),
+            (
+                Permission::run_query(old_name.to_owned()),
+                Permission::run_query(new_name.to_owned()),
+            )
         ];
 
-        for (old_permission, new_permission) in permission_list {
-           controller
-               .user_management
-               .rename_permission(&old_permission, &new_permission);
-        }
-       */
-        Ok(())
+        rename_permissions(controller, permission_list)
     }
 
     fn delete_permission(controller: &EntityModifierController, old: &data::DataQueryEntity) -> Result<(), EntityError> {
@@ -287,18 +299,20 @@ impl UpdatePermissionFunctions for data::DataStoreEntity {
     }
 
     fn update_permission(controller: &EntityModifierController, old: &data::DataStoreEntity, new: &data::DataStoreEntity) -> Result<(), EntityError> {
-        /* TODO:...
         let old_name = old.my_name().to_owned();
         let new_name = new.my_name().to_owned();
+        if old_name == new_name {
+            return Ok(());
+        }
 
         let permission_list = vec![
             (
-                Permission::read_entity::<Table>(old_name.to_owned()),
-                Permission::read_entity::<Table>(new_name.to_owned()),
+                Permission::read_entity::<data::DataStoreEntity>(old_name.to_owned()),
+                Permission::read_entity::<data::DataStoreEntity>(new_name.to_owned()),
             ),
             (
-                Permission::modify_entity::<Table>(old_name.to_owned()),
-                Permission::modify_entity::<Table>(new_name.to_owned()),
+                Permission::modify_entity::<data::DataStoreEntity>(old_name.to_owned()),
+                Permission::modify_entity::<data::DataStoreEntity>(new_name.to_owned()),
             ),
             (
                 Permission::get_table_data(old_name.to_owned()),
@@ -310,13 +324,7 @@ impl UpdatePermissionFunctions for data::DataStoreEntity {
             )
         ];
 
-        for (old_permission, new_permission) in permission_list {
-            controller
-                .user_management
-                .rename_permission(&old_permission, &new_permission);
-        }
-        */
-        Ok(())
+        rename_permissions(controller, permission_list)
     }
 
     fn delete_permission(controller: &EntityModifierController, old: &data::DataStoreEntity) -> Result<(), EntityError> {
@@ -345,7 +353,24 @@ impl UpdatePermissionFunctions for data::View {
     }
 
     fn update_permission(controller: &EntityModifierController, old: &data::View, new: &data::View) -> Result<(), EntityError> {
-        Ok(())
+        let old_name = old.my_name().to_owned();
+        let new_name = new.my_name().to_owned();
+        if old_name == new_name {
+            return Ok(());
+        }
+
+        let permission_list = vec![
+            (
+                Permission::read_entity::<data::View>(old_name.to_owned()),
+                Permission::read_entity::<data::View>(new_name.to_owned()),
+            ),
+            (
+                Permission::modify_entity::<data::View>(old_name.to_owned()),
+                Permission::modify_entity::<data::View>(new_name.to_owned()),
+            ),
+        ];
+
+        rename_permissions(controller, permission_list)
     }
 
     fn delete_permission(controller: &EntityModifierController, old: &data::View) -> Result<(), EntityError> {
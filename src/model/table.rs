@@ -1,6 +1,9 @@
 
 use data;
 use data::Named;
+use data::DataMutation;
+use data::DataChange;
+use data::DataRevert;
 use data::error::DatastoreError;
 
 use connection::executor::DomainError;
@@ -15,13 +18,15 @@ pub struct DatastoreAction<'a> {
 pub trait DatastoreActionOps {
     fn query(&self, table: &data::DataStoreEntity, query: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
 
-    fn insert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value, fail_on_duplicate: bool) -> Result<serde_json::Value, DatastoreError>;
+    fn insert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value, fail_on_duplicate: bool) -> Result<DataMutation, DatastoreError>;
 
-    fn upsert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value) -> Result<serde_json::Value, DatastoreError>;
+    fn upsert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value) -> Result<DataMutation, DatastoreError>;
 
-    fn update_row(&self, table: &data::DataStoreEntity, keyed_data: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError>;
+    fn update_row(&self, table: &data::DataStoreEntity, keyed_data: &serde_json::Value, fail_on_not_found: bool) -> Result<DataMutation, DatastoreError>;
 
-    fn delete_row(&self, table: &data::DataStoreEntity, keys: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError>;
+    fn delete_row(&self, table: &data::DataStoreEntity, keys: &serde_json::Value, fail_on_not_found: bool) -> Result<DataMutation, DatastoreError>;
+
+    fn revert_changes(&self, table: &data::DataStoreEntity, changes: &[DataChange]) -> Result<DataRevert, DatastoreError>;
 }
 
 impl From<&DomainError> for DatastoreError {
@@ -43,32 +48,39 @@ impl<'a> DatastoreActionOps for DatastoreAction<'a> {
         }
     }
 
-    fn insert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value, fail_on_duplicate: bool) -> Result<serde_json::Value, DatastoreError> {
+    fn insert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value, fail_on_duplicate: bool) -> Result<DataMutation, DatastoreError> {
         match self.conn {
             Ok(conn) => conn.insert(table, data),
             Err(err) => Err(err.into())
         }
     }
 
-    fn upsert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value) -> Result<serde_json::Value, DatastoreError> {
+    fn upsert_row(&self, table: &data::DataStoreEntity, data: &serde_json::Value) -> Result<DataMutation, DatastoreError> {
         match self.conn {
             Ok(conn) => conn.upsert(table, data),
             Err(err) => Err(err.into())
         }
     }
 
-    fn update_row(&self, table: &data::DataStoreEntity, keyed_data: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError> {
+    fn update_row(&self, table: &data::DataStoreEntity, keyed_data: &serde_json::Value, fail_on_not_found: bool) -> Result<DataMutation, DatastoreError> {
         match self.conn {
             Ok(conn) => conn.update(table, keyed_data),
             Err(err) => Err(err.into())
         }
     }
 
-    fn delete_row(&self, table: &data::DataStoreEntity, keys: &serde_json::Value, fail_on_not_found: bool) -> Result<serde_json::Value, DatastoreError> {
+    fn delete_row(&self, table: &data::DataStoreEntity, keys: &serde_json::Value, fail_on_not_found: bool) -> Result<DataMutation, DatastoreError> {
         match self.conn {
             Ok(conn) => conn.delete(table, keys),
             Err(err) => Err(err.into())
         }
 
     }
+
+    fn revert_changes(&self, table: &data::DataStoreEntity, changes: &[DataChange]) -> Result<DataRevert, DatastoreError> {
+        match self.conn {
+            Ok(conn) => conn.revert(table, changes),
+            Err(err) => Err(err.into())
+        }
+    }
 }
\ No newline at end of file
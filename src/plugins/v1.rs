@@ -5,6 +5,11 @@ use serde::de::DeserializeOwned;
 pub use data::DataStoreEntity;
 pub use data::DataQueryEntity;
 pub use data::error::DatastoreError;
+pub use data::DataMutation;
+pub use data::DataChange;
+pub use data::DataOperation;
+pub use data::DataRevert;
+pub use data::DataConflict;
 
 pub trait DomainBuilder
     where
@@ -49,10 +54,14 @@ pub trait Datastore:
     */
 
     fn retrieve(&self, data_store: &DataStoreEntity, query: &serde_json::Value) -> Result<Dataset, DatastoreError>;
-    fn insert(&self, data_store: &DataStoreEntity, rows: &Rows) -> Result<Dataset, DatastoreError>;
-    fn upsert(&self, data_store: &DataStoreEntity, rows: &Rows) -> Result<Dataset, DatastoreError>;
-    fn update(&self, data_store: &DataStoreEntity, key_values: &KeyValues) -> Result<Dataset, DatastoreError>;
-    fn delete(&self, data_store: &DataStoreEntity, keys: &Keys) -> Result<Dataset, DatastoreError>;
+
+    // modifications also return every row that changed, with the old and new values, for the audit log
+    fn insert(&self, data_store: &DataStoreEntity, rows: &Rows) -> Result<DataMutation, DatastoreError>;
+    fn upsert(&self, data_store: &DataStoreEntity, rows: &Rows) -> Result<DataMutation, DatastoreError>;
+    fn update(&self, data_store: &DataStoreEntity, key_values: &KeyValues) -> Result<DataMutation, DatastoreError>;
+    fn delete(&self, data_store: &DataStoreEntity, keys: &Keys) -> Result<DataMutation, DatastoreError>;
+    /// undoes the changes in order, all at once, skipping the rows that changed since
+    fn revert(&self, data_store: &DataStoreEntity, changes: &[DataChange]) -> Result<DataRevert, DatastoreError>;
 
     fn on_datastore_created(&self, new: &DataStoreEntity) -> Result<(), DatastoreError>;
     fn on_datastore_updated(&self, old: &DataStoreEntity, new: &DataStoreEntity) -> Result<(), DatastoreError>;
@@ -9,6 +9,14 @@ pub enum ScriptError {
     ExecuteError(String),
     #[fail(display = "runtime error: {:?}", 0)]
     RuntimeError(String),
+    #[fail(display = "invalid file path in script: {:?}", 0)]
+    InvalidPath(String),
+    #[fail(display = "the entrypoint {:?} is not one of the files of the script", 0)]
+    MissingEntrypoint(String),
+    #[fail(display = "the archive is larger than {} bytes", 0)]
+    ArchiveTooLarge(u64),
+    #[fail(display = "could not install requirements: {:?}", 0)]
+    InstallError(String),
     #[fail(display = "An unknown error occurred")]
     Unknown,
 }
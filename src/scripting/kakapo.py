@@ -0,0 +1,102 @@
+"""Kakapo client, injected into the script runtime
+
+Every call is made with the token of the user that ran the script, so the script can
+only do what that user is allowed to do.
+"""
+
+import http.client
+import json
+import os
+import socket
+import urllib.parse
+import urllib.request
+
+_URL = os.environ.get('KAKAPO_URL', '').rstrip('/')
+_SOCKET = os.environ.get('KAKAPO_SOCKET')
+_DOMAIN = os.environ.get('KAKAPO_DOMAIN')
+
+
+def _read_token():
+    path = os.environ.get('KAKAPO_TOKEN_FILE')
+    if not path:
+        return None
+    with open(path) as token_file:
+        return token_file.read().strip()
+
+
+_TOKEN = _read_token()
+
+
+class _UnixConnection(http.client.HTTPConnection):
+    def __init__(self, path):
+        super().__init__('localhost')
+        self._path = path
+
+    def connect(self):
+        self.sock = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+        self.sock.connect(self._path)
+
+
+class KakapoError(Exception):
+    pass
+
+
+def _call_socket(path, body, headers):
+    connection = _UnixConnection(_SOCKET)
+    try:
+        connection.request('POST', path, body=body, headers=headers)
+        response = connection.getresponse()
+        text = response.read().decode('utf-8')
+        if response.status >= 400:
+            raise KakapoError('{}: {}'.format(response.status, text))
+        return json.loads(text)
+    finally:
+        connection.close()
+
+
+def _call(path, data, query):
+    if not (_URL or _SOCKET) or not _TOKEN:
+        raise KakapoError('kakapo is not available in this script run')
+
+    path = '{}?{}'.format(path, urllib.parse.urlencode(query))
+    body = json.dumps(data).encode('utf-8')
+    headers = {
+        'Content-Type': 'application/json',
+        'Authorization': 'Bearer {}'.format(_TOKEN),
+    }
+
+    # the socket also works when the script is not allowed to use the network
+    if _SOCKET:
+        return _call_socket(path, body, headers)
+
+    request = urllib.request.Request(
+        '{}{}'.format(_URL, path),
+        data=body,
+        headers=headers,
+        method='POST',
+    )
+
+    try:
+        with urllib.request.urlopen(request) as response:
+            return json.loads(response.read().decode('utf-8'))
+    except urllib.error.HTTPError as err:
+        raise KakapoError('{}: {}'.format(err.code, err.read().decode('utf-8')))
+
+
+def _domain(domain):
+    domain = domain or _DOMAIN
+    if domain is None:
+        raise KakapoError('no domain specified')
+    return domain
+
+
+def query_table(name, query=None, domain=None):
+    return _call('/manage/queryTableData', query or {}, {'name': name, 'domain': _domain(domain)})
+
+
+def run_query(name, params=None, domain=None):
+    return _call('/manage/runQuery', params or [], {'name': name, 'domain': _domain(domain)})
+
+
+def publish(channel, data, domain=None):
+    return _call('/pubsub/publish', {'channel': channel, 'data': data}, {'domain': _domain(domain)})
@@ -1,63 +1,424 @@
 
 pub mod error;
 pub mod update_state;
+pub mod sandbox;
 
 use std::fs;
-use std::env;
+use std::fs::OpenOptions;
+use std::fmt;
+use std::io;
+use std::thread;
+use std::path::Path;
 use std::path::PathBuf;
+use std::path::Component;
+use std::collections::BTreeMap;
 use std::process::Command;
 use std::process::Stdio;
+use std::sync::mpsc;
 use std::io::Write;
 use std::io::Read;
+use std::io::BufRead;
+use std::io::BufReader;
 use std::str::from_utf8;
+use std::os::unix::fs::OpenOptionsExt;
+
+use actix::Message;
+use actix::Recipient;
 
 use tempfile;
+use openssl::sha::sha256;
 
 use scripting::error::ScriptError;
+use scripting::sandbox::ScriptRunner;
+use scripting::sandbox::check_sandbox_paths;
 use data::Script;
+use data::ScriptSandbox;
 use data::Named;
 
 
 
 /// Roadmap for scripts
 /// - Better permissioning
-///     - Every script should have it's own user with it's own user
+///     - seccomp filters on top of the bubblewrap sandbox
 /// - More run options
 ///     - Run on docker, serverless
-/// - library support (i.e. pip install ..., custom libraries)
+/// - library support (custom libraries, private package indexes)
 /// - Versioning scripts ( + Full git integration)
 /// - More languages
 /// - Cron support
-/// - More efficient updates (i.e. don't upload the entire script all the time)
 
 pub trait ScriptFunctions {
-    fn run(&self, script: &Script, params: &serde_json::Value) -> Result<ScriptResult, ScriptError>;
+    /// `token` is handed to the kakapo client in the script, so that the script acts as the user
+    /// `on_output` is called for every line the script writes to stdout or stderr, as it is written
+    fn run(&self, script: &Script, params: &serde_json::Value, token: Option<String>, on_output: &Fn(&ScriptOutput)) -> Result<ScriptResult, ScriptError>;
+
+    /// Builds the virtualenv for the script from its requirements. The environment is only
+    /// rebuilt if the requirements changed since the last build
+    fn build_environment(&self, script: &Script) -> Result<InstallResult, ScriptError>;
+
+    /// Unpacks a gzipped tarball into a file map, the paths are relative to the root of the archive
+    fn unpack_archive(&self, archive: &[u8]) -> Result<BTreeMap<String, String>, ScriptError>;
+
+    /// Fails if the sandbox mounts a path that is not inside one of the allowed paths
+    fn check_sandbox(&self, sandbox: &ScriptSandbox) -> Result<(), ScriptError>;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Scripting {
     script_home: PathBuf,
+    runner: ScriptRunner,
+    allowed_paths: Vec<PathBuf>,
+    api_url: Option<String>,
+    api_socket: Option<PathBuf>,
+    domain_name: Option<String>,
+    output_recipient: Option<Recipient<ScriptOutput>>,
+}
+
+impl fmt::Debug for Scripting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Scripting {{ script_home: {:?}, runner: {:?} }}", &self.script_home, &self.runner)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ScriptResult {
     pub successful: bool,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
     pub stdout: String,
     pub stderr: String,
     pub output: serde_json::Value,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallResult {
+    pub rebuilt: bool,
+    pub requirements_hash: Option<String>,
+    pub log: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// A single line of output of a running script
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptOutput {
+    pub script_name: String,
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+impl Message for ScriptOutput {
+    type Result = ();
+}
+
+/// Output of a running script, as it's published to the subscribers of the script
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptOutputBatch {
+    pub lines: Vec<ScriptOutput>,
+    /// the rest of the output was not published, it's only in the result of the run
+    pub truncated: bool,
+}
+
 const PYTHON: &'static str = "python3";
-const SCRIPT_NAME: &'static str = "script.py";
+const VERSION_NAME: &'static str = ".version";
+const TAR: &'static str = "tar";
+const GZIP: &'static str = "gzip";
+/// Archives are uploaded as one request, anything bigger is rejected before it is unpacked
+const MAX_ARCHIVE_SIZE: u64 = 16 * 1024 * 1024;
+/// The files in an archive together can't be bigger than this once they are unpacked
+const MAX_UNPACKED_SIZE: u64 = 64 * 1024 * 1024;
+/// Files in the script home that are managed by kakapo, scripts can't overwrite these
+const RESERVED_NAMES: [&'static str; 4] = [VENV_NAME, REQUIREMENTS_NAME, INSTALL_LOG_NAME, VERSION_NAME];
+const VENV_NAME: &'static str = ".venv";
+pub const REQUIREMENTS_NAME: &'static str = "requirements.txt";
+const REQUIREMENTS_HASH_NAME: &'static str = "requirements.sha256";
+const INSTALL_LOG_NAME: &'static str = "install.log";
+const IO_FILE_NAME: &'static str = "io.json";
+const CLIENT_NAME: &'static str = "kakapo.py";
+const TOKEN_FILE_NAME: &'static str = ".kakapo_token";
+const CLIENT_TEXT: &'static str = include_str!("kakapo.py");
+
+/// Only the owner can read the file, it is created that way so that it is never readable by others
+fn write_private_file(path: &Path, contents: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+
+    file.write_all(contents.as_bytes())
+}
+
+fn requirements_text(requirements: &Vec<String>) -> String {
+    let mut lines: Vec<String> = requirements
+        .iter()
+        .map(|x| x.trim().to_owned())
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .collect();
+    lines.sort();
+    lines.dedup();
+
+    lines.join("\n")
+}
+
+fn requirements_hash(requirements_text: &str) -> String {
+    sha256(requirements_text.as_bytes())
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect()
+}
+
+fn read_lines<R>(script_name: &str, stream: OutputStream, pipe: R, sender: mpsc::Sender<ScriptOutput>) -> thread::JoinHandle<()>
+    where R: Read + Send + 'static,
+{
+    let script_name = script_name.to_owned();
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines() {
+            let line = match line {
+                Ok(x) => x,
+                Err(err) => {
+                    warn!("could not read script output: {:?}", &err);
+                    break;
+                },
+            };
+            let output = ScriptOutput {
+                script_name: script_name.to_owned(),
+                stream: stream.to_owned(),
+                line,
+            };
+            if sender.send(output).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Ungzips the archive, stops reading once it is bigger than `max_unpacked_size` so that it is never unpacked completely
+fn decompress(archive: &[u8], max_unpacked_size: u64) -> Result<Vec<u8>, ScriptError> {
+    let mut child = Command::new(GZIP)
+        .arg("-dc")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+
+    // written from another thread, gzip blocks on it's output until it is read
+    let writer = child.stdin.take().map(|mut stdin| {
+        let archive = archive.to_vec();
+        thread::spawn(move || {
+            let _ = stdin.write_all(&archive);
+        })
+    });
+
+    let mut tarball = vec![];
+    if let Some(stdout) = child.stdout.take() {
+        stdout
+            .take(max_unpacked_size + 1)
+            .read_to_end(&mut tarball)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+    }
+
+    let is_too_large = tarball.len() as u64 > max_unpacked_size;
+    if is_too_large {
+        let _ = child.kill();
+    }
+    let status = child.wait()
+        .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+
+    if is_too_large {
+        Err(ScriptError::ArchiveTooLarge(max_unpacked_size))
+    } else if !status.success() {
+        Err(ScriptError::IOError("Could not unpack archive, it is not a gzipped tarball".to_string()))
+    } else {
+        Ok(tarball)
+    }
+}
+
+/// Unpacks a gzipped tarball into a file map, fails if the archive, or the files in it, are larger than the limits
+pub fn unpack_archive(archive: &[u8], max_archive_size: u64, max_unpacked_size: u64) -> Result<BTreeMap<String, String>, ScriptError> {
+    if archive.len() as u64 > max_archive_size {
+        return Err(ScriptError::ArchiveTooLarge(max_archive_size));
+    }
+    let tarball = decompress(archive, max_unpacked_size)?;
+
+    let unpack_dir = tempfile::tempdir()
+        .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+    let mut child = Command::new(TAR)
+        .arg("-xf")
+        .arg("-")
+        .arg("--no-same-owner")
+        .arg("-C")
+        .arg(unpack_dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&tarball)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+    }
+
+    let output = child.wait_with_output()
+        .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+    if !output.status.success() {
+        let mut log = String::new();
+        output_log(&mut log, &output);
+        return Err(ScriptError::IOError(format!("Could not unpack archive: {}", log)));
+    }
+
+    let mut files = BTreeMap::new();
+    read_tree(unpack_dir.path(), unpack_dir.path(), &mut files, &mut 0, max_unpacked_size)?;
+
+    Ok(files)
+}
+
+/// Packs a file map into a gzipped tarball, the opposite of `unpack_archive`
+pub fn pack_archive(files: &BTreeMap<String, String>) -> Result<Vec<u8>, ScriptError> {
+    let pack_dir = tempfile::tempdir()
+        .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+    for (file_name, contents) in files {
+        if !is_relative_path(file_name) {
+            return Err(ScriptError::InvalidPath(file_name.to_owned()));
+        }
+
+        let path = pack_dir.path().join(file_name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| ScriptError::IOError(err.to_string()))?;
+        }
+        fs::write(&path, contents)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+    }
+
+    let output = Command::new(TAR)
+        .arg("-czf")
+        .arg("-")
+        .arg("-C")
+        .arg(pack_dir.path())
+        .arg(".")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+    if !output.status.success() {
+        let mut log = String::new();
+        output_log(&mut log, &output);
+        return Err(ScriptError::IOError(format!("Could not pack archive: {}", log)));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Reads the files under `dir`, fails once they add up to more than `max_size`
+fn read_tree(root: &Path, dir: &Path, files: &mut BTreeMap<String, String>, total_size: &mut u64, max_size: u64) -> Result<(), ScriptError> {
+    let entries = fs::read_dir(dir)
+        .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|err| ScriptError::IOError(err.to_string()))?
+            .path();
+        let metadata = fs::symlink_metadata(&path)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+        if metadata.is_dir() {
+            read_tree(root, &path, files, total_size, max_size)?;
+        } else if metadata.is_file() {
+            *total_size += metadata.len();
+            if *total_size > max_size {
+                return Err(ScriptError::ArchiveTooLarge(max_size));
+            }
+
+            let relative_path = path.strip_prefix(root)
+                .map_err(|err| ScriptError::IOError(err.to_string()))?
+                .to_string_lossy()
+                .to_string();
+            let contents = fs::read_to_string(&path)
+                .map_err(|_| ScriptError::IOError(format!("{} is not a text file, only text files are supported", &relative_path)))?;
+            files.insert(relative_path, contents);
+        } else {
+            warn!("skipping {:?} in archive, only files and directories are supported", &path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Relative path that can't escape the directory it is joined to
+fn is_relative_path(file_name: &str) -> bool {
+    !file_name.is_empty() && Path::new(file_name)
+        .components()
+        .all(|component| match component {
+            Component::Normal(_) => true,
+            _ => false,
+        })
+}
+
+fn output_log(log: &mut String, output: &std::process::Output) {
+    log.push_str(from_utf8(&output.stdout).unwrap_or_default());
+    log.push_str(from_utf8(&output.stderr).unwrap_or_default());
+}
 
 impl Scripting {
     pub fn new(script_home: PathBuf) -> Self {
         Self {
-            script_home
+            script_home,
+            runner: ScriptRunner::default(),
+            allowed_paths: vec![],
+            api_url: None,
+            api_socket: None,
+            domain_name: None,
+            output_recipient: None,
         }
     }
 
+    /// Streams the output of the scripts to the recipient while they run
+    pub fn with_output_recipient(mut self, output_recipient: Option<Recipient<ScriptOutput>>) -> Self {
+        self.output_recipient = output_recipient;
+        self
+    }
+
+    /// Where the kakapo client in the scripts should connect to
+    pub fn with_api(mut self, api_url: Option<String>, domain_name: Option<String>) -> Self {
+        self.api_url = api_url;
+        self.domain_name = domain_name;
+        self
+    }
+
+    /// The unix socket of the api, scripts use it instead of the api url if it is set
+    pub fn with_api_socket(mut self, api_socket: Option<PathBuf>) -> Self {
+        self.api_socket = api_socket;
+        self
+    }
+
+    pub fn with_runner(mut self, runner: ScriptRunner) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// The paths that scripts can mount in their sandbox, nothing can be mounted by default
+    pub fn with_allowed_paths(mut self, allowed_paths: Vec<PathBuf>) -> Self {
+        self.allowed_paths = allowed_paths;
+        self
+    }
+
     pub fn get_home(&self) -> PathBuf {
         self.script_home.to_owned()
     }
@@ -69,44 +430,178 @@ impl Scripting {
         path
     }
 
-    pub fn get_script_path(&self, script_name: &str) -> PathBuf {
-        let mut path = self.get_home();
-        path.push(script_name);
-        path.push(SCRIPT_NAME);
+    /// Path of a file of the script, fails if the file would end up outside of the script home
+    pub fn get_file_path(&self, script_name: &str, file_name: &str) -> Result<PathBuf, ScriptError> {
+        let relative_path = Path::new(file_name);
+        let is_valid = is_relative_path(file_name);
+        let is_reserved = RESERVED_NAMES.iter()
+            .any(|reserved| relative_path.starts_with(reserved));
+
+        if !is_valid || is_reserved {
+            return Err(ScriptError::InvalidPath(file_name.to_string()));
+        }
+
+        let mut path = self.get_script_home(script_name);
+        path.push(relative_path);
+
+        Ok(path)
+    }
+
+    pub fn get_version_path(&self, script_name: &str) -> PathBuf {
+        let mut path = self.get_script_home(script_name);
+        path.push(VERSION_NAME);
+
+        path
+    }
+
+    /// Version of the script tree that is currently in the script home
+    pub fn get_installed_version(&self, script_name: &str) -> Option<String> {
+        fs::read_to_string(self.get_version_path(script_name)).ok()
+    }
+
+    pub fn get_venv_path(&self, script_name: &str) -> PathBuf {
+        let mut path = self.get_script_home(script_name);
+        path.push(VENV_NAME);
+
+        path
+    }
+
+    pub fn get_requirements_path(&self, script_name: &str) -> PathBuf {
+        let mut path = self.get_script_home(script_name);
+        path.push(REQUIREMENTS_NAME);
+
+        path
+    }
+
+    pub fn get_install_log_path(&self, script_name: &str) -> PathBuf {
+        let mut path = self.get_script_home(script_name);
+        path.push(INSTALL_LOG_NAME);
 
         path
     }
+
+    /// The python interpreter for the script, the one in the virtualenv if the script has one
+    pub fn get_python(&self, script_name: &str) -> PathBuf {
+        let mut path = self.get_venv_path(script_name);
+        path.push("bin");
+        path.push("python");
+
+        if path.exists() {
+            path
+        } else {
+            PathBuf::from(PYTHON)
+        }
+    }
 }
 
 impl ScriptFunctions for Scripting {
 
-    fn run(&self, script: &Script, params: &serde_json::Value) -> Result<ScriptResult, ScriptError> {
-        let script_home = &self.script_home;
-        let path = self.get_script_home(script.my_name());
-
-        env::set_current_dir(path)
+    fn run(&self, script: &Script, params: &serde_json::Value, token: Option<String>, on_output: &Fn(&ScriptOutput)) -> Result<ScriptResult, ScriptError> {
+        let script_name = script.my_name();
+        let script_home = fs::canonicalize(self.get_script_home(script_name))
             .map_err(|err| ScriptError::IOError(err.to_string()))?;
+        if self.get_installed_version(script_name) != Some(script.version()) {
+            warn!("script home of {:?} does not match the latest revision of the script", script_name);
+        }
 
-        let mut temp = tempfile::NamedTempFile::new()
-            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+        // makes sure the entrypoint is inside the script home, and the script wasn't stored before the paths were checked
+        self.get_file_path(script_name, &script.entrypoint)?;
+        self.check_sandbox(&script.sandbox)?;
+        let script_path = script_home.join(&script.entrypoint);
+        let python = match self.get_python(script_name) {
+            ref x if x.is_relative() && x.exists() => fs::canonicalize(x)
+                .map_err(|err| ScriptError::IOError(err.to_string()))?,
+            x => x,
+        };
 
-        let io_file_path = temp.path().to_str()
-            .ok_or_else(|| ScriptError::IOError("Could not convert path to string".to_string()))?
-            .to_owned();
-        let mut io_file = temp.as_file_mut();
+        // Every run gets it's own working directory, the cwd of the server is never touched
+        let work_dir = tempfile::tempdir()
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+        let mut io_file_path = work_dir.path().to_owned();
+        io_file_path.push(IO_FILE_NAME);
 
         let params_text = serde_json::to_string(&params)
             .map_err(|err| ScriptError::IOError(err.to_string()))?;
-        io_file.write_all(&params_text.as_bytes())
+        fs::write(&io_file_path, &params_text.as_bytes())
             .map_err(|err| ScriptError::IOError(err.to_string()))?;
 
-        let output = Command::new(PYTHON)
-            .arg(SCRIPT_NAME)
-            .arg(&io_file_path)
-            .output()
+        let mut env = vec![];
+        let mut api_socket = None;
+        let has_api = self.api_url.is_some() || self.api_socket.is_some();
+        if let Some(token) = token.filter(|_| has_api) {
+            let mut client_path = work_dir.path().to_owned();
+            client_path.push(CLIENT_NAME);
+            fs::write(&client_path, CLIENT_TEXT)
+                .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+            // the token is never put in the env or the arguments, those can be read from /proc
+            let mut token_path = work_dir.path().to_owned();
+            token_path.push(TOKEN_FILE_NAME);
+            write_private_file(&token_path, &token)
+                .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+            env.push(("PYTHONPATH".to_string(), work_dir.path().to_string_lossy().to_string()));
+            env.push(("KAKAPO_TOKEN_FILE".to_string(), token_path.to_string_lossy().to_string()));
+            if let Some(api_url) = &self.api_url {
+                env.push(("KAKAPO_URL".to_string(), api_url.to_owned()));
+            }
+            if let Some(domain_name) = &self.domain_name {
+                env.push(("KAKAPO_DOMAIN".to_string(), domain_name.to_owned()));
+            }
+            api_socket = self.api_socket.as_ref().map(|x| x.as_path());
+        }
+
+        self.runner
+            .prepare_work_dir(script_name, work_dir.path())
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+        let mut child = self.runner
+            .command(
+                script_name,
+                &script.sandbox,
+                &python,
+                &script_home,
+                &script_path,
+                work_dir.path(),
+                &io_file_path,
+                &env,
+                api_socket)?
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
             .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
 
-        let is_successful = output.status.success();
+        let (sender, receiver) = mpsc::channel();
+        let mut readers = vec![];
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(read_lines(script_name, OutputStream::Stdout, stdout, sender.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(read_lines(script_name, OutputStream::Stderr, stderr, sender.clone()));
+        }
+        drop(sender);
+
+        // receives until both of the pipes are closed
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        for line in receiver {
+            match line.stream {
+                OutputStream::Stdout => { stdout.push_str(&line.line); stdout.push('\n'); },
+                OutputStream::Stderr => { stderr.push_str(&line.line); stderr.push('\n'); },
+            }
+            on_output(&line);
+            if let Some(recipient) = &self.output_recipient {
+                let _ = recipient.do_send(line)
+                    .map_err(|_| warn!("could not stream script output, recipient is gone"));
+            }
+        }
+        for reader in readers {
+            let _ = reader.join();
+        }
+
+        let status = child.wait()
+            .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+        let is_successful = status.success();
 
         if is_successful {
             info!("Ran script successfully");
@@ -116,8 +611,9 @@ impl ScriptFunctions for Scripting {
 
             Ok(ScriptResult {
                 successful: is_successful,
-                stdout: from_utf8(&output.stdout).unwrap_or_default().to_string(),
-                stderr: from_utf8(&output.stderr).unwrap_or_default().to_string(),
+                exit_code: status.code(),
+                stdout,
+                stderr,
                 output: output_value,
             })
 
@@ -126,10 +622,199 @@ impl ScriptFunctions for Scripting {
 
             Ok(ScriptResult {
                 successful: is_successful,
-                stdout: from_utf8(&output.stdout).unwrap_or_default().to_string(),
-                stderr: from_utf8(&output.stderr).unwrap_or_default().to_string(),
+                exit_code: status.code(),
+                stdout,
+                stderr,
                 output: serde_json::Value::default(),
             })
         }
     }
+
+    fn unpack_archive(&self, archive: &[u8]) -> Result<BTreeMap<String, String>, ScriptError> {
+        unpack_archive(archive, MAX_ARCHIVE_SIZE, MAX_UNPACKED_SIZE)
+    }
+
+    fn build_environment(&self, script: &Script) -> Result<InstallResult, ScriptError> {
+        let script_name = script.my_name();
+        let venv_path = self.get_venv_path(script_name);
+        let requirements_path = self.get_requirements_path(script_name);
+        let mut hash_path = venv_path.to_owned();
+        hash_path.push(REQUIREMENTS_HASH_NAME);
+
+        let requirements = requirements_text(&script.requirements);
+        if requirements.is_empty() {
+            info!("script {:?} has no requirements, using the system python", script_name);
+            if venv_path.exists() {
+                fs::remove_dir_all(&venv_path)
+                    .map_err(|err| ScriptError::IOError(err.to_string()))?;
+            }
+            if requirements_path.exists() {
+                fs::remove_file(&requirements_path)
+                    .map_err(|err| ScriptError::IOError(err.to_string()))?;
+            }
+            let install_log_path = self.get_install_log_path(script_name);
+            if install_log_path.exists() {
+                fs::remove_file(&install_log_path)
+                    .map_err(|err| ScriptError::IOError(err.to_string()))?;
+            }
+
+            return Ok(InstallResult {
+                rebuilt: false,
+                requirements_hash: None,
+                log: String::new(),
+            });
+        }
+
+        let hash = requirements_hash(&requirements);
+        let old_hash = fs::read_to_string(&hash_path).ok();
+        if old_hash.as_ref() == Some(&hash) {
+            info!("requirements for script {:?} did not change, reusing the environment", script_name);
+            return Ok(InstallResult {
+                rebuilt: false,
+                requirements_hash: Some(hash),
+                log: fs::read_to_string(self.get_install_log_path(script_name)).unwrap_or_default(),
+            });
+        }
+
+        info!("building the environment for script {:?}", script_name);
+        if venv_path.exists() {
+            fs::remove_dir_all(&venv_path)
+                .map_err(|err| ScriptError::IOError(err.to_string()))?;
+        }
+
+        fs::write(&requirements_path, &requirements)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+        let mut log = String::new();
+
+        let output = Command::new(PYTHON)
+            .arg("-m")
+            .arg("venv")
+            .arg(&venv_path)
+            .output()
+            .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+        output_log(&mut log, &output);
+
+        if output.status.success() {
+            let mut pip = venv_path.to_owned();
+            pip.push("bin");
+            pip.push("pip");
+
+            let output = Command::new(pip)
+                .arg("install")
+                .arg("--disable-pip-version-check")
+                .arg("-r")
+                .arg(&requirements_path)
+                .current_dir(self.get_script_home(script_name))
+                .output()
+                .map_err(|err| ScriptError::ExecuteError(err.to_string()))?;
+            output_log(&mut log, &output);
+
+            if output.status.success() {
+                fs::write(&hash_path, &hash)
+                    .map_err(|err| ScriptError::IOError(err.to_string()))?;
+            }
+        }
+
+        fs::write(self.get_install_log_path(script_name), &log)
+            .map_err(|err| ScriptError::IOError(err.to_string()))?;
+
+        if hash_path.exists() {
+            info!("built the environment for script {:?}", script_name);
+            debug!("install log: {}", &log);
+
+            Ok(InstallResult {
+                rebuilt: true,
+                requirements_hash: Some(hash),
+                log,
+            })
+        } else {
+            warn!("could not build the environment for script {:?}", script_name);
+            // don't leave a half built environment around, the next build should start from scratch
+            let _ = fs::remove_dir_all(&venv_path);
+
+            Err(ScriptError::InstallError(log))
+        }
+    }
+
+    fn check_sandbox(&self, sandbox: &ScriptSandbox) -> Result<(), ScriptError> {
+        check_sandbox_paths(sandbox, &self.allowed_paths)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_requirements_hash_ignores_order_and_comments() {
+        let a = requirements_text(&vec!["requests==2.21.0".to_string(), "numpy".to_string()]);
+        let b = requirements_text(&vec![
+            "# numerical stuff".to_string(),
+            " numpy ".to_string(),
+            "".to_string(),
+            "requests==2.21.0".to_string(),
+        ]);
+
+        assert_eq!(a, b);
+        assert_eq!(requirements_hash(&a), requirements_hash(&b));
+
+        let c = requirements_text(&vec!["requests==2.20.0".to_string(), "numpy".to_string()]);
+        assert_ne!(requirements_hash(&a), requirements_hash(&c));
+    }
+
+    #[test]
+    fn test_private_file_is_only_readable_by_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TOKEN_FILE_NAME);
+        write_private_file(&path, "secret").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "secret");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(write_private_file(&path, "other secret").is_err());
+    }
+
+    #[test]
+    fn test_no_requirements_uses_system_python() {
+        let scripting = Scripting::new(PathBuf::from("/tmp/kakapo_nonexistent_home"));
+        assert_eq!(scripting.get_python("some_script"), PathBuf::from(PYTHON));
+    }
+
+    #[test]
+    fn test_file_path_stays_in_script_home() {
+        let scripting = Scripting::new(PathBuf::from("/tmp/scripts"));
+
+        assert_eq!(
+            scripting.get_file_path("my_script", "lib/helpers.py"),
+            Ok(PathBuf::from("/tmp/scripts/my_script/lib/helpers.py")));
+        assert!(scripting.get_file_path("my_script", "../other_script/script.py").is_err());
+        assert!(scripting.get_file_path("my_script", "/etc/passwd").is_err());
+        assert!(scripting.get_file_path("my_script", ".venv/bin/python").is_err());
+        assert!(scripting.get_file_path("my_script", "").is_err());
+    }
+
+    #[test]
+    fn test_pack_and_unpack_archive() {
+        let scripting = Scripting::new(PathBuf::from("/tmp/scripts"));
+        let mut files = BTreeMap::new();
+        files.insert("manifest.json".to_string(), "{}".to_string());
+        files.insert("scripts/my_script/script.py".to_string(), "print(42)".to_string());
+
+        let archive = pack_archive(&files).unwrap();
+        assert_eq!(scripting.unpack_archive(&archive).unwrap(), files);
+
+        let too_large = vec![0u8; MAX_ARCHIVE_SIZE as usize + 1];
+        assert_eq!(scripting.unpack_archive(&too_large), Err(ScriptError::ArchiveTooLarge(MAX_ARCHIVE_SIZE)));
+
+        let mut large_files = BTreeMap::new();
+        large_files.insert("zeros.txt".to_string(), "0".repeat(MAX_UNPACKED_SIZE as usize + 1));
+        let archive = pack_archive(&large_files).unwrap();
+        assert_eq!(scripting.unpack_archive(&archive), Err(ScriptError::ArchiveTooLarge(MAX_UNPACKED_SIZE)));
+
+        let mut invalid_files = BTreeMap::new();
+        invalid_files.insert("../outside.py".to_string(), "".to_string());
+        assert!(pack_archive(&invalid_files).is_err());
+    }
 }
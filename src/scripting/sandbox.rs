@@ -0,0 +1,352 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::path::Component;
+use std::process::Command;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::ffi::CString;
+
+use libc;
+use openssl::sha::sha256;
+
+use data::ScriptSandbox;
+use scripting::error::ScriptError;
+
+/// System directories that are mounted read only in the sandbox so that the interpreter can run
+const SYSTEM_PATHS: [&'static str; 6] = ["/usr", "/bin", "/lib", "/lib64", "/etc/alternatives", "/etc/ssl"];
+/// Only used if the script is allowed to use the network
+const NETWORK_PATHS: [&'static str; 2] = ["/etc/resolv.conf", "/etc/hosts"];
+
+/// Where the api socket is mounted in the sandbox
+const SANDBOX_API_SOCKET: &'static str = "/run/kakapo/api.sock";
+
+/// Number of uids reserved for scripts, starting at the base uid
+const UID_RANGE: u32 = 50000;
+/// The seccomp filter is handed to bubblewrap on this file descriptor
+const SECCOMP_FD: i32 = 3;
+
+#[derive(Clone, Debug)]
+pub enum ScriptRunner {
+    /// Runs the script directly as the server user
+    Local,
+    /// Runs the script with bubblewrap, in it's own namespaces with it's own uid
+    Bubblewrap {
+        bwrap_path: PathBuf,
+        base_uid: u32,
+        /// A compiled seccomp bpf program that is loaded before the script starts
+        seccomp_filter: Option<PathBuf>,
+    },
+}
+
+impl Default for ScriptRunner {
+    fn default() -> Self {
+        ScriptRunner::Local
+    }
+}
+
+/// Absolute path without `.` or `..`, so that it can be compared with the allowed paths
+fn is_normal_absolute_path(path: &Path) -> bool {
+    path.is_absolute() && path
+        .components()
+        .all(|component| match component {
+            Component::RootDir | Component::Normal(_) => true,
+            _ => false,
+        })
+}
+
+/// Symlinks are resolved for paths that exist, a symlink inside an allowed path could point anywhere
+fn resolve_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Every path that the script mounts has to be inside one of the paths that the admin allowed
+pub fn check_sandbox_paths(policy: &ScriptSandbox, allowed_paths: &[PathBuf]) -> Result<(), ScriptError> {
+    let allowed_paths: Vec<PathBuf> = allowed_paths
+        .iter()
+        .map(|allowed_path| resolve_path(allowed_path))
+        .collect();
+
+    for path in policy.read_only_paths.iter().chain(policy.read_write_paths.iter()) {
+        let requested_path = Path::new(path);
+        let is_allowed = is_normal_absolute_path(requested_path) && {
+            let resolved_path = resolve_path(requested_path);
+            allowed_paths
+                .iter()
+                .any(|allowed_path| resolved_path.starts_with(allowed_path))
+        };
+
+        if !is_allowed {
+            return Err(ScriptError::InvalidPath(path.to_owned()));
+        }
+    }
+
+    Ok(())
+}
+
+fn chown(path: &Path, uid: u32) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    let result = unsafe { libc::chown(path.as_ptr(), uid, uid) };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Without root the uid only exists inside the user namespace, on the host the script runs as the server user
+fn can_switch_uid() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+impl ScriptRunner {
+
+    /// Every script gets a stable uid of it's own, so that scripts can't read each others files
+    pub fn script_uid(base_uid: u32, script_name: &str) -> u32 {
+        let hash = sha256(script_name.as_bytes());
+        let offset = (hash[0] as u32) << 24 | (hash[1] as u32) << 16 | (hash[2] as u32) << 8 | hash[3] as u32;
+
+        base_uid + offset % UID_RANGE
+    }
+
+    /// Warns about the isolation that is missing with this setup
+    pub fn check_isolation(&self) {
+        if let ScriptRunner::Bubblewrap { seccomp_filter, .. } = self {
+            if !can_switch_uid() {
+                warn!("the server is not running as root, scripts run as the server user on the host and only get their own uid inside the sandbox");
+            }
+            if seccomp_filter.is_none() {
+                warn!("scripts are sandboxed without a seccomp filter");
+            }
+        }
+    }
+
+    /// The files of the run have to belong to the uid that the script runs as on the host
+    pub fn prepare_work_dir(&self, script_name: &str, work_dir: &Path) -> io::Result<()> {
+        match self {
+            ScriptRunner::Bubblewrap { base_uid, .. } if can_switch_uid() => {
+                let uid = ScriptRunner::script_uid(*base_uid, script_name);
+                chown(work_dir, uid)?;
+                for entry in fs::read_dir(work_dir)? {
+                    chown(&entry?.path(), uid)?;
+                }
+
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Build the command for running `python script io_file` inside `work_dir` with the extra `env`,
+    /// the script can reach the api through `api_socket` even if it can't use the network
+    pub fn command(
+        &self,
+        script_name: &str,
+        policy: &ScriptSandbox,
+        python: &Path,
+        script_home: &Path,
+        script_path: &Path,
+        work_dir: &Path,
+        io_file: &Path,
+        env: &Vec<(String, String)>,
+        api_socket: Option<&Path>,
+    ) -> Result<Command, ScriptError> {
+        match self {
+            ScriptRunner::Local => {
+                if policy != &ScriptSandbox::default() {
+                    warn!("script {:?} has a sandbox policy, but scripts are not sandboxed", script_name);
+                }
+
+                let mut command = Command::new(python);
+                command
+                    .arg(script_path)
+                    .arg(io_file)
+                    .envs(env.iter().map(|(key, value)| (key, value)))
+                    .current_dir(work_dir);
+                if let Some(api_socket) = api_socket {
+                    command.env("KAKAPO_SOCKET", api_socket);
+                }
+
+                Ok(command)
+            },
+            ScriptRunner::Bubblewrap { bwrap_path, base_uid, seccomp_filter } => {
+                let script_uid = ScriptRunner::script_uid(*base_uid, script_name);
+                let uid = script_uid.to_string();
+                let mut command = Command::new(bwrap_path);
+
+                if can_switch_uid() {
+                    command.uid(script_uid).gid(script_uid);
+                }
+
+                command
+                    .arg("--unshare-all")
+                    .arg("--die-with-parent")
+                    .arg("--new-session")
+                    .arg("--clearenv")
+                    .args(&["--uid", uid.as_str(), "--gid", uid.as_str()])
+                    .args(&["--proc", "/proc"])
+                    .args(&["--dev", "/dev"])
+                    .args(&["--tmpfs", "/tmp"]);
+
+                if let Some(seccomp_filter) = seccomp_filter {
+                    let filter = File::open(seccomp_filter)
+                        .map_err(|err| ScriptError::IOError(format!("Could not open the seccomp filter: {}", err)))?;
+                    let filter_fd = filter.as_raw_fd();
+
+                    command.args(&["--seccomp", &SECCOMP_FD.to_string()]);
+                    // the filter is moved into the closure, so it stays open until the command is spawned,
+                    // only async signal safe calls are made between fork and exec
+                    unsafe {
+                        command.pre_exec(move || {
+                            let _ = &filter;
+                            let result = if filter_fd == SECCOMP_FD {
+                                libc::fcntl(filter_fd, libc::F_SETFD, 0)
+                            } else {
+                                libc::dup2(filter_fd, SECCOMP_FD)
+                            };
+
+                            if result < 0 {
+                                Err(io::Error::last_os_error())
+                            } else {
+                                Ok(())
+                            }
+                        });
+                    }
+                }
+
+                for path in SYSTEM_PATHS.iter() {
+                    command.arg("--ro-bind-try").arg(path).arg(path);
+                }
+
+                if policy.network {
+                    command.arg("--share-net");
+                    for path in NETWORK_PATHS.iter() {
+                        command.arg("--ro-bind-try").arg(path).arg(path);
+                    }
+                }
+
+                for path in policy.read_only_paths.iter() {
+                    command.arg("--ro-bind-try").arg(path).arg(path);
+                }
+                for path in policy.read_write_paths.iter() {
+                    command.arg("--bind-try").arg(path).arg(path);
+                }
+
+                // the script home is kept at the same path, so that the virtualenv still works
+                command
+                    .arg("--ro-bind").arg(script_home).arg(script_home)
+                    .arg("--bind").arg(work_dir).arg(work_dir)
+                    .arg("--chdir").arg(work_dir)
+                    .args(&["--setenv", "HOME"]).arg(work_dir)
+                    .args(&["--setenv", "PATH", "/usr/local/bin:/usr/bin:/bin"]);
+
+                if let Some(api_socket) = api_socket {
+                    command
+                        .arg("--bind").arg(api_socket).arg(SANDBOX_API_SOCKET)
+                        .args(&["--setenv", "KAKAPO_SOCKET", SANDBOX_API_SOCKET]);
+                } else if !policy.network && env.iter().any(|(key, _)| key == "KAKAPO_URL") {
+                    warn!("script {:?} has no network access, it can only reach the api through the script api socket", script_name);
+                }
+
+                for (key, value) in env.iter() {
+                    command.arg("--setenv").arg(key).arg(value);
+                }
+
+                command
+                    .arg("--")
+                    .arg(python)
+                    .arg(script_path)
+                    .arg(io_file);
+
+                Ok(command)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_script_uid_is_stable_and_in_range() {
+        let uid = ScriptRunner::script_uid(100000, "my_script");
+
+        assert_eq!(uid, ScriptRunner::script_uid(100000, "my_script"));
+        assert!(uid >= 100000 && uid < 100000 + UID_RANGE);
+        assert_ne!(uid, ScriptRunner::script_uid(100000, "my_other_script"));
+    }
+
+    #[test]
+    fn test_sandbox_paths_have_to_be_allowed() {
+        let allowed_paths = vec![PathBuf::from("/srv/kakapo/data")];
+        let policy = |path: &str| ScriptSandbox {
+            network: false,
+            read_only_paths: vec![],
+            read_write_paths: vec![path.to_string()],
+        };
+
+        assert_eq!(check_sandbox_paths(&ScriptSandbox::default(), &vec![]), Ok(()));
+        assert_eq!(check_sandbox_paths(&policy("/srv/kakapo/data/exports"), &allowed_paths), Ok(()));
+        assert!(check_sandbox_paths(&policy("/"), &allowed_paths).is_err());
+        assert!(check_sandbox_paths(&policy("/srv/kakapo/data/../../../etc"), &allowed_paths).is_err());
+        assert!(check_sandbox_paths(&policy("/srv/kakapo/database"), &allowed_paths).is_err());
+        assert!(check_sandbox_paths(&policy("srv/kakapo/data"), &allowed_paths).is_err());
+        assert!(check_sandbox_paths(&policy("/srv/kakapo/data/exports"), &vec![]).is_err());
+    }
+
+    fn bubblewrap_args(policy: &ScriptSandbox, api_socket: Option<&Path>) -> String {
+        let runner = ScriptRunner::Bubblewrap {
+            bwrap_path: PathBuf::from("/usr/bin/bwrap"),
+            base_uid: 100000,
+            seccomp_filter: None,
+        };
+        let env = vec![
+            ("KAKAPO_URL".to_string(), "http://localhost:1845".to_string()),
+            ("KAKAPO_TOKEN_FILE".to_string(), "/tmp/run/.kakapo_token".to_string()),
+        ];
+        let command = runner
+            .command(
+                "my_script",
+                policy,
+                Path::new("/usr/bin/python3"),
+                Path::new("/srv/scripts/my_script"),
+                Path::new("/srv/scripts/my_script/main.py"),
+                Path::new("/tmp/run"),
+                Path::new("/tmp/run/io.json"),
+                &env,
+                api_socket)
+            .unwrap();
+
+        format!("{:?}", command)
+    }
+
+    #[test]
+    fn test_bubblewrap_command() {
+        let args = bubblewrap_args(&ScriptSandbox::default(), Some(Path::new("/var/run/kakapo.sock")));
+
+        assert!(args.contains(r#""--unshare-all""#));
+        assert!(!args.contains(r#""--share-net""#));
+        assert!(args.contains(r#""--bind" "/var/run/kakapo.sock" "/run/kakapo/api.sock""#));
+        assert!(args.contains(r#""--setenv" "KAKAPO_SOCKET" "/run/kakapo/api.sock""#));
+        assert!(args.contains(r#""--setenv" "KAKAPO_TOKEN_FILE" "/tmp/run/.kakapo_token""#));
+        assert!(!args.contains("KAKAPO_TOKEN\""));
+        assert!(args.ends_with(r#""--" "/usr/bin/python3" "/srv/scripts/my_script/main.py" "/tmp/run/io.json""#));
+
+        let network_policy = ScriptSandbox {
+            network: true,
+            read_only_paths: vec!["/srv/kakapo/data".to_string()],
+            read_write_paths: vec![],
+        };
+        let args = bubblewrap_args(&network_policy, None);
+
+        assert!(args.contains(r#""--share-net""#));
+        assert!(args.contains(r#""--ro-bind-try" "/srv/kakapo/data" "/srv/kakapo/data""#));
+        assert!(!args.contains("KAKAPO_SOCKET"));
+    }
+}
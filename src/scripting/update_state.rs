@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 use std::fs;
+use std::io;
 use std::process::Command;
 
 use data;
@@ -12,7 +13,10 @@ use model::entity::EntityModifierController;
 use model::entity::RawEntityTypes;
 use model::entity::update_state::UpdateActionFunctions;
 use model::entity::update_state::UpdatePermissionFunctions;
+use model::entity::update_state::rename_permissions;
 use state::user_management::UserManagementOps;
+use scripting::ScriptFunctions;
+use scripting::error::ScriptError;
 
 //TODO: there could be different types of script runners
 // docker, serverless, or local
@@ -22,32 +26,49 @@ impl UpdateActionFunctions for data::Script {
     fn create_entity(controller: &EntityModifierController, new: &data::Script) -> Result<(), EntityError> {
         info!("Creating the directory for script {:?}", &new.my_name());
         let script_name = &new.my_name();
+        check_sandbox(controller, new)?;
 
         let path_dir = controller.scripting.get_script_home(&script_name);
-        let script_path = controller.scripting.get_script_path(&script_name);
 
         fs::create_dir_all(&path_dir)
             .map_err(|err| EntityError::FileSystemError(format!("Could not create directory: {}", err.to_string())))?;
         info!("created the directory for script {:?} at {:?}", &new.my_name(), &path_dir);
 
+        for (file_name, contents) in new.all_files() {
+            write_file(controller, new, &file_name, &contents)?;
+        }
+        write_version(controller, new)?;
 
-        let script_text = &new.text;
-        fs::write(&script_path, script_text.to_owned())
-            .map_err(|err| EntityError::FileSystemError(format!("Could not create file: {}", err.to_string())))?;
-
-        info!("created the file for script {:?} at {:?}", &new.my_name(), &script_path);
-
-        //TODO: pip env this
-
-        Ok(())
+        build_environment(controller, new)
     }
 
     fn update_entity(controller: &EntityModifierController, old: &data::Script, new: &data::Script) -> Result<(), EntityError> {
+        check_sandbox(controller, new)?;
 
-        data::Script::delete_entity(controller, old)?;
-        data::Script::create_entity(controller, new)?;
+        if old.my_name() == new.my_name() {
+            return update_files(controller, old, new);
+        }
 
-        Ok(())
+        let old_dir = controller.scripting.get_script_home(old.my_name());
+        let new_dir = controller.scripting.get_script_home(new.my_name());
+
+        if !old_dir.exists() {
+            return data::Script::create_entity(controller, new);
+        }
+
+        fs::rename(&old_dir, &new_dir)
+            .map_err(|err| EntityError::FileSystemError(format!("Could not rename directory: {}", err.to_string())))?;
+        info!("moved the directory for script {:?} to {:?}", &old.my_name(), &new_dir);
+
+        // The transaction is rolled back on an error, so the directory has to go back where it was as well
+        update_files(controller, old, new)
+            .or_else(|err| {
+                match fs::rename(&new_dir, &old_dir) {
+                    Ok(_) => info!("moved the directory for script {:?} back to {:?}", &new.my_name(), &old_dir),
+                    Err(rename_err) => error!("Could not move the directory for script {:?} back to {:?}: {:?}", &new.my_name(), &old_dir, &rename_err),
+                };
+                Err(err)
+            })
     }
 
     fn delete_entity(controller: &EntityModifierController, old: &data::Script) -> Result<(), EntityError> {
@@ -66,6 +87,79 @@ impl UpdateActionFunctions for data::Script {
     }
 }
 
+/// Only touches the files that changed, anything the script generated in it's home stays around
+fn update_files(controller: &EntityModifierController, old: &data::Script, new: &data::Script) -> Result<(), EntityError> {
+    let old_files = old.all_files();
+    let new_files = new.all_files();
+
+    for file_name in old_files.keys().filter(|x| !new_files.contains_key(*x)) {
+        let file_path = controller.scripting
+            .get_file_path(new.my_name(), file_name)
+            .map_err(|err| EntityError::FileSystemError(err.to_string()))?;
+        info!("removing file {:?} for script {:?}", &file_path, &new.my_name());
+        fs::remove_file(&file_path)
+            .or_else(|err| match err.kind() {
+                io::ErrorKind::NotFound => Ok(()),
+                _ => Err(EntityError::FileSystemError(format!("Could not delete file: {}", err.to_string()))),
+            })?;
+    }
+
+    for (file_name, contents) in new_files.iter() {
+        if old_files.get(file_name) != Some(contents) {
+            write_file(controller, new, file_name, contents)?;
+        }
+    }
+    write_version(controller, new)?;
+
+    build_environment(controller, new)
+}
+
+fn write_file(controller: &EntityModifierController, script: &data::Script, file_name: &str, contents: &str) -> Result<(), EntityError> {
+    let file_path = controller.scripting
+        .get_file_path(script.my_name(), file_name)
+        .map_err(|err| EntityError::FileSystemError(err.to_string()))?;
+
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| EntityError::FileSystemError(format!("Could not create directory: {}", err.to_string())))?;
+    }
+    fs::write(&file_path, contents)
+        .map_err(|err| EntityError::FileSystemError(format!("Could not create file: {}", err.to_string())))?;
+
+    info!("wrote the file for script {:?} at {:?}", &script.my_name(), &file_path);
+
+    Ok(())
+}
+
+fn check_sandbox(controller: &EntityModifierController, script: &data::Script) -> Result<(), EntityError> {
+    controller.scripting
+        .check_sandbox(&script.sandbox)
+        .map_err(|err| EntityError::InvalidSandbox(err.to_string()))
+}
+
+fn write_version(controller: &EntityModifierController, script: &data::Script) -> Result<(), EntityError> {
+    let version_path = controller.scripting.get_version_path(script.my_name());
+
+    fs::write(&version_path, script.version())
+        .map_err(|err| EntityError::FileSystemError(format!("Could not create file: {}", err.to_string())))
+}
+
+fn build_environment(controller: &EntityModifierController, new: &data::Script) -> Result<(), EntityError> {
+    let install_result = controller.scripting
+        .build_environment(new)
+        .map_err(|err| match err {
+            ScriptError::InstallError(log) => EntityError::ScriptEnvironmentError(log),
+            _ => EntityError::FileSystemError(format!("Could not build environment: {}", err.to_string())),
+        })?;
+
+    if install_result.rebuilt {
+        info!("built the environment for script {:?}: {}", &new.my_name(), &install_result.log);
+        controller.install_log.replace(Some(install_result.log));
+    }
+
+    Ok(())
+}
+
 impl UpdatePermissionFunctions for data::Script {
     fn create_permission(controller: &EntityModifierController, new: &data::Script) -> Result<(), EntityError> {
         let permission_list = vec![
@@ -110,13 +204,7 @@ impl UpdatePermissionFunctions for data::Script {
             )
         ];
 
-        for (old_permission, new_permission) in permission_list {
-            controller
-                .user_management
-                .rename_permission(&old_permission, &new_permission);
-        }
-
-        Ok(())
+        rename_permissions(controller, permission_list)
     }
 
     fn delete_permission(controller: &EntityModifierController, old: &data::Script) -> Result<(), EntityError> {
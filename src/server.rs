@@ -1,3 +1,5 @@
+use std::fs as std_fs;
+use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
 use std::path::Path;
 
@@ -13,6 +15,7 @@ use openssl::ssl::SslAcceptor;
 use openssl::ssl::SslFiletype;
 use openssl::ssl::SslMethod;
 use actix_web::App;
+use tokio_uds::UnixListener;
 
 use AppStateBuilder;
 use AppState;
@@ -60,44 +63,10 @@ impl Server {
 
         let frontend_path = self.frontend_path;
 
-        let mut server_cfg = actix_web::server::new(move || {
-
-            let app = App::with_state(state.clone())
-                .middleware(Logger::new("Responded [%s] %b bytes %Dms"))
-                .middleware(Logger::new(r#"Requested [%r] FROM %a "%{User-Agent}i""#))
-                .configure(move |app| {
-                    Cors::for_app(app)
-                        .allowed_origin("http://localhost:3000")
-                        .allowed_origin("http://localhost:1845")
-                        .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
-                        .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
-                        .allowed_header(http::header::CONTENT_TYPE)
-                        .max_age(3600)
-                        .add_routes()
-                        .register()
-                });
-
-
-            if let Some(ref static_files) = frontend_path {
-
-                let mut index_file = static_files.to_owned();
-                index_file.push("index.html");
-
-                app.resource("/", |r| {
-                        r.method(http::Method::GET).f(move |req| {
-                            fs::NamedFile::open(index_file.to_owned())
-                        })
-                    })
-                    .handler(
-                    "/",
-                    fs::StaticFiles::new(&static_files)
-                        .unwrap()
-                        .show_files_listing())
-            } else {
-                app
-            }
-
-        });
+        let script_api_socket = state.get_script_api_socket();
+        let app_state = state.clone();
+        let app_frontend_path = frontend_path.clone();
+        let mut server_cfg = actix_web::server::new(move || build_app(app_state.clone(), app_frontend_path.clone()));
 
         server_cfg = server_cfg
             .workers(num_cpus::get())
@@ -136,6 +105,60 @@ impl Server {
 
         info!("Kakapo server started on \"{:?}\"", server_addr);
 
+        if let Some(socket_path) = script_api_socket {
+            // a socket left over from the last run would make the bind fail
+            let _ = std_fs::remove_file(&socket_path);
+            let listener = UnixListener::bind(&socket_path)
+                .expect("Could not bind the script api socket");
+            // scripts run with their own uid, the token is what authorizes them
+            std_fs::set_permissions(&socket_path, std_fs::Permissions::from_mode(0o666))
+                .expect("Could not set the permissions of the script api socket");
+
+            actix_web::server::new(move || build_app(state.clone(), frontend_path.clone()))
+                .workers(1)
+                .keep_alive(30)
+                .start_incoming(listener.incoming(), false);
+
+            info!("Kakapo server started for scripts on {:?}", &socket_path);
+        }
+
         self.system.run()
     }
 }
+
+fn build_app(state: AppState, frontend_path: Option<PathBuf>) -> App<AppState> {
+    let app = App::with_state(state)
+        .middleware(Logger::new("Responded [%s] %b bytes %Dms"))
+        .middleware(Logger::new(r#"Requested [%r] FROM %a "%{User-Agent}i""#))
+        .configure(move |app| {
+            Cors::for_app(app)
+                .allowed_origin("http://localhost:3000")
+                .allowed_origin("http://localhost:1845")
+                .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
+                .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
+                .allowed_header(http::header::CONTENT_TYPE)
+                .max_age(3600)
+                .add_routes()
+                .register()
+        });
+
+
+    if let Some(ref static_files) = frontend_path {
+
+        let mut index_file = static_files.to_owned();
+        index_file.push("index.html");
+
+        app.resource("/", |r| {
+                r.method(http::Method::GET).f(move |req| {
+                    fs::NamedFile::open(index_file.to_owned())
+                })
+            })
+            .handler(
+            "/",
+            fs::StaticFiles::new(&static_files)
+                .unwrap()
+                .show_files_listing())
+    } else {
+        app
+    }
+}
@@ -2,17 +2,109 @@ use data::auth::NewUser;
 use data::auth::User;
 use data::auth::SessionToken;
 use data::auth::UserInfo;
+use data::auth::Session;
+use data::auth::ApiKey;
+use data::auth::NewApiKey;
+use data::auth::MfaEnrollment;
+use data::auth::MfaChallenge;
+use data::auth::LoginFailure;
+use data::auth::ExternalUser;
+use data::claims::AuthClaims;
+use data::permissions::Permission;
+
+use chrono::NaiveDateTime;
 
 use state::error::UserManagementError;
+use auth::oidc::RoleMapping;
 
 pub trait AuthenticationOps {
     fn verify_password(&self, hashed_password: &str, raw_password: &str) -> Result<bool, UserManagementError>;
 
     fn hash_password(&self, raw_password: &str) -> Result<String, UserManagementError>;
 
+    /// Binds as the user when the directory knows them, `None` means that the password has to be checked locally.
+    /// Users of the directory never fall back to their local password, a wrong password or an unreachable directory is final
+    fn authenticate_with_directory(&self, user_identifier: &str, password: &str) -> Result<Option<ExternalUser>, UserManagementError>;
+
+    /// The roles that the groups of the directory users give them
+    fn get_directory_role_mappings(&self) -> Vec<RoleMapping>;
+
     fn create_session(&self, user: UserInfo) -> Result<SessionToken, UserManagementError>;
 
     fn refresh_session(&self, token_string: String) -> Result<SessionToken, UserManagementError>;
 
+    /// Ends all of the sessions of the user, on every device
     fn delete_session(&self, user_id: i64) -> Result<(), UserManagementError>;
-}
\ No newline at end of file
+
+    /// Active sessions of the user, `current_session_id` is the session of the token that is asking
+    fn get_sessions(&self, user_id: i64, current_session_id: Option<i64>) -> Result<Vec<Session>, UserManagementError>;
+
+    /// Ends a single session, only if it belongs to the user
+    fn revoke_session(&self, user_id: i64, session_id: i64) -> Result<(), UserManagementError>;
+
+    /// Ends all of the sessions of the user with the username or email
+    fn revoke_user_sessions(&self, user_identifier: &str) -> Result<(), UserManagementError>;
+
+    /// Whether the session of an access token is still there, also marks it as used
+    fn is_session_active(&self, session_id: i64) -> Result<bool, UserManagementError>;
+
+    /// short lived access token for the user (i.e. for handing to scripts), it is limited to the same scopes
+    /// as the caller and is revoked together with the caller's session or api key
+    fn create_scoped_token(&self, user_id: i64, session_id: Option<i64>, api_key_id: Option<i64>, scopes: Option<Vec<Permission>>, duration: i64) -> Result<String, UserManagementError>;
+
+    /// Whether the api key that a token was handed out for still exists and hasn't expired
+    fn is_api_key_active(&self, api_key_id: i64) -> Result<bool, UserManagementError>;
+
+    /// The key is in the result, it can't be retrieved again
+    fn create_api_key(&self, user_id: i64, name: &str, scopes: Option<Vec<Permission>>, expires_at: Option<NaiveDateTime>) -> Result<NewApiKey, UserManagementError>;
+
+    fn get_api_keys(&self, user_id: i64) -> Result<Vec<ApiKey>, UserManagementError>;
+
+    /// Only if the key belongs to the user
+    fn revoke_api_key(&self, user_id: i64, api_key_id: i64) -> Result<(), UserManagementError>;
+
+    /// The claims of the owner of the key, records that the key was used
+    fn authenticate_api_key(&self, key: &str) -> Result<AuthClaims, UserManagementError>;
+
+    /// A new secret and recovery codes, it's only used for logging in once a code was verified
+    fn enroll_mfa(&self, user_id: i64) -> Result<MfaEnrollment, UserManagementError>;
+
+    /// Whether the user has a confirmed authenticator
+    fn is_mfa_enabled(&self, user_id: i64) -> Result<bool, UserManagementError>;
+
+    /// Whether the user holds a permission that two-factor authentication is required for
+    fn is_mfa_required(&self, user_id: i64) -> Result<bool, UserManagementError>;
+
+    /// Checks a code from the authenticator or a recovery code, codes are only accepted once
+    fn verify_mfa_code(&self, user_id: i64, code: &str) -> Result<bool, UserManagementError>;
+
+    /// Removes the secret and the recovery codes of the user with the username or email
+    fn disable_mfa(&self, user_identifier: &str) -> Result<(), UserManagementError>;
+
+    /// Short lived token that is exchanged for a session with a code
+    fn create_mfa_challenge(&self, user_id: i64) -> Result<MfaChallenge, UserManagementError>;
+
+    /// The user that the password was checked for, i.e. for enrolling when 2FA is required
+    fn get_mfa_challenge_user(&self, challenge_token: &str) -> Result<UserInfo, UserManagementError>;
+
+    /// The challenge is used up if the code is right, wrong codes are counted
+    fn verify_mfa_challenge(&self, challenge_token: &str, code: &str) -> Result<UserInfo, UserManagementError>;
+
+    /// Rejects the login before the password is checked, when the user or the address has to wait
+    fn check_login_throttle(&self, user_identifier: &str) -> Result<(), UserManagementError>;
+
+    /// Counts the failure for the user and the address, and records it for auditing
+    fn record_failed_login(&self, user_identifier: &str) -> Result<(), UserManagementError>;
+
+    /// Wrong two-factor codes count against the same limits as wrong passwords
+    fn record_failed_mfa_code(&self, user_identifier: &str) -> Result<(), UserManagementError>;
+
+    /// After a successful login, the failures of the user are forgotten
+    fn clear_failed_logins(&self, user_identifier: &str) -> Result<(), UserManagementError>;
+
+    /// Lifts the lockout and the backoff of the user with the username or email
+    fn unlock_user(&self, user_identifier: &str) -> Result<(), UserManagementError>;
+
+    /// The latest failed logins, of one user or of everyone
+    fn get_login_failures(&self, user_identifier: Option<String>) -> Result<Vec<LoginFailure>, UserManagementError>;
+}
@@ -19,4 +19,13 @@ pub trait AuthorizationOps {
 
     fn username(&self) -> Option<String>;
 
+    /// the session that the token belongs to, if it has one
+    fn session_id(&self) -> Option<i64>;
+
+    /// the api key that the token belongs to, if it was authenticated with one
+    fn api_key_id(&self) -> Option<i64>;
+
+    /// the permissions that an api key is limited to, `None` if it's not a scoped api key
+    fn scopes(&self) -> Option<Vec<Permission>>;
+
 }
\ No newline at end of file
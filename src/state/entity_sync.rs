@@ -0,0 +1,16 @@
+use std::collections::BTreeMap;
+
+use state::error::EntitySyncError;
+
+pub trait EntitySyncOps {
+    /// all of the files in the sync directory of the domain, hidden files and directories (i.e. `.git`) are left out
+    fn read_directory(&self) -> Result<BTreeMap<String, String>, EntitySyncError>;
+
+    /// the revision of the entity as of the last sync, `None` if it was never synced
+    fn get_synced_revision(&self, entity_id: i64) -> Result<Option<i64>, EntitySyncError>;
+
+    fn mark_synced(&self, entity_id: i64, revision_id: i64) -> Result<(), EntitySyncError>;
+
+    /// the entity is no longer managed by the sync directory
+    fn unmark_synced(&self, entity_id: i64) -> Result<(), EntitySyncError>;
+}
@@ -1,14 +1,28 @@
 
 use argonautica;
 
+use auth::password_policy::PasswordViolation;
+
 #[derive(Debug, Fail, PartialEq, Eq)]
 pub enum UserManagementError {
     #[fail(display = "Already exists")]
     AlreadyExists,
     #[fail(display = "Not found")]
     NotFound,
+    #[fail(display = "The identifier matches more than one user")]
+    AmbiguousIdentifier,
+    #[fail(display = "The email address is not valid")]
+    InvalidEmail,
     #[fail(display = "Unauthorized")]
     Unauthorized,
+    #[fail(display = "Too many failed logins, try again in {} seconds", _0)]
+    TooManyAttempts(i64),
+    #[fail(display = "Locked after too many failed logins")]
+    Locked,
+    #[fail(display = "The password does not meet the password policy")]
+    PasswordPolicy(Vec<PasswordViolation>),
+    #[fail(display = "The password has expired, it has to be reset")]
+    PasswordExpired,
     #[fail(display = "{:?}", 0)]
     AuthenticationError(String),
     #[fail(display = "Hash Error")]
@@ -44,3 +58,43 @@ pub enum DomainManagementError {
     #[fail(display = "An unknown error occurred")]
     Unknown,
 }
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum TagManagementError {
+    #[fail(display = "Already exists")]
+    AlreadyExists,
+    #[fail(display = "Not found")]
+    NotFound,
+    #[fail(display = "Internal error")]
+    InternalError(String), //returns back the DatabaseError variant of sql error
+    #[fail(display = "An unknown error occurred")]
+    Unknown,
+}
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum UsageTrackingError {
+    #[fail(display = "Internal error")]
+    InternalError(String), //returns back the DatabaseError variant of sql error
+    #[fail(display = "An unknown error occurred")]
+    Unknown,
+}
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum TableAuditError {
+    #[fail(display = "Internal error")]
+    InternalError(String), //returns back the DatabaseError variant of sql error
+    #[fail(display = "An unknown error occurred")]
+    Unknown,
+}
+
+#[derive(Debug, Fail, PartialEq, Eq)]
+pub enum EntitySyncError {
+    #[fail(display = "No sync directory configured for this domain")]
+    NoSyncDirectory,
+    #[fail(display = "Could not read the sync directory: {}", 0)]
+    ReadError(String),
+    #[fail(display = "Internal error")]
+    InternalError(String), //returns back the DatabaseError variant of sql error
+    #[fail(display = "An unknown error occurred")]
+    Unknown,
+}
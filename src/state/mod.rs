@@ -4,12 +4,18 @@ pub mod authentication;
 pub mod authorization;
 pub mod user_management;
 pub mod domain_management;
+pub mod tag_management;
+pub mod usage_tracking;
+pub mod table_audit;
+pub mod entity_sync;
 
 use serde_json;
 
 use std::fmt::Debug;
 use std::fmt;
+use std::cell::RefCell;
 use std::sync::Arc;
+use std::path::PathBuf;
 
 use diesel::Connection;
 use serde::Serialize;
@@ -29,12 +35,27 @@ use model::entity::ModifierFunctions;
 use model::table::DatastoreAction;
 use model::table::DatastoreActionOps;
 use auth::send_mail::EmailSender;
+use auth::send_mail::MailSettings;
 use auth::send_mail::EmailOps;
+use auth::sessions::SessionCache;
+use auth::oidc::OidcClient;
+use auth::oidc::OidcSettings;
+use auth::oidc::OidcOps;
+use auth::ldap::LdapClient;
+use auth::ldap::LdapSettings;
+use auth::ldap::DirectoryOps;
+use auth::totp::MfaSettings;
+use auth::login_throttle::LoginThrottleSettings;
+use auth::password_policy::PasswordPolicy;
 
 use state::authorization::AuthorizationOps;
 use state::authentication::AuthenticationOps;
 use state::user_management::UserManagementOps;
 use state::domain_management::DomainManagementOps;
+use state::tag_management::TagManagementOps;
+use state::usage_tracking::UsageTrackingOps;
+use state::table_audit::TableAuditOps;
+use state::entity_sync::EntitySyncOps;
 use state::error::BroadcastError;
 
 use scripting::ScriptFunctions;
@@ -44,6 +65,7 @@ use data::claims::AuthClaims;
 use data::channels::Channels;
 use data::channels::Subscription;
 use data::auth::User;
+use data::auth::ClientInfo;
 use data::Message;
 use plugins::v1::Datastore;
 use plugins::v1::DataQuery;
@@ -62,6 +84,17 @@ pub struct ActionState {
     pub jwt_issuer: String,
     pub jwt_duration: i64,
     pub jwt_refresh_duration: i64,
+    pub sync_directory: Option<PathBuf>,
+    pub mail_settings: MailSettings,
+    pub oidc_settings: Option<OidcSettings>,
+    pub ldap_settings: Option<LdapSettings>,
+    pub mfa_settings: MfaSettings,
+    pub login_throttle: LoginThrottleSettings,
+    pub password_policy: PasswordPolicy,
+    pub client_info: ClientInfo,
+    pub session_cache: SessionCache,
+    /// The log of the script environment that was built while handling the request
+    pub install_log: RefCell<Option<String>>,
 }
 
 impl fmt::Debug for ActionState {
@@ -78,12 +111,18 @@ pub trait StateFunctions<'a>
         Self::Scripting: ScriptFunctions,
         Self::PubSub: PubSubOps,
         Self::EmailSender: EmailOps,
+        Self::OidcClient: OidcOps,
+        Self::Directory: DirectoryOps,
         //TODO: managementstore
         Self::EntityRetrieverFunctions: RetrieverFunctions,
         Self::EntityModifierFunctions: ModifierFunctions,
         //managementstore
         Self::UserManagement: UserManagementOps,
         Self::DomainManagement: DomainManagementOps,
+        Self::TagManagement: TagManagementOps,
+        Self::UsageTracking: UsageTrackingOps,
+        Self::TableAudit: TableAuditOps,
+        Self::EntitySync: EntitySyncOps,
         Self::Authorization: AuthorizationOps,
         Self::Authentication: AuthenticationOps,
 {
@@ -100,6 +139,18 @@ pub trait StateFunctions<'a>
     type DomainManagement;
     fn get_domain_management(&'a self) -> Self::DomainManagement;
 
+    type TagManagement;
+    fn get_tag_management(&'a self) -> Self::TagManagement;
+
+    type UsageTracking;
+    fn get_usage_tracking(&'a self) -> Self::UsageTracking;
+
+    type TableAudit;
+    fn get_table_audit(&'a self) -> Self::TableAudit;
+
+    type EntitySync;
+    fn get_entity_sync(&'a self) -> Self::EntitySync;
+
     // tables management
     type EntityRetrieverFunctions;
     fn get_entity_retreiver_functions(&'a self) -> Self::EntityRetrieverFunctions;
@@ -117,12 +168,21 @@ pub trait StateFunctions<'a>
     type Scripting;
     fn get_script_runner(&'a self) -> Self::Scripting;
 
+    /// The log of the script environment that was built since the last call, `None` if nothing was built
+    fn take_install_log(&self) -> Option<String>;
+
     type Database;
     fn get_database(&'a self) -> Self::Database;
 
     type EmailSender;
     fn get_email_sender(&'a self) -> Self::EmailSender;
 
+    type OidcClient;
+    fn get_oidc_client(&'a self) -> Self::OidcClient;
+
+    type Directory;
+    fn get_directory(&'a self) -> Self::Directory;
+
     type PubSub;
     fn get_pub_sub(&'a self) -> Self::PubSub;
 
@@ -141,6 +201,12 @@ impl<'a> StateFunctions<'a> for ActionState {
             jwt_duration: self.jwt_duration,
             jwt_refresh_duration: self.jwt_refresh_duration,
             jwt_issuer: self.jwt_issuer.to_owned(),
+            client_info: self.client_info.to_owned(),
+            session_cache: self.session_cache.to_owned(),
+            mfa_settings: self.mfa_settings.to_owned(),
+            login_throttle: self.login_throttle.to_owned(),
+            password_policy: self.password_policy.to_owned(),
+            directory: Box::new(self.get_directory()),
         }
     }
 
@@ -168,6 +234,35 @@ impl<'a> StateFunctions<'a> for ActionState {
         }
     }
 
+    type TagManagement = TagManagement<'a>;
+    fn get_tag_management(&'a self) -> Self::TagManagement {
+        TagManagement {
+            conn: &self.database,
+        }
+    }
+
+    type UsageTracking = UsageTracking<'a>;
+    fn get_usage_tracking(&'a self) -> Self::UsageTracking {
+        UsageTracking {
+            conn: &self.database,
+        }
+    }
+
+    type TableAudit = TableAudit<'a>;
+    fn get_table_audit(&'a self) -> Self::TableAudit {
+        TableAudit {
+            conn: &self.database,
+        }
+    }
+
+    type EntitySync = EntitySync<'a>;
+    fn get_entity_sync(&'a self) -> Self::EntitySync {
+        EntitySync {
+            conn: &self.database,
+            directory: &self.sync_directory,
+        }
+    }
+
     type EntityRetrieverFunctions = EntityRetrieverController<'a>;
     fn get_entity_retreiver_functions(&'a self) -> Self::EntityRetrieverFunctions {
         EntityRetrieverController {
@@ -186,6 +281,7 @@ impl<'a> StateFunctions<'a> for ActionState {
             domain_conn: &self.datastore_conn, //TODO: should be a separate thing, permissions
             claims: &self.claims,
             scripting: &self.scripting,
+            install_log: &self.install_log,
             user_management,
             domain_name: &self.domain_name,
         }
@@ -210,14 +306,34 @@ impl<'a> StateFunctions<'a> for ActionState {
         self.scripting.clone()
     }
 
+    fn take_install_log(&self) -> Option<String> {
+        self.install_log.borrow_mut().take()
+    }
+
     type Database = &'a Conn;
     fn get_database(&'a self) -> Self::Database {
         &self.database
     }
 
-    type EmailSender = EmailSender;
+    type EmailSender = EmailSender<'a>;
     fn get_email_sender(&'a self) -> Self::EmailSender {
-        EmailSender {}
+        EmailSender {
+            settings: &self.mail_settings,
+        }
+    }
+
+    type OidcClient = OidcClient<'a>;
+    fn get_oidc_client(&'a self) -> Self::OidcClient {
+        OidcClient {
+            settings: &self.oidc_settings,
+        }
+    }
+
+    type Directory = LdapClient<'a>;
+    fn get_directory(&'a self) -> Self::Directory {
+        LdapClient {
+            settings: &self.ldap_settings,
+        }
     }
 
     type PubSub = PublishCallback<'a>;
@@ -259,8 +375,83 @@ impl ActionState {
             jwt_issuer, //TODO: put these in config
             jwt_duration,
             jwt_refresh_duration,
+            sync_directory: None,
+            mail_settings: MailSettings::default(),
+            oidc_settings: None,
+            ldap_settings: None,
+            mfa_settings: MfaSettings::default(),
+            login_throttle: LoginThrottleSettings::default(),
+            password_policy: PasswordPolicy::default(),
+            client_info: ClientInfo::default(),
+            session_cache: SessionCache::default(),
+            install_log: RefCell::new(None),
         }
     }
+
+    /// The directory that the entities of the domain are synced with
+    pub fn with_sync_directory(mut self, sync_directory: Option<PathBuf>) -> Self {
+        self.sync_directory = sync_directory;
+        self
+    }
+
+    /// How invitations and notifications are sent
+    pub fn with_mail_settings(mut self, mail_settings: MailSettings) -> Self {
+        self.mail_settings = mail_settings;
+        self
+    }
+
+    /// The identity provider for single sign-on
+    pub fn with_oidc_settings(mut self, oidc_settings: Option<OidcSettings>) -> Self {
+        self.oidc_settings = oidc_settings;
+        self
+    }
+
+    /// The LDAP directory that passwords are checked against and users are synced from
+    pub fn with_ldap_settings(mut self, ldap_settings: Option<LdapSettings>) -> Self {
+        self.ldap_settings = ldap_settings;
+        self
+    }
+
+    /// Who has to use two-factor authentication
+    pub fn with_mfa_settings(mut self, mfa_settings: MfaSettings) -> Self {
+        self.mfa_settings = mfa_settings;
+        self
+    }
+
+    /// How failed logins are slowed down and when users are locked
+    pub fn with_login_throttle(mut self, login_throttle: LoginThrottleSettings) -> Self {
+        self.login_throttle = login_throttle;
+        self
+    }
+
+    /// What new passwords are checked against
+    pub fn with_password_policy(mut self, password_policy: PasswordPolicy) -> Self {
+        self.password_policy = password_policy;
+        self
+    }
+
+    /// The device and address that new sessions are recorded with
+    pub fn with_client_info(mut self, client_info: ClientInfo) -> Self {
+        self.client_info = client_info;
+        self
+    }
+
+    /// Shared with the executors, so that revoked sessions are evicted everywhere
+    pub fn with_session_cache(mut self, session_cache: SessionCache) -> Self {
+        self.session_cache = session_cache;
+        self
+    }
+
+    pub fn with_claims(mut self, claims: Option<AuthClaims>) -> Self {
+        self.claims = claims;
+        self
+    }
+
+    /// The token is treated as if it was never sent, i.e. when it's session was revoked
+    pub fn without_claims(mut self) -> Self {
+        self.claims = None;
+        self
+    }
 }
 
 pub struct Authentication<'a> {
@@ -270,6 +461,20 @@ pub struct Authentication<'a> {
     pub jwt_duration: i64,
     pub jwt_refresh_duration: i64,
     pub jwt_issuer: String,
+    pub client_info: ClientInfo,
+    pub session_cache: SessionCache,
+    pub mfa_settings: MfaSettings,
+    pub login_throttle: LoginThrottleSettings,
+    pub password_policy: PasswordPolicy,
+    /// Passwords of the users that come from the directory are checked with a bind
+    pub directory: Box<DirectoryOps + 'a>,
+}
+
+impl<'a> Authentication<'a> {
+    pub fn with_directory<D: DirectoryOps + 'a>(mut self, directory: D) -> Self {
+        self.directory = Box::new(directory);
+        self
+    }
 }
 
 pub struct Authorization<'a> {
@@ -286,6 +491,23 @@ pub struct DomainManagement<'a> {
     pub conn: &'a Conn,
 }
 
+pub struct TagManagement<'a> {
+    pub conn: &'a Conn,
+}
+
+pub struct UsageTracking<'a> {
+    pub conn: &'a Conn,
+}
+
+pub struct TableAudit<'a> {
+    pub conn: &'a Conn,
+}
+
+pub struct EntitySync<'a> {
+    pub conn: &'a Conn,
+    pub directory: &'a Option<PathBuf>,
+}
+
 pub struct PublishCallback<'a> {
     pub conn: &'a Conn,
 }
@@ -302,6 +524,9 @@ pub trait PubSubOps {
 
     fn get_subscribers(&self, channel: Channels) -> Result<Vec<User>, BroadcastError>;
 
+    /// moves the subscribers and messages over, used when the entity behind the channel is renamed
+    fn rename_channel(&self, old_channel: Channels, new_channel: Channels) -> Result<(), BroadcastError>;
+
     fn get_messages(
         &self,
         user_id: i64,
@@ -0,0 +1,12 @@
+use state::error::TableAuditError;
+use data::DataChange;
+use data::TableAuditRecord;
+use data::AuditLogFilter;
+
+pub trait TableAuditOps {
+    /// appends the changes to the audit log of the table, against it's latest schema
+    fn record_changes(&self, entity_id: i64, user_id: i64, changes: &[DataChange]) -> Result<(), TableAuditError>;
+
+    /// audit log of the table, oldest change first
+    fn get_audit_log(&self, entity_id: i64, filter: &AuditLogFilter) -> Result<Vec<TableAuditRecord>, TableAuditError>;
+}
@@ -0,0 +1,17 @@
+use state::error::TagManagementError;
+use data::Tag;
+
+pub trait TagManagementOps {
+    fn get_all_tags(&self) -> Result<Vec<Tag>, TagManagementError>;
+
+    fn create_tag(&self, tag: &Tag) -> Result<Tag, TagManagementError>;
+
+    fn delete_tag(&self, name: &str) -> Result<Tag, TagManagementError>;
+
+    /// tags of a single entity, `entity_id` is the same for all the revisions of the entity
+    fn get_entity_tags(&self, entity_id: i64) -> Result<Vec<Tag>, TagManagementError>;
+
+    fn attach_tag(&self, entity_id: i64, name: &str) -> Result<Tag, TagManagementError>;
+
+    fn detach_tag(&self, entity_id: i64, name: &str) -> Result<Tag, TagManagementError>;
+}
@@ -0,0 +1,15 @@
+use state::error::UsageTrackingError;
+use data::UsageRecord;
+use data::EntityUsage;
+
+pub trait UsageTrackingOps {
+    fn record_usage(&self, record: &UsageRecord) -> Result<(), UsageTrackingError>;
+
+    /// usage of every entity in the domain between `start_time` and `end_time`, most used first
+    fn get_entity_usage(
+        &self,
+        domain_name: &str,
+        start_time: chrono::NaiveDateTime,
+        end_time: chrono::NaiveDateTime,
+    ) -> Result<Vec<EntityUsage>, UsageTrackingError>;
+}
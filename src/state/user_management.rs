@@ -1,25 +1,56 @@
 use state::error::UserManagementError;
 use data::auth::NewUser;
 use data::auth::InvitationToken;
+use data::auth::PasswordResetToken;
+use data::auth::LoginRequest;
+use data::auth::ExternalUser;
 use data::auth::User;
+use data::auth::UserModification;
 use data::auth::UserInfo;
 use data::auth::Role;
 use data::permissions::Permission;
 
 pub trait UserManagementOps {
+    /// Fails with `PasswordExpired` if the password was right but is too old
     fn get_user(&self, user_identifier: &str, password: &str) -> Result<UserInfo, UserManagementError>;
+    /// Only checks the password, an expired one is still accepted so that it can be changed
+    fn verify_user_password(&self, user_identifier: &str, password: &str) -> Result<UserInfo, UserManagementError>;
+    /// Without a password, fails with `NotFound` or with `AmbiguousIdentifier` if the identifier matches more than one user
+    fn get_user_info(&self, user_identifier: &str) -> Result<UserInfo, UserManagementError>;
     fn add_user(&self, user: &NewUser) -> Result<User, UserManagementError>;
     fn remove_user(&self, user_identifier: &str) -> Result<User, UserManagementError>;
+    /// Users for automation, they don't have a password or an email and only authenticate with api keys
+    fn add_service_account(&self, username: &str, display_name: Option<String>) -> Result<User, UserManagementError>;
+    /// Fails with `NotFound` if the user is not a service account
+    fn get_service_account(&self, username: &str) -> Result<UserInfo, UserManagementError>;
 
     fn create_user_token(&self, email: &str) -> Result<InvitationToken, UserManagementError>;
-    //TODO: all modifications
+    /// Fails with `AlreadyExists` if the new email belongs to someone else
+    fn modify_user(&self, user_identifier: &str, modification: &UserModification) -> Result<User, UserManagementError>;
     fn modify_user_password(&self, user_identifier: &str, password: &str) -> Result<User, UserManagementError>;
+    /// Disabled users can't log in or refresh their tokens, everything else about them is kept
+    fn set_user_disabled(&self, user_identifier: &str, disabled: bool) -> Result<User, UserManagementError>;
+    /// For users that were removed from the directory, they can't log in or refresh their tokens but everything else about them is kept
+    fn set_user_deprovisioned(&self, user_identifier: &str, deprovisioned: bool) -> Result<User, UserManagementError>;
+    /// Any older token that the user hasn't used stops working
+    fn create_password_reset_token(&self, user_identifier: &str) -> Result<PasswordResetToken, UserManagementError>;
+    /// Marks the token as used, fails if it was already used or has expired
+    fn use_password_reset_token(&self, token: &str) -> Result<UserInfo, UserManagementError>;
+
+    fn create_login_request(&self, login_request: &LoginRequest) -> Result<(), UserManagementError>;
+    /// Each login request can only come back once, fails if it was never made or has expired
+    fn take_login_request(&self, state: &str) -> Result<LoginRequest, UserManagementError>;
+    /// Users from an identity provider are created the first time they log in
+    fn get_or_create_external_user(&self, external_user: &ExternalUser) -> Result<UserInfo, UserManagementError>;
+    /// The users from the identity provider that are not deprovisioned, with their subject
+    fn get_active_external_users(&self, issuer: &str) -> Result<Vec<(String, UserInfo)>, UserManagementError>;
     fn get_all_users(&self) -> Result<Vec<User>, UserManagementError>;
 
     fn add_role(&self, rolename: &Role) -> Result<Role, UserManagementError>;
     fn rename_role(&self, oldname: &str, newname: &str) -> Result<Role, UserManagementError>;
     fn remove_role(&self, name: &str) -> Result<Role, UserManagementError>;
     fn get_all_roles(&self) -> Result<Vec<Role>, UserManagementError>;
+    fn get_role_permissions(&self, rolename: &str) -> Result<Vec<Permission>, UserManagementError>;
 
     fn add_permission(&self, permission: &Permission) -> Result<Permission, UserManagementError>;
     fn rename_permission(&self, old_permission: &Permission, new_permission: &Permission) -> Result<Permission, UserManagementError>;
@@ -3,6 +3,7 @@ use actix_web::test::TestServer;
 
 use std::sync::Arc;
 use std::path::PathBuf;
+use std::net::IpAddr;
 
 use actix_web::test::TestServerBuilder;
 use actix_web::HttpMessage;
@@ -26,6 +27,7 @@ use scripting::Scripting;
 use serde::Serialize;
 use data::auth::InvitationToken;
 use data::auth::Invitation;
+use data::auth::PasswordResetToken;
 use auth::send_mail::EmailError;
 use diesel::r2d2::Pool;
 use model::actions;
@@ -169,6 +171,10 @@ impl AppStateLike for TestState {
     fn connect(&self) -> &Addr<Executor> {
         self.0.connect()
     }
+
+    fn get_trusted_proxies(&self) -> &[IpAddr] {
+        self.0.get_trusted_proxies()
+    }
 }
 
 impl GetSecrets for TestState {
@@ -198,6 +204,14 @@ impl EmailOps for MockMailer {
             expires_at: invitation_token.expires_at,
         })
     }
+
+    fn send_password_reset(&self, _password_reset_token: PasswordResetToken) -> Result<(), EmailError> {
+        Ok(())
+    }
+
+    fn send_notification(&self, _email: &str, _subject: &str, _message: &str) -> Result<(), EmailError> {
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -223,6 +237,26 @@ impl<'a> StateFunctions<'a> for MockState {
         self.0.get_domain_management()
     }
 
+    type TagManagement = <ActionState as StateFunctions<'a>>::TagManagement;
+    fn get_tag_management(&'a self) -> <Self as StateFunctions<'a>>::TagManagement {
+        self.0.get_tag_management()
+    }
+
+    type UsageTracking = <ActionState as StateFunctions<'a>>::UsageTracking;
+    fn get_usage_tracking(&'a self) -> <Self as StateFunctions<'a>>::UsageTracking {
+        self.0.get_usage_tracking()
+    }
+
+    type TableAudit = <ActionState as StateFunctions<'a>>::TableAudit;
+    fn get_table_audit(&'a self) -> <Self as StateFunctions<'a>>::TableAudit {
+        self.0.get_table_audit()
+    }
+
+    type EntitySync = <ActionState as StateFunctions<'a>>::EntitySync;
+    fn get_entity_sync(&'a self) -> <Self as StateFunctions<'a>>::EntitySync {
+        self.0.get_entity_sync()
+    }
+
 
     type EntityRetrieverFunctions = <ActionState as StateFunctions<'a>>::EntityRetrieverFunctions;
     fn get_entity_retreiver_functions(&'a self) -> <Self as StateFunctions<'a>>::EntityRetrieverFunctions {
@@ -249,6 +283,10 @@ impl<'a> StateFunctions<'a> for MockState {
         self.0.get_script_runner()
     }
 
+    fn take_install_log(&self) -> Option<String> {
+        self.0.take_install_log()
+    }
+
     type Database = <ActionState as StateFunctions<'a>>::Database;
     fn get_database(&'a self) -> <Self as StateFunctions<'a>>::Database {
         self.0.get_database()
@@ -259,6 +297,16 @@ impl<'a> StateFunctions<'a> for MockState {
         MockMailer
     }
 
+    type OidcClient = <ActionState as StateFunctions<'a>>::OidcClient;
+    fn get_oidc_client(&'a self) -> Self::OidcClient {
+        self.0.get_oidc_client()
+    }
+
+    type Directory = <ActionState as StateFunctions<'a>>::Directory;
+    fn get_directory(&'a self) -> Self::Directory {
+        self.0.get_directory()
+    }
+
     type PubSub = <ActionState as StateFunctions<'a>>::PubSub;
     fn get_pub_sub(&'a self) -> Self::PubSub {
         self.0.get_pub_sub()
@@ -7,14 +7,20 @@ use actix::dev::MessageResponse;
 use model::actions::Action;
 use state::ActionState;
 use data::claims::AuthClaims;
+use data::auth::ClientInfo;
 use model::actions::ActionResult;
 use model::actions::error::Error;
 use scripting::Scripting;
+use scripting::ScriptOutput;
 use std::str;
 use jsonwebtoken;
 use std::fmt;
 use view::bearer_token::parse_bearer_token;
+use view::bearer_token::parse_api_key;
 use state::PublishCallback;
+use state::StateFunctions;
+use state::authentication::AuthenticationOps;
+use connection::GetSecrets;
 
 
 pub struct ActionWrapper<A>
@@ -22,7 +28,10 @@ pub struct ActionWrapper<A>
 {
     action: Result<A, serde_json::Error>,
     auth_header: Option<Vec<u8>>,
+    claims: Option<AuthClaims>,
     domain_name: Option<String>,
+    script_output: Option<Recipient<ScriptOutput>>,
+    client_info: ClientInfo,
 }
 
 impl<A> fmt::Debug for ActionWrapper<A>
@@ -42,21 +51,30 @@ impl<A> ActionWrapper<A>
                 Self {
                     action: Ok(action),
                     auth_header: None,
+                    claims: None,
                     domain_name: Some(domain_name),
+                    script_output: None,
+                    client_info: ClientInfo::default(),
                 }
             },
             Ok((None, action)) => {
                 Self {
                     action: Ok(action),
                     auth_header: None,
+                    claims: None,
                     domain_name: None,
+                    script_output: None,
+                    client_info: ClientInfo::default(),
                 }
             },
             Err(err) => {
                 Self {
                     action: Err(err),
                     auth_header: None,
+                    claims: None,
                     domain_name: None,
+                    script_output: None,
+                    client_info: ClientInfo::default(),
                 }
             }
         }
@@ -66,7 +84,22 @@ impl<A> ActionWrapper<A>
         Self {
             action: self.action,
             auth_header: Some(auth.to_owned()),
+            claims: self.claims,
             domain_name: self.domain_name,
+            script_output: self.script_output,
+            client_info: self.client_info,
+        }
+    }
+
+    /// For calls made by the server itself, there is no token to decode
+    pub fn with_claims(self, claims: AuthClaims) -> Self {
+        Self {
+            action: self.action,
+            auth_header: self.auth_header,
+            claims: Some(claims),
+            domain_name: self.domain_name,
+            script_output: self.script_output,
+            client_info: self.client_info,
         }
     }
 
@@ -74,7 +107,34 @@ impl<A> ActionWrapper<A>
         Self {
             action: self.action,
             auth_header: self.auth_header,
+            claims: self.claims,
             domain_name: Some(domain_name.to_owned()),
+            script_output: self.script_output,
+            client_info: self.client_info,
+        }
+    }
+
+    /// Script output is streamed to the recipient while the action runs
+    pub fn with_script_output(self, script_output: Recipient<ScriptOutput>) -> Self {
+        Self {
+            action: self.action,
+            auth_header: self.auth_header,
+            claims: self.claims,
+            domain_name: self.domain_name,
+            script_output: Some(script_output),
+            client_info: self.client_info,
+        }
+    }
+
+    /// The device and address of the caller, new sessions are recorded with them
+    pub fn with_client_info(self, client_info: ClientInfo) -> Self {
+        Self {
+            action: self.action,
+            auth_header: self.auth_header,
+            claims: self.claims,
+            domain_name: self.domain_name,
+            script_output: self.script_output,
+            client_info,
         }
     }
 
@@ -82,11 +142,22 @@ impl<A> ActionWrapper<A>
         self.domain_name.to_owned()
     }
 
-    fn decode_token(&self, token_secret: String) -> Option<AuthClaims> {
-        let auth_header = self.auth_header.to_owned();
+    /// Either a bearer token, or an api key which has to be looked up
+    fn decode_token(&self, state: &ActionState) -> Option<AuthClaims> {
+        let auth_header = self.auth_header
+            .to_owned()
+            .and_then(|bytes| str::from_utf8(&bytes).ok().map(|x| x.to_string()));
+
+        if let Some(api_key) = auth_header.as_ref().and_then(|data| parse_api_key(data)) {
+            return state
+                .get_authentication()
+                .authenticate_api_key(&api_key)
+                .map_err(|err| error!("encountered error trying to authenticate api key: {:?}", &err))
+                .ok();
+        }
 
+        let token_secret = state.get_token_secret();
         auth_header
-            .and_then(|bytes| str::from_utf8(&bytes).ok().map(|x| x.to_string()))
             .and_then(|data| parse_bearer_token(data))
             .and_then(|auth| {
                 let decoded = jsonwebtoken::decode::<AuthClaims>(
@@ -103,6 +174,11 @@ impl<A> ActionWrapper<A>
                 }
             })
             .and_then(|token_data| Some(token_data.claims))
+            // a token handed out for an api key stops working together with the key
+            .filter(|claims| match claims.get_api_key_id() {
+                Some(api_key_id) => is_api_key_active(state, api_key_id),
+                None => true,
+            })
     }
 
     fn get_action(self) -> Result<A, Error> {
@@ -128,37 +204,33 @@ impl<A: Action + Send> Handler<ActionWrapper<A>> for Executor
 
     fn handle(&mut self, msg: ActionWrapper<A>, _: &mut Self::Context) -> Self::Result {
 
-        let auth_claims = msg.decode_token(self.get_token_secret());
         let domain_name = msg.get_domain_name();
+        let script_output = msg.script_output.to_owned();
+        let client_info = msg.client_info.to_owned();
         info!("Request for domain: {:?}", &domain_name);
 
-        // Unauthorized has priority over serialization failed
-        let action_req = match msg.get_action() {
-            Err(action_req_serialization_error) => {
-                if auth_claims.is_none() {
-                    Err(Error::Unauthorized)
-                } else {
-                    Err(action_req_serialization_error)
-                }
-            },
-            Ok(x) => Ok(x),
-        };
-
-        let action_req = action_req?;
         let conn = self.get_connection();
 
         let domain_name_unwrapped = domain_name.to_owned().unwrap_or_default();
         let datastore_conn = self.get_datastore_conn(&domain_name_unwrapped);
         let query_conn = self.get_query_conn(&domain_name_unwrapped);
 
-        let scripting = Scripting::new(self.get_scripts_path());
+        let scripting = Scripting::new(self.get_scripts_path())
+            .with_runner(self.get_script_runner())
+            .with_allowed_paths(self.get_script_allowed_paths())
+            .with_api(self.get_script_api_url(), domain_name.to_owned())
+            .with_api_socket(self.get_script_api_socket())
+            .with_output_recipient(script_output);
         let secrets = self.get_secrets();
 
         //TODO: this is getting out of hand, builder pattern is the way to do this
+        let sync_directory = domain_name
+            .to_owned()
+            .and_then(|domain_name| self.get_sync_directory(&domain_name));
         let state = ActionState::new(
             conn,
             scripting,
-            auth_claims,
+            None,
             secrets,
             domain_name,
             datastore_conn,
@@ -166,13 +238,72 @@ impl<A: Action + Send> Handler<ActionWrapper<A>> for Executor
             self.jwt_issuer.to_owned(),
             self.jwt_token_duration,
             self.jwt_refresh_token_duration,
-        );
+        )
+            .with_sync_directory(sync_directory)
+            .with_mail_settings(self.get_mail_settings())
+            .with_oidc_settings(self.get_oidc_settings())
+            .with_ldap_settings(self.get_ldap_settings())
+            .with_mfa_settings(self.get_mfa_settings())
+            .with_login_throttle(self.get_login_throttle())
+            .with_password_policy(self.get_password_policy())
+            .with_client_info(client_info)
+            .with_session_cache(self.get_session_cache());
+
+        // api keys are looked up in the metastore, so the claims need the state
+        let auth_claims = match msg.claims.to_owned() {
+            Some(claims) => Some(claims),
+            None => msg.decode_token(&state),
+        };
+        let session_id = auth_claims.as_ref().and_then(|claims| claims.get_session_id());
+        let is_authenticated = auth_claims.is_some();
+        let state = state.with_claims(auth_claims);
+
+        // Unauthorized has priority over serialization failed
+        let action_req = match msg.get_action() {
+            Err(action_req_serialization_error) => {
+                if !is_authenticated {
+                    Err(Error::Unauthorized)
+                } else {
+                    Err(action_req_serialization_error)
+                }
+            },
+            Ok(x) => Ok(x),
+        };
+
+        let action_req = action_req?;
+
+        // the token of a revoked session is treated like an invalid token
+        let state = match session_id {
+            Some(session_id) if !is_session_active(&state, session_id) => state.without_claims(),
+            _ => state,
+        };
+
         let result = action_req.call(&state);
         debug!("action result: {:?}", &result);
         result
     }
 }
 
+fn is_session_active(state: &ActionState, session_id: i64) -> bool {
+    state
+        .get_authentication()
+        .is_session_active(session_id)
+        .unwrap_or_else(|err| {
+            error!("Could not check the session {}: {:?}", session_id, &err);
+            false
+        })
+}
+
+fn is_api_key_active(state: &ActionState, api_key_id: i64) -> bool {
+    state
+        .get_authentication()
+        .is_api_key_active(api_key_id)
+        .unwrap_or_else(|err| {
+            error!("Could not check the api key {}: {:?}", api_key_id, &err);
+            false
+        })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -241,4 +372,13 @@ mod test {
 
         assert_eq!(output, None);
     }
+
+    #[test]
+    fn test_parse_api_key() {
+        let output = parse_api_key("ApiKey kak_MY_KEY_HERE");
+        assert_eq!(output.unwrap(), "kak_MY_KEY_HERE");
+
+        let output = parse_api_key("Bearer MY_TOKEN_HERE");
+        assert_eq!(output, None);
+    }
 }
\ No newline at end of file
@@ -1,6 +1,18 @@
 
 
 const BEARER: &'static str = "Bearer ";
+const API_KEY: &'static str = "ApiKey ";
+
+/// `Authorization: ApiKey <key>`, the alternative to a bearer token for automation
+pub fn parse_api_key(data: &str) -> Option<String> {
+    if data.starts_with(API_KEY) {
+        let (_, key_str) = data.split_at(API_KEY.len());
+
+        Some(key_str.to_string())
+    } else {
+        None
+    }
+}
 
 pub fn parse_bearer_token(data: String) -> Option<String> {
     let is_bearer = data.starts_with(BEARER);
@@ -0,0 +1,79 @@
+use std::net::IpAddr;
+
+use actix_web::HttpRequest;
+use actix_web::http::header;
+
+use connection::AppStateLike;
+use data::auth::ClientInfo;
+
+const X_FORWARDED_FOR: &'static str = "X-Forwarded-For";
+
+/// The user agent and the address of the caller, `X-Forwarded-For` is only used when the connection
+/// comes from a trusted proxy, since anyone else could put whatever they want in it
+pub fn get_client_info<S>(req: &HttpRequest<S>) -> ClientInfo
+    where
+        S: AppStateLike,
+{
+    let device = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|user_agent| user_agent.to_str().ok())
+        .map(|user_agent| user_agent.to_string());
+    let forwarded_for = req
+        .headers()
+        .get(X_FORWARDED_FOR)
+        .and_then(|forwarded_for| forwarded_for.to_str().ok());
+    let ip_address = req
+        .peer_addr()
+        .map(|peer_addr| get_client_address(peer_addr.ip(), forwarded_for, req.state().get_trusted_proxies()))
+        .map(|ip_address| ip_address.to_string());
+
+    ClientInfo {
+        device,
+        ip_address,
+    }
+}
+
+/// Every proxy appends the address it got the request from, so the list is read from the end
+/// and the first address that isn't one of the trusted proxies is the client
+fn get_client_address(peer_address: IpAddr, forwarded_for: Option<&str>, trusted_proxies: &[IpAddr]) -> IpAddr {
+    if !trusted_proxies.contains(&peer_address) {
+        return peer_address;
+    }
+
+    let mut client_address = peer_address;
+    for forwarded_address in forwarded_for.unwrap_or("").rsplit(',') {
+        match forwarded_address.trim().parse::<IpAddr>() {
+            Ok(address) => {
+                client_address = address;
+                if !trusted_proxies.contains(&address) {
+                    break;
+                }
+            },
+            Err(_) => break,
+        }
+    }
+
+    client_address
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_client_address() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        let spoofed = Some("198.51.100.1, 203.0.113.7");
+
+        // without a trusted proxy the header is ignored
+        assert_eq!(get_client_address(client, spoofed, &[]), client);
+        assert_eq!(get_client_address(client, spoofed, &[proxy]), client);
+
+        // behind the proxy only the address it appended counts
+        assert_eq!(get_client_address(proxy, spoofed, &[proxy]), client);
+        assert_eq!(get_client_address(proxy, None, &[proxy]), proxy);
+        assert_eq!(get_client_address(proxy, Some("garbage"), &[proxy]), proxy);
+    }
+}
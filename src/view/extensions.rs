@@ -27,6 +27,7 @@ use model::actions::Action;
 
 use view::routes::users;
 use view::routes::manage;
+use view::routes::pubsub;
 use view::websocket;
 
 use connection::executor::Executor;
@@ -104,48 +105,117 @@ impl<S> ProcedureExt<S> for CorsBuilder<S>
     fn add_routes(&mut self) -> &mut Self {
         self
             .add_route("/manage/getAllDomains", manage::get_all_domains)
+            .add_route("/manage/exportDomain", manage::export_domain)
+            .add_route("/manage/importDomain", manage::import_domain)
+            .add_route("/manage/planSync", manage::plan_sync)
+            .add_route("/manage/applySync", manage::apply_sync)
             //TODO: manage domains?
 
             .add_route("/manage/getAllTables", manage::get_all_tables)
             .add_route("/manage/getAllQueries", manage::get_all_queries)
             .add_route("/manage/getAllScripts", manage::get_all_scripts)
+            .add_route("/manage/getAllViews", manage::get_all_views)
 
             .add_route("/manage/getTable", manage::get_table)
             .add_route("/manage/getQuery", manage::get_query)
             .add_route("/manage/getScript", manage::get_script)
+            .add_route("/manage/getView", manage::get_view)
 
             .add_route("/manage/createTable", manage::create_table)
             .add_route("/manage/createQuery", manage::create_query)
             .add_route("/manage/createScript", manage::create_script)
+            .add_route("/manage/createView", manage::create_view)
 
             .add_route("/manage/updateTable", manage::update_table)
             .add_route("/manage/updateQuery", manage::update_query)
             .add_route("/manage/updateScript", manage::update_script)
+            .add_route("/manage/updateView", manage::update_view)
+            .add_route("/manage/renameTable", manage::rename_table)
+            .add_route("/manage/renameQuery", manage::rename_query)
+            .add_route("/manage/renameScript", manage::rename_script)
+            .add_route("/manage/renameView", manage::rename_view)
+            .add_route("/manage/patchScript", manage::patch_script)
+            .add_route("/manage/uploadScriptArchive", manage::upload_script_archive)
 
             .add_route("/manage/deleteTable", manage::delete_table)
             .add_route("/manage/deleteQuery", manage::delete_query)
             .add_route("/manage/deleteScript", manage::delete_script)
+            .add_route("/manage/deleteView", manage::delete_view)
+
+            .add_route("/manage/getTableHistory", manage::get_table_history)
+            .add_route("/manage/getQueryHistory", manage::get_query_history)
+            .add_route("/manage/getScriptHistory", manage::get_script_history)
+
+            .add_route("/manage/getTableRevision", manage::get_table_revision)
+            .add_route("/manage/getQueryRevision", manage::get_query_revision)
+            .add_route("/manage/getScriptRevision", manage::get_script_revision)
+
+            .add_route("/manage/diffTableRevisions", manage::diff_table_revisions)
+            .add_route("/manage/diffQueryRevisions", manage::diff_query_revisions)
+            .add_route("/manage/diffScriptRevisions", manage::diff_script_revisions)
+
+            .add_route("/manage/revertTable", manage::revert_table)
+            .add_route("/manage/revertQuery", manage::revert_query)
+            .add_route("/manage/revertScript", manage::revert_script)
+
+            .add_route("/manage/getAllTags", manage::get_all_tags)
+            .add_route("/manage/createTag", manage::create_tag)
+            .add_route("/manage/deleteTag", manage::delete_tag)
+            .add_route("/manage/getDependents", manage::get_dependents)
+            .add_route("/manage/getEntityTags", manage::get_entity_tags)
+            .add_route("/manage/attachTag", manage::attach_tag)
+            .add_route("/manage/detachTag", manage::detach_tag)
+            .add_route("/manage/searchEntities", manage::search_entities)
+
+            .add_route("/manage/getEntityUsage", manage::get_entity_usage)
 
             .add_route("/manage/queryTableData", manage::query_table_data)
             .add_route("/manage/insertTableData", manage::insert_table_data)
             .add_route("/manage/modifyTableData", manage::modify_table_data)
             .add_route("/manage/removeTableData", manage::remove_table_data)
+            .add_route("/manage/getTableAuditLog", manage::get_table_audit_log)
+            .add_route("/manage/revertTableChanges", manage::revert_table_changes)
 
             .add_route("/manage/runQuery", manage::run_query)
             .add_route("/manage/runScript", manage::run_script)
+            .add_route("/manage/getViewData", manage::get_view_data)
+
+            .add_route("/pubsub/publish", pubsub::publish)
 
             //TODO: subscriptions maybe?
 
             .add_route("/users/login", users::login)
             .add_route("/users/refresh", users::refresh)
+            .add_route("/users/oidcLogin", users::oidc_login)
+            .add_route("/users/oidcCallback", users::oidc_callback)
+            .add_route("/users/syncDirectory", users::sync_directory)
             .add_route("/users/logout", users::logout)
+            .add_route("/users/listMySessions", users::list_my_sessions)
+            .add_route("/users/revokeSession", users::revoke_session)
+            .add_route("/users/addServiceAccount", users::add_service_account)
+            .add_route("/users/createApiKey", users::create_api_key)
+            .add_route("/users/listApiKeys", users::list_api_keys)
+            .add_route("/users/revokeApiKey", users::revoke_api_key)
+            .add_route("/users/enrollMfa", users::enroll_mfa)
+            .add_route("/users/verifyMfa", users::verify_mfa)
+            .add_route("/users/disableMfa", users::disable_mfa)
+            .add_route("/users/resetMfa", users::reset_mfa)
+            .add_route("/users/forceLogout", users::force_logout)
+            .add_route("/users/unlockUser", users::unlock_user)
+            .add_route("/users/getLoginFailures", users::get_login_failures)
             .add_route("/users/getAllUsers", users::get_all_users)
 
             .add_route("/users/addUser", users::add_user)
             .add_route("/users/removeUser", users::remove_user)
+            .add_route("/users/modifyUser", users::modify_user)
+            .add_route("/users/disableUser", users::disable_user)
+            .add_route("/users/enableUser", users::enable_user)
             .add_route("/users/inviteUser", users::invite_user)
             .add_route("/users/setupUser", users::setup_user)
             .add_route("/users/setUserPassword", users::set_user_password)
+            .add_route("/users/changeMyPassword", users::change_my_password)
+            .add_route("/users/requestPasswordReset", users::request_password_reset)
+            .add_route("/users/confirmPasswordReset", users::confirm_password_reset)
 
             .add_route("/users/addRole", users::add_role)
             .add_route("/users/removeRole", users::remove_role)
@@ -199,45 +269,114 @@ impl<S> ProcedureExt<S> for TestApp<S>
     fn add_routes(&mut self) -> &mut Self {
         self
             .add_route("/manage/getAllDomains", manage::get_all_domains)
+            .add_route("/manage/exportDomain", manage::export_domain)
+            .add_route("/manage/importDomain", manage::import_domain)
+            .add_route("/manage/planSync", manage::plan_sync)
+            .add_route("/manage/applySync", manage::apply_sync)
 
             .add_route("/manage/getAllTables", manage::get_all_tables)
             .add_route("/manage/getAllQueries", manage::get_all_queries)
             .add_route("/manage/getAllScripts", manage::get_all_scripts)
+            .add_route("/manage/getAllViews", manage::get_all_views)
 
             .add_route("/manage/getTable", manage::get_table)
             .add_route("/manage/getQuery", manage::get_query)
             .add_route("/manage/getScript", manage::get_script)
+            .add_route("/manage/getView", manage::get_view)
 
             .add_route("/manage/createTable", manage::create_table)
             .add_route("/manage/createQuery", manage::create_query)
             .add_route("/manage/createScript", manage::create_script)
+            .add_route("/manage/createView", manage::create_view)
 
             .add_route("/manage/updateTable", manage::update_table)
             .add_route("/manage/updateQuery", manage::update_query)
             .add_route("/manage/updateScript", manage::update_script)
+            .add_route("/manage/updateView", manage::update_view)
+            .add_route("/manage/renameTable", manage::rename_table)
+            .add_route("/manage/renameQuery", manage::rename_query)
+            .add_route("/manage/renameScript", manage::rename_script)
+            .add_route("/manage/renameView", manage::rename_view)
+            .add_route("/manage/patchScript", manage::patch_script)
+            .add_route("/manage/uploadScriptArchive", manage::upload_script_archive)
 
             .add_route("/manage/deleteTable", manage::delete_table)
             .add_route("/manage/deleteQuery", manage::delete_query)
             .add_route("/manage/deleteScript", manage::delete_script)
+            .add_route("/manage/deleteView", manage::delete_view)
+
+            .add_route("/manage/getTableHistory", manage::get_table_history)
+            .add_route("/manage/getQueryHistory", manage::get_query_history)
+            .add_route("/manage/getScriptHistory", manage::get_script_history)
+
+            .add_route("/manage/getTableRevision", manage::get_table_revision)
+            .add_route("/manage/getQueryRevision", manage::get_query_revision)
+            .add_route("/manage/getScriptRevision", manage::get_script_revision)
+
+            .add_route("/manage/diffTableRevisions", manage::diff_table_revisions)
+            .add_route("/manage/diffQueryRevisions", manage::diff_query_revisions)
+            .add_route("/manage/diffScriptRevisions", manage::diff_script_revisions)
+
+            .add_route("/manage/revertTable", manage::revert_table)
+            .add_route("/manage/revertQuery", manage::revert_query)
+            .add_route("/manage/revertScript", manage::revert_script)
+
+            .add_route("/manage/getAllTags", manage::get_all_tags)
+            .add_route("/manage/createTag", manage::create_tag)
+            .add_route("/manage/deleteTag", manage::delete_tag)
+            .add_route("/manage/getDependents", manage::get_dependents)
+            .add_route("/manage/getEntityTags", manage::get_entity_tags)
+            .add_route("/manage/attachTag", manage::attach_tag)
+            .add_route("/manage/detachTag", manage::detach_tag)
+            .add_route("/manage/searchEntities", manage::search_entities)
+
+            .add_route("/manage/getEntityUsage", manage::get_entity_usage)
 
             .add_route("/manage/queryTableData", manage::query_table_data)
             .add_route("/manage/insertTableData", manage::insert_table_data)
             .add_route("/manage/modifyTableData", manage::modify_table_data)
             .add_route("/manage/removeTableData", manage::remove_table_data)
+            .add_route("/manage/getTableAuditLog", manage::get_table_audit_log)
+            .add_route("/manage/revertTableChanges", manage::revert_table_changes)
 
             .add_route("/manage/runQuery", manage::run_query)
             .add_route("/manage/runScript", manage::run_script)
+            .add_route("/manage/getViewData", manage::get_view_data)
+
+            .add_route("/pubsub/publish", pubsub::publish)
 
             .add_route("/users/login", users::login)
             .add_route("/users/refresh", users::refresh)
+            .add_route("/users/oidcLogin", users::oidc_login)
+            .add_route("/users/oidcCallback", users::oidc_callback)
+            .add_route("/users/syncDirectory", users::sync_directory)
             .add_route("/users/logout", users::logout)
+            .add_route("/users/listMySessions", users::list_my_sessions)
+            .add_route("/users/revokeSession", users::revoke_session)
+            .add_route("/users/addServiceAccount", users::add_service_account)
+            .add_route("/users/createApiKey", users::create_api_key)
+            .add_route("/users/listApiKeys", users::list_api_keys)
+            .add_route("/users/revokeApiKey", users::revoke_api_key)
+            .add_route("/users/enrollMfa", users::enroll_mfa)
+            .add_route("/users/verifyMfa", users::verify_mfa)
+            .add_route("/users/disableMfa", users::disable_mfa)
+            .add_route("/users/resetMfa", users::reset_mfa)
+            .add_route("/users/forceLogout", users::force_logout)
+            .add_route("/users/unlockUser", users::unlock_user)
+            .add_route("/users/getLoginFailures", users::get_login_failures)
             .add_route("/users/getAllUsers", users::get_all_users)
 
             .add_route("/users/addUser", users::add_user)
             .add_route("/users/removeUser", users::remove_user)
+            .add_route("/users/modifyUser", users::modify_user)
+            .add_route("/users/disableUser", users::disable_user)
+            .add_route("/users/enableUser", users::enable_user)
             .add_route("/users/inviteUser", users::invite_user)
             .add_route("/users/setupUser", users::setup_user)
             .add_route("/users/setUserPassword", users::set_user_password)
+            .add_route("/users/changeMyPassword", users::change_my_password)
+            .add_route("/users/requestPasswordReset", users::request_password_reset)
+            .add_route("/users/confirmPasswordReset", users::confirm_password_reset)
 
             .add_route("/users/addRole", users::add_role)
             .add_route("/users/removeRole", users::remove_role)
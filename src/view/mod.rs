@@ -9,6 +9,7 @@ pub mod routes;
 pub mod action_wrapper;
 pub mod extensions;
 pub mod bearer_token;
+pub mod client_info;
 
 use std::result::Result;
 use std::result::Result::Ok;
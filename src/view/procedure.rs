@@ -23,6 +23,7 @@ use connection::AppStateLike;
 
 use model::actions::Action;
 use view::action_wrapper::ActionWrapper;
+use view::client_info::get_client_info;
 
 type AsyncResponse = Box<Future<Item=HttpResponse, Error=ActixError>>;
 
@@ -129,7 +130,8 @@ pub fn procedure_handler_function<S, JP, QP, PB, A>(
     let state = req.state();
 
     let auth_header = req.headers().get(header::AUTHORIZATION).map(|x| x.as_bytes());
-    let mut action_wrapper = ActionWrapper::new(action);
+    let mut action_wrapper = ActionWrapper::new(action)
+        .with_client_info(get_client_info(&req));
     if let Some(auth) = auth_header {
         action_wrapper = action_wrapper.with_auth(auth);
     }
@@ -147,8 +149,17 @@ pub fn procedure_handler_function<S, JP, QP, PB, A>(
             },
             Err(err) => {
                 debug!("Responding with error message: {:?}", &err);
-                Ok(HttpResponse::InternalServerError()
-                    .json(json!({ "error": err.to_string() })))
+                let mut response = json!({ "error": err.to_string() });
+                match err.get_details() {
+                    // the request was fine, but what's in it isn't allowed, e.g. a password that breaks the policy
+                    Some(details) => {
+                        response["details"] = details;
+                        Ok(HttpResponse::UnprocessableEntity()
+                            .json(response))
+                    },
+                    None => Ok(HttpResponse::InternalServerError()
+                        .json(response)),
+                }
             }
         })
         .responder()
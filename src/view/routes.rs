@@ -3,6 +3,7 @@ use model::actions;
 
 use view::procedure::NoQuery;
 use data;
+use data::utils::DependentsPolicy;
 use model::actions::Action;
 use serde_json::Value;
 use serde_json::Error;
@@ -15,6 +16,8 @@ pub struct GetAllEntities {
     pub domain: String,
     #[serde(default)]
     pub show_deleted: bool,
+    #[serde(flatten)]
+    pub filter: data::EntityFilter,
 }
 
 #[derive(Deserialize, Debug)]
@@ -24,6 +27,73 @@ pub struct GetEntity {
     pub domain: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameEntity {
+    pub new_name: String,
+    #[serde(default)]
+    pub rewrite_queries: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ModifyEntity {
+    pub name: String,
+    pub domain: String,
+    #[serde(default)]
+    pub on_dependents: DependentsPolicy,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEntityRevision {
+    pub name: String,
+    pub domain: String,
+    pub revision: i64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffEntityRevisions {
+    pub name: String,
+    pub domain: String,
+    pub from: i64,
+    pub to: i64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTag {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GetEntityOfType {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub name: String,
+    pub domain: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityTag {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub name: String,
+    pub domain: String,
+    pub tag: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchEntities {
+    pub domain: String,
+    #[serde(flatten)]
+    pub filter: data::EntityFilter,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct GetFromDomain {
@@ -71,12 +141,109 @@ struct RoleData {
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-struct PasswordResetRequest {
+struct OidcCallback {
+    pub code: String,
+    pub state: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GetSession {
+    pub session_id: i64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct NewServiceAccount {
+    pub username: String,
+    pub display_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct NewApiKey {
+    pub name: String,
+    pub scopes: Option<Vec<data::permissions::Permission>>,
+    pub expires_at: Option<chrono::NaiveDateTime>,
+}
+
+/// Api keys of the logged in user, or of the service account
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GetApiKeys {
+    pub service_account: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GetApiKey {
+    pub api_key_id: i64,
+    pub service_account: Option<String>,
+}
+
+/// Enrolling with the challenge token from `login` is for users that 2FA is required for
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MfaEnrollment {
+    pub challenge_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MfaVerification {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MfaCode {
+    pub code: String,
+}
+
+/// Failed logins of the user, or of everyone
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GetLoginFailures {
+    #[serde(rename = "username")]
+    pub user_identifier: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SetPassword {
     pub username: String,
+    pub new_password: String,
+}
+
+/// No username is the logged in user
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GetUserOrSelf {
+    #[serde(rename = "username")]
+    pub user_identifier: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ChangePassword {
     pub old_password: String,
     pub new_password: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PasswordResetRequest {
+    pub user_identifier: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PasswordResetConfirmation {
+    pub token: String,
+    pub new_password: String,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 struct TimeRange {
@@ -86,6 +253,69 @@ struct TimeRange {
     pub end_time: chrono::NaiveDateTime,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct UsageRange {
+    pub domain: String,
+    #[serde(rename = "start")]
+    pub start_time: chrono::NaiveDateTime,
+    #[serde(rename = "end")]
+    pub end_time: chrono::NaiveDateTime,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GetTableAuditLog {
+    pub name: String,
+    pub domain: String,
+    #[serde(flatten)]
+    pub filter: data::AuditLogFilter,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ScriptArchive {
+    pub archive: String,
+    #[serde(default)]
+    pub entrypoint: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ExportDomain {
+    pub domain: String,
+    #[serde(default)]
+    pub include_data: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct DomainArchive {
+    pub archive: String,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub on_conflict: data::utils::OnConflict,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ApplySync {
+    #[serde(default)]
+    pub force: bool,
+    #[serde(default)]
+    pub on_dependents: DependentsPolicy,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct PublishMessage {
+    pub channel: data::channels::Channels,
+    pub data: Value,
+    #[serde(default)]
+    pub notify: bool,
+}
+
 
 pub mod manage {
     use super::*;
@@ -96,25 +326,60 @@ pub mod manage {
         Ok((None, actions::GetAllDomains::<_>::new()))
     }
 
+    pub fn export_domain(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let export_domain: ExportDomain = from_value(query)?;
+        let domain = export_domain.domain;
+        Ok((Some(domain.to_owned()), actions::ExportDomain::<_>::new(domain, export_domain.include_data)))
+    }
+
+    pub fn import_domain(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let domain_archive: DomainArchive = from_value(data)?;
+        let domain_query: GetFromDomain = from_value(query)?;
+        let domain = domain_query.domain;
+        Ok((Some(domain), actions::ImportDomain::<_>::new(domain_archive.archive, domain_archive.dry_run, domain_archive.on_conflict)))
+    }
+
+    pub fn plan_sync(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let domain_query: GetFromDomain = from_value(query)?;
+        let domain = domain_query.domain;
+        Ok((Some(domain), actions::PlanSync::<_>::new()))
+    }
+
+    pub fn apply_sync(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let apply_sync: ApplySync = from_value(data)?;
+        let domain_query: GetFromDomain = from_value(query)?;
+        let domain = domain_query.domain;
+        Ok((Some(domain), actions::ApplySync::<_>::new(apply_sync.force, apply_sync.on_dependents)))
+    }
+
     pub fn get_all_tables(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_all_entities: GetAllEntities = from_value(query)?;
         let domain = get_all_entities.domain;
-        Ok((Some(domain), actions::GetAllEntities::<data::DataStoreEntity>::new(get_all_entities.show_deleted)))
+        Ok((Some(domain), actions::GetAllEntities::<data::DataStoreEntity>::new(get_all_entities.show_deleted, get_all_entities.filter)))
     }
 
     pub fn get_all_queries(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_all_entities: GetAllEntities = from_value(query)?;
         let domain = get_all_entities.domain;
-        Ok((Some(domain), actions::GetAllEntities::<data::DataQueryEntity>::new(get_all_entities.show_deleted)))
+        Ok((Some(domain), actions::GetAllEntities::<data::DataQueryEntity>::new(get_all_entities.show_deleted, get_all_entities.filter)))
     }
 
     pub fn get_all_scripts(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_all_entities: GetAllEntities = from_value(query)?;
         let domain = get_all_entities.domain;
-        Ok((Some(domain), actions::GetAllEntities::<data::Script>::new(get_all_entities.show_deleted)))
+        Ok((Some(domain), actions::GetAllEntities::<data::Script>::new(get_all_entities.show_deleted, get_all_entities.filter)))
+    }
+
+    pub fn get_all_views(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_all_entities: GetAllEntities = from_value(query)?;
+        let domain = get_all_entities.domain;
+        Ok((Some(domain), actions::GetAllEntities::<data::View>::new(get_all_entities.show_deleted, get_all_entities.filter)))
     }
 
     pub fn create_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -135,7 +400,14 @@ pub mod manage {
         let entity: data::Script = from_value(data)?;
         let domain_query: GetFromDomain = from_value(query)?;
         let domain = domain_query.domain;
-        Ok((Some(domain), actions::CreateEntity::<data::Script>::new(entity)))
+        Ok((Some(domain), actions::WithInstallLog::new(actions::CreateEntity::<data::Script>::new(entity))))
+    }
+
+    pub fn create_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let entity: data::View = from_value(data)?;
+        let domain_query: GetFromDomain = from_value(query)?;
+        let domain = domain_query.domain;
+        Ok((Some(domain), actions::CreateEntity::<data::View>::new(entity)))
     }
 
     pub fn get_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -159,46 +431,239 @@ pub mod manage {
         Ok((Some(domain), actions::GetEntity::<data::Script>::new(get_entity.name)))
     }
 
-    pub fn update_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
-        let entity: data::DataStoreEntity = from_value(data)?;
+    pub fn get_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
         let domain = get_entity.domain;
-        Ok((Some(domain), actions::UpdateEntity::<data::DataStoreEntity>::new(get_entity.name, entity)))
+        Ok((Some(domain), actions::GetEntity::<data::View>::new(get_entity.name)))
+    }
+
+    pub fn update_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let entity: data::DataStoreEntity = from_value(data)?;
+        let modify_entity: ModifyEntity = from_value(query)?;
+        let domain = modify_entity.domain;
+        Ok((Some(domain), actions::UpdateEntity::<data::DataStoreEntity>::new(modify_entity.name, entity, modify_entity.on_dependents)))
     }
 
     pub fn update_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let entity: data::DataQueryEntity = from_value(data)?;
-        let get_entity: GetEntity = from_value(query)?;
-        let domain = get_entity.domain;
-        Ok((Some(domain), actions::UpdateEntity::<data::DataQueryEntity>::new(get_entity.name, entity)))
+        let modify_entity: ModifyEntity = from_value(query)?;
+        let domain = modify_entity.domain;
+        Ok((Some(domain), actions::UpdateEntity::<data::DataQueryEntity>::new(modify_entity.name, entity, modify_entity.on_dependents)))
     }
 
     pub fn update_script(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let entity: data::Script = from_value(data)?;
+        let modify_entity: ModifyEntity = from_value(query)?;
+        let domain = modify_entity.domain;
+        Ok((Some(domain), actions::WithInstallLog::new(actions::UpdateEntity::<data::Script>::new(modify_entity.name, entity, modify_entity.on_dependents))))
+    }
+
+    pub fn update_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let entity: data::View = from_value(data)?;
+        let modify_entity: ModifyEntity = from_value(query)?;
+        let domain = modify_entity.domain;
+        Ok((Some(domain), actions::UpdateEntity::<data::View>::new(modify_entity.name, entity, modify_entity.on_dependents)))
+    }
+
+    pub fn rename_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let rename_entity: RenameEntity = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::RenameEntity::<data::DataStoreEntity>::new(get_entity.name, rename_entity.new_name, rename_entity.rewrite_queries)))
+    }
+
+    pub fn rename_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let rename_entity: RenameEntity = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::RenameEntity::<data::DataQueryEntity>::new(get_entity.name, rename_entity.new_name, rename_entity.rewrite_queries)))
+    }
+
+    pub fn rename_script(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let rename_entity: RenameEntity = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::RenameEntity::<data::Script>::new(get_entity.name, rename_entity.new_name, rename_entity.rewrite_queries)))
+    }
+
+    pub fn rename_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let rename_entity: RenameEntity = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
         let domain = get_entity.domain;
-        Ok((Some(domain), actions::UpdateEntity::<data::Script>::new(get_entity.name, entity)))
+        Ok((Some(domain), actions::RenameEntity::<data::View>::new(get_entity.name, rename_entity.new_name, rename_entity.rewrite_queries)))
     }
 
     pub fn delete_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let modify_entity: ModifyEntity = from_value(query)?;
+        let domain = modify_entity.domain;
+        Ok((Some(domain), actions::DeleteEntity::<data::DataStoreEntity>::new(modify_entity.name, modify_entity.on_dependents)))
+    }
+
+    pub fn delete_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let modify_entity: ModifyEntity = from_value(query)?;
+        let domain = modify_entity.domain;
+        Ok((Some(domain), actions::DeleteEntity::<data::DataQueryEntity>::new(modify_entity.name, modify_entity.on_dependents)))
+    }
+
+    pub fn delete_script(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let modify_entity: ModifyEntity = from_value(query)?;
+        let domain = modify_entity.domain;
+        Ok((Some(domain), actions::DeleteEntity::<data::Script>::new(modify_entity.name, modify_entity.on_dependents)))
+    }
+
+    pub fn delete_view(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let modify_entity: ModifyEntity = from_value(query)?;
+        let domain = modify_entity.domain;
+        Ok((Some(domain), actions::DeleteEntity::<data::View>::new(modify_entity.name, modify_entity.on_dependents)))
+    }
+
+    pub fn get_table_history(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
         let domain = get_entity.domain;
-        Ok((Some(domain), actions::DeleteEntity::<data::DataStoreEntity>::new(get_entity.name)))
+        Ok((Some(domain), actions::GetEntityHistory::<data::DataStoreEntity>::new(get_entity.name)))
     }
 
-    pub fn delete_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+    pub fn get_table_revision(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_revision: GetEntityRevision = from_value(query)?;
+        let domain = get_revision.domain;
+        Ok((Some(domain), actions::GetEntityRevision::<data::DataStoreEntity>::new(get_revision.name, get_revision.revision)))
+    }
+
+    pub fn diff_table_revisions(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let diff_revisions: DiffEntityRevisions = from_value(query)?;
+        let domain = diff_revisions.domain;
+        Ok((Some(domain), actions::DiffEntityRevisions::<data::DataStoreEntity>::new(diff_revisions.name, diff_revisions.from, diff_revisions.to)))
+    }
+
+    pub fn revert_table(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_revision: GetEntityRevision = from_value(query)?;
+        let domain = get_revision.domain;
+        Ok((Some(domain), actions::RevertEntity::<data::DataStoreEntity>::new(get_revision.name, get_revision.revision)))
+    }
+
+    pub fn get_query_history(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
         let domain = get_entity.domain;
-        Ok((Some(domain), actions::DeleteEntity::<data::DataQueryEntity>::new(get_entity.name)))
+        Ok((Some(domain), actions::GetEntityHistory::<data::DataQueryEntity>::new(get_entity.name)))
     }
 
-    pub fn delete_script(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+    pub fn get_query_revision(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_revision: GetEntityRevision = from_value(query)?;
+        let domain = get_revision.domain;
+        Ok((Some(domain), actions::GetEntityRevision::<data::DataQueryEntity>::new(get_revision.name, get_revision.revision)))
+    }
+
+    pub fn diff_query_revisions(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let diff_revisions: DiffEntityRevisions = from_value(query)?;
+        let domain = diff_revisions.domain;
+        Ok((Some(domain), actions::DiffEntityRevisions::<data::DataQueryEntity>::new(diff_revisions.name, diff_revisions.from, diff_revisions.to)))
+    }
+
+    pub fn revert_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_revision: GetEntityRevision = from_value(query)?;
+        let domain = get_revision.domain;
+        Ok((Some(domain), actions::RevertEntity::<data::DataQueryEntity>::new(get_revision.name, get_revision.revision)))
+    }
+
+    pub fn get_script_history(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
         let domain = get_entity.domain;
-        Ok((Some(domain), actions::DeleteEntity::<data::Script>::new(get_entity.name)))
+        Ok((Some(domain), actions::GetEntityHistory::<data::Script>::new(get_entity.name)))
+    }
+
+    pub fn get_script_revision(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_revision: GetEntityRevision = from_value(query)?;
+        let domain = get_revision.domain;
+        Ok((Some(domain), actions::GetEntityRevision::<data::Script>::new(get_revision.name, get_revision.revision)))
+    }
+
+    pub fn diff_script_revisions(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let diff_revisions: DiffEntityRevisions = from_value(query)?;
+        let domain = diff_revisions.domain;
+        Ok((Some(domain), actions::DiffEntityRevisions::<data::Script>::new(diff_revisions.name, diff_revisions.from, diff_revisions.to)))
+    }
+
+    pub fn revert_script(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_revision: GetEntityRevision = from_value(query)?;
+        let domain = get_revision.domain;
+        Ok((Some(domain), actions::RevertEntity::<data::Script>::new(get_revision.name, get_revision.revision)))
+    }
+
+    pub fn get_all_tags(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::GetAllTags::<_>::new()))
+    }
+
+    pub fn create_tag(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let tag: data::Tag = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::CreateTag::<_>::new(tag)))
+    }
+
+    pub fn delete_tag(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_tag: GetTag = from_value(query)?;
+        Ok((None, actions::DeleteTag::<_>::new(get_tag.name)))
+    }
+
+    pub fn get_dependents(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntityOfType = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetDependents::<_>::new(get_entity.type_name, get_entity.name)))
+    }
+
+    pub fn get_entity_tags(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntityOfType = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetEntityTags::<_>::new(get_entity.type_name, get_entity.name)))
+    }
+
+    pub fn attach_tag(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let entity_tag: EntityTag = from_value(query)?;
+        let domain = entity_tag.domain;
+        Ok((Some(domain), actions::AttachTag::<_>::new(entity_tag.type_name, entity_tag.name, entity_tag.tag)))
+    }
+
+    pub fn detach_tag(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let entity_tag: EntityTag = from_value(query)?;
+        let domain = entity_tag.domain;
+        Ok((Some(domain), actions::DetachTag::<_>::new(entity_tag.type_name, entity_tag.name, entity_tag.tag)))
+    }
+
+    pub fn search_entities(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let search: SearchEntities = from_value(query)?;
+        let domain = search.domain;
+        Ok((Some(domain), actions::SearchEntities::<_>::new(search.filter)))
+    }
+
+    pub fn get_entity_usage(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let usage_range: UsageRange = from_value(query)?;
+        let domain = usage_range.domain;
+        Ok((Some(domain.to_owned()), actions::GetEntityUsage::<_>::new(domain, usage_range.start_time, usage_range.end_time)))
     }
 
     pub fn query_table_data(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
@@ -229,6 +694,20 @@ pub mod manage {
         Ok((Some(domain), actions::RemoveTableData::<_>::new(get_entity.name, keys)))
     }
 
+    pub fn get_table_audit_log(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_audit_log: GetTableAuditLog = from_value(query)?;
+        let domain = get_audit_log.domain;
+        Ok((Some(domain), actions::GetTableAuditLog::<_>::new(get_audit_log.name, get_audit_log.filter)))
+    }
+
+    pub fn revert_table_changes(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let range: data::RevertRange = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::RevertTableChanges::<_>::new(get_entity.name, range)))
+    }
+
     pub fn run_query(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let params: Value = data;
         let get_entity: GetEntity = from_value(query)?;
@@ -236,12 +715,33 @@ pub mod manage {
         Ok((Some(domain), actions::RunQuery::<_>::new(get_entity.name, params)))
     }
 
+    pub fn patch_script(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let patch: data::ScriptPatch = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::PatchScript::<_>::new(get_entity.name, patch)))
+    }
+
+    pub fn upload_script_archive(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let script_archive: ScriptArchive = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::UploadScriptArchive::<_>::new(get_entity.name, script_archive.archive, script_archive.entrypoint)))
+    }
+
     pub fn run_script(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let param: data::ScriptParam = from_value(data)?;
         let get_entity: GetEntity = from_value(query)?;
         let domain = get_entity.domain;
         Ok((Some(domain), actions::RunScript::<_>::new(get_entity.name, param)))
     }
+
+    pub fn get_view_data(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_entity: GetEntity = from_value(query)?;
+        let domain = get_entity.domain;
+        Ok((Some(domain), actions::GetViewData::<_>::new(get_entity.name)))
+    }
 }
 
 pub mod pubsub {
@@ -275,6 +775,13 @@ pub mod pubsub {
         Ok((Some(domain), actions::GetSubscribers::<_>::new(channel)))
     }
 
+    pub fn publish(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let message: PublishMessage = from_value(data)?;
+        let domain_query: GetFromDomain = from_value(query)?;
+        let domain = domain_query.domain;
+        Ok((Some(domain), actions::Publish::<_>::new(message.channel, message.data, message.notify)))
+    }
+
     pub fn get_messages(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let range: TimeRange = from_value(query)?;
@@ -299,12 +806,108 @@ pub mod users {
         Ok((None, actions::Refresh::<_>::new(auth_data.refresh_token)))
     }
 
+    pub fn oidc_login(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::StartOidcLogin::<_>::new()))
+    }
+
+    pub fn oidc_callback(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let callback: OidcCallback = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::FinishOidcLogin::<_>::new(callback.code, callback.state)))
+    }
+
+    pub fn enroll_mfa(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let enrollment: MfaEnrollment = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::EnrollMfa::<_>::new(enrollment.challenge_token)))
+    }
+
+    pub fn verify_mfa(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let verification: MfaVerification = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::VerifyMfa::<_>::new(verification.challenge_token, verification.code)))
+    }
+
+    pub fn disable_mfa(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let mfa_code: MfaCode = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::DisableMfa::<_>::new(mfa_code.code)))
+    }
+
+    pub fn reset_mfa(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_user: GetUser = from_value(query)?;
+        Ok((None, actions::ResetMfa::<_>::new(get_user.user_identifier)))
+    }
+
+    pub fn sync_directory(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::SyncDirectoryUsers::<_>::new()))
+    }
+
     pub fn logout(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let _: NoQuery = from_value(query)?;
         Ok((None, actions::Logout::<_>::new()))
     }
 
+    pub fn list_my_sessions(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::ListMySessions::<_>::new()))
+    }
+
+    pub fn revoke_session(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_session: GetSession = from_value(query)?;
+        Ok((None, actions::RevokeSession::<_>::new(get_session.session_id)))
+    }
+
+    pub fn add_service_account(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let service_account: NewServiceAccount = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::AddServiceAccount::<_>::new(service_account.username, service_account.display_name)))
+    }
+
+    pub fn create_api_key(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let api_key: NewApiKey = from_value(data)?;
+        let get_api_keys: GetApiKeys = from_value(query)?;
+        Ok((None, actions::CreateApiKey::<_>::new(api_key.name, api_key.scopes, api_key.expires_at, get_api_keys.service_account)))
+    }
+
+    pub fn list_api_keys(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_api_keys: GetApiKeys = from_value(query)?;
+        Ok((None, actions::ListApiKeys::<_>::new(get_api_keys.service_account)))
+    }
+
+    pub fn revoke_api_key(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_api_key: GetApiKey = from_value(query)?;
+        Ok((None, actions::RevokeApiKey::<_>::new(get_api_key.api_key_id, get_api_key.service_account)))
+    }
+
+    pub fn force_logout(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_user: GetUser = from_value(query)?;
+        Ok((None, actions::ForceLogout::<_>::new(get_user.user_identifier)))
+    }
+
+    pub fn unlock_user(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_user: GetUser = from_value(query)?;
+        Ok((None, actions::UnlockUser::<_>::new(get_user.user_identifier)))
+    }
+
+    pub fn get_login_failures(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_login_failures: GetLoginFailures = from_value(query)?;
+        Ok((None, actions::GetLoginFailures::<_>::new(get_login_failures.user_identifier)))
+    }
+
     pub fn get_all_users(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let _: NoQuery = from_value(data)?;
         let _: NoQuery = from_value(query)?;
@@ -336,12 +939,46 @@ pub mod users {
     }
 
     pub fn set_user_password(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let data: SetPassword = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::SetUserPassword::<_>::new(data.username, data.new_password)))
+    }
+
+    pub fn change_my_password(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let data: ChangePassword = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::ChangeMyPassword::<_>::new(data.old_password, data.new_password)))
+    }
+
+    pub fn request_password_reset(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let data: PasswordResetRequest = from_value(data)?;
         let _: NoQuery = from_value(query)?;
-        Ok((None, actions::SetUserPassword::<_>::new(data.username, data.new_password))) //TODO: add old password too
+        Ok((None, actions::RequestPasswordReset::<_>::new(data.user_identifier)))
     }
 
-    //TODO: modify user
+    pub fn confirm_password_reset(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let data: PasswordResetConfirmation = from_value(data)?;
+        let _: NoQuery = from_value(query)?;
+        Ok((None, actions::ConfirmPasswordReset::<_>::new(data.token, data.new_password)))
+    }
+
+    pub fn modify_user(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let modification: data::auth::UserModification = from_value(data)?;
+        let get_user: GetUserOrSelf = from_value(query)?;
+        Ok((None, actions::ModifyUser::<_>::new(get_user.user_identifier, modification)))
+    }
+
+    pub fn disable_user(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_user: GetUser = from_value(query)?;
+        Ok((None, actions::DisableUser::<_>::new(get_user.user_identifier)))
+    }
+
+    pub fn enable_user(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
+        let _: NoQuery = from_value(data)?;
+        let get_user: GetUser = from_value(query)?;
+        Ok((None, actions::EnableUser::<_>::new(get_user.user_identifier)))
+    }
 
     pub fn add_role(data: Value, query: Value) -> Result<(Option<String>, impl Action), Error> {
         let role: data::auth::Role = from_value(data)?;
@@ -7,6 +7,7 @@ use actix_web::ws;
 
 use connection::AppStateLike;
 use broker::WsClientSession;
+use view::client_info::get_client_info;
 
 
 
@@ -15,5 +16,5 @@ pub fn handler<S>(req: &HttpRequest<S>) -> Result<HttpResponse, Error>
         S: AppStateLike + 'static,
 {
     debug!("connection to the websocket");
-    ws::start(req, WsClientSession::<S>::new())
+    ws::start(req, WsClientSession::<S>::new().with_client_info(get_client_info(req)))
 }
\ No newline at end of file